@@ -0,0 +1,5 @@
+// Embedded migrations are read at compile time, changes to the directory
+// must trigger a rebuild.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
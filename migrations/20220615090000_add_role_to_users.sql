@@ -0,0 +1,5 @@
+-- Add migration script here
+
+CREATE TYPE role AS ENUM ('user', 'admin');
+
+ALTER TABLE users ADD COLUMN role role NOT NULL DEFAULT 'user';
@@ -0,0 +1,3 @@
+-- Add migration script here
+
+ALTER TABLE users ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT FALSE;
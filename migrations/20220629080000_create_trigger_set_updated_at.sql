@@ -0,0 +1,16 @@
+-- Add migration script here
+
+CREATE OR REPLACE FUNCTION set_updated_at() RETURNS TRIGGER AS $$
+BEGIN
+  NEW.updated_at = CURRENT_TIMESTAMP;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER users_set_updated_at
+  BEFORE UPDATE ON users
+  FOR EACH ROW EXECUTE PROCEDURE set_updated_at();
+
+CREATE TRIGGER posts_set_updated_at
+  BEFORE UPDATE ON posts
+  FOR EACH ROW EXECUTE PROCEDURE set_updated_at();
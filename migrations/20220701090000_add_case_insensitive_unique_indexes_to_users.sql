@@ -0,0 +1,8 @@
+-- Add migration script here
+
+-- Fails if two accounts only differ in casing, these must be merged first
+UPDATE users SET email = LOWER(email), username = LOWER(username);
+
+CREATE UNIQUE INDEX users_email_lower_key ON users (LOWER(email));
+
+CREATE UNIQUE INDEX users_username_lower_key ON users (LOWER(username));
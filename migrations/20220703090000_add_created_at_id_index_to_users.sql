@@ -0,0 +1,4 @@
+-- Add migration script here
+
+-- Backs the `(created_at, id)` keyset pagination of users
+CREATE INDEX users_created_at_id_idx ON users (created_at, id);
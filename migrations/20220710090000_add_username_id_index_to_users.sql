@@ -0,0 +1,4 @@
+-- Add migration script here
+
+-- Backs the `(username, id)` keyset pagination of users
+CREATE INDEX users_username_id_idx ON users (username, id);
@@ -0,0 +1,26 @@
+-- Add migration script here
+
+CREATE TYPE audit_action AS ENUM (
+  'user_create',
+  'token_create',
+  'password_change',
+  'user_delete',
+  'role_update'
+);
+
+CREATE TYPE audit_outcome AS ENUM ('success', 'failure');
+
+CREATE TABLE IF NOT EXISTS audit_log (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  actor_id UUID,
+  target_id UUID,
+  action audit_action NOT NULL,
+  outcome audit_outcome NOT NULL,
+  ip_address TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (actor_id) REFERENCES users(id),
+  FOREIGN KEY (target_id) REFERENCES users(id)
+);
+
+-- Backs the `(created_at, id)` keyset pagination of the audit log
+CREATE INDEX audit_log_created_at_id_idx ON audit_log (created_at, id);
@@ -0,0 +1,19 @@
+-- Add migration script here
+
+CREATE TABLE IF NOT EXISTS organizations (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  name VARCHAR(120) NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Existing and self-registered users belong to the default organization
+INSERT INTO organizations (id, name)
+VALUES ('00000000-0000-0000-0000-000000000000', 'Default');
+
+ALTER TABLE users
+  ADD COLUMN organization_id UUID NOT NULL
+    DEFAULT '00000000-0000-0000-0000-000000000000'
+    REFERENCES organizations(id);
+
+CREATE INDEX users_organization_id_idx ON users (organization_id);
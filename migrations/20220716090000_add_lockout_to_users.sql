@@ -0,0 +1,8 @@
+-- Add migration script here
+
+-- Consecutive failed logins, reset on success and once the account locks
+ALTER TABLE users
+  ADD COLUMN failed_login_count INTEGER NOT NULL DEFAULT 0,
+  ADD COLUMN locked_until TIMESTAMPTZ;
+
+ALTER TYPE audit_action ADD VALUE 'user_unlock';
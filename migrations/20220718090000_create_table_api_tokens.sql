@@ -0,0 +1,18 @@
+-- Add migration script here
+
+CREATE TABLE IF NOT EXISTS api_tokens (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  user_id UUID NOT NULL,
+  name VARCHAR(64) NOT NULL,
+  scopes TEXT[] NOT NULL,
+  -- Tokens without expiry remain valid until revoked
+  expires_at TIMESTAMPTZ,
+  revoked_at TIMESTAMPTZ,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
+
+ALTER TYPE audit_action ADD VALUE 'api_token_create';
+ALTER TYPE audit_action ADD VALUE 'api_token_revoke';
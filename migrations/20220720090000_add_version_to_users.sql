@@ -0,0 +1,5 @@
+-- Add migration script here
+
+-- Incremented by every profile update, which must provide the version it's
+-- based on so concurrent updates don't overwrite each other
+ALTER TABLE users ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
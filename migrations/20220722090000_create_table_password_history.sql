@@ -0,0 +1,12 @@
+-- Add migration script here
+
+CREATE TABLE IF NOT EXISTS password_history (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  user_id UUID NOT NULL,
+  -- Hash the user's password was replaced from, kept to prevent its reuse
+  password_hash TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX password_history_user_id_idx ON password_history (user_id, created_at DESC);
@@ -1,4 +1,12 @@
+use async_graphql::Pos;
+use rocket::data::ToByteUnit;
+use rocket::http::Status;
 use rocket::request::Request;
+use rocket::response::status;
+use rocket::serde::json::Json;
+
+use crate::config::{DEFAULT_MAX_REQUEST_BYTES, GRAPHQL_DATA_LIMIT};
+use crate::error::Error;
 
 #[rocket::catch(404)]
 pub fn not_found(request: &Request) -> String {
@@ -7,3 +15,20 @@ pub fn not_found(request: &Request) -> String {
         request.uri()
     )
 }
+
+/// Responds to bodies exceeding the data limit with a GraphQL response
+/// carrying the `PAYLOAD_TOO_LARGE` error
+#[rocket::catch(413)]
+pub fn payload_too_large(request: &Request) -> status::Custom<Json<async_graphql::Response>> {
+    let limit = request
+        .limits()
+        .get(GRAPHQL_DATA_LIMIT)
+        .unwrap_or_else(|| DEFAULT_MAX_REQUEST_BYTES.bytes());
+    let error = async_graphql::Error::from(Error::payload_too_large(limit.as_u64()))
+        .into_server_error(Pos::default());
+
+    status::Custom(
+        Status::PayloadTooLarge,
+        Json(async_graphql::Response::from_errors(vec![error])),
+    )
+}
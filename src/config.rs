@@ -1,22 +1,1182 @@
-use rocket::config::LogLevel;
+use ipnet::IpNet;
+use jsonwebtoken::Algorithm;
+#[cfg(unix)]
+use rocket::config::Sig;
+use rocket::config::{LogLevel, Shutdown};
+use rocket::data::{Limits, ToByteUnit};
+use rocket::http::{Cookie, SameSite};
+use rocket::time;
 use std::env;
+use std::fmt::Display;
 use std::net::IpAddr;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
+
+use crate::error::ErrorFormat;
+
+/// Minimum length of the secrets tokens are signed with, that of the
+/// SHA-256 output HS256 keys are expected to match
+pub const MIN_JWT_SECRET_LENGTH: usize = 32;
+
+/// Default seconds an access token remains valid
+pub const DEFAULT_JWT_EXPIRY_SECS: u64 = 60 * 60 * 24 * 30;
+
+/// Default `iss` claim for signed tokens
+pub const DEFAULT_JWT_ISSUER: &str = "nexus";
+
+/// Default `aud` claim for signed tokens
+pub const DEFAULT_JWT_AUDIENCE: &str = "nexus";
+
+/// Key id assigned to `JWT_SECRET` and to tokens issued without a `kid`
+pub const DEFAULT_JWT_KEY_ID: &str = "default";
+
+/// Default argon2 memory cost in KiB for new password hashes
+pub const DEFAULT_ARGON2_MEMORY_KIB: u32 = 4096;
+
+/// Default argon2 amount of passes for new password hashes
+pub const DEFAULT_ARGON2_ITERATIONS: u32 = 3;
+
+/// Default argon2 degree of parallelism for new password hashes
+pub const DEFAULT_ARGON2_PARALLELISM: u32 = 1;
+
+/// Default minimum amount of characters of a password
+pub const DEFAULT_PASSWORD_MIN_LENGTH: usize = 8;
+
+/// Default amount of previous passwords which can't be reused
+pub const DEFAULT_PASSWORD_HISTORY_SIZE: usize = 5;
+
+/// Default usernames which can't be registered
+pub const DEFAULT_RESERVED_USERNAMES: &[&str] = &[
+    "admin",
+    "administrator",
+    "nexus",
+    "root",
+    "support",
+    "system",
+];
+
+/// Default amount of failed logins after which a username or IP is locked
+pub const DEFAULT_LOGIN_MAX_FAILURES: u32 = 5;
+
+/// Default seconds failed logins are counted for
+pub const DEFAULT_LOGIN_FAILURE_WINDOW_SECS: u64 = 60 * 15;
+
+/// Default seconds logins are rejected for once locked
+pub const DEFAULT_LOGIN_COOLDOWN_SECS: u64 = 60 * 15;
+
+/// Default amount of consecutive failed logins after which an account is
+/// locked
+pub const DEFAULT_ACCOUNT_LOCKOUT_THRESHOLD: u32 = 10;
+
+/// Default seconds an account remains locked for
+pub const DEFAULT_ACCOUNT_LOCKOUT_SECS: u64 = 60 * 15;
+
+/// Default maximum depth for incoming GraphQL queries
+pub const DEFAULT_GRAPHQL_DEPTH_LIMIT: usize = 15;
+
+/// Default maximum complexity for incoming GraphQL queries
+pub const DEFAULT_GRAPHQL_COMPLEXITY_LIMIT: usize = 1000;
+
+/// Default maximum of aliased or repeated fields for incoming GraphQL queries
+pub const DEFAULT_GRAPHQL_MAX_ALIASES: usize = 30;
+
+/// Default maximum amount of ids a batch lookup such as `usersByIds` accepts
+pub const DEFAULT_GRAPHQL_MAX_BATCH_IDS: usize = 100;
+
+/// Default maximum amount of nodes a connection returns per page
+pub const DEFAULT_MAX_PAGE_SIZE: usize = 100;
+
+/// Default amount of nodes a connection returns when neither `first` nor
+/// `last` is provided
+pub const DEFAULT_PAGE_SIZE: usize = 10;
+
+/// Name of the Rocket data limit applied to GraphQL request bodies
+pub const GRAPHQL_DATA_LIMIT: &str = "graphql";
+
+/// Default maximum size of a GraphQL request body, 1 MiB
+pub const DEFAULT_MAX_REQUEST_BYTES: u64 = 1024 * 1024;
+
+/// Default maximum amount of queries registered as persisted queries
+pub const DEFAULT_PERSISTED_QUERIES_CAPACITY: usize = 1000;
+
+/// Default amount of query cost a client can spend at once
+pub const DEFAULT_RATE_LIMIT_CAPACITY: u32 = 5000;
+
+/// Default amount of query cost restored to a client every second
+pub const DEFAULT_RATE_LIMIT_REFILL_PER_SEC: u32 = 50;
+
+/// Default seconds between keepalive pings sent over subscription
+/// connections
+pub const DEFAULT_SUBSCRIPTION_KEEPALIVE_SECS: u64 = 30;
+
+/// Default port subscriptions are served on over WebSockets
+pub const DEFAULT_SUBSCRIPTION_PORT: u16 = 7879;
+
+/// Default seconds an operation may execute for before being cancelled
+pub const DEFAULT_QUERY_TIMEOUT_SECS: u64 = 30;
+
+/// Default seconds in-flight requests are given to complete on shutdown
+pub const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+
+/// Default seconds between purges of expired tokens
+pub const DEFAULT_TOKEN_CLEANUP_INTERVAL_SECS: u64 = 3600;
+
+/// Default maximum amount of connections held by the database pool
+pub const DEFAULT_DATABASE_MAX_CONNECTIONS: u32 = 5;
+
+/// Default minimum amount of idle connections kept by the database pool
+pub const DEFAULT_DATABASE_MIN_IDLE: u32 = 0;
+
+/// Default seconds to wait for a connection to be checked out of the pool
+pub const DEFAULT_DATABASE_CONNECTION_TIMEOUT_SECS: u64 = 30;
+
+/// Default seconds an idle connection is kept before being closed
+pub const DEFAULT_DATABASE_IDLE_TIMEOUT_SECS: u64 = 600;
+
+/// Default milliseconds after which a timed query is logged as slow
+pub const DEFAULT_SLOW_QUERY_MS: u64 = 200;
+
+/// Default port of the SMTP server emails are submitted to
+pub const DEFAULT_SMTP_PORT: u16 = 587;
 
 pub struct Config {
-    pub host: IpAddr,
-    pub jwt_secret: String,
-    pub port: u16,
+    pub jwt: JwtConfig,
+    pub argon2: Argon2Config,
+    pub password_policy: PasswordPolicyConfig,
+    pub reserved_usernames: ReservedUsernames,
+    pub login_throttle: LoginThrottleConfig,
+    pub account_lockout: AccountLockoutConfig,
     pub database_url: String,
+    pub database_pool: DatabasePoolConfig,
+    pub graphql: GraphQLConfig,
+    pub cors: CorsConfig,
+    pub auth_cookie: AuthCookieConfig,
+    /// Proxies whose `X-Forwarded-For` header is trusted to carry the
+    /// client's IP address
+    pub trusted_proxies: TrustedProxies,
+    /// SMTP server emails are delivered through, they are only logged when
+    /// unset
+    pub smtp: Option<SmtpConfig>,
+    /// Applies pending migrations before the server starts accepting
+    /// requests
+    pub run_migrations_on_start: bool,
+    /// Reports the underlying message of unhandled errors to clients, must
+    /// stay disabled in production to avoid leaking internal details
+    pub expose_internal_errors: bool,
+    /// Format of the error extensions for requests which don't negotiate
+    /// one through the `X-Error-Format` header
+    pub error_format: ErrorFormat,
+    /// Time in-flight requests are given to complete once a `SIGTERM` or
+    /// `SIGINT` is received
+    pub shutdown_timeout: Duration,
+    /// Interval expired refresh tokens and denylist entries are purged at
+    pub token_cleanup_interval: Duration,
     pub server_config: rocket::Config,
 }
 
+/// Origins browsers are allowed to call the API from
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AllowedOrigins {
+    Any,
+    List(Vec<String>),
+}
+
+impl AllowedOrigins {
+    pub fn allows(&self, origin: &str) -> bool {
+        match self {
+            AllowedOrigins::Any => true,
+            AllowedOrigins::List(origins) => origins.iter().any(|allowed| allowed == origin),
+        }
+    }
+}
+
+/// Parses either `*` or a comma separated list of origins, as provided
+/// through `CORS_ALLOWED_ORIGINS`. An empty list allows no origin.
+impl FromStr for AllowedOrigins {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.trim() == "*" {
+            return Ok(AllowedOrigins::Any);
+        }
+
+        let origins = value
+            .split(',')
+            .map(|origin| origin.trim().trim_end_matches('/'))
+            .filter(|origin| !origin.is_empty())
+            .map(String::from)
+            .collect();
+
+        Ok(AllowedOrigins::List(origins))
+    }
+}
+
+/// Networks of the proxies allowed to forward requests, such as load
+/// balancers. Empty unless configured, the peer's address is then used as is.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TrustedProxies(pub Vec<IpNet>);
+
+impl TrustedProxies {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|network| network.contains(&ip))
+    }
+}
+
+/// Parses a comma separated list of CIDR blocks, as provided through
+/// `TRUSTED_PROXIES`. Bare addresses stand for a single host.
+impl FromStr for TrustedProxies {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|network| !network.is_empty())
+            .map(|network| {
+                network
+                    .parse::<IpNet>()
+                    .or_else(|_| network.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("Invalid trusted proxy network: {network}"))
+            })
+            .collect::<Result<Vec<IpNet>, String>>()
+            .map(TrustedProxies)
+    }
+}
+
+/// Cross-origin requests handling, denies every origin unless configured
+/// otherwise outside of debug builds
+#[derive(Clone, Debug)]
+pub struct CorsConfig {
+    pub allowed_origins: AllowedOrigins,
+    /// Allows browsers to send cookies and `Authorization` headers, allowed
+    /// origins are then echoed back instead of `*`
+    pub allow_credentials: bool,
+}
+
+/// Secret used to sign and validate tokens, identified by the `kid` header of
+/// the tokens it signs
+#[derive(Clone, Debug)]
+pub struct JwtKey {
+    pub id: String,
+    pub secret: String,
+}
+
+/// Keys parsed from a comma separated list of `id:secret` pairs, as provided
+/// through `JWT_KEYS`
+#[derive(Debug)]
+pub struct JwtKeys(pub Vec<JwtKey>);
+
+impl FromStr for JwtKeys {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split(',')
+            .map(|pair| match pair.trim().split_once(':') {
+                Some((id, secret)) if !id.is_empty() && !secret.is_empty() => Ok(JwtKey {
+                    id: id.to_string(),
+                    secret: secret.to_string(),
+                }),
+                _ => Err(format!("Expected an `id:secret` pair, got: {pair}")),
+            })
+            .collect::<Result<Vec<JwtKey>, String>>()
+            .map(JwtKeys)
+    }
+}
+
+/// Settings used to sign and validate JSON Web Tokens. Using a different
+/// `issuer` or `audience` per environment keeps tokens from being accepted
+/// across environments sharing the same secret.
+///
+/// Tokens are signed with the key identified by `current_key_id` and
+/// validated with the key named by their `kid` header, so retired keys can
+/// be kept to validate outstanding tokens while rotating secrets.
+pub struct JwtConfig {
+    pub keys: Vec<JwtKey>,
+    pub current_key_id: String,
+    pub algorithm: Algorithm,
+    pub expiry: Duration,
+    pub issuer: String,
+    pub audience: String,
+}
+
+impl JwtConfig {
+    /// Creates settings for the provided `secret` using default values
+    pub fn new(secret: &str) -> Self {
+        Self::with_keys(
+            vec![JwtKey {
+                id: String::from(DEFAULT_JWT_KEY_ID),
+                secret: secret.to_string(),
+            }],
+            DEFAULT_JWT_KEY_ID,
+        )
+    }
+
+    /// Creates settings signing with the key identified by `current_key_id`
+    /// using default values
+    pub fn with_keys(keys: Vec<JwtKey>, current_key_id: &str) -> Self {
+        Self {
+            keys,
+            current_key_id: current_key_id.to_string(),
+            algorithm: Algorithm::HS256,
+            expiry: Duration::from_secs(DEFAULT_JWT_EXPIRY_SECS),
+            issuer: String::from(DEFAULT_JWT_ISSUER),
+            audience: String::from(DEFAULT_JWT_AUDIENCE),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !matches!(
+            self.algorithm,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(format!(
+                "Only HMAC algorithms are supported, got {:?}",
+                self.algorithm
+            ));
+        }
+
+        for (index, key) in self.keys.iter().enumerate() {
+            if self.keys[..index].iter().any(|other| other.id == key.id) {
+                return Err(format!("Key id {} is used more than once", key.id));
+            }
+
+            if key.secret.len() < MIN_JWT_SECRET_LENGTH {
+                return Err(format!(
+                    "The secret of key {} must have at least {MIN_JWT_SECRET_LENGTH} characters",
+                    key.id
+                ));
+            }
+        }
+
+        if !self.keys.iter().any(|key| key.id == self.current_key_id) {
+            return Err(format!(
+                "Current key id {} doesn't match any key",
+                self.current_key_id
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Parameters used when hashing new passwords. Stored hashes created with
+/// weaker parameters are upgraded on login.
+#[derive(Clone, Debug)]
+pub struct Argon2Config {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    /// Secret passwords are keyed with before hashing, kept out of the
+    /// database so its hashes can't be cracked on their own
+    pub pepper: Option<String>,
+}
+
+impl Default for Argon2Config {
+    fn default() -> Self {
+        Self {
+            memory_kib: DEFAULT_ARGON2_MEMORY_KIB,
+            iterations: DEFAULT_ARGON2_ITERATIONS,
+            parallelism: DEFAULT_ARGON2_PARALLELISM,
+            pepper: None,
+        }
+    }
+}
+
+/// Rules new passwords must comply with
+#[derive(Clone, Copy, Debug)]
+pub struct PasswordPolicyConfig {
+    pub min_length: usize,
+    pub require_mixed_case: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    /// Amount of previous passwords, besides the current one, a new
+    /// password can't match. `0` only rejects the current password.
+    pub history_size: usize,
+}
+
+impl Default for PasswordPolicyConfig {
+    fn default() -> Self {
+        Self {
+            min_length: DEFAULT_PASSWORD_MIN_LENGTH,
+            require_mixed_case: true,
+            require_digit: true,
+            require_symbol: false,
+            history_size: DEFAULT_PASSWORD_HISTORY_SIZE,
+        }
+    }
+}
+
+/// Usernames which can't be registered, parsed from a comma separated list
+/// as provided through `RESERVED_USERNAMES`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReservedUsernames(pub Vec<String>);
+
+impl ReservedUsernames {
+    pub fn contains(&self, username: &str) -> bool {
+        self.0.iter().any(|reserved| reserved == username)
+    }
+}
+
+impl FromStr for ReservedUsernames {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let usernames = value
+            .split(',')
+            .map(|username| username.trim().to_lowercase())
+            .filter(|username| !username.is_empty())
+            .collect();
+
+        Ok(ReservedUsernames(usernames))
+    }
+}
+
+impl Default for ReservedUsernames {
+    fn default() -> Self {
+        Self(
+            DEFAULT_RESERVED_USERNAMES
+                .iter()
+                .map(|username| username.to_string())
+                .collect(),
+        )
+    }
+}
+
+/// Limits on failed `tokenCreate` attempts per username and per IP
+#[derive(Clone, Copy, Debug)]
+pub struct LoginThrottleConfig {
+    pub max_failures: u32,
+    pub window: Duration,
+    pub cooldown: Duration,
+}
+
+impl LoginThrottleConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_failures == 0 {
+            return Err(String::from("LOGIN_MAX_FAILURES must be greater than 0"));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for LoginThrottleConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: DEFAULT_LOGIN_MAX_FAILURES,
+            window: Duration::from_secs(DEFAULT_LOGIN_FAILURE_WINDOW_SECS),
+            cooldown: Duration::from_secs(DEFAULT_LOGIN_COOLDOWN_SECS),
+        }
+    }
+}
+
+/// Locks accounts after consecutive failed logins. Unlike the login
+/// throttle, which is kept in memory per username and IP, the counter is
+/// stored along with the user so it holds across instances and restarts.
+#[derive(Clone, Copy, Debug)]
+pub struct AccountLockoutConfig {
+    pub threshold: u32,
+    pub duration: Duration,
+}
+
+impl AccountLockoutConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.threshold == 0 {
+            return Err(String::from(
+                "ACCOUNT_LOCKOUT_THRESHOLD must be greater than 0",
+            ));
+        }
+
+        if self.duration.is_zero() {
+            return Err(String::from("ACCOUNT_LOCKOUT_SECS must be greater than 0"));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for AccountLockoutConfig {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_ACCOUNT_LOCKOUT_THRESHOLD,
+            duration: Duration::from_secs(DEFAULT_ACCOUNT_LOCKOUT_SECS),
+        }
+    }
+}
+
+/// Settings applied to the GraphQL Schema
+pub struct GraphQLConfig {
+    pub depth_limit: usize,
+    pub complexity_limit: usize,
+    /// Queries selecting more aliased or repeated fields are rejected before
+    /// execution, as repeating a field multiplies its cost without deepening
+    /// the query
+    pub max_aliases: usize,
+    /// Batch lookups given more ids are rejected with a validation error
+    pub max_batch_ids: usize,
+    pub rate_limit: RateLimitConfig,
+    pub page_size: PageSizeConfig,
+    /// Rejects mutations while serving queries, can be toggled at runtime
+    /// through the `readOnlySet` mutation
+    pub read_only: bool,
+    pub persisted_queries: PersistedQueriesConfig,
+    /// JSON manifest of the only operations allowed to run, introspection is
+    /// disabled when set
+    pub allowlist: Option<PathBuf>,
+    /// Rejects `__schema` and `__type` queries and stops serving the
+    /// playground
+    pub disable_introspection: bool,
+    /// Bodies exceeding this size are rejected with `413 Payload Too Large`
+    /// before being parsed
+    pub max_request_bytes: u64,
+    /// Interval pings are sent at over subscription connections, so proxies
+    /// don't drop them while idle
+    pub subscription_keepalive: Duration,
+    /// Port of the WebSocket server subscriptions are served by, on the same
+    /// address as the HTTP server
+    pub subscription_port: u16,
+    /// Operations executing for longer are cancelled with `TIMEOUT`, which
+    /// also bounds the statements they run
+    pub query_timeout: Duration,
+}
+
+impl Default for GraphQLConfig {
+    fn default() -> Self {
+        Self {
+            depth_limit: DEFAULT_GRAPHQL_DEPTH_LIMIT,
+            complexity_limit: DEFAULT_GRAPHQL_COMPLEXITY_LIMIT,
+            max_aliases: DEFAULT_GRAPHQL_MAX_ALIASES,
+            max_batch_ids: DEFAULT_GRAPHQL_MAX_BATCH_IDS,
+            rate_limit: RateLimitConfig::default(),
+            page_size: PageSizeConfig::default(),
+            read_only: false,
+            persisted_queries: PersistedQueriesConfig::default(),
+            allowlist: None,
+            disable_introspection: false,
+            max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+            subscription_keepalive: Duration::from_secs(DEFAULT_SUBSCRIPTION_KEEPALIVE_SECS),
+            subscription_port: DEFAULT_SUBSCRIPTION_PORT,
+            query_timeout: Duration::from_secs(DEFAULT_QUERY_TIMEOUT_SECS),
+        }
+    }
+}
+
+impl GraphQLConfig {
+    /// Whether the schema is hidden from clients, always the case for
+    /// deployments locked down through an allowlist
+    pub fn is_introspection_disabled(&self) -> bool {
+        self.disable_introspection || self.allowlist.is_some()
+    }
+}
+
+/// Automatic persisted queries settings
+#[derive(Clone, Debug)]
+pub struct PersistedQueriesConfig {
+    pub capacity: usize,
+    /// Rejects queries missing from the `manifest` instead of registering
+    /// them
+    pub only: bool,
+    /// JSON file mapping SHA-256 hashes to the queries registered on startup
+    pub manifest: Option<PathBuf>,
+}
+
+impl PersistedQueriesConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.capacity == 0 {
+            return Err(String::from(
+                "PERSISTED_QUERIES_CAPACITY must be greater than 0",
+            ));
+        }
+
+        if self.only && self.manifest.is_none() {
+            return Err(String::from(
+                "PERSISTED_QUERIES_MANIFEST must be set when PERSISTED_QUERIES_ONLY is enabled",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for PersistedQueriesConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_PERSISTED_QUERIES_CAPACITY,
+            only: false,
+            manifest: None,
+        }
+    }
+}
+
+/// Handling of `first` and `last` connection arguments greater than the
+/// maximum page size
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PageSizePolicy {
+    /// Silently returns at most `max` nodes
+    #[default]
+    Clamp,
+    /// Rejects the request with a `PAGE_SIZE_EXCEEDED` error
+    Reject,
+}
+
+impl FromStr for PageSizePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "clamp" => Ok(Self::Clamp),
+            "reject" => Ok(Self::Reject),
+            _ => Err(format!("Unknown page size policy: {value}")),
+        }
+    }
+}
+
+/// Default and maximum page sizes enforced on every connection
+#[derive(Clone, Copy, Debug)]
+pub struct PageSizeConfig {
+    pub default: usize,
+    pub max: usize,
+    pub policy: PageSizePolicy,
+}
+
+impl PageSizeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max == 0 {
+            return Err(String::from("MAX_PAGE_SIZE must be greater than 0"));
+        }
+
+        if self.default == 0 || self.default > self.max {
+            return Err(String::from(
+                "DEFAULT_PAGE_SIZE must be between 1 and MAX_PAGE_SIZE",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for PageSizeConfig {
+    fn default() -> Self {
+        Self {
+            default: DEFAULT_PAGE_SIZE,
+            max: DEFAULT_MAX_PAGE_SIZE,
+            policy: PageSizePolicy::default(),
+        }
+    }
+}
+
+/// Token bucket settings for the per client rate limiter. Every query drains
+/// as many tokens as its complexity.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimitConfig {
+    pub capacity: u32,
+    pub refill_per_sec: u32,
+}
+
+impl RateLimitConfig {
+    pub fn validate(&self, complexity_limit: usize) -> Result<(), String> {
+        if self.refill_per_sec == 0 {
+            return Err(String::from(
+                "RATE_LIMIT_REFILL_PER_SEC must be greater than 0",
+            ));
+        }
+
+        if (self.capacity as usize) < complexity_limit {
+            return Err(format!(
+                "RATE_LIMIT_CAPACITY ({}) must not be lower than GRAPHQL_COMPLEXITY_LIMIT ({})",
+                self.capacity, complexity_limit
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_RATE_LIMIT_CAPACITY,
+            refill_per_sec: DEFAULT_RATE_LIMIT_REFILL_PER_SEC,
+        }
+    }
+}
+
+/// Settings applied to the database connection pool
+#[derive(Debug)]
+pub struct DatabasePoolConfig {
+    pub max_connections: u32,
+    pub min_idle: u32,
+    pub connection_timeout: Duration,
+    pub idle_timeout: Duration,
+    /// Queries run through `Database::timed_query` taking longer than this
+    /// are logged
+    pub slow_query_threshold: Duration,
+}
+
+impl DatabasePoolConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_connections == 0 {
+            return Err(String::from(
+                "DATABASE_MAX_CONNECTIONS must be greater than 0",
+            ));
+        }
+
+        if self.min_idle > self.max_connections {
+            return Err(format!(
+                "DATABASE_MIN_IDLE ({}) must not be greater than DATABASE_MAX_CONNECTIONS ({})",
+                self.min_idle, self.max_connections
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for DatabasePoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: DEFAULT_DATABASE_MAX_CONNECTIONS,
+            min_idle: DEFAULT_DATABASE_MIN_IDLE,
+            connection_timeout: Duration::from_secs(DEFAULT_DATABASE_CONNECTION_TIMEOUT_SECS),
+            idle_timeout: Duration::from_secs(DEFAULT_DATABASE_IDLE_TIMEOUT_SECS),
+            slow_query_threshold: Duration::from_millis(DEFAULT_SLOW_QUERY_MS),
+        }
+    }
+}
+
+/// Submission of emails to an SMTP server, read from `SMTP_*` variables
+#[derive(Clone, Debug)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    /// Credentials for `AUTH PLAIN`, the server isn't authenticated with
+    /// when unset
+    pub credentials: Option<(String, String)>,
+    /// Address emails are sent from
+    pub from: String,
+    /// Upgrades the connection with `STARTTLS` before authenticating, only
+    /// to be disabled for servers on a trusted network
+    pub starttls: bool,
+}
+
+impl SmtpConfig {
+    fn from_env(env: &mut EnvVars) -> Option<Self> {
+        let host = env.raw("SMTP_HOST").filter(|host| !host.is_empty())?;
+        let credentials = match (env.raw("SMTP_USERNAME"), env.raw("SMTP_PASSWORD")) {
+            (Some(username), Some(password)) => Some((username, password)),
+            (None, None) => None,
+            _ => {
+                env.fail("Invalid SMTP configuration: SMTP_USERNAME and SMTP_PASSWORD must be provided together");
+                None
+            }
+        };
+
+        Some(Self {
+            host,
+            port: env.optional::<u16>("SMTP_PORT", DEFAULT_SMTP_PORT),
+            credentials,
+            from: env.required::<String>("SMTP_FROM").unwrap_or_default(),
+            starttls: env.optional::<bool>("SMTP_STARTTLS", true),
+        })
+    }
+}
+
+/// Cookie browsers can be handed the access token in by `tokenCreate`,
+/// accepted in place of the `Authorization` header. Disabled unless
+/// `AUTH_COOKIE_NAME` is set.
+#[derive(Clone, Debug)]
+pub struct AuthCookieConfig {
+    pub name: Option<String>,
+    /// Only sends the cookie over HTTPS
+    pub secure: bool,
+    pub same_site: SameSite,
+    /// Lifetime of the cookie, that of the access token it carries
+    pub max_age: Duration,
+}
+
+impl AuthCookieConfig {
+    fn from_env(env: &mut EnvVars, max_age: Duration) -> Self {
+        let same_site = env.optional::<String>("AUTH_COOKIE_SAME_SITE", String::from("strict"));
+        let same_site = match same_site.to_lowercase().as_str() {
+            "strict" => SameSite::Strict,
+            "lax" => SameSite::Lax,
+            "none" => SameSite::None,
+            _ => {
+                env.fail("Invalid auth cookie configuration: AUTH_COOKIE_SAME_SITE must be one of strict, lax or none");
+                SameSite::Strict
+            }
+        };
+
+        Self {
+            name: env.raw("AUTH_COOKIE_NAME").filter(|name| !name.is_empty()),
+            secure: env.optional::<bool>("AUTH_COOKIE_SECURE", true),
+            same_site,
+            max_age,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.same_site == SameSite::None && !self.secure {
+            return Err(String::from(
+                "AUTH_COOKIE_SAME_SITE=none requires AUTH_COOKIE_SECURE",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Builds the cookie carrying `access_token`, `None` when cookies are
+    /// disabled
+    pub fn cookie(&self, access_token: &str) -> Option<Cookie<'static>> {
+        let name = self.name.clone()?;
+
+        Some(
+            Cookie::build(name, String::from(access_token))
+                .path("/")
+                .http_only(true)
+                .secure(self.secure)
+                .same_site(self.same_site)
+                .max_age(time::Duration::seconds(self.max_age.as_secs() as i64))
+                .finish(),
+        )
+    }
+}
+
+impl Default for AuthCookieConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            secure: true,
+            same_site: SameSite::Strict,
+            max_age: Duration::from_secs(DEFAULT_JWT_EXPIRY_SECS),
+        }
+    }
+}
+
+/// OpenTelemetry export settings. Read on their own as tracing is set up
+/// before the rest of the configuration is loaded.
+#[cfg(feature = "otel")]
+#[derive(Debug)]
+pub struct TelemetryConfig {
+    /// Base URL of the OTLP/HTTP collector, spans aren't exported when unset
+    pub endpoint: Option<String>,
+    pub service_name: String,
+}
+
+#[cfg(feature = "otel")]
+impl TelemetryConfig {
+    pub fn from_env() -> Self {
+        Self {
+            endpoint: env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+                .ok()
+                .filter(|endpoint| !endpoint.is_empty()),
+            service_name: env::var("OTEL_SERVICE_NAME")
+                .unwrap_or_else(|_| String::from("nexus-api")),
+        }
+    }
+}
+
+/// Every problem found in the configuration, reported at once so they can
+/// all be fixed before the next attempt
+#[derive(Debug, Eq, PartialEq, thiserror::Error)]
+#[error("Invalid configuration:{}", .problems.iter().map(|problem| format!("\n  - {problem}")).collect::<String>())]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+/// Reads settings from environment variables, recording missing and
+/// malformed values as problems instead of failing on the first one
+struct EnvVars<'a> {
+    lookup: &'a dyn Fn(&str) -> Option<String>,
+    problems: Vec<String>,
+}
+
+impl<'a> EnvVars<'a> {
+    fn new(lookup: &'a dyn Fn(&str) -> Option<String>) -> Self {
+        Self {
+            lookup,
+            problems: Vec::new(),
+        }
+    }
+
+    fn raw(&self, key: &str) -> Option<String> {
+        (self.lookup)(key)
+    }
+
+    fn parse<T>(&mut self, key: &str, value: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match value.parse::<T>() {
+            Ok(parsed) => Some(parsed),
+            Err(err) => {
+                self.fail(&format!("{key} has an invalid value {value:?}: {err}"));
+                None
+            }
+        }
+    }
+
+    /// Reads a variable which must be set, `None` once the problem is
+    /// recorded
+    fn required<T>(&mut self, key: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.raw(key) {
+            Some(value) => self.parse(key, &value),
+            None => {
+                self.fail(&format!("{key} is required but not set"));
+                None
+            }
+        }
+    }
+
+    /// Reads an optional variable, falling back to `default` when it's not
+    /// present or malformed, the latter being recorded
+    fn optional<T>(&mut self, key: &str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.raw(key) {
+            Some(value) => self.parse(key, &value).unwrap_or(default),
+            None => default,
+        }
+    }
+
+    /// Records the outcome of validating a group of settings
+    fn check(&mut self, group: &str, result: Result<(), String>) {
+        if let Err(message) = result {
+            self.fail(&format!("Invalid {group} configuration: {message}"));
+        }
+    }
+
+    fn fail(&mut self, problem: &str) {
+        self.problems.push(String::from(problem));
+    }
+}
+
 impl Config {
-    pub fn new() -> Self {
-        let port = Config::env_var::<u16>("PORT");
-        let host = Config::env_var::<IpAddr>("HOST");
-        let jwt_secret = Config::env_var::<String>("JWT_SECRET");
-        let database_url = Config::env_var::<String>("DATABASE_URL");
+    /// Reads and validates the configuration from environment variables
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Config::from_vars(&|key| env::var(key).ok())
+    }
+
+    fn from_vars(lookup: &dyn Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut env = EnvVars::new(lookup);
+        let port = env.required::<u16>("PORT");
+        let host = env.required::<IpAddr>("HOST");
+        // `JWT_KEYS` takes precedence over the single `JWT_SECRET`
+        let jwt_keys = if env.raw("JWT_KEYS").is_some() {
+            env.required::<JwtKeys>("JWT_KEYS").map(|keys| {
+                let keys = keys.0;
+                let newest_key_id = keys.last().map(|key| key.id.clone()).unwrap_or_default();
+                let current_key_id = env.optional::<String>("JWT_CURRENT_KEY_ID", newest_key_id);
+
+                JwtConfig::with_keys(keys, &current_key_id)
+            })
+        } else {
+            env.required::<String>("JWT_SECRET")
+                .map(|secret| JwtConfig::new(&secret))
+        };
+        let has_jwt_keys = jwt_keys.is_some();
+        let jwt = JwtConfig {
+            algorithm: env.optional::<Algorithm>("JWT_ALGORITHM", Algorithm::HS256),
+            expiry: Duration::from_secs(
+                env.optional::<u64>("JWT_EXPIRY_SECS", DEFAULT_JWT_EXPIRY_SECS),
+            ),
+            issuer: env.optional::<String>("JWT_ISSUER", String::from(DEFAULT_JWT_ISSUER)),
+            audience: env.optional::<String>("JWT_AUDIENCE", String::from(DEFAULT_JWT_AUDIENCE)),
+            ..jwt_keys.unwrap_or_else(|| JwtConfig::with_keys(Vec::new(), DEFAULT_JWT_KEY_ID))
+        };
+
+        // Missing keys are reported already
+        if has_jwt_keys {
+            env.check("JWT", jwt.validate());
+        }
+
+        let argon2 = Argon2Config {
+            memory_kib: env.optional::<u32>("ARGON2_MEMORY_KIB", DEFAULT_ARGON2_MEMORY_KIB),
+            iterations: env.optional::<u32>("ARGON2_ITERATIONS", DEFAULT_ARGON2_ITERATIONS),
+            parallelism: env.optional::<u32>("ARGON2_PARALLELISM", DEFAULT_ARGON2_PARALLELISM),
+            pepper: env
+                .raw("PASSWORD_PEPPER")
+                .filter(|pepper| !pepper.is_empty()),
+        };
+
+        if argon2.pepper.is_none() {
+            tracing::warn!("PASSWORD_PEPPER is not set, password hashes are not peppered");
+        }
+        let password_policy = PasswordPolicyConfig {
+            min_length: env.optional::<usize>("PASSWORD_MIN_LENGTH", DEFAULT_PASSWORD_MIN_LENGTH),
+            require_mixed_case: env.optional::<bool>("PASSWORD_REQUIRE_MIXED_CASE", true),
+            require_digit: env.optional::<bool>("PASSWORD_REQUIRE_DIGIT", true),
+            require_symbol: env.optional::<bool>("PASSWORD_REQUIRE_SYMBOL", false),
+            history_size: env
+                .optional::<usize>("PASSWORD_HISTORY_SIZE", DEFAULT_PASSWORD_HISTORY_SIZE),
+        };
+        let reserved_usernames =
+            env.optional::<ReservedUsernames>("RESERVED_USERNAMES", ReservedUsernames::default());
+        let login_throttle = LoginThrottleConfig {
+            max_failures: env.optional::<u32>("LOGIN_MAX_FAILURES", DEFAULT_LOGIN_MAX_FAILURES),
+            window: Duration::from_secs(env.optional::<u64>(
+                "LOGIN_FAILURE_WINDOW_SECS",
+                DEFAULT_LOGIN_FAILURE_WINDOW_SECS,
+            )),
+            cooldown: Duration::from_secs(
+                env.optional::<u64>("LOGIN_COOLDOWN_SECS", DEFAULT_LOGIN_COOLDOWN_SECS),
+            ),
+        };
+
+        env.check("login throttle", login_throttle.validate());
+
+        let account_lockout = AccountLockoutConfig {
+            threshold: env.optional::<u32>(
+                "ACCOUNT_LOCKOUT_THRESHOLD",
+                DEFAULT_ACCOUNT_LOCKOUT_THRESHOLD,
+            ),
+            duration: Duration::from_secs(
+                env.optional::<u64>("ACCOUNT_LOCKOUT_SECS", DEFAULT_ACCOUNT_LOCKOUT_SECS),
+            ),
+        };
+
+        env.check("account lockout", account_lockout.validate());
+
+        let database_url = env.required::<String>("DATABASE_URL").unwrap_or_default();
+        let database_pool = DatabasePoolConfig {
+            max_connections: env
+                .optional::<u32>("DATABASE_MAX_CONNECTIONS", DEFAULT_DATABASE_MAX_CONNECTIONS),
+            min_idle: env.optional::<u32>("DATABASE_MIN_IDLE", DEFAULT_DATABASE_MIN_IDLE),
+            connection_timeout: Duration::from_secs(env.optional::<u64>(
+                "DATABASE_CONNECTION_TIMEOUT_SECS",
+                DEFAULT_DATABASE_CONNECTION_TIMEOUT_SECS,
+            )),
+            idle_timeout: Duration::from_secs(env.optional::<u64>(
+                "DATABASE_IDLE_TIMEOUT_SECS",
+                DEFAULT_DATABASE_IDLE_TIMEOUT_SECS,
+            )),
+            slow_query_threshold: Duration::from_millis(
+                env.optional::<u64>("SLOW_QUERY_MS", DEFAULT_SLOW_QUERY_MS),
+            ),
+        };
+
+        env.check("database pool", database_pool.validate());
+
+        let graphql = GraphQLConfig {
+            depth_limit: env.optional::<usize>("GRAPHQL_DEPTH_LIMIT", DEFAULT_GRAPHQL_DEPTH_LIMIT),
+            complexity_limit: env
+                .optional::<usize>("GRAPHQL_COMPLEXITY_LIMIT", DEFAULT_GRAPHQL_COMPLEXITY_LIMIT),
+            max_aliases: env.optional::<usize>("MAX_ALIASES", DEFAULT_GRAPHQL_MAX_ALIASES),
+            max_batch_ids: env.optional::<usize>("MAX_BATCH_IDS", DEFAULT_GRAPHQL_MAX_BATCH_IDS),
+            rate_limit: RateLimitConfig {
+                capacity: env.optional::<u32>("RATE_LIMIT_CAPACITY", DEFAULT_RATE_LIMIT_CAPACITY),
+                refill_per_sec: env.optional::<u32>(
+                    "RATE_LIMIT_REFILL_PER_SEC",
+                    DEFAULT_RATE_LIMIT_REFILL_PER_SEC,
+                ),
+            },
+            page_size: PageSizeConfig {
+                default: env.optional::<usize>("DEFAULT_PAGE_SIZE", DEFAULT_PAGE_SIZE),
+                max: env.optional::<usize>("MAX_PAGE_SIZE", DEFAULT_MAX_PAGE_SIZE),
+                policy: env
+                    .optional::<PageSizePolicy>("PAGE_SIZE_POLICY", PageSizePolicy::default()),
+            },
+            read_only: env.optional::<bool>("READ_ONLY", false),
+            persisted_queries: PersistedQueriesConfig {
+                capacity: env.optional::<usize>(
+                    "PERSISTED_QUERIES_CAPACITY",
+                    DEFAULT_PERSISTED_QUERIES_CAPACITY,
+                ),
+                only: env.optional::<bool>("PERSISTED_QUERIES_ONLY", false),
+                manifest: env.raw("PERSISTED_QUERIES_MANIFEST").map(PathBuf::from),
+            },
+            allowlist: env.raw("OPERATION_ALLOWLIST").map(PathBuf::from),
+            disable_introspection: env
+                .optional::<bool>("DISABLE_INTROSPECTION", !cfg!(debug_assertions)),
+            max_request_bytes: env.optional::<u64>("MAX_REQUEST_BYTES", DEFAULT_MAX_REQUEST_BYTES),
+            subscription_keepalive: Duration::from_secs(env.optional::<u64>(
+                "SUBSCRIPTION_KEEPALIVE_SECS",
+                DEFAULT_SUBSCRIPTION_KEEPALIVE_SECS,
+            )),
+            subscription_port: env.optional::<u16>("SUBSCRIPTION_PORT", DEFAULT_SUBSCRIPTION_PORT),
+            query_timeout: Duration::from_secs(
+                env.optional::<u64>("QUERY_TIMEOUT_SECS", DEFAULT_QUERY_TIMEOUT_SECS),
+            ),
+        };
+
+        env.check(
+            "rate limit",
+            graphql.rate_limit.validate(graphql.complexity_limit),
+        );
+
+        env.check("page size", graphql.page_size.validate());
+
+        env.check("persisted queries", graphql.persisted_queries.validate());
+
+        if graphql.subscription_keepalive.is_zero() {
+            env.fail("Invalid subscription configuration: SUBSCRIPTION_KEEPALIVE_SECS must be greater than 0");
+        }
+
+        if port == Some(graphql.subscription_port) {
+            env.fail("Invalid subscription configuration: SUBSCRIPTION_PORT must differ from PORT");
+        }
+
+        if graphql.query_timeout.is_zero() {
+            env.fail(
+                "Invalid query timeout configuration: QUERY_TIMEOUT_SECS must be greater than 0",
+            );
+        }
+
+        let cors = CorsConfig {
+            allowed_origins: env.optional::<AllowedOrigins>(
+                "CORS_ALLOWED_ORIGINS",
+                if cfg!(debug_assertions) {
+                    AllowedOrigins::Any
+                } else {
+                    AllowedOrigins::List(Vec::new())
+                },
+            ),
+            allow_credentials: env
+                .optional::<bool>("CORS_ALLOW_CREDENTIALS", cfg!(debug_assertions)),
+        };
+
+        let auth_cookie = AuthCookieConfig::from_env(&mut env, jwt.expiry);
+
+        env.check("auth cookie", auth_cookie.validate());
+
+        let trusted_proxies =
+            env.optional::<TrustedProxies>("TRUSTED_PROXIES", TrustedProxies::default());
+        let smtp = SmtpConfig::from_env(&mut env);
+        let run_migrations_on_start = env.optional::<bool>("RUN_MIGRATIONS_ON_START", false);
+        let expose_internal_errors =
+            env.optional::<bool>("EXPOSE_INTERNAL_ERRORS", cfg!(debug_assertions));
+        let error_format = env.optional::<ErrorFormat>("ERROR_FORMAT", ErrorFormat::default());
+        let shutdown_timeout = Duration::from_secs(
+            env.optional::<u64>("SHUTDOWN_TIMEOUT_SECS", DEFAULT_SHUTDOWN_TIMEOUT_SECS),
+        );
+        let token_cleanup_interval = Duration::from_secs(env.optional::<u64>(
+            "TOKEN_CLEANUP_INTERVAL_SECS",
+            DEFAULT_TOKEN_CLEANUP_INTERVAL_SECS,
+        ));
+
+        if token_cleanup_interval.is_zero() {
+            env.fail("Invalid token cleanup configuration: TOKEN_CLEANUP_INTERVAL_SECS must be greater than 0");
+        }
+
+        // Required values are only missing along with the problem recorded
+        let (port, host) = match (port, host) {
+            (Some(port), Some(host)) if env.problems.is_empty() => (port, host),
+            _ => {
+                return Err(ConfigError {
+                    problems: env.problems,
+                })
+            }
+        };
+
         let log_level = if cfg!(debug_assertions) {
             LogLevel::Debug
         } else {
@@ -26,52 +1186,381 @@ impl Config {
             address: host,
             port,
             log_level,
+            limits: Limits::default().limit(GRAPHQL_DATA_LIMIT, graphql.max_request_bytes.bytes()),
+            shutdown: Shutdown {
+                ctrlc: true,
+                #[cfg(unix)]
+                signals: [Sig::Term, Sig::Int].into_iter().collect(),
+                grace: shutdown_timeout.as_secs() as u32,
+                ..Shutdown::default()
+            },
             ..rocket::Config::default()
         };
 
-        Config {
-            host,
-            jwt_secret,
-            port,
+        Ok(Config {
+            jwt,
+            argon2,
+            password_policy,
+            reserved_usernames,
+            login_throttle,
+            account_lockout,
             database_url,
+            database_pool,
+            graphql,
+            cors,
+            auth_cookie,
+            trusted_proxies,
+            smtp,
+            run_migrations_on_start,
+            expose_internal_errors,
+            error_format,
+            shutdown_timeout,
+            token_cleanup_interval,
             server_config,
+        })
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// Defaults for tests building services connected to `database_url`
+    pub fn testing(database_url: &str) -> Self {
+        Config {
+            jwt: JwtConfig::new("secret"),
+            argon2: Argon2Config::default(),
+            password_policy: PasswordPolicyConfig::default(),
+            reserved_usernames: ReservedUsernames::default(),
+            login_throttle: LoginThrottleConfig::default(),
+            account_lockout: AccountLockoutConfig::default(),
+            database_url: String::from(database_url),
+            database_pool: DatabasePoolConfig::default(),
+            graphql: GraphQLConfig::default(),
+            cors: CorsConfig {
+                allowed_origins: AllowedOrigins::Any,
+                allow_credentials: false,
+            },
+            auth_cookie: AuthCookieConfig::default(),
+            trusted_proxies: TrustedProxies::default(),
+            smtp: None,
+            run_migrations_on_start: false,
+            expose_internal_errors: false,
+            error_format: ErrorFormat::default(),
+            shutdown_timeout: Duration::from_secs(1),
+            token_cleanup_interval: Duration::from_secs(1),
+            server_config: rocket::Config::default(),
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::SameSite;
+    use std::collections::HashMap;
+
+    use super::{
+        AccountLockoutConfig, AllowedOrigins, AuthCookieConfig, Config, ConfigError,
+        DatabasePoolConfig, JwtConfig, JwtKeys, PageSizeConfig, PageSizePolicy,
+        PersistedQueriesConfig, RateLimitConfig, ReservedUsernames, TrustedProxies,
+        DEFAULT_GRAPHQL_COMPLEXITY_LIMIT,
+    };
+
+    const SECRET: &str = "a-secret-long-enough-to-sign-tokens";
+
+    fn from_vars(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let vars = vars
+            .iter()
+            .map(|(key, value)| (String::from(*key), String::from(*value)))
+            .collect::<HashMap<String, String>>();
+
+        Config::from_vars(&|key| vars.get(key).cloned())
+    }
 
-    fn env_var<T: FromStr>(key: &str) -> T {
-        let value =
-            env::var(key).unwrap_or_else(|_| panic!("Missing environment variable: {}", key));
+    fn required_vars() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("PORT", "7878"),
+            ("HOST", "127.0.0.1"),
+            ("JWT_SECRET", SECRET),
+            ("DATABASE_URL", "postgres://localhost/nexus"),
+        ]
+    }
 
-        if let Ok(parsed) = str::parse::<T>(&value) {
-            return parsed;
+    fn problems(vars: &[(&str, &str)]) -> Vec<String> {
+        match from_vars(vars) {
+            Ok(_) => Vec::new(),
+            Err(err) => err.problems,
         }
+    }
+
+    #[test]
+    fn reads_the_required_variables() {
+        let config = from_vars(&required_vars()).unwrap();
+
+        assert_eq!(config.server_config.port, 7878);
+        assert_eq!(config.database_url, "postgres://localhost/nexus");
+    }
 
-        panic!(
-            "Failed to parse environment variable value from key: {}",
-            key
+    #[test]
+    fn reports_every_missing_variable() {
+        assert_eq!(
+            problems(&[]),
+            [
+                "PORT is required but not set",
+                "HOST is required but not set",
+                "JWT_SECRET is required but not set",
+                "DATABASE_URL is required but not set",
+            ]
         );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::env;
+    #[test]
+    fn reports_malformed_values_along_with_missing_ones() {
+        let problems = problems(&[
+            ("PORT", "NOT_A_NUMBER"),
+            ("HOST", "127.0.0.1"),
+            ("JWT_SECRET", SECRET),
+            ("GRAPHQL_DEPTH_LIMIT", "deep"),
+        ]);
 
-    use super::Config;
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        assert!(problems[0].starts_with("PORT has an invalid value \"NOT_A_NUMBER\""));
+        assert!(problems.contains(&String::from("DATABASE_URL is required but not set")));
+        assert!(problems
+            .iter()
+            .any(|problem| problem.starts_with("GRAPHQL_DEPTH_LIMIT has an invalid value")));
+    }
 
     #[test]
-    #[should_panic(expected = "Missing environment variable: PORT")]
-    fn panics_if_env_variable_not_present() {
-        env::remove_var("PORT");
+    fn reports_invalid_settings() {
+        let mut vars = required_vars();
+
+        vars.push(("JWT_SECRET", "short"));
+        vars.push(("LOGIN_MAX_FAILURES", "0"));
 
-        Config::new();
+        let problems = problems(&vars);
+
+        assert_eq!(problems.len(), 2, "{problems:?}");
+        assert!(problems[0].starts_with("Invalid JWT configuration"));
+        assert!(problems[1].starts_with("Invalid login throttle configuration"));
     }
 
     #[test]
-    #[should_panic(expected = "Failed to parse environment variable value from key: PORT")]
-    fn panics_if_env_variable_couldnt_be_parsed() {
-        env::set_var("PORT", "NOT_A_NUMBER");
+    fn serves_subscriptions_on_a_port_of_their_own() {
+        let config = from_vars(&required_vars()).unwrap();
+
+        assert_eq!(config.graphql.subscription_port, 7879);
+
+        let mut vars = required_vars();
+
+        vars.push(("SUBSCRIPTION_PORT", "7878"));
+
+        assert_eq!(
+            problems(&vars),
+            ["Invalid subscription configuration: SUBSCRIPTION_PORT must differ from PORT"]
+        );
+    }
+
+    #[test]
+    fn lists_problems_on_their_own_line() {
+        let err = ConfigError {
+            problems: vec![
+                String::from("PORT is required but not set"),
+                String::from("HOST is required but not set"),
+            ],
+        };
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid configuration:\n  - PORT is required but not set\n  - HOST is required but not set"
+        );
+    }
+
+    #[test]
+    fn database_pool_rejects_min_idle_greater_than_max_connections() {
+        let database_pool = DatabasePoolConfig {
+            max_connections: 2,
+            min_idle: 3,
+            ..DatabasePoolConfig::default()
+        };
+
+        assert!(database_pool.validate().is_err());
+    }
+
+    #[test]
+    fn database_pool_defaults_are_valid() {
+        assert!(DatabasePoolConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn account_lockout_rejects_zero_threshold_and_duration() {
+        let threshold = AccountLockoutConfig {
+            threshold: 0,
+            ..AccountLockoutConfig::default()
+        };
+        let duration = AccountLockoutConfig {
+            duration: std::time::Duration::ZERO,
+            ..AccountLockoutConfig::default()
+        };
+
+        assert!(threshold.validate().is_err());
+        assert!(duration.validate().is_err());
+        assert!(AccountLockoutConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn rate_limit_rejects_capacity_lower_than_complexity_limit() {
+        let rate_limit = RateLimitConfig {
+            capacity: 10,
+            ..RateLimitConfig::default()
+        };
+
+        assert!(rate_limit.validate(100).is_err());
+    }
+
+    #[test]
+    fn rate_limit_defaults_are_valid() {
+        assert!(RateLimitConfig::default()
+            .validate(DEFAULT_GRAPHQL_COMPLEXITY_LIMIT)
+            .is_ok());
+    }
+
+    #[test]
+    fn parses_page_size_policy() {
+        assert_eq!("clamp".parse(), Ok(PageSizePolicy::Clamp));
+        assert_eq!("Reject".parse(), Ok(PageSizePolicy::Reject));
+        assert!("truncate".parse::<PageSizePolicy>().is_err());
+    }
+
+    #[test]
+    fn page_size_rejects_default_greater_than_max() {
+        let page_size = PageSizeConfig {
+            default: 50,
+            max: 20,
+            ..PageSizeConfig::default()
+        };
+
+        assert!(page_size.validate().is_err());
+        assert!(PageSizeConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn persisted_only_mode_requires_a_manifest() {
+        let persisted_queries = PersistedQueriesConfig {
+            only: true,
+            ..PersistedQueriesConfig::default()
+        };
+
+        assert!(persisted_queries.validate().is_err());
+        assert!(PersistedQueriesConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn parses_allowed_origins() {
+        assert_eq!("*".parse::<AllowedOrigins>(), Ok(AllowedOrigins::Any));
+        assert_eq!(
+            "https://nexus.dev/, http://localhost:3000".parse::<AllowedOrigins>(),
+            Ok(AllowedOrigins::List(vec![
+                String::from("https://nexus.dev"),
+                String::from("http://localhost:3000"),
+            ]))
+        );
+        assert_eq!(
+            "".parse::<AllowedOrigins>(),
+            Ok(AllowedOrigins::List(Vec::new()))
+        );
+    }
+
+    #[test]
+    fn auth_cookies_are_http_only() {
+        let auth_cookie = AuthCookieConfig {
+            name: Some(String::from("nexus_token")),
+            ..AuthCookieConfig::default()
+        };
+        let cookie = auth_cookie.cookie("token").unwrap().to_string();
+
+        assert!(cookie.starts_with("nexus_token=token;"));
+        assert!(cookie.contains("HttpOnly"));
+        assert!(cookie.contains("SameSite=Strict"));
+        assert!(cookie.contains("Secure"));
+        assert!(cookie.contains("Path=/"));
+        assert!(AuthCookieConfig::default().cookie("token").is_none());
+    }
+
+    #[test]
+    fn auth_cookie_same_site_none_requires_secure() {
+        let auth_cookie = AuthCookieConfig {
+            secure: false,
+            same_site: SameSite::None,
+            ..AuthCookieConfig::default()
+        };
+
+        assert!(auth_cookie.validate().is_err());
+        assert!(AuthCookieConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn parses_trusted_proxies() {
+        let proxies = "10.0.0.0/8, 192.168.1.7,, fd00::/8"
+            .parse::<TrustedProxies>()
+            .unwrap();
+
+        assert!(proxies.contains("10.1.2.3".parse().unwrap()));
+        assert!(proxies.contains("192.168.1.7".parse().unwrap()));
+        assert!(proxies.contains("fd00::1".parse().unwrap()));
+        assert!(!proxies.contains("192.168.1.8".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<TrustedProxies>().is_err());
+        assert_eq!("".parse::<TrustedProxies>(), Ok(TrustedProxies::default()));
+    }
+
+    #[test]
+    fn parses_reserved_usernames() {
+        assert_eq!(
+            " Admin, root,,".parse::<ReservedUsernames>(),
+            Ok(ReservedUsernames(vec![
+                String::from("admin"),
+                String::from("root"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn parses_jwt_keys() {
+        let keys = "2022-01:old, 2022-06:new:with:colons"
+            .parse::<JwtKeys>()
+            .unwrap()
+            .0;
+
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].id, "2022-01");
+        assert_eq!(keys[1].secret, "new:with:colons");
+        assert!("2022-01".parse::<JwtKeys>().is_err());
+        assert!(":secret".parse::<JwtKeys>().is_err());
+    }
+
+    #[test]
+    fn jwt_rejects_unknown_current_key_and_duplicate_ids() {
+        let keys = format!("a:{SECRET},b:{SECRET}")
+            .parse::<JwtKeys>()
+            .unwrap()
+            .0;
+
+        assert!(JwtConfig::with_keys(keys.clone(), "b").validate().is_ok());
+        assert!(JwtConfig::with_keys(keys, "c").validate().is_err());
+
+        let duplicated = format!("a:{SECRET},a:{SECRET}")
+            .parse::<JwtKeys>()
+            .unwrap()
+            .0;
+
+        assert!(JwtConfig::with_keys(duplicated, "a").validate().is_err());
+    }
+
+    #[test]
+    fn jwt_rejects_asymmetric_algorithms() {
+        let jwt = JwtConfig {
+            algorithm: jsonwebtoken::Algorithm::RS256,
+            ..JwtConfig::new("secret")
+        };
 
-        Config::new();
+        assert!(jwt.validate().is_err());
     }
 }
@@ -1,7 +1,52 @@
-use sqlx::pool::Pool;
-use sqlx::postgres::{PgPoolOptions, Postgres};
+use futures::future::BoxFuture;
+use once_cell::sync::OnceCell;
+use rocket::tokio::time::timeout;
+use serde::Serialize;
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::pool::{Pool, PoolConnection};
+use sqlx::postgres::{PgConnection, PgPoolOptions, Postgres};
+use sqlx::Transaction;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
 
-use crate::config::Config;
+use crate::config::{Config, DEFAULT_SLOW_QUERY_MS};
+use crate::error::Result;
+
+/// Connection pool usage
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct PoolStats {
+    pub idle_connections: u32,
+    pub active_connections: u32,
+}
+
+/// Handle to the pool used to report its usage from places without access to
+/// the `Database` instance, such as error conversions.
+static CONN_POOL: OnceCell<Pool<Postgres>> = OnceCell::new();
+
+/// Duration after which timed queries are logged as slow, set once on
+/// startup from `DatabasePoolConfig::slow_query_threshold`
+static SLOW_QUERY_THRESHOLD: OnceCell<Duration> = OnceCell::new();
+
+/// Migrations from the `migrations` directory, embedded at compile time
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Retrieves the usage of the application's connection pool, if any
+pub fn pool_stats() -> Option<PoolStats> {
+    CONN_POOL.get().map(PoolStats::from)
+}
+
+impl From<&Pool<Postgres>> for PoolStats {
+    fn from(pool: &Pool<Postgres>) -> Self {
+        let size = pool.size();
+        let idle_connections = pool.num_idle() as u32;
+
+        PoolStats {
+            idle_connections,
+            active_connections: size.saturating_sub(idle_connections),
+        }
+    }
+}
 
 pub struct Database {
     pub conn_pool: Pool<Postgres>,
@@ -9,12 +54,311 @@ pub struct Database {
 
 impl Database {
     pub async fn new(config: &Config) -> Self {
+        let pool_config = &config.database_pool;
         let conn_pool = PgPoolOptions::new()
-            .max_connections(5)
+            .max_connections(pool_config.max_connections)
+            .min_connections(pool_config.min_idle)
+            .connect_timeout(pool_config.connection_timeout)
+            .idle_timeout(pool_config.idle_timeout)
             .connect(&config.database_url)
             .await
             .expect("Failed to establish a Database Connection");
 
+        CONN_POOL.get_or_init(|| conn_pool.clone());
+        SLOW_QUERY_THRESHOLD.get_or_init(|| pool_config.slow_query_threshold);
+
         Self { conn_pool }
     }
+
+    /// Checks out a connection from the pool and runs a trivial query on it.
+    /// Returns `false` if either step fails or takes longer than `limit`.
+    pub async fn ping(&self, limit: Duration) -> bool {
+        let ping = async {
+            let mut conn = self.conn_pool.acquire().await?;
+
+            sqlx::query("SELECT 1").execute(&mut conn).await
+        };
+
+        matches!(timeout(limit, ping).await, Ok(Ok(_)))
+    }
+
+    /// Checks that every embedded migration was applied, unmodified, and
+    /// none is left dirty. Returns `false` if the check fails or takes longer
+    /// than `limit`.
+    pub async fn is_migrated(&self, limit: Duration) -> bool {
+        let check = async {
+            let mut conn = self.conn_pool.acquire().await?;
+
+            if conn.dirty_version().await?.is_some() {
+                return Ok(false);
+            }
+
+            let applied: HashMap<i64, _> = conn
+                .list_applied_migrations()
+                .await?
+                .into_iter()
+                .map(|migration| (migration.version, migration.checksum))
+                .collect();
+
+            Ok::<_, MigrateError>(
+                MIGRATOR
+                    .iter()
+                    .filter(|migration| !migration.migration_type.is_down_migration())
+                    .all(|migration| applied.get(&migration.version) == Some(&migration.checksum)),
+            )
+        };
+
+        matches!(timeout(limit, check).await, Ok(Ok(true)))
+    }
+
+    /// Checks out a connection and runs `query` on it, logging a warning
+    /// when both take longer than the slow query threshold. `name` identifies
+    /// the operation in the log, e.g. `"users.find_page"`.
+    ///
+    /// Warnings are emitted within the request's span, so they carry its
+    /// `request_id`. With the `otel` feature, `query` runs within a
+    /// `db_query` span exported as a child of the request's.
+    pub async fn timed_query<T, F, Fut>(&self, name: &'static str, query: F) -> Result<T>
+    where
+        F: FnOnce(PoolConnection<Postgres>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let started_at = Instant::now();
+        let conn = self.conn_pool.acquire().await?;
+        let checked_out_in = started_at.elapsed();
+        let result = query(conn);
+        #[cfg(feature = "otel")]
+        let result = tracing::Instrument::instrument(
+            result,
+            tracing::info_span!("db_query", otel.kind = "client", db.operation = name),
+        );
+        let result = result.await;
+
+        warn_if_slow(
+            name,
+            checked_out_in,
+            started_at.elapsed(),
+            slow_query_threshold(),
+        );
+
+        result
+    }
+
+    /// Applies pending migrations in order while holding the migrations lock,
+    /// returns the amount of migrations applied. Stops at the first failure,
+    /// and fails without applying any if an applied migration was modified.
+    pub async fn migrate(&self) -> std::result::Result<usize, MigrateError> {
+        let mut conn = self.conn_pool.acquire().await?;
+
+        conn.lock().await?;
+
+        let applied = apply_pending_migrations(&mut conn).await;
+
+        conn.unlock().await?;
+
+        applied
+    }
+
+    /// Runs `f` within a transaction which is committed if `f` succeeds and
+    /// rolled back if it returns an error, so multi-step writes are never
+    /// partially applied.
+    ///
+    /// ```ignore
+    /// database
+    ///     .with_transaction(|tx| Box::pin(async move { insert(tx, &dto).await }))
+    ///     .await?;
+    /// ```
+    pub async fn with_transaction<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send,
+        F: for<'c> FnOnce(&'c mut Transaction<'static, Postgres>) -> BoxFuture<'c, Result<T>>,
+    {
+        let mut tx = self.conn_pool.begin().await?;
+
+        match f(&mut tx).await {
+            Ok(value) => {
+                tx.commit().await?;
+
+                Ok(value)
+            }
+            Err(err) => {
+                if let Err(rollback_err) = tx.rollback().await {
+                    tracing::error!(error = %rollback_err, "failed to roll back transaction");
+                }
+
+                Err(err)
+            }
+        }
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        PoolStats::from(&self.conn_pool)
+    }
+
+    /// Closes every connection of the pool, waiting for checked out
+    /// connections to be returned for up to `limit`
+    pub async fn close(&self, limit: Duration) {
+        if timeout(limit, self.conn_pool.close()).await.is_err() {
+            tracing::warn!("timed out closing the database pool");
+        }
+    }
+}
+
+fn slow_query_threshold() -> Duration {
+    SLOW_QUERY_THRESHOLD
+        .get()
+        .copied()
+        .unwrap_or_else(|| Duration::from_millis(DEFAULT_SLOW_QUERY_MS))
+}
+
+/// Logs the operation `name` if it took longer than `threshold`, returns
+/// whether it did
+fn warn_if_slow(
+    name: &str,
+    checked_out_in: Duration,
+    elapsed: Duration,
+    threshold: Duration,
+) -> bool {
+    if elapsed <= threshold {
+        return false;
+    }
+
+    tracing::warn!(
+        operation = name,
+        elapsed_ms = elapsed.as_millis() as u64,
+        checkout_ms = checked_out_in.as_millis() as u64,
+        threshold_ms = threshold.as_millis() as u64,
+        "slow query"
+    );
+
+    true
+}
+
+async fn apply_pending_migrations(
+    conn: &mut PgConnection,
+) -> std::result::Result<usize, MigrateError> {
+    conn.ensure_migrations_table().await?;
+
+    if let Some(version) = conn.dirty_version().await? {
+        return Err(MigrateError::Dirty(version));
+    }
+
+    let applied: HashMap<i64, _> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| (migration.version, migration.checksum))
+        .collect();
+    let mut applied_count = 0;
+
+    for migration in MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+    {
+        match applied.get(&migration.version) {
+            Some(checksum) if *checksum != migration.checksum => {
+                return Err(MigrateError::VersionMismatch(migration.version));
+            }
+            Some(_) => {}
+            None => {
+                let elapsed = conn.apply(migration).await?;
+
+                tracing::info!(
+                    version = migration.version,
+                    description = %migration.description,
+                    elapsed_ms = elapsed.as_millis() as u64,
+                    "applied migration"
+                );
+                applied_count += 1;
+            }
+        }
+    }
+
+    Ok(applied_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::postgres::PgPoolOptions;
+    use std::time::Duration;
+
+    use crate::error::Error;
+    use crate::modules::audit::{insert_audit_entry, AuditAction, AuditOutcome, NewAuditEntry};
+
+    use super::{warn_if_slow, Database, MIGRATOR};
+
+    #[test]
+    fn only_queries_exceeding_the_threshold_are_slow() {
+        let threshold = Duration::from_millis(200);
+
+        assert!(!warn_if_slow(
+            "users.count",
+            Duration::ZERO,
+            Duration::from_millis(200),
+            threshold
+        ));
+        assert!(warn_if_slow(
+            "users.count",
+            Duration::from_millis(150),
+            Duration::from_millis(201),
+            threshold
+        ));
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn reports_migrated_databases() {
+        let database_url = std::env::var("DATABASE_URL").unwrap();
+        let database = Database {
+            conn_pool: PgPoolOptions::new().connect(&database_url).await.unwrap(),
+        };
+
+        assert!(database.is_migrated(Duration::from_secs(2)).await);
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn failed_transactions_are_rolled_back() {
+        let database_url = std::env::var("DATABASE_URL").unwrap();
+        let database = Database {
+            conn_pool: PgPoolOptions::new().connect(&database_url).await.unwrap(),
+        };
+        let marker = uuid::Uuid::new_v4().to_string();
+        let entry = NewAuditEntry {
+            actor_id: None,
+            target_id: None,
+            action: AuditAction::TokenCreate,
+            outcome: AuditOutcome::Success,
+            ip_address: Some(marker.clone()),
+        };
+
+        let result: crate::error::Result<()> = database
+            .with_transaction(|tx| {
+                Box::pin(async move {
+                    insert_audit_entry(tx, &entry).await?;
+
+                    Err(Error::server_error("rolling the transaction back"))
+                })
+            })
+            .await;
+
+        let (count,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM audit_log WHERE ip_address = $1")
+                .bind(&marker)
+                .fetch_one(&database.conn_pool)
+                .await
+                .unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn embeds_every_migration_in_order() {
+        let files = std::fs::read_dir("migrations").unwrap().count();
+        let versions: Vec<i64> = MIGRATOR.iter().map(|migration| migration.version).collect();
+
+        assert_eq!(versions.len(), files);
+        assert!(versions.windows(2).all(|pair| pair[0] < pair[1]));
+    }
 }
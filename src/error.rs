@@ -1,33 +1,241 @@
-use async_graphql::{Enum, ErrorExtensions};
+use async_graphql::indexmap::IndexMap;
+use async_graphql::{Enum, ErrorExtensionValues, ErrorExtensions, Name, ServerError, Value};
+use chrono::{DateTime, SecondsFormat, Utc};
+use once_cell::sync::{Lazy, OnceCell};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgDatabaseError;
+use std::str::FromStr;
+use std::time::Duration;
 
-use crate::graphql::relay::Base64CursorError;
+use crate::metrics::METRICS;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Message reported to clients for unhandled errors, unless internal errors
+/// are exposed
+const UNHANDLED_ERROR_MESSAGE: &str = "An unhandled error occurred";
+
+/// Whether unhandled errors report their underlying message to clients, set
+/// once on startup from `Config::expose_internal_errors`
+static EXPOSE_INTERNAL_ERRORS: OnceCell<bool> = OnceCell::new();
+
+/// Sets whether unhandled errors report their underlying message to clients.
+/// Has no effect after the first call.
+pub fn expose_internal_errors(expose: bool) {
+    let _ = EXPOSE_INTERNAL_ERRORS.set(expose);
+}
+
+/// PostgreSQL error code for `unique_violation`
+const PG_UNIQUE_VIOLATION: &str = "23505";
+
+/// PostgreSQL error code for `foreign_key_violation`
+const PG_FOREIGN_KEY_VIOLATION: &str = "23503";
+
+/// PostgreSQL error code for `not_null_violation`
+const PG_NOT_NULL_VIOLATION: &str = "23502";
+
+/// Unique constraints mapped to the field they guard and the message reported
+/// when violated, so reported errors don't depend on the database's message
+/// wording.
+const UNIQUE_CONSTRAINTS: &[(&str, &str, &str)] = &[
+    (
+        "users_email_key",
+        "email",
+        "A user with that email already exists",
+    ),
+    (
+        "users_username_key",
+        "username",
+        "A user with that username already exists",
+    ),
+    (
+        "users_email_lower_key",
+        "email",
+        "A user with that email already exists",
+    ),
+    (
+        "users_username_lower_key",
+        "username",
+        "A user with that username already exists",
+    ),
+];
+
+/// Captures the column name from a unique violation detail message such as
+/// `Key (username)=(esteban) already exists.`. Only used for constraints
+/// missing from `UNIQUE_CONSTRAINTS`.
+static UNIQUE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?:\w*)(?:\()(\w*)*(?:\))").unwrap());
+
+/// Captures the related table from a foreign key violation detail message
+/// such as `Key (user_id)=(...) is not present in table "users".`
+static REFERENCED_TABLE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"table "(\w+)""#).unwrap());
+
+/// Captures the argument, and the input object field if any, of
+/// async-graphql's input coercion errors such as `Invalid value for argument
+/// "input", field "lastName" of type "AccountRegisterInput" is required`.
+/// Values rejected by a scalar name the path to them, e.g. `"input.expiresIn"`,
+/// of which the last segment is captured.
+static INVALID_ARGUMENT_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^Invalid value for argument "(?:[\w.]*\.)?(\w+)"(?:, field "(\w+)")?"#).unwrap()
+});
+
 #[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, thiserror::Error, PartialEq, Serialize)]
 pub enum ErrorCode {
+    #[error("ACCOUNT_LOCKED")]
+    AccountLocked,
+    #[error("BAD_INPUT")]
+    BadInput,
     #[error("BASE64_CURSOR_ERROR")]
     Base64CursorError,
+    #[error("CONFLICT")]
+    Conflict,
     #[error("SERVER_ERROR")]
     ServerError,
     #[error("INVALID_CREDENTIALS")]
     InvalidCredentials,
+    #[error("FORBIDDEN")]
+    Forbidden,
     #[error("INVALID_JWT")]
     InvalidJsonWebToken,
+    #[error("EXPIRED_JWT")]
+    ExpiredJsonWebToken,
+    #[error("IMMATURE_JWT")]
+    ImmatureJsonWebToken,
+    #[error("NOT_FOUND")]
+    NotFound,
+    #[error("OPERATION_NOT_ALLOWED")]
+    OperationNotAllowed,
+    #[error("PAGE_SIZE_EXCEEDED")]
+    PageSizeExceeded,
+    #[error("PAYLOAD_TOO_LARGE")]
+    PayloadTooLarge,
+    #[error("PERSISTED_QUERY_NOT_FOUND")]
+    PersistedQueryNotFound,
+    #[error("RATE_LIMITED")]
+    RateLimited,
+    #[error("READ_ONLY")]
+    ReadOnly,
+    #[error("REFERENCE")]
+    Reference,
+    #[error("SERVICE_UNAVAILABLE")]
+    ServiceUnavailable,
+    #[error("TIMEOUT")]
+    Timeout,
+    #[error("TOO_MANY_ALIASES")]
+    TooManyAliases,
     #[error("UNAUTHORIZED")]
     Unauthorized,
     #[error("UNIQUE")]
     Unique,
+    #[error("VALIDATION_ERROR")]
+    ValidationError,
     #[error("UNHANDLED")]
     Unhandled,
 }
 
+/// Coarse grouping of error codes, lets clients handle errors without
+/// enumerating every code
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, thiserror::Error)]
+pub enum ErrorCategory {
+    #[error("AUTH")]
+    Auth,
+    #[error("CONFLICT")]
+    Conflict,
+    #[error("NOT_FOUND")]
+    NotFound,
+    #[error("RATE_LIMIT")]
+    RateLimit,
+    #[error("SERVER")]
+    Server,
+    #[error("VALIDATION")]
+    Validation,
+}
+
+impl ErrorCode {
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            ErrorCode::AccountLocked
+            | ErrorCode::InvalidCredentials
+            | ErrorCode::Forbidden
+            | ErrorCode::InvalidJsonWebToken
+            | ErrorCode::ExpiredJsonWebToken
+            | ErrorCode::ImmatureJsonWebToken
+            | ErrorCode::Unauthorized => ErrorCategory::Auth,
+            ErrorCode::Conflict | ErrorCode::Reference | ErrorCode::Unique => {
+                ErrorCategory::Conflict
+            }
+            ErrorCode::NotFound => ErrorCategory::NotFound,
+            ErrorCode::RateLimited => ErrorCategory::RateLimit,
+            ErrorCode::ReadOnly
+            | ErrorCode::ServerError
+            | ErrorCode::ServiceUnavailable
+            | ErrorCode::Timeout
+            | ErrorCode::Unhandled => ErrorCategory::Server,
+            ErrorCode::BadInput
+            | ErrorCode::Base64CursorError
+            | ErrorCode::OperationNotAllowed
+            | ErrorCode::PageSizeExceeded
+            | ErrorCode::PayloadTooLarge
+            | ErrorCode::PersistedQueryNotFound
+            | ErrorCode::TooManyAliases
+            | ErrorCode::ValidationError => ErrorCategory::Validation,
+        }
+    }
+}
+
+/// Sets the extensions every error carries: its `code`, the `category` of
+/// the code and the `timestamp` it was reported at.
+fn set_envelope(extensions: &mut ErrorExtensionValues, code: ErrorCode) {
+    extensions.set("code", code.to_string());
+    extensions.set("category", code.category().to_string());
+    extensions.set(
+        "timestamp",
+        Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+    );
+}
+
+/// Gives errors reported by async-graphql itself, such as parse errors,
+/// unknown fields or values of the wrong type, the envelope of our own errors
+/// with the `BAD_INPUT` code. The offending `field` is set when the message
+/// names it. Errors which already carry a `code` are left untouched.
+pub fn reshape_framework_error(error: &mut ServerError) {
+    if error
+        .extensions
+        .as_ref()
+        .is_some_and(|extensions| extensions.get("code").is_some())
+    {
+        return;
+    }
+
+    METRICS.record_error(ErrorCode::BadInput);
+
+    let field = INVALID_ARGUMENT_RE
+        .captures(&error.message)
+        .and_then(|captures| {
+            captures
+                .get(2)
+                .or_else(|| captures.get(1))
+                .map(|field| field.as_str().to_string())
+        });
+    let extensions = error.extensions.get_or_insert_with(Default::default);
+
+    set_envelope(extensions, ErrorCode::BadInput);
+
+    if let Some(field) = field {
+        extensions.set("field", field);
+    }
+
+    extensions.set("message", error.message.clone());
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct Error {
     pub field: Option<String>,
     pub message: Option<String>,
     pub code: ErrorCode,
+    /// Seconds to wait before retrying, set for `RATE_LIMITED` errors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
 }
 
 impl Error {
@@ -36,6 +244,7 @@ impl Error {
             field: Some(field.to_string()),
             message: Some(message.to_string()),
             code,
+            retry_after_secs: None,
         }
     }
 
@@ -44,14 +253,142 @@ impl Error {
             field: None,
             message: None,
             code,
+            retry_after_secs: None,
         }
     }
 
-    pub fn server_error() -> Self {
+    /// Creates an error hiding `source`, the failure, from the client. It's
+    /// logged so it can be correlated through the request's `requestId`.
+    pub fn server_error(source: impl std::fmt::Display) -> Self {
+        tracing::error!(error = %source, "server error");
+
         Self {
             field: None,
             message: None,
             code: ErrorCode::ServerError,
+            retry_after_secs: None,
+        }
+    }
+
+    /// Creates an error for a transient failure, clients are expected to retry
+    /// the request later
+    pub fn service_unavailable() -> Self {
+        Self {
+            field: None,
+            message: Some(String::from(
+                "The service is temporarily unavailable, please retry",
+            )),
+            code: ErrorCode::ServiceUnavailable,
+            retry_after_secs: None,
+        }
+    }
+
+    /// Creates the error reported for request bodies larger than
+    /// `limit_bytes`
+    pub fn payload_too_large(limit_bytes: u64) -> Self {
+        Self {
+            field: None,
+            message: Some(format!(
+                "The request body must not exceed {limit_bytes} bytes"
+            )),
+            code: ErrorCode::PayloadTooLarge,
+            retry_after_secs: None,
+        }
+    }
+
+    /// Creates the error reported for operations selecting more than `limit`
+    /// aliased or repeated fields
+    pub fn too_many_aliases(limit: usize) -> Self {
+        Self {
+            field: None,
+            message: Some(format!(
+                "The query exceeds the limit of {limit} aliased or repeated fields"
+            )),
+            code: ErrorCode::TooManyAliases,
+            retry_after_secs: None,
+        }
+    }
+
+    /// Creates the error reported for operations cancelled after executing
+    /// for longer than `limit`
+    pub fn timeout(limit: Duration) -> Self {
+        Self {
+            field: None,
+            message: Some(format!(
+                "The operation exceeded the time limit of {} seconds",
+                limit.as_secs()
+            )),
+            code: ErrorCode::Timeout,
+            retry_after_secs: None,
+        }
+    }
+
+    /// Creates the error reported for operations missing from the allowlist
+    pub fn operation_not_allowed() -> Self {
+        Self {
+            field: None,
+            message: Some(String::from(
+                "The operation is not on the allowlist of this server",
+            )),
+            code: ErrorCode::OperationNotAllowed,
+            retry_after_secs: None,
+        }
+    }
+
+    /// Creates the error reported for mutations while read-only mode is
+    /// enabled
+    pub fn read_only() -> Self {
+        Self {
+            field: None,
+            message: Some(String::from(
+                "The server is in read-only mode, mutations are temporarily disabled",
+            )),
+            code: ErrorCode::ReadOnly,
+            retry_after_secs: None,
+        }
+    }
+
+    /// Creates an error for an authenticated user lacking the permissions
+    /// to perform the provided `action`, e.g. `"delete this user"`.
+    pub fn forbidden(action: &str) -> Self {
+        Self {
+            field: None,
+            message: Some(format!("You are not allowed to {action}")),
+            code: ErrorCode::Forbidden,
+            retry_after_secs: None,
+        }
+    }
+
+    /// Creates an error for a state change the current state of the
+    /// resource doesn't allow, e.g. when it was already performed
+    pub fn conflict(message: &str) -> Self {
+        Self {
+            field: None,
+            message: Some(String::from(message)),
+            code: ErrorCode::Conflict,
+            retry_after_secs: None,
+        }
+    }
+
+    /// The `resource` was modified since the client fetched the `version`
+    /// it provided, the client must refetch it before trying again
+    pub fn stale_version(resource: &str) -> Self {
+        Self {
+            field: Some(String::from("version")),
+            message: Some(format!(
+                "The {resource} was modified in the meantime, refetch it and try again"
+            )),
+            code: ErrorCode::Conflict,
+            retry_after_secs: None,
+        }
+    }
+
+    pub fn not_found(resource: &str, id: &str) -> Self {
+        Self {
+            field: None,
+            message: Some(format!("No {resource} found with id {id}")),
+            code: ErrorCode::NotFound,
+            retry_after_secs: None,
         }
     }
 
@@ -61,6 +398,7 @@ impl Error {
                 field: Some(field.to_string()),
                 message: Some(format!("A {field} with {value} already exists")),
                 code: ErrorCode::Unique,
+                retry_after_secs: None,
             };
         }
 
@@ -68,14 +406,32 @@ impl Error {
             field: Some(field.to_string()),
             message: Some(format!("The {field} already exists")),
             code: ErrorCode::Unique,
+            retry_after_secs: None,
         }
     }
 
+    /// Creates an error for an unexpected failure. The underlying error is
+    /// logged and only reported to clients when internal errors are exposed.
     pub fn unhandled(err: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        let expose = EXPOSE_INTERNAL_ERRORS.get().copied().unwrap_or(false);
+
+        Self::unhandled_with(err.as_ref(), expose)
+    }
+
+    fn unhandled_with(err: &(dyn std::error::Error + Send + Sync), expose: bool) -> Self {
+        tracing::error!(error = %err, "unhandled error");
+
+        let message = if expose {
+            err.to_string()
+        } else {
+            String::from(UNHANDLED_ERROR_MESSAGE)
+        };
+
         Self {
-            field: Some(String::from("An unhandled error ocurred")),
-            message: Some(err.to_string()),
+            field: None,
+            message: Some(message),
             code: ErrorCode::Unhandled,
+            retry_after_secs: None,
         }
     }
 
@@ -84,40 +440,343 @@ impl Error {
             field: None,
             message: Some(String::from("Missing access token")),
             code: ErrorCode::Unauthorized,
+            retry_after_secs: None,
+        }
+    }
+
+    pub fn rate_limited(retry_after: Duration) -> Self {
+        // Rounded up, retrying earlier than the hint is rejected again
+        let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+        Self {
+            field: None,
+            message: Some(format!("Too many requests, retry after {seconds} seconds")),
+            code: ErrorCode::RateLimited,
+            retry_after_secs: Some(seconds),
         }
     }
+
+    /// Creates the error rejecting logins to an account locked until
+    /// `locked_until`, clients are told when to retry as when rate limited
+    pub fn account_locked(locked_until: DateTime<Utc>) -> Self {
+        let remaining = (locked_until - Utc::now()).to_std().unwrap_or_default();
+        let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+
+        Self {
+            field: None,
+            message: Some(format!(
+                "The account is locked until {}",
+                locked_until.to_rfc3339_opts(SecondsFormat::Secs, true)
+            )),
+            code: ErrorCode::AccountLocked,
+            retry_after_secs: Some(seconds),
+        }
+    }
+}
+
+/// A set of field-level errors reported together, so clients can display
+/// every invalid field in a single round trip.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ValidationError {
+    pub errors: Vec<Error>,
 }
 
+impl ValidationError {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, error: Error) {
+        self.errors.push(error);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns `Ok(())` when no errors were collected, otherwise returns
+    /// `self` as the error.
+    pub fn into_result(self) -> std::result::Result<(), ValidationError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        Err(self)
+    }
+}
+
+/// Keys of the error extensions shortened by `ErrorFormat::Compact`
+const COMPACT_KEYS: [(&str, &str); 3] = [("code", "c"), ("field", "f"), ("message", "m")];
+
+/// Shape of the error extensions sent to clients, negotiated through the
+/// `X-Error-Format` header and defaulting to `ERROR_FORMAT`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ErrorFormat {
+    /// `code`, `field` and `message`
+    #[default]
+    Verbose,
+    /// `c`, `f` and `m`, for clients sensitive to the size of responses
+    Compact,
+}
+
+impl ErrorFormat {
+    /// Rewrites the extensions of `errors`, validation errors included.
+    /// Errors are built verbose, as extensions and routes inspecting them
+    /// expect, and only rewritten once the response is complete.
+    pub fn apply(self, errors: &mut [ServerError]) {
+        if self == ErrorFormat::Verbose {
+            return;
+        }
+
+        for extensions in errors
+            .iter_mut()
+            .filter_map(|error| error.extensions.as_mut())
+        {
+            for (key, short) in COMPACT_KEYS {
+                if let Some(value) = extensions.get(key).cloned() {
+                    extensions.unset(key);
+                    extensions.set(short, value);
+                }
+            }
+
+            if let Some(Value::List(errors)) = extensions.get("errors").cloned() {
+                extensions.set(
+                    "errors",
+                    Value::List(errors.into_iter().map(compact_object).collect()),
+                );
+            }
+        }
+    }
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "verbose" => Ok(Self::Verbose),
+            "compact" => Ok(Self::Compact),
+            _ => Err(format!("Unknown error format: {value}")),
+        }
+    }
+}
+
+/// Shortens the keys of an error rendered by `From<&Error> for Value`
+fn compact_object(value: Value) -> Value {
+    let Value::Object(object) = value else {
+        return value;
+    };
+
+    Value::Object(
+        object
+            .into_iter()
+            .map(|(key, value)| {
+                let short = COMPACT_KEYS
+                    .iter()
+                    .find(|(long, _)| key.as_str() == *long)
+                    .map(|(_, short)| Name::new(short));
+
+                (short.unwrap_or(key), value)
+            })
+            .collect(),
+    )
+}
+
+impl From<&Error> for Value {
+    fn from(err: &Error) -> Self {
+        let mut object = IndexMap::new();
+
+        if let Some(field) = &err.field {
+            object.insert(Name::new("field"), Value::from(field.to_string()));
+        }
+
+        if let Some(message) = &err.message {
+            object.insert(Name::new("message"), Value::from(message.to_string()));
+        }
+
+        object.insert(Name::new("code"), Value::from(err.code.to_string()));
+
+        Value::Object(object)
+    }
+}
+
+impl From<ValidationError> for async_graphql::Error {
+    fn from(err: ValidationError) -> Self {
+        METRICS.record_error(ErrorCode::ValidationError);
+
+        let gql_error = async_graphql::Error::new("Validation failed");
+
+        gql_error.extend_with(|_, e| {
+            set_envelope(e, ErrorCode::ValidationError);
+            e.set(
+                "errors",
+                Value::List(err.errors.iter().map(Value::from).collect()),
+            );
+        })
+    }
+}
+
+/// Resolvers' errors are located by async-graphql, which sets their `path`
+/// once they are returned, see `graphql::error_path::ErrorPath`
 impl From<Error> for async_graphql::Error {
     fn from(err: Error) -> Self {
+        METRICS.record_error(err.code);
+
         let gql_error = async_graphql::Error::new("An error occurred");
 
         gql_error.extend_with(|_, e| {
-            if let Some(message) = &err.message {
-                e.set("message", message.to_string());
-            }
+            set_envelope(e, err.code);
 
             if let Some(field) = &err.field {
                 e.set("field", field.to_string());
             }
 
-            e.set("code", err.code.to_string());
+            if let Some(message) = &err.message {
+                e.set("message", message.to_string());
+            }
+
+            if let Some(retry_after_secs) = err.retry_after_secs {
+                e.set("retryAfterSecs", retry_after_secs);
+            }
         })
     }
 }
 
 impl From<sqlx::error::Error> for Error {
     fn from(err: sqlx::error::Error) -> Self {
+        if let sqlx::error::Error::RowNotFound = &err {
+            return Error::code(ErrorCode::NotFound);
+        }
+
+        // Every connection is busy, the request may succeed once one is
+        // released
+        if let sqlx::error::Error::PoolTimedOut = &err {
+            let stats = crate::database::pool_stats().unwrap_or_default();
+
+            tracing::warn!(
+                idle_connections = stats.idle_connections,
+                active_connections = stats.active_connections,
+                "timed out waiting for a database connection"
+            );
+            return Error::service_unavailable();
+        }
+
+        if let sqlx::error::Error::Database(db_err) = &err {
+            let pg_err = db_err.try_downcast_ref::<PgDatabaseError>();
+
+            match db_err.code().as_deref() {
+                Some(PG_UNIQUE_VIOLATION) => {
+                    let error = unique_violation(
+                        pg_err.and_then(|pg_err| pg_err.constraint()),
+                        pg_err.and_then(|pg_err| pg_err.detail()),
+                    );
+
+                    if let Some(error) = error {
+                        tracing::debug!(field = ?error.field, "unique constraint violation");
+                        return error;
+                    }
+                }
+                Some(PG_FOREIGN_KEY_VIOLATION) => {
+                    let error = foreign_key_violation(
+                        pg_err.and_then(|pg_err| pg_err.detail()),
+                        pg_err.and_then(|pg_err| pg_err.table()),
+                    );
+
+                    tracing::debug!(
+                        constraint = ?pg_err.and_then(|pg_err| pg_err.constraint()),
+                        "foreign key constraint violation"
+                    );
+                    return error;
+                }
+                Some(PG_NOT_NULL_VIOLATION) => {
+                    if let Some(column) = pg_err.and_then(|pg_err| pg_err.column()) {
+                        tracing::debug!(column, "not null constraint violation");
+                        return not_null_violation(column);
+                    }
+                }
+                _ => {}
+            }
+        }
+
         Self::unhandled(Box::new(err))
     }
 }
 
+/// Extracts the offending column name from a unique violation detail
+/// message. Returns `None` if the message doesn't match the expected format.
+fn unique_violation_field(details: &str) -> Option<String> {
+    UNIQUE_RE
+        .captures(details)
+        .and_then(|captures| captures.get(1))
+        .map(|field| field.as_str().to_string())
+        .filter(|field| !field.is_empty())
+}
+
+/// Builds the error for a unique violation, preferring the constraint name
+/// over the detail message which varies across locales and versions.
+fn unique_violation(constraint: Option<&str>, details: Option<&str>) -> Option<Error> {
+    let known = constraint.and_then(|constraint| {
+        UNIQUE_CONSTRAINTS
+            .iter()
+            .find(|(name, _, _)| *name == constraint)
+    });
+
+    if let Some((_, field, message)) = known {
+        return Some(Error::new(field, message, ErrorCode::Unique));
+    }
+
+    details
+        .and_then(unique_violation_field)
+        .map(|field| Error::unique(&field, None))
+}
+
+/// Builds the error for a foreign key violation. The relation named is the
+/// one from the detail message, which is the referenced table on inserts and
+/// the referencing one on deletes, falling back to the table the statement
+/// ran against.
+fn foreign_key_violation(details: Option<&str>, table: Option<&str>) -> Error {
+    let field = details.and_then(unique_violation_field);
+    let relation = details
+        .and_then(|details| REFERENCED_TABLE_RE.captures(details))
+        .and_then(|captures| captures.get(1))
+        .map(|relation| relation.as_str())
+        .or(table);
+    let message = match relation {
+        Some(relation) => {
+            format!("The record is related to a missing or existing \"{relation}\" record")
+        }
+        None => String::from("The record is related to a missing or existing record"),
+    };
+
+    Error {
+        field,
+        message: Some(message),
+        code: ErrorCode::Reference,
+        retry_after_secs: None,
+    }
+}
+
+/// Builds the error for a not null violation on `column`
+fn not_null_violation(column: &str) -> Error {
+    Error::new(column, "A value is required", ErrorCode::ValidationError)
+}
+
 impl From<jsonwebtoken::errors::Error> for Error {
     fn from(err: jsonwebtoken::errors::Error) -> Self {
         use jsonwebtoken::errors::ErrorKind;
 
         match err.kind() {
-            ErrorKind::InvalidToken => Error::code(ErrorCode::InvalidJsonWebToken),
+            ErrorKind::InvalidToken
+            | ErrorKind::InvalidSignature
+            | ErrorKind::InvalidIssuer
+            | ErrorKind::InvalidAudience
+            | ErrorKind::InvalidAlgorithm
+            | ErrorKind::MissingRequiredClaim(_)
+            | ErrorKind::Base64(_)
+            | ErrorKind::Json(_)
+            | ErrorKind::Utf8(_) => Error::code(ErrorCode::InvalidJsonWebToken),
+            ErrorKind::ExpiredSignature => Error::code(ErrorCode::ExpiredJsonWebToken),
+            ErrorKind::ImmatureSignature => Error::code(ErrorCode::ImmatureJsonWebToken),
             _ => Error::unhandled(Box::new(err)),
         }
     }
@@ -129,15 +788,400 @@ impl From<argon2::Error> for Error {
     }
 }
 
-impl From<Base64CursorError> for Error {
-    fn from(_err: Base64CursorError) -> Self {
-        Error::code(ErrorCode::Base64CursorError)
+impl From<async_graphql::Error> for Error {
+    fn from(err: async_graphql::Error) -> Self {
+        tracing::error!(error = ?err, "graphql error collapsed into server error");
+        Error::code(ErrorCode::ServerError)
     }
 }
 
-impl From<async_graphql::Error> for Error {
-    fn from(err: async_graphql::Error) -> Self {
-        println!("{:?}", err);
-        Error::server_error()
+#[cfg(test)]
+mod tests {
+    use async_graphql::Value;
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::{
+        foreign_key_violation, not_null_violation, unique_violation, unique_violation_field, Error,
+        ErrorCategory, ErrorCode, ErrorFormat, ValidationError,
+    };
+
+    #[test]
+    fn validation_error_emits_every_error_in_extensions() {
+        let mut validation_error = ValidationError::new();
+
+        validation_error.push(Error::new(
+            "email",
+            "Invalid email",
+            ErrorCode::ValidationError,
+        ));
+        validation_error.push(Error::new(
+            "password",
+            "Password is too short",
+            ErrorCode::ValidationError,
+        ));
+
+        let gql_error = async_graphql::Error::from(validation_error);
+        let extensions = gql_error.extensions.unwrap();
+
+        match extensions.get("errors") {
+            Some(Value::List(errors)) => assert_eq!(errors.len(), 2),
+            _ => panic!("Expected a list of errors"),
+        }
+    }
+
+    #[test]
+    fn forbidden_message_names_action() {
+        let error = Error::forbidden("delete this user");
+
+        assert_eq!(error.code, ErrorCode::Forbidden);
+        assert_eq!(
+            error.message,
+            Some(String::from("You are not allowed to delete this user"))
+        );
+    }
+
+    #[test]
+    fn account_locked_reports_when_the_lock_expires() {
+        let locked_until = Utc.ymd(2099, 1, 1).and_hms(12, 0, 0);
+        let error = Error::account_locked(locked_until);
+
+        assert_eq!(error.code, ErrorCode::AccountLocked);
+        assert_eq!(
+            error.message.as_deref(),
+            Some("The account is locked until 2099-01-01T12:00:00Z")
+        );
+        assert!(error.retry_after_secs.unwrap() > 0);
+    }
+
+    #[test]
+    fn conflicts_are_not_tied_to_a_field() {
+        let error = Error::conflict("The token is already revoked");
+
+        assert_eq!(error.code, ErrorCode::Conflict);
+        assert_eq!(error.field, None);
+        assert_eq!(
+            error.message.as_deref(),
+            Some("The token is already revoked")
+        );
+    }
+
+    #[test]
+    fn stale_versions_point_at_the_version_field() {
+        let error = Error::stale_version("user");
+
+        assert_eq!(error.code, ErrorCode::Conflict);
+        assert_eq!(error.field.as_deref(), Some("version"));
+    }
+
+    #[test]
+    fn not_found_message_names_resource_and_id() {
+        let error = Error::not_found("user", "abc");
+
+        assert_eq!(error.code, ErrorCode::NotFound);
+        assert_eq!(
+            error.message,
+            Some(String::from("No user found with id abc"))
+        );
+    }
+
+    #[test]
+    fn expired_signature_maps_to_expired_jwt() {
+        use jsonwebtoken::errors::ErrorKind;
+
+        let error = Error::from(jsonwebtoken::errors::Error::from(
+            ErrorKind::ExpiredSignature,
+        ));
+
+        assert_eq!(error.code, ErrorCode::ExpiredJsonWebToken);
+    }
+
+    #[test]
+    fn immature_signature_maps_to_immature_jwt() {
+        use jsonwebtoken::errors::ErrorKind;
+
+        let error = Error::from(jsonwebtoken::errors::Error::from(
+            ErrorKind::ImmatureSignature,
+        ));
+
+        assert_eq!(error.code, ErrorCode::ImmatureJsonWebToken);
+    }
+
+    #[test]
+    fn row_not_found_maps_to_not_found() {
+        let error = Error::from(sqlx::error::Error::RowNotFound);
+
+        assert_eq!(error.code, ErrorCode::NotFound);
+    }
+
+    #[test]
+    fn unique_violation_field_from_details() {
+        let details = "Key (username)=(esteban) already exists.";
+
+        assert_eq!(
+            unique_violation_field(details),
+            Some(String::from("username"))
+        );
+    }
+
+    #[test]
+    fn unique_violation_field_is_none_on_unexpected_details() {
+        assert_eq!(unique_violation_field("duplicate key value"), None);
+    }
+
+    #[test]
+    fn unique_violation_maps_known_constraint() {
+        let error = unique_violation(
+            Some("users_email_key"),
+            Some("Clé (email)=(esteban@nexus.dev) existe déjà."),
+        )
+        .unwrap();
+
+        assert_eq!(error.code, ErrorCode::Unique);
+        assert_eq!(error.field.as_deref(), Some("email"));
+        assert_eq!(
+            error.message.as_deref(),
+            Some("A user with that email already exists")
+        );
+    }
+
+    #[test]
+    fn unique_violation_falls_back_to_details() {
+        let error = unique_violation(
+            Some("refresh_tokens_token_hash_key"),
+            Some("Key (token_hash)=(abc) already exists."),
+        )
+        .unwrap();
+
+        assert_eq!(error.field.as_deref(), Some("token_hash"));
+        assert_eq!(
+            error.message.as_deref(),
+            Some("The token_hash already exists")
+        );
+    }
+
+    #[test]
+    fn unique_violation_is_none_without_constraint_or_details() {
+        assert!(unique_violation(None, None).is_none());
+    }
+
+    #[test]
+    fn pool_timeout_maps_to_service_unavailable() {
+        let error = Error::from(sqlx::error::Error::PoolTimedOut);
+
+        assert_eq!(error.code, ErrorCode::ServiceUnavailable);
+        assert!(error.message.unwrap().contains("retry"));
+    }
+
+    #[test]
+    fn connection_failure_is_not_retryable() {
+        let error = Error::from(sqlx::error::Error::Configuration(
+            "password authentication failed".into(),
+        ));
+
+        assert_ne!(error.code, ErrorCode::ServiceUnavailable);
+    }
+
+    #[test]
+    fn unique_violation_maps_case_insensitive_email_index() {
+        let error = unique_violation(
+            Some("users_email_lower_key"),
+            Some("Key (lower(email::text))=(user@example.com) already exists."),
+        )
+        .unwrap();
+
+        assert_eq!(error.code, ErrorCode::Unique);
+        assert_eq!(error.field.as_deref(), Some("email"));
+    }
+
+    #[test]
+    fn unhandled_hides_internal_message() {
+        let err = std::io::Error::other("relation \"users\" does not exist");
+        let error = Error::unhandled_with(&err, false);
+
+        assert_eq!(error.code, ErrorCode::Unhandled);
+        assert_eq!(error.field, None);
+        assert_eq!(
+            error.message.as_deref(),
+            Some("An unhandled error occurred")
+        );
+    }
+
+    #[test]
+    fn unhandled_exposes_internal_message_when_enabled() {
+        let err = std::io::Error::other("relation \"users\" does not exist");
+        let error = Error::unhandled_with(&err, true);
+
+        assert_eq!(
+            error.message.as_deref(),
+            Some("relation \"users\" does not exist")
+        );
+    }
+
+    #[test]
+    fn foreign_key_violation_names_the_relation() {
+        let error = foreign_key_violation(
+            Some(
+                r#"Key (user_id)=(2f0d0d6e-9c1a-4a0e-8d2b-3f3c53d5e0a1) is not present in table "users"."#,
+            ),
+            Some("refresh_tokens"),
+        );
+
+        assert_eq!(error.code, ErrorCode::Reference);
+        assert_eq!(error.field.as_deref(), Some("user_id"));
+        assert!(error.message.unwrap().contains(r#""users""#));
+    }
+
+    #[test]
+    fn foreign_key_violation_falls_back_to_table() {
+        let error = foreign_key_violation(None, Some("refresh_tokens"));
+
+        assert_eq!(error.code, ErrorCode::Reference);
+        assert_eq!(error.field, None);
+        assert!(error.message.unwrap().contains(r#""refresh_tokens""#));
+    }
+
+    #[test]
+    fn not_null_violation_names_the_column() {
+        let error = not_null_violation("username");
+
+        assert_eq!(error.code, ErrorCode::ValidationError);
+        assert_eq!(error.field.as_deref(), Some("username"));
+    }
+
+    #[test]
+    fn every_error_carries_the_envelope_extensions() {
+        let codes = [
+            (ErrorCode::AccountLocked, ErrorCategory::Auth),
+            (ErrorCode::BadInput, ErrorCategory::Validation),
+            (ErrorCode::Base64CursorError, ErrorCategory::Validation),
+            (ErrorCode::ServerError, ErrorCategory::Server),
+            (ErrorCode::InvalidCredentials, ErrorCategory::Auth),
+            (ErrorCode::Forbidden, ErrorCategory::Auth),
+            (ErrorCode::InvalidJsonWebToken, ErrorCategory::Auth),
+            (ErrorCode::ExpiredJsonWebToken, ErrorCategory::Auth),
+            (ErrorCode::ImmatureJsonWebToken, ErrorCategory::Auth),
+            (ErrorCode::NotFound, ErrorCategory::NotFound),
+            (ErrorCode::PageSizeExceeded, ErrorCategory::Validation),
+            (ErrorCode::OperationNotAllowed, ErrorCategory::Validation),
+            (ErrorCode::PayloadTooLarge, ErrorCategory::Validation),
+            (ErrorCode::PersistedQueryNotFound, ErrorCategory::Validation),
+            (ErrorCode::RateLimited, ErrorCategory::RateLimit),
+            (ErrorCode::ReadOnly, ErrorCategory::Server),
+            (ErrorCode::Conflict, ErrorCategory::Conflict),
+            (ErrorCode::Reference, ErrorCategory::Conflict),
+            (ErrorCode::ServiceUnavailable, ErrorCategory::Server),
+            (ErrorCode::Timeout, ErrorCategory::Server),
+            (ErrorCode::TooManyAliases, ErrorCategory::Validation),
+            (ErrorCode::Unauthorized, ErrorCategory::Auth),
+            (ErrorCode::Unique, ErrorCategory::Conflict),
+            (ErrorCode::ValidationError, ErrorCategory::Validation),
+            (ErrorCode::Unhandled, ErrorCategory::Server),
+        ];
+
+        for (code, category) in codes {
+            let extensions = async_graphql::Error::from(Error::code(code))
+                .extensions
+                .unwrap();
+
+            assert_eq!(extensions.get("code"), Some(&Value::from(code.to_string())));
+            assert_eq!(
+                extensions.get("category"),
+                Some(&Value::from(category.to_string()))
+            );
+            assert!(extensions.get("field").is_none());
+            assert!(extensions.get("message").is_none());
+
+            match extensions.get("timestamp") {
+                Some(Value::String(timestamp)) => {
+                    assert!(DateTime::parse_from_rfc3339(timestamp).is_ok())
+                }
+                _ => panic!("Expected a timestamp"),
+            }
+        }
+    }
+
+    #[test]
+    fn envelope_includes_field_and_message_when_present() {
+        let extensions = async_graphql::Error::from(Error::new(
+            "email",
+            "Invalid email",
+            ErrorCode::ValidationError,
+        ))
+        .extensions
+        .unwrap();
+
+        assert_eq!(extensions.get("field"), Some(&Value::from("email")));
+        assert_eq!(
+            extensions.get("message"),
+            Some(&Value::from("Invalid email"))
+        );
+    }
+
+    #[test]
+    fn validation_error_carries_the_envelope_extensions() {
+        let extensions = async_graphql::Error::from(ValidationError::new())
+            .extensions
+            .unwrap();
+
+        assert_eq!(extensions.get("category"), Some(&Value::from("VALIDATION")));
+        assert!(extensions.get("timestamp").is_some());
+    }
+
+    fn formatted(format: ErrorFormat, error: async_graphql::Error) -> serde_json::Value {
+        let mut errors = [error.into_server_error(Default::default())];
+
+        format.apply(&mut errors);
+        serde_json::to_value(&errors[0]).unwrap()["extensions"].clone()
+    }
+
+    #[test]
+    fn verbose_errors_keep_readable_keys() {
+        let error = Error::new("email", "Invalid email", ErrorCode::ValidationError);
+        let extensions = formatted(ErrorFormat::Verbose, error.into());
+
+        assert_eq!(extensions["code"], "VALIDATION_ERROR");
+        assert_eq!(extensions["field"], "email");
+        assert_eq!(extensions["message"], "Invalid email");
+        assert!(extensions.get("c").is_none());
+    }
+
+    #[test]
+    fn compact_errors_use_short_keys() {
+        let error = Error::new("email", "Invalid email", ErrorCode::ValidationError);
+        let extensions = formatted(ErrorFormat::Compact, error.into());
+
+        assert_eq!(extensions["c"], "VALIDATION_ERROR");
+        assert_eq!(extensions["f"], "email");
+        assert_eq!(extensions["m"], "Invalid email");
+        assert_eq!(extensions["category"], "VALIDATION");
+        assert!(extensions.get("code").is_none());
+        assert!(extensions.get("field").is_none());
+        assert!(extensions.get("message").is_none());
+    }
+
+    #[test]
+    fn compact_validation_errors_shorten_every_error() {
+        let mut validation_error = ValidationError::new();
+
+        validation_error.push(Error::new(
+            "email",
+            "Invalid email",
+            ErrorCode::ValidationError,
+        ));
+
+        let extensions = formatted(ErrorFormat::Compact, validation_error.into());
+
+        assert_eq!(extensions["c"], "VALIDATION_ERROR");
+        assert_eq!(
+            extensions["errors"],
+            serde_json::json!([{ "f": "email", "m": "Invalid email", "c": "VALIDATION_ERROR" }])
+        );
+    }
+
+    #[test]
+    fn parses_error_formats() {
+        assert_eq!("compact".parse(), Ok(ErrorFormat::Compact));
+        assert_eq!("Verbose".parse(), Ok(ErrorFormat::Verbose));
+        assert!("tiny".parse::<ErrorFormat>().is_err());
     }
 }
@@ -1,13 +1,52 @@
 use rocket::fairing::{Fairing, Info, Kind};
-use rocket::http::Header;
+use rocket::http::{Header, Method};
 use rocket::{Request, Response};
 
+use crate::config::{AllowedOrigins, CorsConfig};
+
 const ACCESS_CONTROL_ALLOW_CREDENTIALS: &str = "Access-Control-Allow-Credentials";
 const ACCESS_CONTROL_ALLOW_HEADERS: &str = "Access-Control-Allow-Headers";
 const ACCESS_CONTROL_ALLOW_METHODS: &str = "Access-Control-Allow-Methods";
 const ACCESS_CONTROL_ALLOW_ORIGIN: &str = "Access-Control-Allow-Origin";
+const ACCESS_CONTROL_EXPOSE_HEADERS: &str = "Access-Control-Expose-Headers";
+const ACCESS_CONTROL_MAX_AGE: &str = "Access-Control-Max-Age";
+const ORIGIN: &str = "Origin";
+const VARY: &str = "Vary";
+
+const ALLOWED_HEADERS: &str =
+    "Authorization, Content-Type, If-None-Match, X-Error-Format, x-request-id";
+const ALLOWED_METHODS: &str = "GET, POST, OPTIONS";
+const EXPOSED_HEADERS: &str = "ETag, Retry-After, x-request-id";
+
+/// Seconds browsers may cache preflight responses for
+const PREFLIGHT_MAX_AGE_SECS: &str = "600";
+
+/// Adds CORS headers to responses to requests from allowed origins, requests
+/// from any other origin get none so browsers block them
+pub struct Cors {
+    config: CorsConfig,
+}
+
+impl Cors {
+    pub fn new(config: CorsConfig) -> Self {
+        Self { config }
+    }
+
+    /// Value of the `Access-Control-Allow-Origin` header for requests from
+    /// `origin`, if allowed. Credentialed requests must not be answered
+    /// with `*`.
+    fn allow_origin<'a>(&self, origin: &'a str) -> Option<&'a str> {
+        if !self.config.allowed_origins.allows(origin) {
+            return None;
+        }
+
+        if self.config.allowed_origins == AllowedOrigins::Any && !self.config.allow_credentials {
+            return Some("*");
+        }
 
-pub struct Cors;
+        Some(origin)
+    }
+}
 
 #[rocket::async_trait]
 impl Fairing for Cors {
@@ -18,10 +57,126 @@ impl Fairing for Cors {
         }
     }
 
-    async fn on_response<'r>(&self, _: &'r Request<'_>, response: &mut Response<'r>) {
-        response.set_header(Header::new(ACCESS_CONTROL_ALLOW_ORIGIN, "*"));
-        response.set_header(Header::new(ACCESS_CONTROL_ALLOW_CREDENTIALS, "*"));
-        response.set_header(Header::new(ACCESS_CONTROL_ALLOW_HEADERS, "*"));
-        response.set_header(Header::new(ACCESS_CONTROL_ALLOW_METHODS, "*"));
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let allow_origin = match request
+            .headers()
+            .get_one(ORIGIN)
+            .and_then(|origin| self.allow_origin(origin))
+        {
+            Some(allow_origin) => allow_origin.to_string(),
+            None => return,
+        };
+
+        if allow_origin != "*" {
+            response.adjoin_header(Header::new(VARY, ORIGIN));
+        }
+
+        response.set_header(Header::new(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin));
+        response.set_header(Header::new(ACCESS_CONTROL_EXPOSE_HEADERS, EXPOSED_HEADERS));
+
+        if self.config.allow_credentials {
+            response.set_header(Header::new(ACCESS_CONTROL_ALLOW_CREDENTIALS, "true"));
+        }
+
+        if request.method() == Method::Options {
+            response.set_header(Header::new(ACCESS_CONTROL_ALLOW_HEADERS, ALLOWED_HEADERS));
+            response.set_header(Header::new(ACCESS_CONTROL_ALLOW_METHODS, ALLOWED_METHODS));
+            response.set_header(Header::new(ACCESS_CONTROL_MAX_AGE, PREFLIGHT_MAX_AGE_SECS));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{Header, Status};
+    use rocket::local::asynchronous::{Client, LocalResponse};
+
+    use crate::config::{AllowedOrigins, CorsConfig};
+
+    use super::Cors;
+
+    async fn cors_client(config: CorsConfig) -> Client {
+        let rocket = rocket::build()
+            .attach(Cors::new(config))
+            .mount("/", rocket::routes![crate::routes::cors_preflight]);
+
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    async fn preflight<'c>(client: &'c Client, origin: &'static str) -> LocalResponse<'c> {
+        client
+            .options("/graphql")
+            .header(Header::new("Origin", origin))
+            .header(Header::new("Access-Control-Request-Method", "POST"))
+            .dispatch()
+            .await
+    }
+
+    #[rocket::async_test]
+    async fn preflight_echoes_allowed_origins() {
+        let client = cors_client(CorsConfig {
+            allowed_origins: AllowedOrigins::List(vec![String::from("https://nexus.dev")]),
+            allow_credentials: true,
+        })
+        .await;
+        let response = preflight(&client, "https://nexus.dev").await;
+        let headers = response.headers();
+
+        assert_eq!(response.status(), Status::NoContent);
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Origin"),
+            Some("https://nexus.dev")
+        );
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Credentials"),
+            Some("true")
+        );
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Methods"),
+            Some("GET, POST, OPTIONS")
+        );
+        assert_eq!(headers.get_one("Vary"), Some("Origin"));
+    }
+
+    #[rocket::async_test]
+    async fn preflight_omits_headers_for_disallowed_origins() {
+        let client = cors_client(CorsConfig {
+            allowed_origins: AllowedOrigins::List(vec![String::from("https://nexus.dev")]),
+            allow_credentials: true,
+        })
+        .await;
+        let response = preflight(&client, "https://evil.dev").await;
+        let headers = response.headers();
+
+        assert!(!headers.contains("Access-Control-Allow-Origin"));
+        assert!(!headers.contains("Access-Control-Allow-Credentials"));
+        assert!(!headers.contains("Access-Control-Allow-Methods"));
+    }
+
+    #[rocket::async_test]
+    async fn any_origin_is_echoed_for_credentialed_requests() {
+        let client = cors_client(CorsConfig {
+            allowed_origins: AllowedOrigins::Any,
+            allow_credentials: true,
+        })
+        .await;
+        let response = preflight(&client, "http://localhost:3000").await;
+
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("http://localhost:3000")
+        );
+
+        let client = cors_client(CorsConfig {
+            allowed_origins: AllowedOrigins::Any,
+            allow_credentials: false,
+        })
+        .await;
+        let response = preflight(&client, "http://localhost:3000").await;
+
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("*")
+        );
     }
 }
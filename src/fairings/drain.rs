@@ -0,0 +1,123 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::tokio::time::{sleep, Instant};
+use rocket::{Data, Orbit, Request, Response, Rocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::database::Database;
+
+/// Interval in-flight requests are checked at while draining
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Seconds the database pool is given to close once requests are drained
+const POOL_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Counts in-flight requests and, once shutdown is requested, waits for them
+/// to complete before closing the database pool. No new connections are
+/// accepted meanwhile.
+pub struct Drain {
+    in_flight: AtomicUsize,
+    timeout: Duration,
+    database: Arc<Database>,
+}
+
+impl Drain {
+    pub fn new(timeout: Duration, database: Arc<Database>) -> Self {
+        Self {
+            in_flight: AtomicUsize::new(0),
+            timeout,
+            database,
+        }
+    }
+}
+
+/// Waits for `in_flight` to reach zero for up to `timeout`, returns the
+/// amount of requests still in flight
+async fn wait_for_drain(in_flight: &AtomicUsize, timeout: Duration) -> usize {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let remaining = in_flight.load(Ordering::SeqCst);
+
+        if remaining == 0 || Instant::now() >= deadline {
+            return remaining;
+        }
+
+        sleep(DRAIN_POLL_INTERVAL).await;
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Drain {
+    fn info(&self) -> Info {
+        Info {
+            name: "Drain Fairing",
+            kind: Kind::Request | Kind::Response | Kind::Shutdown,
+        }
+    }
+
+    async fn on_request(&self, _: &mut Request<'_>, _: &mut Data<'_>) {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+    }
+
+    async fn on_response<'r>(&self, _: &'r Request<'_>, _: &mut Response<'r>) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    async fn on_shutdown(&self, _: &Rocket<Orbit>) {
+        let in_flight = self.in_flight.load(Ordering::SeqCst);
+
+        tracing::info!(
+            in_flight,
+            timeout_secs = self.timeout.as_secs(),
+            "shutdown requested, draining in-flight requests"
+        );
+
+        let remaining = wait_for_drain(&self.in_flight, self.timeout).await;
+
+        if remaining > 0 {
+            tracing::warn!(
+                remaining,
+                "requests still in flight after the drain timeout"
+            );
+        }
+
+        self.database.close(POOL_CLOSE_TIMEOUT).await;
+        tracing::info!("database pool closed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::wait_for_drain;
+
+    #[rocket::async_test]
+    async fn waits_for_in_flight_requests() {
+        let in_flight = Arc::new(AtomicUsize::new(2));
+        let requests = Arc::clone(&in_flight);
+
+        rocket::tokio::spawn(async move {
+            for _ in 0..2 {
+                rocket::tokio::time::sleep(Duration::from_millis(20)).await;
+                requests.fetch_sub(1, Ordering::SeqCst);
+            }
+        });
+
+        assert_eq!(wait_for_drain(&in_flight, Duration::from_secs(5)).await, 0);
+    }
+
+    #[rocket::async_test]
+    async fn gives_up_after_timeout() {
+        let in_flight = AtomicUsize::new(1);
+
+        assert_eq!(
+            wait_for_drain(&in_flight, Duration::from_millis(100)).await,
+            1
+        );
+    }
+}
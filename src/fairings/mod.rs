@@ -1 +1,5 @@
 pub mod cors;
+pub mod drain;
+pub mod request_id;
+pub mod subscriptions;
+pub mod token_cleanup;
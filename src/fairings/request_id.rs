@@ -0,0 +1,125 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request, Response};
+use std::fmt;
+use uuid::Uuid;
+
+pub const X_REQUEST_ID: &str = "x-request-id";
+
+/// Correlation identifier of a request, honored from the `x-request-id`
+/// header when it holds a valid UUID, generated otherwise.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RequestId(pub Uuid);
+
+impl RequestId {
+    fn from_header(value: Option<&str>) -> Self {
+        value
+            .and_then(|value| Uuid::parse_str(value.trim()).ok())
+            .map(RequestId)
+            .unwrap_or_else(|| RequestId(Uuid::new_v4()))
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(
+            *request
+                .local_cache(|| RequestId::from_header(request.headers().get_one(X_REQUEST_ID))),
+        )
+    }
+}
+
+/// Assigns a `RequestId` to every request and returns it to the client in
+/// the `x-request-id` response header
+pub struct RequestIdFairing;
+
+#[rocket::async_trait]
+impl Fairing for RequestIdFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request ID Fairing",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let request_id = RequestId::from_header(request.headers().get_one(X_REQUEST_ID));
+
+        request.local_cache(|| request_id);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let request_id =
+            request.local_cache(|| RequestId::from_header(request.headers().get_one(X_REQUEST_ID)));
+
+        response.set_header(Header::new(X_REQUEST_ID, request_id.to_string()));
+    }
+}
+
+#[cfg(test)]
+// See `routes` in `main.rs`, the test route triggers the same lint
+#[allow(unused_imports)]
+mod tests {
+    use rocket::local::asynchronous::Client;
+    use uuid::Uuid;
+
+    use super::{RequestId, RequestIdFairing, X_REQUEST_ID};
+
+    #[rocket::get("/")]
+    fn request_id(request_id: RequestId) -> String {
+        request_id.to_string()
+    }
+
+    async fn client() -> Client {
+        let rocket = rocket::build()
+            .attach(RequestIdFairing)
+            .mount("/", rocket::routes![request_id]);
+
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn honors_incoming_request_id() {
+        let client = client().await;
+        let request_id = Uuid::new_v4().to_string();
+        let response = client
+            .get("/")
+            .header(rocket::http::Header::new(X_REQUEST_ID, request_id.clone()))
+            .dispatch()
+            .await;
+
+        assert_eq!(
+            response.headers().get_one(X_REQUEST_ID),
+            Some(request_id.as_str())
+        );
+        assert_eq!(response.into_string().await, Some(request_id));
+    }
+
+    #[rocket::async_test]
+    async fn generates_request_id_when_missing_or_invalid() {
+        let client = client().await;
+        let response = client
+            .get("/")
+            .header(rocket::http::Header::new(X_REQUEST_ID, "not-a-uuid"))
+            .dispatch()
+            .await;
+        let header = response
+            .headers()
+            .get_one(X_REQUEST_ID)
+            .unwrap()
+            .to_string();
+
+        assert!(Uuid::parse_str(&header).is_ok());
+        assert_eq!(response.into_string().await, Some(header));
+    }
+}
@@ -0,0 +1,68 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::tokio::net::TcpListener;
+use rocket::tokio::task::JoinHandle;
+use rocket::{Orbit, Rocket};
+use std::sync::Mutex;
+
+use crate::graphql::websocket::server::SubscriptionServer;
+
+/// Serves subscriptions over WebSockets on `port`, as Rocket can't upgrade
+/// its own connections. Listens on the same address as Rocket once it
+/// launched, connections are closed once shutdown is requested.
+pub struct Subscriptions {
+    port: u16,
+    server: SubscriptionServer,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Subscriptions {
+    pub fn new(port: u16, server: SubscriptionServer) -> Self {
+        Self {
+            port,
+            server,
+            task: Mutex::new(None),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Subscriptions {
+    fn info(&self) -> Info {
+        Info {
+            name: "Subscriptions Fairing",
+            kind: Kind::Liftoff | Kind::Shutdown,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let address = (rocket.config().address, self.port);
+        let listener = match TcpListener::bind(address).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                // Clients relying on subscriptions would be left without
+                // them, the server mustn't run as if nothing happened
+                tracing::error!(%err, port = self.port, "failed to serve subscriptions");
+                rocket.shutdown().notify();
+                return;
+            }
+        };
+
+        tracing::info!(port = self.port, "serving subscriptions over WebSockets");
+
+        let task = rocket::tokio::spawn(self.server.clone().serve(listener, rocket.shutdown()));
+
+        *self.task.lock().unwrap() = Some(task);
+    }
+
+    async fn on_shutdown(&self, _: &Rocket<Orbit>) {
+        let task = self.task.lock().unwrap().take();
+
+        if let Some(task) = task {
+            if let Err(err) = task.await {
+                tracing::error!(%err, "subscription server failed");
+            }
+        }
+
+        tracing::info!("subscriptions stopped");
+    }
+}
@@ -0,0 +1,89 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::tokio::task::JoinHandle;
+use rocket::tokio::time::{interval, MissedTickBehavior};
+use rocket::{Orbit, Rocket, Shutdown};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::modules::auth::AuthService;
+
+/// Periodically purges expired refresh tokens and denylist entries on a task
+/// of its own, so request handling is never blocked by it. The task stops
+/// once shutdown is requested, a purge in progress completes first.
+pub struct TokenCleanup {
+    interval: Duration,
+    auth_service: Arc<AuthService>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl TokenCleanup {
+    pub fn new(interval: Duration, auth_service: Arc<AuthService>) -> Self {
+        Self {
+            interval,
+            auth_service,
+            task: Mutex::new(None),
+        }
+    }
+}
+
+/// Purges expired tokens once, failures are logged and retried on the next
+/// run
+async fn purge_expired_tokens(auth_service: &AuthService) {
+    match auth_service.purge_expired_tokens().await {
+        Ok(purged) => tracing::info!(
+            refresh_tokens = purged.refresh_tokens,
+            revoked_tokens = purged.revoked_tokens,
+            "purged expired tokens"
+        ),
+        Err(err) => tracing::warn!(?err, "failed to purge expired tokens"),
+    }
+}
+
+/// Purges expired tokens every `period`, starting right away, until
+/// `shutdown` resolves
+async fn run(auth_service: Arc<AuthService>, period: Duration, mut shutdown: Shutdown) {
+    let mut ticks = interval(period);
+
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        rocket::tokio::select! {
+            _ = ticks.tick() => {}
+            _ = &mut shutdown => break,
+        }
+
+        purge_expired_tokens(&auth_service).await;
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for TokenCleanup {
+    fn info(&self) -> Info {
+        Info {
+            name: "Token Cleanup Fairing",
+            kind: Kind::Liftoff | Kind::Shutdown,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let task = rocket::tokio::spawn(run(
+            Arc::clone(&self.auth_service),
+            self.interval,
+            rocket.shutdown(),
+        ));
+
+        *self.task.lock().unwrap() = Some(task);
+    }
+
+    async fn on_shutdown(&self, _: &Rocket<Orbit>) {
+        let task = self.task.lock().unwrap().take();
+
+        if let Some(task) = task {
+            if let Err(err) = task.await {
+                tracing::error!(%err, "token cleanup task failed");
+            }
+        }
+
+        tracing::info!("token cleanup stopped");
+    }
+}
@@ -0,0 +1,192 @@
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextParseQuery};
+use async_graphql::parser::types::{ExecutableDocument, Selection, SelectionSet};
+use async_graphql::{Name, Pos, ServerResult, Variables};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::error::Error;
+
+/// Rejects documents selecting more than `limit` aliased or repeated fields
+/// with a `TOO_MANY_ALIASES` error. Repeated selections are resolved once
+/// each, so they multiply the cost of a field without deepening the query
+/// or, when below the complexity limit, being noticed by it.
+pub struct AliasLimit {
+    limit: usize,
+}
+
+impl AliasLimit {
+    pub fn new(limit: usize) -> Self {
+        Self { limit }
+    }
+}
+
+impl ExtensionFactory for AliasLimit {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(AliasLimitExtension { limit: self.limit })
+    }
+}
+
+struct AliasLimitExtension {
+    limit: usize,
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for AliasLimitExtension {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+
+        if exceeds_alias_limit(&document, self.limit) {
+            let error = async_graphql::Error::from(Error::too_many_aliases(self.limit));
+
+            return Err(error.into_server_error(Pos::default()));
+        }
+
+        Ok(document)
+    }
+}
+
+/// Checks whether any operation of the document selects more than `limit`
+/// aliased or repeated fields, fragments counting once per spread
+fn exceeds_alias_limit(document: &ExecutableDocument, limit: usize) -> bool {
+    document.operations.iter().any(|(_, operation)| {
+        let mut counter = AliasCounter {
+            document,
+            limit,
+            count: 0,
+            visiting: Vec::new(),
+        };
+
+        counter.exceeds(&operation.node.selection_set.node, &mut HashSet::new())
+    })
+}
+
+struct AliasCounter<'a> {
+    document: &'a ExecutableDocument,
+    limit: usize,
+    count: usize,
+    /// Documents aren't validated yet, fragments being expanded break cycles
+    visiting: Vec<&'a Name>,
+}
+
+impl<'a> AliasCounter<'a> {
+    /// Counts the aliased fields of `selection_set` and those whose name was
+    /// already `seen` at this level, fragments being part of the level they
+    /// are spread in. Stops as soon as the limit is exceeded, as fragments
+    /// spreading each other make expanding them exponential.
+    fn exceeds(&mut self, selection_set: &'a SelectionSet, seen: &mut HashSet<&'a str>) -> bool {
+        for selection in &selection_set.items {
+            let exceeded = match &selection.node {
+                Selection::Field(field) => {
+                    let field = &field.node;
+
+                    if field.alias.is_some() || !seen.insert(field.name.node.as_str()) {
+                        self.count += 1;
+                    }
+
+                    self.count > self.limit
+                        || self.exceeds(&field.selection_set.node, &mut HashSet::new())
+                }
+                Selection::FragmentSpread(spread) => {
+                    let name = &spread.node.fragment_name.node;
+
+                    match self.document.fragments.get(name) {
+                        Some(fragment) if !self.visiting.contains(&name) => {
+                            self.visiting.push(name);
+
+                            let exceeded = self.exceeds(&fragment.node.selection_set.node, seen);
+
+                            self.visiting.pop();
+                            exceeded
+                        }
+                        _ => false,
+                    }
+                }
+                Selection::InlineFragment(fragment) => {
+                    self.exceeds(&fragment.node.selection_set.node, seen)
+                }
+            };
+
+            if exceeded {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::parser::parse_query;
+    use async_graphql::Request;
+
+    use crate::config::GraphQLConfig;
+    use crate::graphql::schema_builder;
+    use crate::routes::AuthToken;
+
+    use super::exceeds_alias_limit;
+
+    fn exceeds(query: &str, limit: usize) -> bool {
+        exceeds_alias_limit(&parse_query(query).unwrap(), limit)
+    }
+
+    #[test]
+    fn counts_aliased_and_repeated_fields() {
+        assert!(!exceeds("{ a: __typename b: __typename }", 2));
+        assert!(exceeds("{ a: __typename b: __typename c: __typename }", 2));
+        assert!(!exceeds("{ __typename me { __typename } }", 0));
+        assert!(exceeds("{ __typename __typename }", 0));
+        assert!(exceeds("{ me { me { a: id b: id } } }", 1));
+    }
+
+    #[test]
+    fn counts_fragments_once_per_spread() {
+        let query = "{ ...F ...F } fragment F on Query { a: __typename }";
+
+        assert!(!exceeds(query, 2));
+        assert!(exceeds(query, 1));
+        assert!(exceeds("{ __typename ... on Query { __typename } }", 0));
+        assert!(!exceeds(
+            "query { ...A } fragment A on Query { ...B } fragment B on Query { ...A }",
+            0
+        ));
+    }
+
+    #[rocket::async_test]
+    async fn rejects_queries_exceeding_the_alias_limit() {
+        let config = GraphQLConfig::default();
+        let schema = schema_builder(&config).finish();
+        let aliases = |count: usize| {
+            let fields = (0..count)
+                .map(|index| format!("u{index}: users(first: 100) {{ totalCount }}"))
+                .collect::<Vec<String>>()
+                .join(" ");
+
+            format!("{{ {fields} }}")
+        };
+
+        let response = schema
+            .execute(Request::new(aliases(config.max_aliases + 1)).data(AuthToken::empty()))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(error["extensions"]["code"], "TOO_MANY_ALIASES");
+
+        let response = schema
+            .execute(Request::new(aliases(2)).data(AuthToken::empty()))
+            .await;
+
+        for error in response.errors {
+            let error = serde_json::to_value(&error).unwrap();
+
+            assert_ne!(error["extensions"]["code"], "TOO_MANY_ALIASES");
+        }
+    }
+}
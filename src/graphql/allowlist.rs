@@ -0,0 +1,302 @@
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest,
+};
+use async_graphql::parser::parse_query;
+use async_graphql::parser::types::DocumentOperations;
+use async_graphql::{Pos, Request, ServerResult};
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::Error;
+
+use super::persisted_queries::hash_query;
+
+const USAGE: &str = "Usage: nexus-api allowlist-gen <directory> [--out <file>]";
+
+/// Operations allowed to run, identified by the SHA-256 hash of their
+/// document or by their operation name. Hashes pin the exact document while
+/// names trust clients to send the document they were given.
+#[derive(Debug, Default)]
+pub struct Allowlist {
+    hashes: HashSet<String>,
+    names: HashSet<String>,
+}
+
+impl Allowlist {
+    /// Creates an allowlist from manifest entries, those which are hex
+    /// encoded SHA-256 hashes match documents while any other matches
+    /// operation names
+    pub fn new(entries: impl IntoIterator<Item = String>) -> Self {
+        let (hashes, names) = entries.into_iter().partition(|entry| is_sha256_hex(entry));
+
+        Self { hashes, names }
+    }
+
+    /// Reads a JSON manifest holding an array of entries
+    pub fn from_manifest(path: &Path) -> Result<Self, String> {
+        let manifest = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        let entries: Vec<String> = serde_json::from_str(&manifest)
+            .map_err(|err| format!("Failed to parse {}: {err}", path.display()))?;
+
+        Ok(Self::new(entries))
+    }
+
+    /// Checks whether the operation of `query` selected by `operation_name`
+    /// is allowed. Variables are left out, so an allowed operation runs with
+    /// any of them.
+    pub fn allows(&self, query: &str, operation_name: Option<&str>) -> bool {
+        if self.hashes.contains(&hash_query(query)) {
+            return true;
+        }
+
+        named_operation(query, operation_name).is_some_and(|name| self.names.contains(&name))
+    }
+}
+
+fn is_sha256_hex(entry: &str) -> bool {
+    entry.len() == 64 && entry.chars().all(|ch| ch.is_ascii_hexdigit())
+}
+
+/// Name of the operation of `query` to be executed, `None` for anonymous
+/// operations, documents which don't parse and names missing from the
+/// document
+fn named_operation(query: &str, operation_name: Option<&str>) -> Option<String> {
+    let operations = match parse_query(query).ok()?.operations {
+        DocumentOperations::Single(_) => return None,
+        DocumentOperations::Multiple(operations) => operations,
+    };
+
+    match operation_name {
+        Some(name) => operations
+            .keys()
+            .find(|key| key.as_str() == name)
+            .map(ToString::to_string),
+        None if operations.len() == 1 => operations.keys().next().map(ToString::to_string),
+        None => None,
+    }
+}
+
+/// Rejects operations missing from the allowlist before they are parsed
+/// into the schema. Runs after persisted queries are resolved, so hashes
+/// sent in place of queries are checked against the stored document.
+pub struct OperationAllowlist {
+    allowlist: Arc<Allowlist>,
+}
+
+impl OperationAllowlist {
+    pub fn new(allowlist: Allowlist) -> Self {
+        Self {
+            allowlist: Arc::new(allowlist),
+        }
+    }
+}
+
+impl ExtensionFactory for OperationAllowlist {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(OperationAllowlistExtension {
+            allowlist: Arc::clone(&self.allowlist),
+        })
+    }
+}
+
+struct OperationAllowlistExtension {
+    allowlist: Arc<Allowlist>,
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for OperationAllowlistExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        if !self
+            .allowlist
+            .allows(&request.query, request.operation_name.as_deref())
+        {
+            let error = async_graphql::Error::from(Error::operation_not_allowed());
+
+            return Err(error.into_server_error(Pos::default()));
+        }
+
+        next.run(ctx, request).await
+    }
+}
+
+/// Hashes every `.graphql` document found within `directory`, sorted so
+/// regenerated manifests diff cleanly. Clients must send each document as
+/// in its file, trimmed of surrounding whitespace.
+pub fn generate_manifest(directory: &Path) -> Result<Vec<String>, String> {
+    let mut files = Vec::new();
+
+    collect_documents(directory, &mut files)?;
+
+    let mut hashes = BTreeSet::new();
+
+    for file in files {
+        let document = std::fs::read_to_string(&file)
+            .map_err(|err| format!("Failed to read {}: {err}", file.display()))?;
+        let document = document.trim();
+
+        parse_query(document).map_err(|err| format!("Invalid {}: {err}", file.display()))?;
+        hashes.insert(hash_query(document));
+    }
+
+    Ok(hashes.into_iter().collect())
+}
+
+fn collect_documents(directory: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(directory)
+        .map_err(|err| format!("Failed to read {}: {err}", directory.display()))?;
+
+    for entry in entries {
+        let path = entry
+            .map_err(|err| format!("Failed to read {}: {err}", directory.display()))?
+            .path();
+
+        if path.is_dir() {
+            collect_documents(&path, files)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "graphql")
+        {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the manifest for the documents of the directory given as first
+/// argument to `--out`, or to the standard output
+pub fn run_generator(mut args: impl Iterator<Item = String>) {
+    let mut directory = None;
+    let mut out = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => {
+                out =
+                    Some(PathBuf::from(args.next().unwrap_or_else(|| {
+                        panic!("--out expects a file\n{}", USAGE)
+                    })))
+            }
+            _ if directory.is_none() => directory = Some(PathBuf::from(arg)),
+            _ => panic!("Unknown argument: {}\n{}", arg, USAGE),
+        }
+    }
+
+    let directory = directory.unwrap_or_else(|| panic!("{}", USAGE));
+    let manifest = generate_manifest(&directory).unwrap_or_else(|message| panic!("{}", message));
+    let manifest = serde_json::to_string_pretty(&manifest).unwrap();
+
+    match out {
+        Some(path) => {
+            std::fs::write(&path, manifest + "\n")
+                .unwrap_or_else(|err| panic!("Failed to write {}: {}", path.display(), err));
+        }
+        None => println!("{manifest}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::Request;
+
+    use crate::graphql::persisted_queries::hash_query;
+    use crate::graphql::{Mutation, Query, Schema, Subscription};
+
+    use super::{generate_manifest, Allowlist, OperationAllowlist};
+
+    const QUERY: &str = "query Typename { __typename }";
+
+    fn schema(entries: &[&str]) -> Schema {
+        let allowlist = Allowlist::new(entries.iter().map(|entry| entry.to_string()));
+
+        Schema::build(
+            Query::default(),
+            Mutation::default(),
+            Subscription::default(),
+        )
+        .extension(OperationAllowlist::new(allowlist))
+        .finish()
+    }
+
+    async fn error_code(schema: &Schema, request: Request) -> Option<serde_json::Value> {
+        let response = schema.execute(request).await;
+
+        response
+            .errors
+            .first()
+            .map(|error| serde_json::to_value(error).unwrap()["extensions"]["code"].clone())
+    }
+
+    #[rocket::async_test]
+    async fn allows_operations_by_hash_with_any_variables() {
+        let schema = schema(&[&hash_query(QUERY)]);
+        let request = Request::new(QUERY).variables(async_graphql::Variables::from_json(
+            serde_json::json!({ "unused": 1 }),
+        ));
+
+        assert_eq!(error_code(&schema, request).await, None);
+        assert_eq!(
+            error_code(&schema, Request::new("query Typename {  __typename }")).await,
+            Some(serde_json::json!("OPERATION_NOT_ALLOWED"))
+        );
+    }
+
+    #[rocket::async_test]
+    async fn allows_operations_by_name() {
+        let schema = schema(&["Typename"]);
+        let document = "query Typename { __typename } query Other { __typename }";
+
+        assert_eq!(error_code(&schema, Request::new(QUERY)).await, None);
+        assert_eq!(
+            error_code(&schema, Request::new(document).operation_name("Typename")).await,
+            None
+        );
+        assert_eq!(
+            error_code(&schema, Request::new(document).operation_name("Other")).await,
+            Some(serde_json::json!("OPERATION_NOT_ALLOWED"))
+        );
+        assert_eq!(
+            error_code(&schema, Request::new(document)).await,
+            Some(serde_json::json!("OPERATION_NOT_ALLOWED"))
+        );
+        assert_eq!(
+            error_code(&schema, Request::new("{ __typename }")).await,
+            Some(serde_json::json!("OPERATION_NOT_ALLOWED"))
+        );
+    }
+
+    #[test]
+    fn generates_sorted_hashes_of_graphql_documents() {
+        let directory = std::env::temp_dir().join(format!("allowlist-{}", uuid::Uuid::new_v4()));
+
+        std::fs::create_dir_all(directory.join("nested")).unwrap();
+        std::fs::write(directory.join("typename.graphql"), format!("{QUERY}\n")).unwrap();
+        std::fs::write(
+            directory.join("nested/me.graphql"),
+            "query Me { me { me { id } } }",
+        )
+        .unwrap();
+        std::fs::write(directory.join("README.md"), "not a document").unwrap();
+
+        let manifest = generate_manifest(&directory).unwrap();
+        let mut expected = vec![
+            hash_query(QUERY),
+            hash_query("query Me { me { me { id } } }"),
+        ];
+
+        expected.sort();
+        assert_eq!(manifest, expected);
+
+        std::fs::write(directory.join("broken.graphql"), "query {").unwrap();
+        assert!(generate_manifest(&directory).is_err());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextRequest, NextSubscribe,
+};
+use async_graphql::futures_util::stream::{BoxStream, StreamExt};
+use async_graphql::registry::{MetaType, Registry};
+use async_graphql::{Response, ServerError};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::sync::Arc;
+
+use crate::error::reshape_framework_error;
+
+/// Matches the message async-graphql reports for unknown enum values, e.g.
+/// `enumeration type "Role" does not contain the value "OWNER"`
+static UNKNOWN_ENUM_VALUE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"enumeration type "(\w+)" does not contain the value"#).unwrap());
+
+/// Reshapes the errors async-graphql reports on its own, e.g. while parsing,
+/// validating or coercing input values, so clients get the same envelope
+/// with a `BAD_INPUT` code as for the errors resolvers return. Unknown enum
+/// values are reported along with the values the enum accepts.
+pub struct BadInput;
+
+impl ExtensionFactory for BadInput {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(BadInputExtension)
+    }
+}
+
+struct BadInputExtension;
+
+fn reshape_errors(mut response: Response, registry: &Registry) -> Response {
+    for error in &mut response.errors {
+        list_enum_values(error, registry);
+        reshape_framework_error(error);
+    }
+
+    response
+}
+
+/// Appends the values accepted by the enum an unknown value was provided for
+/// to the message of the error
+fn list_enum_values(error: &mut ServerError, registry: &Registry) {
+    let enum_name = match UNKNOWN_ENUM_VALUE_RE.captures(&error.message) {
+        Some(captures) => captures[1].to_string(),
+        None => return,
+    };
+
+    if let Some(MetaType::Enum { enum_values, .. }) = registry.types.get(&enum_name) {
+        let values = enum_values
+            .values()
+            .filter(|value| value.visible.is_none())
+            .map(|value| value.name)
+            .collect::<Vec<&str>>()
+            .join(", ");
+
+        error.message = format!("{}, expected one of: {values}", error.message);
+    }
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for BadInputExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        reshape_errors(next.run(ctx).await, &ctx.schema_env.registry)
+    }
+
+    fn subscribe<'s>(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        stream: BoxStream<'s, Response>,
+        next: NextSubscribe<'_>,
+    ) -> BoxStream<'s, Response> {
+        let schema_env = ctx.schema_env.clone();
+
+        next.run(ctx, stream)
+            .map(move |response| reshape_errors(response, &schema_env.registry))
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::futures_util::StreamExt;
+    use async_graphql::{Request, Variables};
+
+    use crate::config::GraphQLConfig;
+    use crate::graphql::schema_builder;
+    use crate::routes::AuthToken;
+
+    async fn errors(query: &str, variables: serde_json::Value) -> Vec<serde_json::Value> {
+        let schema = schema_builder(&GraphQLConfig::default()).finish();
+        let request = Request::new(query)
+            .variables(Variables::from_json(variables))
+            .data(AuthToken::empty());
+        let response = schema.execute(request).await;
+
+        response
+            .errors
+            .iter()
+            .map(|error| serde_json::to_value(error).unwrap())
+            .collect()
+    }
+
+    #[rocket::async_test]
+    async fn wrong_typed_variables_are_bad_input() {
+        let errors = errors(
+            "query Users($first: Int) { users(first: $first) { __typename } }",
+            serde_json::json!({ "first": "ten" }),
+        )
+        .await;
+        let extensions = &errors[0]["extensions"];
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(extensions["code"], "BAD_INPUT");
+        assert_eq!(extensions["category"], "VALIDATION");
+        assert_eq!(extensions["field"], "first");
+        assert_eq!(
+            extensions["message"],
+            r#"Invalid value for argument "first", expected type "Int""#
+        );
+        assert!(extensions["timestamp"].is_string());
+    }
+
+    #[rocket::async_test]
+    async fn missing_input_fields_name_the_field() {
+        let errors = errors(
+            r#"mutation { accountRegister(input: { name: "Esteban" }) { user { id } } }"#,
+            serde_json::json!({}),
+        )
+        .await;
+
+        assert_eq!(errors[0]["extensions"]["code"], "BAD_INPUT");
+        assert_eq!(errors[0]["extensions"]["field"], "lastName");
+    }
+
+    #[rocket::async_test]
+    async fn unknown_enum_values_list_the_valid_ones() {
+        let errors = errors(
+            "query Users($role: Role) { users(first: 1, filter: { role: $role }) { __typename } }",
+            serde_json::json!({ "role": "OWNER" }),
+        )
+        .await;
+        let extensions = &errors[0]["extensions"];
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(extensions["code"], "BAD_INPUT");
+        assert_eq!(extensions["field"], "role");
+        assert_eq!(
+            extensions["message"],
+            r#"Invalid value for argument "filter.role", enumeration type "Role" does not contain the value "OWNER", expected one of: USER, ADMIN"#
+        );
+    }
+
+    #[rocket::async_test]
+    async fn unknown_orders_list_the_valid_ones() {
+        let errors = errors(
+            "{ users(first: 1, orderBy: NAME_ASC) { __typename } }",
+            serde_json::json!({}),
+        )
+        .await;
+        let extensions = &errors[0]["extensions"];
+
+        assert_eq!(extensions["code"], "BAD_INPUT");
+        assert_eq!(extensions["field"], "orderBy");
+        assert!(extensions["message"].as_str().unwrap().ends_with(
+            "expected one of: CREATED_AT_ASC, CREATED_AT_DESC, USERNAME_ASC, USERNAME_DESC"
+        ));
+    }
+
+    #[rocket::async_test]
+    async fn parse_errors_are_bad_input() {
+        let errors = errors("{ me {", serde_json::json!({})).await;
+
+        assert_eq!(errors[0]["extensions"]["code"], "BAD_INPUT");
+        assert!(errors[0]["extensions"].get("field").is_none());
+    }
+
+    #[rocket::async_test]
+    async fn resolver_errors_keep_their_code() {
+        let schema = schema_builder(&GraphQLConfig::default()).finish();
+        let request =
+            Request::new("subscription { sessionEvents { kind } }").data(AuthToken::empty());
+        let response = schema.execute_stream(request).next().await.unwrap();
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(error["extensions"]["code"], "UNAUTHORIZED");
+    }
+}
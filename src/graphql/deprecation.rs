@@ -0,0 +1,169 @@
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextRequest, NextResolve, NextSubscribe,
+    ResolveInfo,
+};
+use async_graphql::futures_util::stream::{BoxStream, StreamExt};
+use async_graphql::registry::Deprecation;
+use async_graphql::{Response, ServerResult, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use crate::metrics::METRICS;
+use crate::modules::auth::Authenticated;
+
+use super::rate_limit::RateLimitKey;
+
+/// Counts the resolutions of fields marked with
+/// `#[graphql(deprecation = "...")]`, telling when nobody calls them anymore.
+///
+/// Resolutions are counted by `Type.field` in the
+/// `nexus_graphql_deprecated_field_resolutions_total` metric and logged once
+/// per request along with the caller, the id of the user its token was
+/// issued for or its IP.
+pub struct DeprecationUsage;
+
+impl ExtensionFactory for DeprecationUsage {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(DeprecationUsageExtension::default())
+    }
+}
+
+/// Created for every request, holds the deprecated fields resolved while
+/// executing it
+#[derive(Default)]
+struct DeprecationUsageExtension {
+    resolved: Arc<Mutex<BTreeMap<String, u64>>>,
+}
+
+fn caller(ctx: &ExtensionContext<'_>) -> String {
+    if let Some(authenticated) = ctx.data_opt::<Authenticated>() {
+        return authenticated.user.id.to_string();
+    }
+
+    match ctx.data_opt::<RateLimitKey>() {
+        Some(RateLimitKey::User(id)) => id.to_string(),
+        Some(RateLimitKey::Ip(ip)) => ip.to_string(),
+        Some(RateLimitKey::Anonymous) | None => String::from("anonymous"),
+    }
+}
+
+/// Records the fields resolved since the last report
+fn report(resolved: &Mutex<BTreeMap<String, u64>>, caller: &str) {
+    for (field, count) in std::mem::take(&mut *resolved.lock().unwrap()) {
+        METRICS.record_deprecated_field(&field, count);
+        tracing::info!(%field, count, %caller, "deprecated field resolved");
+    }
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for DeprecationUsageExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let response = next.run(ctx).await;
+
+        report(&self.resolved, &caller(ctx));
+        response
+    }
+
+    /// Fields resolved for an event are reported along with it
+    fn subscribe<'s>(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        stream: BoxStream<'s, Response>,
+        next: NextSubscribe<'_>,
+    ) -> BoxStream<'s, Response> {
+        let resolved = Arc::clone(&self.resolved);
+        let caller = caller(ctx);
+
+        next.run(ctx, stream)
+            .map(move |response| {
+                report(&resolved, &caller);
+                response
+            })
+            .boxed()
+    }
+
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let deprecated = ctx
+            .schema_env
+            .registry
+            .types
+            .get(info.parent_type)
+            .and_then(|parent| parent.field_by_name(info.name))
+            .is_some_and(|field| matches!(field.deprecation, Deprecation::Deprecated { .. }));
+
+        if deprecated {
+            let field = format!("{}.{}", info.parent_type, info.name);
+
+            *self.resolved.lock().unwrap().entry(field).or_default() += 1;
+        }
+
+        next.run(ctx, info).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema};
+
+    use crate::metrics::METRICS;
+
+    use super::DeprecationUsage;
+
+    struct Item;
+
+    #[Object]
+    impl Item {
+        async fn name(&self) -> &str {
+            "item"
+        }
+
+        #[graphql(deprecation = "Use `name` instead")]
+        async fn legacy_name(&self) -> &str {
+            "item"
+        }
+    }
+
+    struct TestQuery;
+
+    #[Object]
+    impl TestQuery {
+        async fn items(&self) -> Vec<Item> {
+            vec![Item, Item, Item]
+        }
+    }
+
+    fn resolutions(field: &str) -> u64 {
+        let metric =
+            format!("nexus_graphql_deprecated_field_resolutions_total{{field=\"{field}\"}} ");
+
+        METRICS
+            .render(None)
+            .lines()
+            .find_map(|line| line.strip_prefix(&metric))
+            .map_or(0, |count| count.parse().unwrap())
+    }
+
+    #[rocket::async_test]
+    async fn counts_resolutions_of_deprecated_fields() {
+        let schema = Schema::build(TestQuery, EmptyMutation, EmptySubscription)
+            .extension(DeprecationUsage)
+            .finish();
+
+        let response = schema.execute("{ items { name } }").await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(resolutions("Item.legacyName"), 0);
+        assert_eq!(resolutions("Item.name"), 0);
+
+        let response = schema.execute("{ items { name legacyName } }").await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(resolutions("Item.legacyName"), 3);
+        assert_eq!(resolutions("Item.name"), 0);
+    }
+}
@@ -0,0 +1,209 @@
+use async_graphql::{InputValueError, InputValueResult, Scalar, ScalarType, Value};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::error::{Error, ErrorCode};
+
+const INVALID_DURATION_MESSAGE: &str =
+    "Duration must be a positive amount of s, m, h or d, e.g. 30d";
+
+static DURATION_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^([0-9]{1,9})([smhd])$").unwrap());
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DurationUnit {
+    Seconds,
+    Minutes,
+    Hours,
+    Days,
+}
+
+impl DurationUnit {
+    fn seconds(&self) -> i64 {
+        match self {
+            DurationUnit::Seconds => 1,
+            DurationUnit::Minutes => 60,
+            DurationUnit::Hours => 60 * 60,
+            DurationUnit::Days => 60 * 60 * 24,
+        }
+    }
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            DurationUnit::Seconds => "s",
+            DurationUnit::Minutes => "m",
+            DurationUnit::Hours => "h",
+            DurationUnit::Days => "d",
+        }
+    }
+}
+
+/// A positive amount of a single unit, such as `30d` or `90m`. The unit is
+/// kept as provided so it's serialized back the way it was written.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct HumanDuration {
+    amount: u32,
+    unit: DurationUnit,
+}
+
+impl HumanDuration {
+    pub fn new(amount: u32, unit: DurationUnit) -> Self {
+        Self { amount, unit }
+    }
+
+    pub fn to_chrono(self) -> chrono::Duration {
+        chrono::Duration::seconds(i64::from(self.amount) * self.unit.seconds())
+    }
+}
+
+impl FromStr for HumanDuration {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            Error::new(
+                "duration",
+                INVALID_DURATION_MESSAGE,
+                ErrorCode::ValidationError,
+            )
+        };
+        let captures = DURATION_RE.captures(value.trim()).ok_or_else(invalid)?;
+        let amount = captures[1].parse::<u32>().map_err(|_| invalid())?;
+        let unit = match &captures[2] {
+            "s" => DurationUnit::Seconds,
+            "m" => DurationUnit::Minutes,
+            "h" => DurationUnit::Hours,
+            _ => DurationUnit::Days,
+        };
+
+        if amount == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Self::new(amount, unit))
+    }
+}
+
+impl Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.amount, self.unit.suffix())
+    }
+}
+
+/// Positive amount of seconds (`s`), minutes (`m`), hours (`h`) or days
+/// (`d`), e.g. `30d`
+#[Scalar(name = "Duration")]
+impl ScalarType for HumanDuration {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        match value {
+            Value::String(value) => value
+                .parse()
+                .map_err(|_| InputValueError::custom(INVALID_DURATION_MESSAGE)),
+            _ => Err(InputValueError::expected_type(value)),
+        }
+    }
+
+    /// Rejecting invalid values while validating the document reports them
+    /// along with the argument they were provided for
+    fn is_valid(value: &Value) -> bool {
+        matches!(value, Value::String(value) if value.parse::<HumanDuration>().is_ok())
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{Request, ScalarType, Value, Variables};
+
+    use crate::config::GraphQLConfig;
+    use crate::error::ErrorCode;
+    use crate::graphql::schema_builder;
+    use crate::routes::AuthToken;
+
+    use super::{DurationUnit, HumanDuration};
+
+    #[test]
+    fn parses_valid_durations() {
+        let cases = [
+            (
+                "30d",
+                HumanDuration::new(30, DurationUnit::Days),
+                30 * 86_400,
+            ),
+            (
+                "12h",
+                HumanDuration::new(12, DurationUnit::Hours),
+                12 * 3_600,
+            ),
+            (
+                "90m",
+                HumanDuration::new(90, DurationUnit::Minutes),
+                90 * 60,
+            ),
+            (" 45s ", HumanDuration::new(45, DurationUnit::Seconds), 45),
+        ];
+
+        for (value, expected, seconds) in cases {
+            let duration = value.parse::<HumanDuration>().unwrap();
+
+            assert_eq!(duration, expected, "{value}");
+            assert_eq!(duration.to_chrono().num_seconds(), seconds, "{value}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for value in [
+            "",
+            "30",
+            "d",
+            "0d",
+            "-1d",
+            "1.5h",
+            "30 d",
+            "1h30m",
+            "2w",
+            "1D",
+            "9999999999d",
+        ] {
+            let error = value.parse::<HumanDuration>().err().unwrap();
+
+            assert_eq!(error.code, ErrorCode::ValidationError, "{value}");
+        }
+    }
+
+    #[test]
+    fn round_trips_through_the_scalar() {
+        for value in ["30d", "12h", "90m", "3600s"] {
+            let duration = <HumanDuration as ScalarType>::parse(Value::from(value)).unwrap();
+
+            assert_eq!(duration.to_value(), Value::from(value));
+        }
+
+        assert!(<HumanDuration as ScalarType>::parse(Value::from(30)).is_err());
+    }
+
+    #[rocket::async_test]
+    async fn invalid_arguments_are_field_level_errors() {
+        let schema = schema_builder(&GraphQLConfig::default()).finish();
+        let literal = Request::new(
+            r#"mutation { apiTokenCreate(input: { name: "ci", scopes: [USERS_READ], expiresIn: "soon" }) { __typename } }"#,
+        );
+        let variable = Request::new(
+            r#"mutation ($expiresIn: Duration!) { apiTokenCreate(input: { name: "ci", scopes: [USERS_READ], expiresIn: $expiresIn }) { __typename } }"#,
+        )
+        .variables(Variables::from_json(serde_json::json!({ "expiresIn": "0d" })));
+
+        for request in [literal, variable] {
+            let response = schema.execute(request.data(AuthToken::empty())).await;
+            let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+            assert_eq!(error["extensions"]["code"], "BAD_INPUT");
+            assert_eq!(error["extensions"]["field"], "expiresIn");
+        }
+    }
+}
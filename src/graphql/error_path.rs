@@ -0,0 +1,162 @@
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextRequest, NextResolve, NextSubscribe,
+    ResolveInfo,
+};
+use async_graphql::futures_util::stream::{BoxStream, StreamExt};
+use async_graphql::{
+    PathSegment, QueryPathNode, QueryPathSegment, Response, ServerError, ServerResult, Value,
+};
+use std::sync::Arc;
+
+/// Extension holding the path of the field an error was raised by until the
+/// response is sent
+const RESOLVER_PATH_KEY: &str = "resolverPath";
+
+/// Keeps the `path` of errors pointing at the field which failed.
+///
+/// async-graphql replaces the path of an error with the path of every list
+/// item it bubbles through, so a node failing within a connection would be
+/// reported at `["users", "edges", 1]` rather than at the failing field of
+/// the node. The path seen by the innermost resolver is recorded and put
+/// back once the response is complete.
+pub struct ErrorPath;
+
+impl ExtensionFactory for ErrorPath {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(ErrorPathExtension)
+    }
+}
+
+struct ErrorPathExtension;
+
+fn path_value(node: &QueryPathNode<'_>) -> Value {
+    let mut segments = std::iter::once(node)
+        .chain(node.parents())
+        .map(|node| match node.segment {
+            QueryPathSegment::Index(index) => Value::from(index as u64),
+            QueryPathSegment::Name(name) => Value::from(name),
+        })
+        .collect::<Vec<_>>();
+
+    segments.reverse();
+
+    Value::List(segments)
+}
+
+fn restore_path(error: &mut ServerError) {
+    let extensions = match error.extensions.as_mut() {
+        Some(extensions) => extensions,
+        None => return,
+    };
+
+    if let Some(Value::List(segments)) = extensions.get(RESOLVER_PATH_KEY) {
+        error.path = segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Value::Number(index) => index
+                    .as_u64()
+                    .map(|index| PathSegment::Index(index as usize)),
+                Value::String(name) => Some(PathSegment::Field(name.clone())),
+                _ => None,
+            })
+            .collect();
+    }
+
+    extensions.unset(RESOLVER_PATH_KEY);
+}
+
+fn restore_paths(mut response: Response) -> Response {
+    response.errors.iter_mut().for_each(restore_path);
+    response
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for ErrorPathExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        restore_paths(next.run(ctx).await)
+    }
+
+    fn subscribe<'s>(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        stream: BoxStream<'s, Response>,
+        next: NextSubscribe<'_>,
+    ) -> BoxStream<'s, Response> {
+        next.run(ctx, stream).map(restore_paths).boxed()
+    }
+
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let path_node = info.path_node;
+
+        next.run(ctx, info).await.map_err(|mut error| {
+            let extensions = error.extensions.get_or_insert_with(Default::default);
+
+            // Resolvers of the enclosing fields see the error after the one
+            // which raised it
+            if extensions.get(RESOLVER_PATH_KEY).is_none() {
+                extensions.set(RESOLVER_PATH_KEY, path_value(path_node));
+            }
+
+            error
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::connection::{Connection, Edge};
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema};
+
+    use crate::error::{Error, Result};
+
+    use super::ErrorPath;
+
+    /// Node whose `secret` can't be read for the second item
+    struct RestrictedItem(i32);
+
+    #[Object]
+    impl RestrictedItem {
+        async fn secret(&self) -> Result<Option<String>> {
+            if self.0 == 1 {
+                return Err(Error::forbidden("read this secret"));
+            }
+
+            Ok(Some(format!("secret-{}", self.0)))
+        }
+    }
+
+    struct TestQuery;
+
+    #[Object]
+    impl TestQuery {
+        async fn restricted_items(&self) -> Connection<usize, RestrictedItem> {
+            let mut connection = Connection::new(false, false);
+
+            connection.append((0..3).map(|index| Edge::new(index as usize, RestrictedItem(index))));
+            connection
+        }
+    }
+
+    #[rocket::async_test]
+    async fn errors_of_nested_nodes_point_at_the_failing_field() {
+        let schema = Schema::build(TestQuery, EmptyMutation, EmptySubscription)
+            .extension(ErrorPath)
+            .finish();
+        let response = schema
+            .execute("{ restrictedItems { edges { node { secret } } } }")
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(
+            error["path"],
+            serde_json::json!(["restrictedItems", "edges", 1, "node", "secret"])
+        );
+        assert_eq!(error["extensions"]["code"], "FORBIDDEN");
+        assert!(error["extensions"].get("resolverPath").is_none());
+    }
+}
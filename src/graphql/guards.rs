@@ -0,0 +1,217 @@
+use async_graphql::{Context, Guard};
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::modules::auth::{ApiScope, Authenticated};
+use crate::modules::user::{Role, User};
+use crate::routes::AuthToken;
+use crate::services::Services;
+
+/// Retrieves the caller authenticated by the request's token, along with
+/// the scopes of the API token it authenticated with.
+///
+/// The token of HTTP requests is verified once before the operation starts,
+/// as is the token of WebSocket connections before each of their
+/// operations. The caller it was verified for is read from the request
+/// data, tokens are only verified here when it's missing.
+pub async fn authenticated(ctx: &Context<'_>) -> Result<Authenticated> {
+    if let Some(authenticated) = ctx.data_opt::<Authenticated>() {
+        return Ok(authenticated.clone());
+    }
+
+    let token = ctx.data_unchecked::<AuthToken>().token()?;
+    let services = ctx.data_unchecked::<Arc<Services>>();
+    let authenticated = services.auth.verify_token(&token).await?;
+
+    tracing::Span::current().record(
+        "enduser.id",
+        &tracing::field::display(authenticated.user.id),
+    );
+
+    Ok(authenticated)
+}
+
+/// Retrieves the user authenticated by the request's token. This is the
+/// single source of truth for "who is the caller", guards and resolvers
+/// requiring authentication must go through it.
+pub async fn current_user(ctx: &Context<'_>) -> Result<User> {
+    let authenticated = authenticated(ctx).await?;
+
+    Ok(authenticated.user)
+}
+
+/// Rejects the field resolution unless the authenticated user's role
+/// satisfies the `required` role.
+///
+/// ```ignore
+/// #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+/// ```
+pub struct RoleGuard {
+    required: Role,
+}
+
+impl RoleGuard {
+    pub fn new(required: Role) -> Self {
+        Self { required }
+    }
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Guard for RoleGuard {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        let user = current_user(ctx).await?;
+
+        if user.role.satisfies(self.required) {
+            return Ok(());
+        }
+
+        Err(Error::forbidden("perform this action").into())
+    }
+}
+
+/// Rejects the field resolution for callers authenticated by an API token
+/// lacking the `required` scope. Session tokens are granted every scope, so
+/// combine it with a `RoleGuard` where the role matters.
+///
+/// ```ignore
+/// #[graphql(guard = "RoleGuard::new(Role::Admin).and(ScopeGuard::new(ApiScope::UsersRead))")]
+/// ```
+pub struct ScopeGuard {
+    required: ApiScope,
+}
+
+impl ScopeGuard {
+    pub fn new(required: ApiScope) -> Self {
+        Self { required }
+    }
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Guard for ScopeGuard {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        let authenticated = authenticated(ctx).await?;
+
+        Ok(ensure_scope(&authenticated, self.required)?)
+    }
+}
+
+/// Fails unless the caller's token grants the `required` scope, for
+/// resolvers whose required scope depends on what they resolve
+pub fn ensure_scope(authenticated: &Authenticated, required: ApiScope) -> Result<()> {
+    if authenticated.allows(required) {
+        return Ok(());
+    }
+
+    Err(Error::forbidden(&format!(
+        "perform this action without the {} scope",
+        required.as_str().to_uppercase()
+    )))
+}
+
+/// Rejects the field resolution for callers authenticated by an API token,
+/// keeps credentials from being managed by scripts and integrations
+pub struct SessionGuard;
+
+#[async_graphql::async_trait::async_trait]
+impl Guard for SessionGuard {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        let authenticated = authenticated(ctx).await?;
+
+        if authenticated.scopes.is_none() {
+            return Ok(());
+        }
+
+        Err(Error::forbidden("perform this action with an API token").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::Request;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use crate::config::GraphQLConfig;
+    use crate::graphql::relay::GlobalId;
+    use crate::graphql::schema_builder;
+    use crate::modules::auth::{ApiScope, Authenticated};
+    use crate::modules::user::{Role, User};
+    use crate::routes::AuthToken;
+
+    fn authenticated(scopes: Option<Vec<ApiScope>>) -> Authenticated {
+        Authenticated {
+            user: User {
+                role: Role::Admin,
+                ..User::fixture("esteban")
+            },
+            expires_at: Utc::now(),
+            scopes,
+        }
+    }
+
+    async fn error_code(query: &str, authenticated: Authenticated) -> Option<serde_json::Value> {
+        let schema = schema_builder(&GraphQLConfig::default()).finish();
+        let request = Request::new(query)
+            .data(AuthToken::empty())
+            .data(authenticated);
+        let response = schema.execute(request).await;
+
+        response
+            .errors
+            .first()
+            .map(|error| serde_json::to_value(error).unwrap()["extensions"]["code"].clone())
+    }
+
+    #[rocket::async_test]
+    async fn api_tokens_lacking_the_scope_are_forbidden() {
+        let query = "{ auditLog { error { code } } }";
+        let code = error_code(query, authenticated(Some(vec![ApiScope::PostsRead]))).await;
+
+        assert_eq!(code, Some(serde_json::json!("FORBIDDEN")));
+    }
+
+    #[rocket::async_test]
+    async fn users_are_listed_by_admins_only() {
+        let mut member = authenticated(None);
+
+        member.user.role = Role::User;
+
+        let code = error_code("{ users(first: 1) { __typename } }", member).await;
+
+        assert_eq!(code, Some(serde_json::json!("FORBIDDEN")));
+    }
+
+    #[rocket::async_test]
+    async fn users_are_resolved_by_ids_for_admins_only() {
+        let mut member = authenticated(None);
+
+        member.user.role = Role::User;
+
+        let code = error_code("{ usersByIds(ids: []) { __typename } }", member).await;
+
+        assert_eq!(code, Some(serde_json::json!("FORBIDDEN")));
+    }
+
+    #[rocket::async_test]
+    async fn nodes_require_the_scope_of_their_type() {
+        let id = Uuid::new_v4();
+
+        for (type_name, scope) in [("User", ApiScope::PostsRead), ("Post", ApiScope::UsersRead)] {
+            let query = format!(
+                r#"{{ node(id: "{}") {{ id }} }}"#,
+                GlobalId::new(type_name, id).encode().0
+            );
+            let code = error_code(&query, authenticated(Some(vec![scope]))).await;
+
+            assert_eq!(code, Some(serde_json::json!("FORBIDDEN")), "{type_name}");
+        }
+    }
+
+    #[rocket::async_test]
+    async fn api_tokens_cant_manage_credentials() {
+        let mutation = r#"mutation { apiTokenRevoke(id: "00000000-0000-0000-0000-000000000000") { __typename } }"#;
+        let code = error_code(mutation, authenticated(Some(vec![ApiScope::UsersWrite]))).await;
+
+        assert_eq!(code, Some(serde_json::json!("FORBIDDEN")));
+    }
+}
@@ -0,0 +1,114 @@
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextParseQuery};
+use async_graphql::parser::types::{ExecutableDocument, Selection, SelectionSet};
+use async_graphql::{Name, Pos, ServerResult, Variables};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::error::Error;
+
+/// Root fields exposing the schema, `__typename` reveals nothing beyond the
+/// type of the current object and stays allowed
+const INTROSPECTION_FIELDS: &[&str] = &["__schema", "__type"];
+
+/// Rejects documents selecting `__schema` or `__type` with a `FORBIDDEN`
+/// error. The schema builder's `disable_introspection` alone resolves them
+/// to `null`, which clients can't tell apart from an empty result.
+pub struct NoIntrospection;
+
+impl ExtensionFactory for NoIntrospection {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(NoIntrospectionExtension)
+    }
+}
+
+struct NoIntrospectionExtension;
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for NoIntrospectionExtension {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+
+        if has_introspection(&document) {
+            let error = async_graphql::Error::from(Error::forbidden("run introspection queries"));
+
+            return Err(error.into_server_error(Pos::default()));
+        }
+
+        Ok(document)
+    }
+}
+
+/// Checks whether any operation of the document selects an introspection
+/// field at its root, directly or through fragments
+fn has_introspection(document: &ExecutableDocument) -> bool {
+    document.operations.iter().any(|(_, operation)| {
+        selects_introspection(
+            document,
+            &operation.node.selection_set.node,
+            &mut HashSet::new(),
+        )
+    })
+}
+
+/// Documents aren't validated yet, `visited` breaks fragment cycles
+fn selects_introspection<'a>(
+    document: &'a ExecutableDocument,
+    selection_set: &'a SelectionSet,
+    visited: &mut HashSet<&'a Name>,
+) -> bool {
+    selection_set
+        .items
+        .iter()
+        .any(|selection| match &selection.node {
+            Selection::Field(field) => {
+                INTROSPECTION_FIELDS.contains(&field.node.name.node.as_str())
+            }
+            Selection::FragmentSpread(spread) => {
+                let name = &spread.node.fragment_name.node;
+
+                visited.insert(name)
+                    && document.fragments.get(name).is_some_and(|fragment| {
+                        selects_introspection(document, &fragment.node.selection_set.node, visited)
+                    })
+            }
+            Selection::InlineFragment(fragment) => {
+                selects_introspection(document, &fragment.node.selection_set.node, visited)
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::parser::parse_query;
+
+    use super::has_introspection;
+
+    #[test]
+    fn detects_introspection_fields_at_the_root() {
+        for (query, introspection) in [
+            ("{ __typename }", false),
+            ("{ me { me { id } } }", false),
+            ("{ __schema { types { name } } }", true),
+            (r#"{ __type(name: "User") { name } }"#, true),
+            ("{ ... on Query { __schema { queryType { name } } } }", true),
+            (
+                "query { ...Schema } fragment Schema on Query { __schema { queryType { name } } }",
+                true,
+            ),
+            (
+                "query { ...A } fragment A on Query { ...B } fragment B on Query { ...A }",
+                false,
+            ),
+        ] {
+            let document = parse_query(query).unwrap();
+
+            assert_eq!(has_introspection(&document), introspection, "{query}");
+        }
+    }
+}
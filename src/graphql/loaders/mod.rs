@@ -1,3 +1,3 @@
 mod user;
 
-pub use user::UserLoader;
+pub use user::{UserKey, UserLoader};
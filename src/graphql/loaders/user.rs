@@ -1,6 +1,5 @@
 use async_graphql::dataloader::Loader;
 use async_graphql::*;
-use itertools::Itertools;
 use std::collections::HashMap;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -9,6 +8,23 @@ use crate::database::Database;
 use crate::error::Error;
 use crate::modules::user::{User, UsersTableRow};
 
+/// Identifies a user as seen from an organization, users of other
+/// organizations are not loaded
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct UserKey {
+    pub organization_id: Uuid,
+    pub id: Uuid,
+}
+
+impl UserKey {
+    pub fn new(organization_id: Uuid, id: Uuid) -> Self {
+        Self {
+            organization_id,
+            id,
+        }
+    }
+}
+
 pub struct UserLoader {
     database: Arc<Database>,
 }
@@ -20,26 +36,78 @@ impl UserLoader {
 }
 
 #[async_trait::async_trait]
-impl Loader<Uuid> for UserLoader {
+impl Loader<UserKey> for UserLoader {
     type Value = User;
     type Error = Error;
 
-    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
-        let query = format!(
-            "SELECT * FROM users WHERE id IN ({})",
-            keys.iter()
-                .map(|uuid| { format!("'{}'::uuid", uuid) })
-                .join(",")
-        );
-        let result: Vec<UsersTableRow> = sqlx::query_as(&query)
+    async fn load(&self, keys: &[UserKey]) -> Result<HashMap<UserKey, Self::Value>, Self::Error> {
+        let ids = keys.iter().map(|key| key.id).collect::<Vec<Uuid>>();
+        let result: Vec<UsersTableRow> = sqlx::query_as("SELECT * FROM users WHERE id = ANY($1)")
+            .bind(ids)
             .fetch_all(&self.database.conn_pool)
             .await?;
-        let mut res: HashMap<Uuid, User> = HashMap::new();
+        let users = result.into_iter().map(User::from).collect();
+
+        Ok(scope_to_keys(keys, users))
+    }
+}
+
+/// Maps every key to its user, as long as the user belongs to the key's
+/// organization. A batch may mix keys of several organizations.
+fn scope_to_keys(keys: &[UserKey], users: Vec<User>) -> HashMap<UserKey, User> {
+    let users = users
+        .into_iter()
+        .map(|user| (user.id, user))
+        .collect::<HashMap<Uuid, User>>();
+
+    keys.iter()
+        .filter_map(|key| {
+            users
+                .get(&key.id)
+                .filter(|user| user.organization_id == key.organization_id)
+                .map(|user| (*key, user.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::modules::user::User;
+
+    use super::{scope_to_keys, UserKey};
+
+    #[test]
+    fn loads_users_of_the_same_organization() {
+        let organization_a = Uuid::new_v4();
+        let user_a = User {
+            organization_id: organization_a,
+            ..User::fixture("esteban")
+        };
+        let key = UserKey::new(organization_a, user_a.id);
+        let loaded = scope_to_keys(&[key], vec![user_a.clone()]);
+
+        assert_eq!(loaded.get(&key).map(|user| user.id), Some(user_a.id));
+    }
 
-        result.into_iter().for_each(|u: UsersTableRow| {
-            res.insert(u.id, User::from(u));
-        });
+    #[test]
+    fn hides_users_of_other_organizations() {
+        let organization_a = Uuid::new_v4();
+        let user_a = User {
+            organization_id: organization_a,
+            ..User::fixture("esteban")
+        };
+        let user_b = User {
+            organization_id: Uuid::new_v4(),
+            ..User::fixture("esteban")
+        };
+        // A caller of organization A presenting the id of a user of B
+        let forged = UserKey::new(organization_a, user_b.id);
+        let own = UserKey::new(organization_a, user_a.id);
+        let loaded = scope_to_keys(&[forged, own], vec![user_a, user_b]);
 
-        Ok(res)
+        assert!(!loaded.contains_key(&forged));
+        assert!(loaded.contains_key(&own));
     }
 }
@@ -1,16 +1,333 @@
+pub mod alias_limit;
+pub mod allowlist;
+pub mod bad_input;
+pub mod deprecation;
+pub mod duration;
+pub mod error_path;
+pub mod guards;
+pub mod introspection;
 pub mod loaders;
+pub mod node;
+pub mod partial_results;
+pub mod persisted_queries;
+pub mod query_timeout;
+pub mod rate_limit;
+pub mod read_only;
 pub mod relay;
+pub mod sdl;
+pub mod websocket;
 
-use async_graphql::{EmptySubscription, MergedObject};
+use async_graphql::{MergedObject, MergedSubscription, SchemaBuilder};
+use std::sync::Arc;
 
-use crate::modules::auth::graphql::AuthMutation;
+use crate::config::GraphQLConfig;
+use crate::modules::audit::graphql::AuditQuery;
+use crate::modules::auth::graphql::{AuthMutation, AuthQuery, AuthSubscription};
 use crate::modules::post::graphql::{PostMutation, PostQuery};
+use crate::modules::user::graphql::users_by_ids::MaxBatchIds;
 use crate::modules::user::graphql::{UserMutation, UserQuery};
 
+use self::alias_limit::AliasLimit;
+use self::allowlist::{Allowlist, OperationAllowlist};
+use self::bad_input::BadInput;
+use self::deprecation::DeprecationUsage;
+use self::error_path::ErrorPath;
+use self::introspection::NoIntrospection;
+use self::node::NodeQuery;
+use self::partial_results::PartialResults;
+use self::persisted_queries::PersistedQueries;
+use self::query_timeout::QueryTimeout;
+use self::rate_limit::{RateLimit, RateLimiter};
+use self::read_only::{ReadOnly, ReadOnlyMode, ReadOnlyMutation};
+use self::websocket::ReverifyConnectionToken;
+
 #[derive(MergedObject, Default)]
-pub struct Query(pub PostQuery, pub UserQuery);
+pub struct Query(
+    pub AuditQuery,
+    pub AuthQuery,
+    pub NodeQuery,
+    pub PostQuery,
+    pub UserQuery,
+);
 
 #[derive(MergedObject, Default)]
-pub struct Mutation(pub AuthMutation, pub PostMutation, pub UserMutation);
+pub struct Mutation(
+    pub AuthMutation,
+    pub PostMutation,
+    pub ReadOnlyMutation,
+    pub UserMutation,
+);
+
+#[derive(MergedSubscription, Default)]
+pub struct Subscription(pub AuthSubscription);
+
+pub type Schema = async_graphql::Schema<Query, Mutation, Subscription>;
+
+/// Creates a `SchemaBuilder` with the query and alias limits, rate limiting,
+/// read-only mode, persisted queries, operation allowlist and introspection
+/// from the provided configuration applied. Queries exceeding these limits
+/// are rejected before execution, and operations executing for longer than
+/// the query timeout are cancelled. Errors keep the path of the field which
+/// failed and null the nearest nullable field rather than the whole response.
+/// Resolutions of deprecated fields are counted, and operations of WebSocket
+/// connections verify the connection's token again.
+pub fn schema_builder(config: &GraphQLConfig) -> SchemaBuilder<Query, Mutation, Subscription> {
+    let read_only_mode = Arc::new(ReadOnlyMode::new(config.read_only));
+    let builder = Schema::build(
+        Query::default(),
+        Mutation::default(),
+        Subscription::default(),
+    )
+    .extension(BadInput)
+    .extension(ErrorPath)
+    .extension(PartialResults)
+    .extension(DeprecationUsage)
+    .extension(PersistedQueries::from_config(&config.persisted_queries))
+    .limit_depth(config.depth_limit)
+    .limit_complexity(config.complexity_limit)
+    .extension(AliasLimit::new(config.max_aliases))
+    .extension(ReverifyConnectionToken)
+    .extension(RateLimit::new(RateLimiter::new(&config.rate_limit)))
+    .extension(ReadOnly::new(Arc::clone(&read_only_mode)))
+    .extension(QueryTimeout::new(config.query_timeout))
+    .data(read_only_mode)
+    .data(MaxBatchIds(config.max_batch_ids));
+
+    let builder = match &config.allowlist {
+        Some(manifest) => builder.extension(OperationAllowlist::new(
+            Allowlist::from_manifest(manifest)
+                .unwrap_or_else(|message| panic!("Invalid operation allowlist: {}", message)),
+        )),
+        None => builder,
+    };
+
+    if config.is_introspection_disabled() {
+        return builder.extension(NoIntrospection).disable_introspection();
+    }
+
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::futures_util::StreamExt;
+    use async_graphql::Request;
+
+    use crate::config::{GraphQLConfig, RateLimitConfig, DEFAULT_GRAPHQL_COMPLEXITY_LIMIT};
+    use crate::routes::AuthToken;
+
+    use super::rate_limit::RateLimitKey;
+    use super::schema_builder;
+
+    #[rocket::async_test]
+    async fn rejects_queries_exceeding_depth_limit() {
+        let schema = schema_builder(&GraphQLConfig::default()).finish();
+        let nested_type = (0..20).fold(String::from("name"), |selection, _| {
+            format!("ofType {{ {selection} }}")
+        });
+        let query =
+            format!("{{ __schema {{ types {{ fields {{ type {{ {nested_type} }} }} }} }} }}");
+        let response = schema.execute(query).await;
+
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(response.errors[0].message, "Query is nested too deep.");
+    }
+
+    #[rocket::async_test]
+    async fn rejects_nested_connection_queries_exceeding_depth_limit() {
+        let config = GraphQLConfig {
+            depth_limit: 4,
+            ..GraphQLConfig::default()
+        };
+        let schema = schema_builder(&config).finish();
+        let query = "{ feed { feed { edges { node { user { username } } } } } }";
+        let response = schema.execute(query).await;
+
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(response.errors[0].message, "Query is nested too deep.");
+    }
+
+    #[rocket::async_test]
+    async fn rejects_queries_exceeding_complexity_limit() {
+        let config = GraphQLConfig {
+            complexity_limit: 3,
+            ..GraphQLConfig::default()
+        };
+        let schema = schema_builder(&config).finish();
+        let query = "{ feed { feed { edges { node { id content } } } } }";
+        let response = schema.execute(query).await;
+
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(response.errors[0].message, "Query is too complex.");
+    }
+
+    #[rocket::async_test]
+    async fn me_is_null_without_token() {
+        let schema = schema_builder(&GraphQLConfig::default()).finish();
+        let request = Request::new("{ me { me { id } error { code } } }").data(AuthToken::empty());
+        let response = schema.execute(request).await;
+        let data = response.data.into_json().unwrap();
+
+        assert!(response.errors.is_empty());
+        assert_eq!(data["me"]["me"], serde_json::Value::Null);
+        assert_eq!(data["me"]["error"]["code"], "UNAUTHORIZED");
+    }
+
+    #[rocket::async_test]
+    async fn allowlist_disables_introspection() {
+        let manifest =
+            std::env::temp_dir().join(format!("allowlist-{}.json", uuid::Uuid::new_v4()));
+
+        std::fs::write(&manifest, r#"["Introspection"]"#).unwrap();
+
+        let config = GraphQLConfig {
+            allowlist: Some(manifest.clone()),
+            ..GraphQLConfig::default()
+        };
+        let schema = schema_builder(&config).finish();
+
+        std::fs::remove_file(manifest).unwrap();
+
+        let response = schema
+            .execute("query Introspection { __schema { queryType { name } } }")
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(error["extensions"]["code"], "FORBIDDEN");
+
+        let response = schema.execute("{ __schema { queryType { name } } }").await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(error["extensions"]["code"], "OPERATION_NOT_ALLOWED");
+    }
+
+    #[rocket::async_test]
+    async fn introspection_is_rejected_when_disabled() {
+        let query = "{ __schema { queryType { name } } }";
+        let schema = schema_builder(&GraphQLConfig::default()).finish();
+        let response = schema.execute(query).await;
+        let data = response.data.into_json().unwrap();
+
+        assert!(response.errors.is_empty());
+        assert_eq!(data["__schema"]["queryType"]["name"], "Query");
+
+        let config = GraphQLConfig {
+            disable_introspection: true,
+            ..GraphQLConfig::default()
+        };
+        let schema = schema_builder(&config).finish();
+        let response = schema.execute(query).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(error["extensions"]["code"], "FORBIDDEN");
+        assert_eq!(
+            error["extensions"]["message"],
+            "You are not allowed to run introspection queries"
+        );
+
+        let response = schema.execute("{ __typename }").await;
+
+        assert!(response.errors.is_empty());
+    }
+
+    #[test]
+    fn exposes_user_timestamps_as_date_time_scalars() {
+        let sdl = schema_builder(&GraphQLConfig::default()).finish().sdl();
+
+        assert!(sdl.contains("createdAt: DateTime!"));
+        assert!(sdl.contains("updatedAt: DateTime!"));
+        assert!(sdl.contains("orderBy: UserOrder"));
+    }
+
+    #[rocket::async_test]
+    async fn rejects_queries_exceeding_rate_limit() {
+        let config = GraphQLConfig {
+            rate_limit: RateLimitConfig {
+                capacity: DEFAULT_GRAPHQL_COMPLEXITY_LIMIT as u32,
+                refill_per_sec: 1,
+            },
+            ..GraphQLConfig::default()
+        };
+        let schema = schema_builder(&config).finish();
+        let key = RateLimitKey::User(uuid::Uuid::new_v4());
+        let query = "{ __schema { types { name fields { name } } } }";
+        let mut response = schema.execute(Request::new(query).data(key.clone())).await;
+
+        while response.errors.is_empty() {
+            response = schema.execute(Request::new(query).data(key.clone())).await;
+        }
+
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(error["extensions"]["code"], "RATE_LIMITED");
+        assert!(error["extensions"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Too many requests, retry after"));
+    }
+
+    #[rocket::async_test]
+    async fn session_events_require_authentication() {
+        let schema = schema_builder(&GraphQLConfig::default()).finish();
+        let request =
+            Request::new("subscription { sessionEvents { kind } }").data(AuthToken::empty());
+        let response = schema.execute_stream(request).next().await.unwrap();
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(error["extensions"]["code"], "UNAUTHORIZED");
+    }
+
+    #[rocket::async_test]
+    async fn read_only_mode_rejects_mutations_only() {
+        let config = GraphQLConfig {
+            read_only: true,
+            ..GraphQLConfig::default()
+        };
+        let schema = schema_builder(&config).finish();
+        let mutation = r#"mutation {
+            accountRegister(input: {
+                name: "Esteban", lastName: "Borai", email: "esteban@nexus.dev",
+                username: "esteban", password: "Password1", birthdate: "1990-01-01T00:00:00Z",
+                gender: MALE, pronoun: HE
+            }) { user { id } }
+        }"#;
+        let response = schema
+            .execute(Request::new(mutation).data(AuthToken::empty()))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(error["extensions"]["code"], "READ_ONLY");
+
+        let request = Request::new("{ me { me { id } error { code } } }").data(AuthToken::empty());
+        let response = schema.execute(request).await;
+        let data = response.data.into_json().unwrap();
+
+        assert!(response.errors.is_empty());
+        assert_eq!(data["me"]["error"]["code"], "UNAUTHORIZED");
+    }
+
+    #[rocket::async_test]
+    async fn read_only_mode_can_be_disabled_while_enabled() {
+        let config = GraphQLConfig {
+            read_only: true,
+            ..GraphQLConfig::default()
+        };
+        let schema = schema_builder(&config).finish();
+        let request = Request::new("mutation { readOnlySet(enabled: false) { enabled } }")
+            .data(AuthToken::empty());
+        let response = schema.execute(request).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        // Reaches the admin guard instead of being rejected as a mutation
+        assert_eq!(error["extensions"]["code"], "UNAUTHORIZED");
+    }
+
+    #[test]
+    fn users_and_posts_implement_node() {
+        let sdl = schema_builder(&GraphQLConfig::default()).finish().sdl();
 
-pub type Schema = async_graphql::Schema<Query, Mutation, EmptySubscription>;
+        assert!(sdl.contains("type User implements Node"));
+        assert!(sdl.contains("type Post implements Node"));
+        assert!(sdl.contains("node(id: ID!): Node!"));
+    }
+}
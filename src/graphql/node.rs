@@ -0,0 +1,87 @@
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{Context, Interface, Object, ID};
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::graphql::guards::{authenticated, ensure_scope};
+use crate::graphql::loaders::{UserKey, UserLoader};
+use crate::graphql::relay::GlobalId;
+use crate::modules::auth::ApiScope;
+use crate::modules::post::graphql::Post;
+use crate::modules::post::Scope;
+use crate::modules::user::User;
+use crate::services::Services;
+
+/// Relay object identification, every object implementing it can be fetched
+/// using the `node` query
+#[derive(Interface)]
+#[graphql(field(name = "id", type = "ID"))]
+pub enum Node {
+    User(User),
+    Post(Post),
+}
+
+#[derive(Default)]
+pub struct NodeQuery;
+
+#[Object]
+impl NodeQuery {
+    /// Fetches an object given its global id. Objects of other organizations
+    /// than the caller's are not found. API tokens require the read scope of
+    /// the object's type, `USERS_READ` for users and `POSTS_READ` for posts.
+    async fn node(&self, ctx: &Context<'_>, id: ID) -> Result<Node> {
+        let not_found = || Error::not_found("node", &id);
+        let global_id = GlobalId::decode(&id).ok_or_else(not_found)?;
+        let required_scope = read_scope(&global_id.type_name).ok_or_else(not_found)?;
+        let authenticated = authenticated(ctx).await?;
+
+        ensure_scope(&authenticated, required_scope)?;
+
+        let user_loader = ctx.data_unchecked::<DataLoader<UserLoader>>();
+        let caller = authenticated.user;
+
+        match global_id.type_name.as_str() {
+            "User" => {
+                let user = user_loader
+                    .load_one(UserKey::new(caller.organization_id, global_id.id))
+                    .await?
+                    .filter(|user| user.deleted_at.is_none())
+                    .ok_or_else(not_found)?;
+
+                Ok(Node::User(user))
+            }
+            "Post" => {
+                let services = ctx.data_unchecked::<Arc<Services>>();
+                let post = services
+                    .post
+                    .find_by_id(global_id.id)
+                    .await?
+                    .ok_or_else(not_found)?;
+
+                // Private posts are only visible to their authors
+                if post.scope == Scope::Private && caller.id != post.user_id {
+                    return Err(not_found());
+                }
+
+                // Posts belong to the organization of their author
+                let user = user_loader
+                    .load_one(UserKey::new(caller.organization_id, post.user_id))
+                    .await?
+                    .ok_or_else(not_found)?;
+
+                Ok(Node::Post(Post::new(post, Some(user))))
+            }
+            _ => Err(not_found()),
+        }
+    }
+}
+
+/// Scope API tokens require to read nodes of the `type_name` type, `None`
+/// for types which aren't nodes
+fn read_scope(type_name: &str) -> Option<ApiScope> {
+    match type_name {
+        "User" => Some(ApiScope::UsersRead),
+        "Post" => Some(ApiScope::PostsRead),
+        _ => None,
+    }
+}
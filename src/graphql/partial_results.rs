@@ -0,0 +1,162 @@
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextRequest, NextResolve, NextSubscribe,
+    ResolveInfo,
+};
+use async_graphql::futures_util::stream::{BoxStream, StreamExt};
+use async_graphql::{Response, ServerError, ServerResult, Value};
+use std::sync::{Arc, Mutex};
+
+/// Resolves the fields failing within a nullable field to `null`, reporting
+/// their error along with the data of the fields which didn't fail.
+///
+/// async-graphql only does so for errors raised while resolving the value of
+/// a nullable field, errors returned by resolvers bubble up to the root and
+/// discard the whole response. Must be registered after `ErrorPath`, so the
+/// path of the errors it catches is still restored.
+pub struct PartialResults;
+
+impl ExtensionFactory for PartialResults {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PartialResultsExtension::default())
+    }
+}
+
+/// Created for every request, holds the errors caught while executing it
+#[derive(Default)]
+struct PartialResultsExtension {
+    errors: Arc<Mutex<Vec<ServerError>>>,
+}
+
+fn append_errors(mut response: Response, errors: &Mutex<Vec<ServerError>>) -> Response {
+    response.errors.append(&mut errors.lock().unwrap());
+    response
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for PartialResultsExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        append_errors(next.run(ctx).await, &self.errors)
+    }
+
+    /// Errors are caught while resolving an event, they are moved to its
+    /// response
+    fn subscribe<'s>(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        stream: BoxStream<'s, Response>,
+        next: NextSubscribe<'_>,
+    ) -> BoxStream<'s, Response> {
+        let errors = Arc::clone(&self.errors);
+
+        next.run(ctx, stream)
+            .map(move |response| append_errors(response, &errors))
+            .boxed()
+    }
+
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let nullable = !info.return_type.ends_with('!');
+
+        match next.run(ctx, info).await {
+            Err(error) if nullable => {
+                self.errors.lock().unwrap().push(error);
+
+                Ok(Some(Value::Null))
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema};
+
+    use crate::error::{Error, Result};
+    use crate::graphql::error_path::ErrorPath;
+
+    use super::PartialResults;
+
+    struct Item(i32);
+
+    #[Object]
+    impl Item {
+        async fn id(&self) -> i32 {
+            self.0
+        }
+
+        /// Non-null, failing for the second item
+        async fn secret(&self) -> Result<String> {
+            if self.0 == 1 {
+                return Err(Error::forbidden("read this secret"));
+            }
+
+            Ok(format!("secret-{}", self.0))
+        }
+    }
+
+    struct TestQuery;
+
+    #[Object]
+    impl TestQuery {
+        async fn greeting(&self) -> &str {
+            "hello"
+        }
+
+        async fn failing(&self) -> Result<Option<String>> {
+            Err(Error::forbidden("read this field"))
+        }
+
+        async fn items(&self) -> Vec<Option<Item>> {
+            (0..3).map(|index| Some(Item(index))).collect()
+        }
+    }
+
+    async fn execute(query: &str) -> (serde_json::Value, Vec<serde_json::Value>) {
+        let schema = Schema::build(TestQuery, EmptyMutation, EmptySubscription)
+            .extension(ErrorPath)
+            .extension(PartialResults)
+            .finish();
+        let response = schema.execute(query).await;
+        let errors = response
+            .errors
+            .iter()
+            .map(|error| serde_json::to_value(error).unwrap())
+            .collect();
+
+        (response.data.into_json().unwrap(), errors)
+    }
+
+    #[rocket::async_test]
+    async fn failing_fields_are_nulled_next_to_their_siblings() {
+        let (data, errors) = execute("{ greeting failing }").await;
+
+        assert_eq!(
+            data,
+            serde_json::json!({ "greeting": "hello", "failing": null })
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0]["path"], serde_json::json!(["failing"]));
+        assert_eq!(errors[0]["extensions"]["code"], "FORBIDDEN");
+    }
+
+    #[rocket::async_test]
+    async fn non_null_failures_null_the_nearest_nullable_field() {
+        let (data, errors) = execute("{ items { id secret } }").await;
+
+        assert_eq!(
+            data["items"],
+            serde_json::json!([
+                { "id": 0, "secret": "secret-0" },
+                null,
+                { "id": 2, "secret": "secret-2" },
+            ])
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0]["path"], serde_json::json!(["items", 1, "secret"]));
+    }
+}
@@ -0,0 +1,313 @@
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest,
+};
+use async_graphql::{Pos, Request, ServerError, ServerResult};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::config::PersistedQueriesConfig;
+use crate::error::{Error, ErrorCode};
+
+/// Request extension carrying the hash of a persisted query
+const PERSISTED_QUERY_EXTENSION: &str = "persistedQuery";
+
+/// Message clients expect when a hash is unknown, they resend the request
+/// along with the full query to register it
+const PERSISTED_QUERY_NOT_FOUND_MESSAGE: &str = "PersistedQueryNotFound";
+
+/// The `persistedQuery` request extension as sent by Apollo clients
+#[derive(Deserialize)]
+struct PersistedQuery {
+    version: i32,
+    #[serde(rename = "sha256Hash")]
+    sha256_hash: String,
+}
+
+/// Queries registered by their SHA-256 hash. Holds at most `capacity`
+/// queries, once full new queries are executed without being registered.
+pub struct PersistedQueryStore {
+    capacity: usize,
+    queries: Mutex<HashMap<String, String>>,
+}
+
+impl PersistedQueryStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            queries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Creates a store with the queries of a JSON manifest mapping hashes to
+    /// queries, the `capacity` grows to fit every query in it
+    pub fn from_manifest(capacity: usize, path: &Path) -> Result<Self, String> {
+        let manifest = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        let queries: HashMap<String, String> = serde_json::from_str(&manifest)
+            .map_err(|err| format!("Failed to parse {}: {err}", path.display()))?;
+
+        if let Some((hash, _)) = queries
+            .iter()
+            .find(|(hash, query)| **hash != hash_query(query))
+        {
+            return Err(format!("Hash {hash} doesn't match its query"));
+        }
+
+        Ok(Self {
+            capacity: capacity.max(queries.len()),
+            queries: Mutex::new(queries),
+        })
+    }
+
+    pub fn get(&self, hash: &str) -> Option<String> {
+        self.queries.lock().unwrap().get(hash).cloned()
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.queries.lock().unwrap().contains_key(hash)
+    }
+
+    /// Registers `query` under `hash`, returns `false` if the store is full
+    pub fn register(&self, hash: String, query: String) -> bool {
+        let mut queries = self.queries.lock().unwrap();
+
+        if queries.len() >= self.capacity && !queries.contains_key(&hash) {
+            return false;
+        }
+
+        queries.insert(hash, query);
+
+        true
+    }
+}
+
+/// Hex encoded SHA-256 hash of `query`, as computed by clients
+pub fn hash_query(query: &str) -> String {
+    hex::encode(Sha256::digest(query.as_bytes()))
+}
+
+/// Automatic persisted queries: requests may carry the hash of a query in
+/// place of the query itself. Unknown hashes get a `PersistedQueryNotFound`
+/// error, upon which clients resend the full query to register it.
+///
+/// In persisted-only mode queries are never registered and those missing
+/// from the store are rejected, so only queries from the manifest run.
+pub struct PersistedQueries {
+    store: Arc<PersistedQueryStore>,
+    only: bool,
+}
+
+impl PersistedQueries {
+    pub fn new(store: Arc<PersistedQueryStore>, only: bool) -> Self {
+        Self { store, only }
+    }
+
+    /// Creates the extension from the provided configuration, loading the
+    /// manifest if any
+    pub fn from_config(config: &PersistedQueriesConfig) -> Self {
+        let store = match &config.manifest {
+            Some(path) => PersistedQueryStore::from_manifest(config.capacity, path).unwrap_or_else(
+                |message| panic!("Invalid persisted queries manifest: {}", message),
+            ),
+            None => PersistedQueryStore::new(config.capacity),
+        };
+
+        Self::new(Arc::new(store), config.only)
+    }
+}
+
+impl ExtensionFactory for PersistedQueries {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PersistedQueriesExtension {
+            store: Arc::clone(&self.store),
+            only: self.only,
+        })
+    }
+}
+
+struct PersistedQueriesExtension {
+    store: Arc<PersistedQueryStore>,
+    only: bool,
+}
+
+impl PersistedQueriesExtension {
+    fn resolve(&self, mut request: Request) -> Result<Request, Error> {
+        let persisted_query = match request.extensions.remove(PERSISTED_QUERY_EXTENSION) {
+            Some(value) => Some(
+                async_graphql::from_value::<PersistedQuery>(value)
+                    .ok()
+                    .filter(|persisted_query| persisted_query.version == 1)
+                    .ok_or_else(|| {
+                        Error::new(
+                            PERSISTED_QUERY_EXTENSION,
+                            "Only version 1 of persisted queries is supported",
+                            ErrorCode::ValidationError,
+                        )
+                    })?,
+            ),
+            None => None,
+        };
+
+        if request.query.is_empty() {
+            let hash = persisted_query.map(|persisted_query| persisted_query.sha256_hash);
+
+            return match hash.and_then(|hash| self.store.get(&hash)) {
+                Some(query) => Ok(Request { query, ..request }),
+                None => Err(Error::code(ErrorCode::PersistedQueryNotFound)),
+            };
+        }
+
+        let hash = hash_query(&request.query);
+
+        if persisted_query.is_some_and(|persisted_query| persisted_query.sha256_hash != hash) {
+            return Err(Error::new(
+                PERSISTED_QUERY_EXTENSION,
+                "The provided hash doesn't match the query",
+                ErrorCode::ValidationError,
+            ));
+        }
+
+        if self.only {
+            if !self.store.contains(&hash) {
+                return Err(Error::forbidden("run queries which are not persisted"));
+            }
+        } else if !self.store.register(hash, request.query.clone()) {
+            tracing::warn!("persisted query store is full");
+        }
+
+        Ok(request)
+    }
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for PersistedQueriesExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        let request = self.resolve(request).map_err(into_server_error)?;
+
+        next.run(ctx, request).await
+    }
+}
+
+/// Apollo clients look for the `PersistedQueryNotFound` message rather than
+/// the error code, it replaces the generic message in that case
+fn into_server_error(err: Error) -> ServerError {
+    let not_found = err.code == ErrorCode::PersistedQueryNotFound;
+    let mut error = async_graphql::Error::from(err);
+
+    if not_found {
+        error.message = String::from(PERSISTED_QUERY_NOT_FOUND_MESSAGE);
+    }
+
+    error.into_server_error(Pos::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{Name, Request, Value};
+    use std::sync::Arc;
+
+    use crate::graphql::{Mutation, Query, Schema, Subscription};
+
+    use super::{hash_query, PersistedQueries, PersistedQueryStore};
+
+    const QUERY: &str = "{ __typename }";
+
+    fn schema(store: Arc<PersistedQueryStore>, only: bool) -> Schema {
+        Schema::build(
+            Query::default(),
+            Mutation::default(),
+            Subscription::default(),
+        )
+        .extension(PersistedQueries::new(store, only))
+        .finish()
+    }
+
+    fn persisted_request(query: &str, hash: &str) -> Request {
+        let mut request = Request::new(query);
+        let persisted_query = Value::Object(
+            [
+                (Name::new("version"), Value::from(1)),
+                (Name::new("sha256Hash"), Value::from(hash)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        request
+            .extensions
+            .insert(String::from("persistedQuery"), persisted_query);
+        request
+    }
+
+    #[rocket::async_test]
+    async fn unknown_hashes_are_not_found() {
+        let schema = schema(Arc::new(PersistedQueryStore::new(10)), false);
+        let response = schema
+            .execute(persisted_request("", &hash_query(QUERY)))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(error["message"], "PersistedQueryNotFound");
+        assert_eq!(error["extensions"]["code"], "PERSISTED_QUERY_NOT_FOUND");
+    }
+
+    #[rocket::async_test]
+    async fn registered_queries_are_replayed_by_hash() {
+        let schema = schema(Arc::new(PersistedQueryStore::new(10)), false);
+        let hash = hash_query(QUERY);
+        let response = schema.execute(persisted_request(QUERY, &hash)).await;
+
+        assert!(response.errors.is_empty());
+
+        let response = schema.execute(persisted_request("", &hash)).await;
+        let data = response.data.into_json().unwrap();
+
+        assert!(response.errors.is_empty());
+        assert_eq!(data["__typename"], "Query");
+    }
+
+    #[rocket::async_test]
+    async fn mismatching_hashes_are_rejected() {
+        let store = Arc::new(PersistedQueryStore::new(10));
+        let schema = schema(Arc::clone(&store), false);
+        let response = schema.execute(persisted_request(QUERY, "abc")).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(error["extensions"]["code"], "VALIDATION_ERROR");
+        assert!(!store.contains("abc"));
+    }
+
+    #[rocket::async_test]
+    async fn persisted_only_mode_rejects_unregistered_queries() {
+        let store = Arc::new(PersistedQueryStore::new(10));
+        let schema = schema(Arc::clone(&store), true);
+        let response = schema.execute(Request::new(QUERY)).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(error["extensions"]["code"], "FORBIDDEN");
+        assert!(!store.contains(&hash_query(QUERY)));
+
+        store.register(hash_query(QUERY), String::from(QUERY));
+
+        let response = schema.execute(Request::new(QUERY)).await;
+
+        assert!(response.errors.is_empty());
+    }
+
+    #[test]
+    fn full_stores_stop_registering() {
+        let store = PersistedQueryStore::new(1);
+
+        assert!(store.register(hash_query(QUERY), String::from(QUERY)));
+        assert!(!store.register(hash_query("{ me }"), String::from("{ me }")));
+        assert!(store.contains(&hash_query(QUERY)));
+    }
+}
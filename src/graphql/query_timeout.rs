@@ -0,0 +1,108 @@
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextExecute};
+use async_graphql::{Pos, Response};
+use rocket::tokio::time::timeout;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::Error;
+
+/// Cancels operations executing for longer than `limit`, answering them with
+/// a `TIMEOUT` error. Cancelling drops the pending resolvers along with
+/// their queries: sqlx then returns the connections to the pool, or closes
+/// them if left unusable, and rolls back open transactions. Resolvers are
+/// only interrupted where they await, subscriptions aren't limited.
+pub struct QueryTimeout {
+    limit: Duration,
+}
+
+impl QueryTimeout {
+    pub fn new(limit: Duration) -> Self {
+        Self { limit }
+    }
+}
+
+impl ExtensionFactory for QueryTimeout {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(QueryTimeoutExtension { limit: self.limit })
+    }
+}
+
+struct QueryTimeoutExtension {
+    limit: Duration,
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for QueryTimeoutExtension {
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        match timeout(self.limit, next.run(ctx, operation_name)).await {
+            Ok(response) => response,
+            Err(_) => {
+                tracing::warn!(
+                    operation_name,
+                    limit_secs = self.limit.as_secs(),
+                    "operation timed out"
+                );
+
+                let error = async_graphql::Error::from(Error::timeout(self.limit));
+
+                Response::from_errors(vec![error.into_server_error(Pos::default())])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema};
+    use std::time::Duration;
+
+    use super::QueryTimeout;
+
+    struct TestQuery;
+
+    #[Object]
+    impl TestQuery {
+        async fn fast(&self) -> bool {
+            true
+        }
+
+        async fn slow(&self) -> bool {
+            rocket::tokio::time::sleep(Duration::from_secs(5)).await;
+
+            true
+        }
+    }
+
+    fn schema() -> Schema<TestQuery, EmptyMutation, EmptySubscription> {
+        Schema::build(TestQuery, EmptyMutation, EmptySubscription)
+            .extension(QueryTimeout::new(Duration::from_millis(100)))
+            .finish()
+    }
+
+    #[rocket::async_test]
+    async fn cancels_slow_operations() {
+        let response = schema().execute("{ fast slow }").await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(response.data, async_graphql::Value::Null);
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(error["extensions"]["code"], "TIMEOUT");
+        assert_eq!(error["extensions"]["category"], "SERVER");
+    }
+
+    #[rocket::async_test]
+    async fn lets_fast_operations_complete() {
+        let response = schema().execute("{ fast }").await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({ "fast": true })
+        );
+    }
+}
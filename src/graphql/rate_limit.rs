@@ -0,0 +1,191 @@
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextValidation};
+use async_graphql::{Pos, ServerError, ValidationResult};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::config::RateLimitConfig;
+use crate::error::Error;
+use crate::ttl_map::TtlMap;
+
+/// Amount of clients whose bucket is tracked at most, the bucket refilled
+/// the soonest is dropped to make room for another
+const MAX_TRACKED_CLIENTS: usize = 100_000;
+
+/// Identifies who is charged for a request
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum RateLimitKey {
+    /// Id of the authenticated user
+    User(Uuid),
+    Ip(IpAddr),
+    Anonymous,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Keeps a token bucket per client. Buckets start full and are refilled at a
+/// constant rate up to their capacity.
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_sec: f64,
+    /// Buckets are dropped once full again, a full bucket is equivalent to a
+    /// missing one
+    buckets: Mutex<TtlMap<RateLimitKey, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            capacity: f64::from(config.capacity),
+            refill_per_sec: f64::from(config.refill_per_sec),
+            buckets: Mutex::new(TtlMap::new(MAX_TRACKED_CLIENTS)),
+        }
+    }
+
+    /// Takes `cost` tokens from the client's bucket. If not enough tokens
+    /// are left, returns how long to wait until the request can be retried.
+    pub fn acquire(&self, key: &RateLimitKey, cost: usize) -> Result<(), Duration> {
+        self.acquire_at(key, cost, Instant::now())
+    }
+
+    fn acquire_at(&self, key: &RateLimitKey, cost: usize, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let mut bucket = buckets.remove(key, now).unwrap_or(Bucket {
+            tokens: self.capacity,
+            refilled_at: now,
+        });
+        let cost = cost as f64;
+
+        bucket.tokens = self.refilled(&bucket, now);
+        bucket.refilled_at = now;
+
+        let acquired = if bucket.tokens < cost {
+            let missing = cost - bucket.tokens;
+
+            Err(Duration::from_secs_f64(missing / self.refill_per_sec))
+        } else {
+            bucket.tokens -= cost;
+
+            Ok(())
+        };
+        let full_at =
+            now + Duration::from_secs_f64((self.capacity - bucket.tokens) / self.refill_per_sec);
+
+        buckets.insert(key.clone(), bucket, full_at, now);
+
+        acquired
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+
+        (bucket.tokens + elapsed.as_secs_f64() * self.refill_per_sec).min(self.capacity)
+    }
+}
+
+/// Charges every request its query complexity against the caller's bucket,
+/// requests are rejected with `RATE_LIMITED` once the bucket runs out.
+pub struct RateLimit {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimit {
+    pub fn new(limiter: RateLimiter) -> Self {
+        Self {
+            limiter: Arc::new(limiter),
+        }
+    }
+}
+
+impl ExtensionFactory for RateLimit {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(RateLimitExtension {
+            limiter: Arc::clone(&self.limiter),
+        })
+    }
+}
+
+struct RateLimitExtension {
+    limiter: Arc<RateLimiter>,
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for RateLimitExtension {
+    async fn validation(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        next: NextValidation<'_>,
+    ) -> Result<ValidationResult, Vec<ServerError>> {
+        let result = next.run(ctx).await?;
+        let key = ctx
+            .data_opt::<RateLimitKey>()
+            .unwrap_or(&RateLimitKey::Anonymous);
+
+        if let Err(retry_after) = self.limiter.acquire(key, result.complexity.max(1)) {
+            tracing::warn!(?key, ?retry_after, "rate limit exceeded");
+            let error = async_graphql::Error::from(Error::rate_limited(retry_after));
+
+            return Err(vec![error.into_server_error(Pos::default())]);
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use uuid::Uuid;
+
+    use crate::config::RateLimitConfig;
+
+    use super::{RateLimitKey, RateLimiter};
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig {
+            capacity: 10,
+            refill_per_sec: 2,
+        })
+    }
+
+    #[test]
+    fn rejects_requests_exceeding_the_budget() {
+        let limiter = limiter();
+        let key = RateLimitKey::User(Uuid::nil());
+        let now = Instant::now();
+
+        assert!(limiter.acquire_at(&key, 6, now).is_ok());
+        assert_eq!(
+            limiter.acquire_at(&key, 6, now),
+            Err(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn refills_buckets_over_time() {
+        let limiter = limiter();
+        let key = RateLimitKey::User(Uuid::nil());
+        let now = Instant::now();
+
+        assert!(limiter.acquire_at(&key, 10, now).is_ok());
+        assert!(limiter.acquire_at(&key, 1, now).is_err());
+        assert!(limiter
+            .acquire_at(&key, 4, now + Duration::from_secs(2))
+            .is_ok());
+    }
+
+    #[test]
+    fn keeps_a_bucket_per_client() {
+        let limiter = limiter();
+        let now = Instant::now();
+        let user = RateLimitKey::User(Uuid::nil());
+        let ip = RateLimitKey::Ip("127.0.0.1".parse().unwrap());
+
+        assert!(limiter.acquire_at(&user, 10, now).is_ok());
+        assert!(limiter.acquire_at(&ip, 10, now).is_ok());
+    }
+}
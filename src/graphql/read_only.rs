@@ -0,0 +1,153 @@
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextParseQuery};
+use async_graphql::parser::types::{ExecutableDocument, OperationType, Selection};
+use async_graphql::{Context, Object, Pos, ServerResult, SimpleObject, Variables};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::Error;
+use crate::graphql::guards::RoleGuard;
+use crate::modules::user::Role;
+
+/// Mutation toggling read-only mode, always allowed so the mode can be
+/// disabled again
+const READ_ONLY_SET_FIELD: &str = "readOnlySet";
+
+/// Whether mutations are rejected, e.g. while migrations run. Starts from
+/// `GraphQLConfig::read_only` and is toggled by admins at runtime.
+#[derive(Debug, Default)]
+pub struct ReadOnlyMode(AtomicBool);
+
+impl ReadOnlyMode {
+    pub fn new(enabled: bool) -> Self {
+        Self(AtomicBool::new(enabled))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.0.store(enabled, Ordering::Relaxed)
+    }
+}
+
+/// Rejects documents containing mutations with `READ_ONLY` while read-only
+/// mode is enabled, queries and subscriptions proceed normally. Documents
+/// are checked as a whole since the operation to run isn't known when
+/// they're parsed.
+pub struct ReadOnly {
+    mode: Arc<ReadOnlyMode>,
+}
+
+impl ReadOnly {
+    pub fn new(mode: Arc<ReadOnlyMode>) -> Self {
+        Self { mode }
+    }
+}
+
+impl ExtensionFactory for ReadOnly {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(ReadOnlyExtension {
+            mode: Arc::clone(&self.mode),
+        })
+    }
+}
+
+struct ReadOnlyExtension {
+    mode: Arc<ReadOnlyMode>,
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for ReadOnlyExtension {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+
+        if self.mode.is_enabled() && has_restricted_mutation(&document) {
+            let error = async_graphql::Error::from(Error::read_only());
+
+            return Err(error.into_server_error(Pos::default()));
+        }
+
+        Ok(document)
+    }
+}
+
+/// Checks whether the document has a mutation selecting anything other than
+/// `READ_ONLY_SET_FIELD`
+fn has_restricted_mutation(document: &ExecutableDocument) -> bool {
+    document
+        .operations
+        .iter()
+        .filter(|(_, operation)| operation.node.ty == OperationType::Mutation)
+        .any(|(_, operation)| {
+            operation
+                .node
+                .selection_set
+                .node
+                .items
+                .iter()
+                .any(|selection| match &selection.node {
+                    Selection::Field(field) => field.node.name.node != READ_ONLY_SET_FIELD,
+                    _ => true,
+                })
+        })
+}
+
+#[derive(SimpleObject)]
+pub struct ReadOnlySet {
+    enabled: bool,
+}
+
+#[derive(Default)]
+pub struct ReadOnlyMutation;
+
+#[Object]
+impl ReadOnlyMutation {
+    /// Enables or disables read-only mode, in which every other mutation is
+    /// rejected
+    #[graphql(name = "readOnlySet", guard = "RoleGuard::new(Role::Admin)")]
+    async fn read_only_set(&self, ctx: &Context<'_>, enabled: bool) -> ReadOnlySet {
+        ctx.data_unchecked::<Arc<ReadOnlyMode>>().set(enabled);
+        tracing::warn!(enabled, "read-only mode toggled");
+
+        ReadOnlySet { enabled }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::parser::parse_query;
+
+    use super::has_restricted_mutation;
+
+    #[test]
+    fn only_mutations_are_restricted() {
+        for (query, restricted) in [
+            ("{ me { me { id } } }", false),
+            ("subscription { sessionEvents { kind } }", false),
+            (
+                "mutation { readOnlySet(enabled: false) { enabled } }",
+                false,
+            ),
+            ("mutation { tokenRevoke { success } }", true),
+            (
+                "mutation { readOnlySet(enabled: false) { enabled } tokenRevoke { success } }",
+                true,
+            ),
+            (
+                "query A { me { me { id } } } mutation B { tokenRevoke { success } }",
+                true,
+            ),
+        ] {
+            let document = parse_query(query).unwrap();
+
+            assert_eq!(has_restricted_mutation(&document), restricted, "{query}");
+        }
+    }
+}
@@ -1,102 +1,531 @@
-use async_graphql::connection::{self, Connection, CursorType, Edge, EmptyFields};
-use async_graphql::SimpleObject;
+use async_graphql::connection::{Connection, CursorType, Edge, EmptyFields};
+use async_graphql::futures_util::future::BoxFuture;
+use async_graphql::{Object, ID};
 use base64::{decode_config, encode_config, DecodeError, URL_SAFE_NO_PAD};
-use std::convert::Infallible;
+use chrono::{DateTime, Utc};
+use once_cell::sync::OnceCell;
+use sqlx::postgres::{PgArguments, PgRow};
+use sqlx::query::QueryAs;
+use sqlx::{Executor, FromRow, Postgres};
 use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use uuid::Uuid;
 
-use crate::error::{Error, Result};
+use crate::config::{PageSizeConfig, PageSizePolicy};
+use crate::error::{Error, ErrorCode, Result};
+
+/// Default and maximum page sizes enforced on every connection, set once on
+/// startup from `GraphQLConfig::page_size`
+static PAGE_SIZE: OnceCell<PageSizeConfig> = OnceCell::new();
+
+/// Sets the default and maximum page sizes enforced on every connection. Has
+/// no effect after the first call.
+pub fn limit_page_size(config: PageSizeConfig) {
+    let _ = PAGE_SIZE.set(config);
+}
 
 #[derive(Debug)]
 pub enum Base64CursorError {
     /// Invalid cursor. This can happen if the base64 string is valid, but its
-    /// contents don't conform to the `name:index` pattern.
+    /// contents don't conform to the `Keyset:order:sort_value:id` pattern.
     Invalid,
     /// Decoding error. If this happens, the string isn't valid base64.
     DecodeError(DecodeError),
+    /// The cursor was issued for a result set sorted in another order, e.g.
+    /// when `orderBy` changes between pages.
+    OrderMismatch,
 }
 
 impl Display for Base64CursorError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Invalid cursor")
+        match self {
+            Self::Invalid => write!(f, "Invalid cursor, it doesn't point at a node"),
+            Self::DecodeError(err) => write!(f, "Invalid cursor, not a valid base64 string: {err}"),
+            Self::OrderMismatch => write!(
+                f,
+                "Invalid cursor, it was issued for another order. Restart pagination when changing the order"
+            ),
+        }
     }
 }
 
-/// Base64 cursor implementation
-pub struct Base64Cursor {
-    name: &'static str,
-    index: usize,
+impl Base64CursorError {
+    /// Reports the error on `field`, the argument the cursor was provided as
+    fn into_error(self, field: &str) -> Error {
+        Error::new(field, &self.to_string(), ErrorCode::Base64CursorError)
+    }
+}
+
+/// Value a keyset paginated result set is sorted by
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum SortValue {
+    Text(String),
+    Timestamp(DateTime<Utc>),
+}
+
+impl SortValue {
+    /// Representation of the value bound to SQL queries, which cast it back
+    /// to the sorted column type
+    pub fn to_sql(&self) -> String {
+        match self {
+            SortValue::Text(value) => value.clone(),
+            SortValue::Timestamp(value) => value.to_rfc3339(),
+        }
+    }
+}
+
+/// Column a keyset paginated result set is sorted by
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct KeysetColumn {
+    pub name: &'static str,
+    /// SQL type cursor values, bound as text, are cast to
+    pub sql_type: &'static str,
+}
+
+/// Sorting applied to a keyset paginated result set, ties are always broken
+/// by id in the same direction
+pub trait KeysetOrder: Copy {
+    /// Name of the order, cursors issued for an order are rejected by others
+    fn name(&self) -> &'static str;
+
+    fn descending(&self) -> bool;
+
+    /// Column sorted by in this order
+    fn column(&self) -> KeysetColumn;
+}
+
+/// Cursor pointing at a node by the value its result set is sorted by and
+/// its id, which breaks ties between nodes sharing the same sort value.
+/// Unlike an offset, it remains valid when rows are inserted or removed
+/// before it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeysetCursor {
+    pub order: String,
+    pub sort_value: SortValue,
+    pub id: Uuid,
 }
 
-impl Base64Cursor {
-    /// Creates a new instance of the `Base64` cursor
-    fn new(index: usize) -> Self {
+impl KeysetCursor {
+    pub fn new(order: &str, sort_value: SortValue, id: Uuid) -> Self {
         Self {
-            name: "Cursor",
-            index,
+            order: order.to_string(),
+            sort_value,
+            id,
         }
     }
 
     /// Returns the `Cursor` encoded as Base64 string
     fn encode(&self) -> String {
-        encode_config(format!("{}:{}", self.name, self.index), URL_SAFE_NO_PAD)
+        let sort_value = match &self.sort_value {
+            SortValue::Text(value) => format!("Text:{value}"),
+            SortValue::Timestamp(value) => format!("Timestamp:{}", value.to_rfc3339()),
+        };
+
+        encode_config(
+            format!("Keyset:{}:{}:{}", self.order, sort_value, self.id),
+            URL_SAFE_NO_PAD,
+        )
     }
 
     /// Decode the Base64 string representation of the `Cursor`
     fn decode(base64_str: &str) -> std::result::Result<Self, Base64CursorError> {
         let bytes =
             decode_config(base64_str, URL_SAFE_NO_PAD).map_err(Base64CursorError::DecodeError)?;
-
         let cursor = String::from_utf8(bytes).map_err(|_| Base64CursorError::Invalid)?;
-        let index = cursor
-            .split(':')
-            .last()
-            .map(|s| s.parse::<usize>())
-            .ok_or(Base64CursorError::Invalid)?
-            .map_err(|_| Base64CursorError::Invalid)?;
+        let (order, key) = cursor
+            .strip_prefix("Keyset:")
+            .and_then(|key| key.split_once(':'))
+            .ok_or(Base64CursorError::Invalid)?;
+        let (sort_value, id) = key.rsplit_once(':').ok_or(Base64CursorError::Invalid)?;
+        let sort_value = match sort_value.split_once(':') {
+            Some(("Text", value)) => SortValue::Text(value.to_string()),
+            Some(("Timestamp", value)) => SortValue::Timestamp(
+                DateTime::parse_from_rfc3339(value)
+                    .map_err(|_| Base64CursorError::Invalid)?
+                    .with_timezone(&Utc),
+            ),
+            _ => return Err(Base64CursorError::Invalid),
+        };
+        let id = Uuid::parse_str(id).map_err(|_| Base64CursorError::Invalid)?;
 
-        Ok(Self::new(index))
+        Ok(Self::new(order, sort_value, id))
+    }
+}
+
+impl CursorType for KeysetCursor {
+    type Error = Base64CursorError;
+
+    fn decode_cursor(s: &str) -> async_graphql::Result<Self, Self::Error> {
+        KeysetCursor::decode(s)
+    }
+
+    fn encode_cursor(&self) -> String {
+        self.encode()
     }
+}
+
+/// Nodes paginated through a `KeysetCursor`
+pub trait Keyset {
+    type Order: KeysetOrder;
+
+    /// Value this node is sorted by in the provided `order`
+    fn sort_value(&self, order: Self::Order) -> SortValue;
 
-    /// Increments and return the index
-    ///
-    /// ## Overflow
-    ///
-    /// This function uses `saturating_add` to avoid overflow issues
-    fn increment(&self) -> usize {
-        self.index.saturating_add(1)
+    fn keyset_id(&self) -> Uuid;
+
+    fn keyset_cursor(&self, order: Self::Order) -> KeysetCursor {
+        KeysetCursor::new(order.name(), self.sort_value(order), self.keyset_id())
     }
 }
 
-impl From<Base64Cursor> for usize {
-    fn from(cursor: Base64Cursor) -> Self {
-        cursor.index
+/// Page requested from a keyset paginated result set. Repositories fetch up
+/// to `limit` rows past `after` and before `before`, walking the result set
+/// from its end when `backward` is set.
+#[derive(Clone, Debug)]
+pub struct KeysetPage {
+    pub after: Option<KeysetCursor>,
+    pub before: Option<KeysetCursor>,
+    pub limit: usize,
+    pub backward: bool,
+}
+
+impl KeysetPage {
+    /// Applies the page over `items` already sorted by `order`, the
+    /// in-memory counterpart of the `(sort_value, id) > (?, ?)` predicate
+    /// standing in for the database in tests.
+    #[cfg(test)]
+    pub fn apply<T: Keyset>(&self, items: Vec<T>, order: T::Order) -> Vec<T> {
+        let follows = |item: &T, cursor: &KeysetCursor| {
+            let item = (item.sort_value(order), item.keyset_id());
+            let cursor = (cursor.sort_value.clone(), cursor.id);
+
+            if order.descending() {
+                item < cursor
+            } else {
+                item > cursor
+            }
+        };
+        let precedes = |item: &T, cursor: &KeysetCursor| {
+            item.keyset_cursor(order) != *cursor && !follows(item, cursor)
+        };
+        let mut items = items
+            .into_iter()
+            .filter(|item| self.after.as_ref().is_none_or(|after| follows(item, after)))
+            .filter(|item| {
+                self.before
+                    .as_ref()
+                    .is_none_or(|before| precedes(item, before))
+            })
+            .collect::<Vec<T>>();
+
+        if self.backward {
+            items.reverse();
+        }
+
+        items.truncate(self.limit);
+        items
     }
 }
 
-impl CursorType for Base64Cursor {
-    type Error = Base64CursorError;
+/// `SELECT` of the rows of a `KeysetPage`. Completes the filtered query of a
+/// repository with the cursor predicates, ordering and limit of the page, so
+/// listing a new entity only takes writing its filter.
+///
+/// The filter binds `$1` to `$n`, the page binds the parameters after them
+/// once the filter ones are bound.
+///
+/// ```ignore
+/// let query = KeysetQuery::new("SELECT * FROM posts WHERE author_id = $1", 1, order, page);
+/// let rows: Vec<PostsTableRow> = query
+///     .fetch_all(&self.database.conn_pool, |query| query.bind(author_id))
+///     .await?;
+/// ```
+pub struct KeysetQuery {
+    sql: String,
+    page: KeysetPage,
+}
 
-    fn decode_cursor(s: &str) -> async_graphql::Result<Self, Self::Error> {
-        Base64Cursor::decode(s)
+impl KeysetQuery {
+    /// Creates the query fetching `page` out of `select`, a query ending with
+    /// a `WHERE` clause which binds `filter_params` parameters
+    pub fn new(
+        select: &str,
+        filter_params: usize,
+        order: impl KeysetOrder,
+        page: KeysetPage,
+    ) -> Self {
+        let KeysetColumn { name, sql_type } = order.column();
+        let (after_op, before_op) = if order.descending() {
+            ("<", ">")
+        } else {
+            (">", "<")
+        };
+        let direction = if order.descending() != page.backward {
+            "DESC"
+        } else {
+            "ASC"
+        };
+        let param = |offset: usize| format!("${}", filter_params + offset);
+        let sql = format!(
+            "{select} \
+            AND ({after}::text IS NULL OR ({name}, id) {after_op} ({after}::text::{sql_type}, {after_id})) \
+            AND ({before}::text IS NULL OR ({name}, id) {before_op} ({before}::text::{sql_type}, {before_id})) \
+            ORDER BY {name} {direction}, id {direction} LIMIT {limit}",
+            after = param(1),
+            after_id = param(2),
+            before = param(3),
+            before_id = param(4),
+            limit = param(5),
+        );
+
+        Self { sql, page }
     }
 
-    fn encode_cursor(&self) -> String {
-        self.encode()
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Fetches the rows of the page, `bind_filter` binds the parameters of
+    /// the filter
+    pub async fn fetch_all<'q, 'c, E, R, F>(&'q self, executor: E, bind_filter: F) -> Result<Vec<R>>
+    where
+        E: Executor<'c, Database = Postgres> + 'q,
+        R: for<'r> FromRow<'r, PgRow> + Send + Unpin + 'q,
+        F: FnOnce(QueryAs<'q, Postgres, R, PgArguments>) -> QueryAs<'q, Postgres, R, PgArguments>,
+    {
+        let page = &self.page;
+        let rows = bind_filter(sqlx::query_as(self.sql()))
+            .bind(page.after.as_ref().map(|cursor| cursor.sort_value.to_sql()))
+            .bind(page.after.as_ref().map(|cursor| cursor.id))
+            .bind(
+                page.before
+                    .as_ref()
+                    .map(|cursor| cursor.sort_value.to_sql()),
+            )
+            .bind(page.before.as_ref().map(|cursor| cursor.id))
+            .bind(page.limit as i64)
+            .fetch_all(executor)
+            .await?;
+
+        Ok(rows)
     }
 }
 
+/// Relay Connection paginated through a `KeysetCursor`
+pub type KeysetConnection<T> = Connection<KeysetCursor, T, ConnectionFields, EmptyFields>;
+
+/// Edge of a `KeysetConnection`, shares its type with the connection edges
+pub type KeysetEdge<T> = Edge<KeysetCursor, T, EmptyFields>;
+
+/// Edge pointing at `node` within a `KeysetConnection` sorted by `order`.
+/// Returned by mutations creating nodes, clients insert it into their cached
+/// connections without refetching them.
+pub fn keyset_edge<T: Keyset + Clone>(node: &T, order: T::Order) -> KeysetEdge<T> {
+    Edge::new(node.keyset_cursor(order), node.clone())
+}
+
+/// Paginates a result set sorted by `order` through keyset cursors. `fetch`
+/// retrieves the rows of the requested `KeysetPage`, one more than its
+/// `limit` is requested to know whether more rows are available.
+pub async fn query_keyset<T, F, Fut>(
+    params: Params,
+    order: T::Order,
+    count_fn: CountFn,
+    fetch: F,
+) -> Result<KeysetConnection<T>>
+where
+    T: Keyset,
+    F: FnOnce(KeysetPage) -> Fut,
+    Fut: Future<Output = Result<Vec<T>>>,
+{
+    let after = decode_keyset_cursor("after", params.after.as_deref(), order.name())?;
+    let before = decode_keyset_cursor("before", params.before.as_deref(), order.name())?;
+    let has_after = after.is_some();
+    let has_before = before.is_some();
+    let config = page_size_config();
+    let (first, last) = page_sizes(&params, config)?;
+    let backward = last.is_some();
+    let limit = first.or(last).unwrap_or(config.default);
+    let mut rows = fetch(KeysetPage {
+        after,
+        before,
+        limit: limit.saturating_add(1),
+        backward,
+    })
+    .await?;
+    let has_more = rows.len() > limit;
+
+    rows.truncate(limit);
+
+    if backward {
+        rows.reverse();
+    }
+
+    let (has_previous_page, has_next_page) = if backward {
+        (has_more, has_before)
+    } else {
+        (has_after, has_more)
+    };
+    let mut connection = Connection::with_additional_fields(
+        has_previous_page,
+        has_next_page,
+        ConnectionFields { count_fn },
+    );
+
+    connection.append(
+        rows.into_iter()
+            .map(|node| Edge::new(node.keyset_cursor(order), node)),
+    );
+
+    Ok(connection)
+}
+
+/// Decodes a keyset cursor, rejecting cursors issued for another order as
+/// they don't point into the current result set
+fn decode_keyset_cursor(
+    field: &str,
+    cursor: Option<&str>,
+    order: &str,
+) -> Result<Option<KeysetCursor>> {
+    cursor
+        .map(|cursor| {
+            KeysetCursor::decode(cursor)
+                .and_then(|cursor| {
+                    if cursor.order != order {
+                        return Err(Base64CursorError::OrderMismatch);
+                    }
+
+                    Ok(cursor)
+                })
+                .map_err(|err| err.into_error(field))
+        })
+        .transpose()
+}
+
+fn page_size_config() -> PageSizeConfig {
+    PAGE_SIZE.get().copied().unwrap_or_default()
+}
+
+/// Validates the `first` and `last` connection arguments. Providing both is
+/// rejected, as the Relay specification strongly discourages it and the
+/// resulting page is hard to reason about.
+fn page_sizes(params: &Params, config: PageSizeConfig) -> Result<(Option<usize>, Option<usize>)> {
+    let first = page_size("first", params.first, config)?;
+    let last = page_size("last", params.last, config)?;
+
+    if first.is_some() && last.is_some() {
+        return Err(Error::new(
+            "last",
+            "The \"first\" and \"last\" parameters must not be provided together",
+            ErrorCode::ValidationError,
+        ));
+    }
+
+    Ok((first, last))
+}
+
+/// Validates a `first` or `last` connection argument provided through
+/// `field`, applying the maximum page size.
+fn page_size(field: &str, value: Option<i32>, config: PageSizeConfig) -> Result<Option<usize>> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    let value = usize::try_from(value).map_err(|_| {
+        Error::new(
+            field,
+            &format!("The \"{field}\" parameter must be a non-negative number"),
+            ErrorCode::ValidationError,
+        )
+    })?;
+
+    if value <= config.max {
+        return Ok(Some(value));
+    }
+
+    match config.policy {
+        PageSizePolicy::Clamp => Ok(Some(config.max)),
+        PageSizePolicy::Reject => Err(Error::new(
+            field,
+            &format!(
+                "The \"{field}\" parameter must not be greater than {}",
+                config.max
+            ),
+            ErrorCode::PageSizeExceeded,
+        )),
+    }
+}
+
+/// Relay global object identifier, the base64 encoded `Type:id` pair of an
+/// object. Encoded the same way cursors are.
+#[derive(Debug, PartialEq)]
+pub struct GlobalId {
+    pub type_name: String,
+    pub id: Uuid,
+}
+
+impl GlobalId {
+    pub fn new(type_name: &str, id: Uuid) -> Self {
+        Self {
+            type_name: type_name.to_string(),
+            id,
+        }
+    }
+
+    pub fn encode(&self) -> ID {
+        ID(encode_config(
+            format!("{}:{}", self.type_name, self.id),
+            URL_SAFE_NO_PAD,
+        ))
+    }
+
+    /// Decodes a global id, returns `None` if it's malformed
+    pub fn decode(global_id: &str) -> Option<Self> {
+        let bytes = decode_config(global_id, URL_SAFE_NO_PAD).ok()?;
+        let decoded = String::from_utf8(bytes).ok()?;
+        let (type_name, id) = decoded.split_once(':')?;
+        let id = Uuid::parse_str(id).ok()?;
+
+        Some(Self::new(type_name, id))
+    }
+}
+
+/// Deferred computation of the total amount of items in a result set,
+/// usually a `COUNT(*)` query using the same filters as the page query.
+pub type CountFn = Arc<dyn Fn() -> BoxFuture<'static, Result<usize>> + Send + Sync>;
+
 /// Additional fields for the connection instance
-#[derive(SimpleObject)]
 pub struct ConnectionFields {
+    /// The count is only computed when the client selects the field
+    count_fn: CountFn,
+}
+
+#[Object]
+impl ConnectionFields {
     /// Total result set count
-    total_count: usize,
+    async fn total_count(&self) -> Result<usize> {
+        (self.count_fn)().await
+    }
 }
 
-/// Relay Connection
-pub type RelayConnection<T> = Connection<Base64Cursor, T, ConnectionFields, EmptyFields>;
+/// Payloads of mutations whose input carries Relay's `clientMutationId`,
+/// which they echo unchanged so clients can match responses to requests
+pub trait MutationPayload {
+    fn client_mutation_id(&mut self) -> &mut Option<String>;
+}
 
-/// Relay connection Result
-pub type ConnectionResult<T> = Result<RelayConnection<T>>;
+/// Echoes `client_mutation_id` in the payload returned by a mutation. Adding
+/// `clientMutationId` to a mutation takes an optional `client_mutation_id`
+/// in its input and payload, passing its result through this.
+pub fn echo_client_mutation_id<P: MutationPayload, E>(
+    client_mutation_id: Option<String>,
+    result: std::result::Result<P, E>,
+) -> std::result::Result<P, E> {
+    result.map(|mut payload| {
+        *payload.client_mutation_id() = client_mutation_id;
+        payload
+    })
+}
 
 /// Relay-compliant connection parameters
 pub struct Params {
@@ -122,47 +551,636 @@ impl Params {
     }
 }
 
-pub async fn query<T, I: ExactSizeIterator<Item = T>>(
-    iter: I,
-    params: Params,
-    default_page_size: usize,
-) -> ConnectionResult<T> {
-    connection::query::<Base64Cursor, T, ConnectionFields, _, _, _, Infallible>(
-        params.after,
-        params.before,
-        params.first,
-        params.last,
-        |after, before, first, last| async move {
-            let iter_len = iter.len();
-            let (start, end) = {
-                let after: usize = after.map(|a| a.increment()).unwrap_or(0);
-                let before: usize = before.map(|b| b.into()).unwrap_or(iter_len);
-
-                match (first, last) {
-                    (Some(first), _) => (after, (after.saturating_add(first)).min(before)),
-                    (_, Some(last)) => ((before.saturating_sub(last)).max(after), before),
-                    _ => (after, default_page_size.min(before)),
-                }
-            };
-
-            let mut connection = Connection::with_additional_fields(
-                start > 0,
-                end < iter_len,
-                ConnectionFields {
-                    total_count: iter_len,
+#[cfg(test)]
+mod tests {
+    use async_graphql::connection::CursorType;
+    use async_graphql::{EmptyMutation, EmptySubscription, Enum, Object, Schema, SimpleObject};
+    use base64::{encode_config, URL_SAFE_NO_PAD};
+    use chrono::{DateTime, TimeZone, Utc};
+    use once_cell::sync::Lazy;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use crate::config::{PageSizeConfig, PageSizePolicy};
+    use crate::error::ErrorCode;
+
+    use super::{
+        page_size, page_sizes, query_keyset, GlobalId, Keyset, KeysetColumn, KeysetConnection,
+        KeysetCursor, KeysetOrder, KeysetPage, KeysetQuery, Params, SortValue,
+    };
+
+    static COUNT_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    static PROBED_COUNT_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    /// `limit` of the last page fetched by `probedKeysetItems`
+    static PROBED_LIMIT: AtomicUsize = AtomicUsize::new(0);
+
+    const PAGE_INFO: &str =
+        "edges { cursor } pageInfo { hasPreviousPage hasNextPage startCursor endCursor }";
+
+    /// Nodes sharing the same `created_at` and some sharing the same `name`
+    static KEYSET_ITEMS: Lazy<Vec<KeysetItem>> = Lazy::new(|| {
+        let created_at = Utc.ymd(2022, 7, 1).and_hms(9, 0, 0);
+
+        (0..25)
+            .map(|index| KeysetItem {
+                id: Uuid::new_v4(),
+                name: format!("item-{}", index % 3),
+                created_at,
+            })
+            .collect::<Vec<KeysetItem>>()
+    });
+
+    #[derive(Clone, SimpleObject)]
+    struct KeysetItem {
+        id: Uuid,
+        name: String,
+        created_at: DateTime<Utc>,
+    }
+
+    #[derive(Clone, Copy, Debug, Enum, Eq, PartialEq)]
+    enum KeysetItemOrder {
+        CreatedAtAsc,
+        NameDesc,
+    }
+
+    impl KeysetOrder for KeysetItemOrder {
+        fn name(&self) -> &'static str {
+            match self {
+                KeysetItemOrder::CreatedAtAsc => "CREATED_AT_ASC",
+                KeysetItemOrder::NameDesc => "NAME_DESC",
+            }
+        }
+
+        fn descending(&self) -> bool {
+            *self == KeysetItemOrder::NameDesc
+        }
+
+        fn column(&self) -> KeysetColumn {
+            match self {
+                KeysetItemOrder::CreatedAtAsc => KeysetColumn {
+                    name: "created_at",
+                    sql_type: "timestamptz",
+                },
+                KeysetItemOrder::NameDesc => KeysetColumn {
+                    name: "name",
+                    sql_type: "text",
                 },
+            }
+        }
+    }
+
+    impl Keyset for KeysetItem {
+        type Order = KeysetItemOrder;
+
+        fn sort_value(&self, order: KeysetItemOrder) -> SortValue {
+            match order {
+                KeysetItemOrder::CreatedAtAsc => SortValue::Timestamp(self.created_at),
+                KeysetItemOrder::NameDesc => SortValue::Text(self.name.clone()),
+            }
+        }
+
+        fn keyset_id(&self) -> Uuid {
+            self.id
+        }
+    }
+
+    /// `KEYSET_ITEMS` sorted by `order` as a repository would
+    fn sorted_keyset_items(order: KeysetItemOrder) -> Vec<KeysetItem> {
+        let mut items = KEYSET_ITEMS.clone();
+
+        items.sort_by(|a, b| {
+            let a = (a.sort_value(order), a.id);
+            let b = (b.sort_value(order), b.id);
+
+            a.partial_cmp(&b).unwrap()
+        });
+
+        if order.descending() {
+            items.reverse();
+        }
+
+        items
+    }
+
+    struct TestQuery;
+
+    #[Object]
+    impl TestQuery {
+        async fn counted_items(
+            &self,
+            first: Option<i32>,
+        ) -> crate::error::Result<KeysetConnection<KeysetItem>> {
+            let order = KeysetItemOrder::CreatedAtAsc;
+
+            query_keyset(
+                Params::new(None, None, first, None),
+                order,
+                Arc::new(|| {
+                    Box::pin(async {
+                        COUNT_CALLS.fetch_add(1, Ordering::SeqCst);
+                        Ok(25)
+                    })
+                }),
+                |page| async move { Ok(page.apply(sorted_keyset_items(order), order)) },
+            )
+            .await
+        }
+
+        async fn empty_keyset_items(
+            &self,
+            first: Option<i32>,
+            last: Option<i32>,
+        ) -> crate::error::Result<KeysetConnection<KeysetItem>> {
+            let order = KeysetItemOrder::CreatedAtAsc;
+
+            query_keyset(
+                Params::new(None, None, first, last),
+                order,
+                Arc::new(|| Box::pin(async { Ok(0) })),
+                |page| async move { Ok(page.apply(Vec::new(), order)) },
+            )
+            .await
+        }
+
+        async fn probed_keyset_items(
+            &self,
+            first: Option<i32>,
+        ) -> crate::error::Result<KeysetConnection<KeysetItem>> {
+            let order = KeysetItemOrder::CreatedAtAsc;
+
+            query_keyset(
+                Params::new(None, None, first, None),
+                order,
+                Arc::new(|| {
+                    Box::pin(async {
+                        PROBED_COUNT_CALLS.fetch_add(1, Ordering::SeqCst);
+                        Ok(KEYSET_ITEMS.len())
+                    })
+                }),
+                |page| async move {
+                    PROBED_LIMIT.store(page.limit, Ordering::SeqCst);
+
+                    Ok(page.apply(sorted_keyset_items(order), order))
+                },
+            )
+            .await
+        }
+
+        async fn keyset_items(
+            &self,
+            after: Option<String>,
+            before: Option<String>,
+            first: Option<i32>,
+            last: Option<i32>,
+            order_by: KeysetItemOrder,
+        ) -> crate::error::Result<KeysetConnection<KeysetItem>> {
+            query_keyset(
+                Params::new(after, before, first, last),
+                order_by,
+                Arc::new(|| Box::pin(async { Ok(KEYSET_ITEMS.len()) })),
+                |page| async move { Ok(page.apply(sorted_keyset_items(order_by), order_by)) },
+            )
+            .await
+        }
+    }
+
+    fn schema() -> Schema<TestQuery, EmptyMutation, EmptySubscription> {
+        Schema::new(TestQuery, EmptyMutation, EmptySubscription)
+    }
+
+    /// Page of `KEYSET_ITEMS` sorted by creation date
+    async fn keyset_page(params: Params) -> crate::error::Result<KeysetConnection<KeysetItem>> {
+        let order = KeysetItemOrder::CreatedAtAsc;
+
+        query_keyset(
+            params,
+            order,
+            Arc::new(|| Box::pin(async { Ok(KEYSET_ITEMS.len()) })),
+            |page| async move { Ok(page.apply(sorted_keyset_items(order), order)) },
+        )
+        .await
+    }
+
+    /// Runs `field` selecting its edge cursors and page info, checks the
+    /// start and end cursors point at the first and last edges and returns
+    /// `(hasPreviousPage, hasNextPage, edges)`.
+    async fn page_info(field: &str) -> (bool, bool, usize) {
+        let response = schema()
+            .execute(format!("{{ connection: {field} {{ {PAGE_INFO} }} }}"))
+            .await;
+
+        assert!(response.errors.is_empty(), "{field}: {:?}", response.errors);
+
+        let data = response.data.into_json().unwrap();
+        let connection = &data["connection"];
+        let cursors = connection["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| edge["cursor"].clone())
+            .collect::<Vec<serde_json::Value>>();
+        let page_info = &connection["pageInfo"];
+        let cursor_or_null =
+            |cursor: Option<&serde_json::Value>| cursor.cloned().unwrap_or(serde_json::Value::Null);
+
+        assert_eq!(
+            page_info["startCursor"],
+            cursor_or_null(cursors.first()),
+            "{field}"
+        );
+        assert_eq!(
+            page_info["endCursor"],
+            cursor_or_null(cursors.last()),
+            "{field}"
+        );
+
+        (
+            page_info["hasPreviousPage"].as_bool().unwrap(),
+            page_info["hasNextPage"].as_bool().unwrap(),
+            cursors.len(),
+        )
+    }
+
+    #[rocket::async_test]
+    async fn empty_pages_have_no_cursors() {
+        for field in ["emptyKeysetItems(first: 10)", "emptyKeysetItems(last: 10)"] {
+            assert_eq!(page_info(field).await, (false, false, 0), "{field}");
+        }
+    }
+
+    #[rocket::async_test]
+    async fn single_item_pages_start_and_end_on_it() {
+        for (field, expected) in [
+            (
+                "keysetItems(first: 1, orderBy: CREATED_AT_ASC)",
+                (false, true, 1),
+            ),
+            (
+                "keysetItems(last: 1, orderBy: CREATED_AT_ASC)",
+                (true, false, 1),
+            ),
+        ] {
+            assert_eq!(page_info(field).await, expected, "{field}");
+        }
+    }
+
+    #[rocket::async_test]
+    async fn full_pages_report_whether_items_remain() {
+        for (field, expected) in [
+            (
+                "keysetItems(first: 25, orderBy: CREATED_AT_ASC)",
+                (false, false, 25),
+            ),
+            (
+                "keysetItems(first: 24, orderBy: CREATED_AT_ASC)",
+                (false, true, 24),
+            ),
+            (
+                "keysetItems(last: 25, orderBy: NAME_DESC)",
+                (false, false, 25),
+            ),
+            (
+                "keysetItems(last: 24, orderBy: NAME_DESC)",
+                (true, false, 24),
+            ),
+        ] {
+            assert_eq!(page_info(field).await, expected, "{field}");
+        }
+    }
+
+    #[rocket::async_test]
+    async fn keyset_pages_fetch_one_more_row_instead_of_counting() {
+        assert_eq!(
+            page_info("probedKeysetItems(first: 3)").await,
+            (false, true, 3)
+        );
+        assert_eq!(PROBED_LIMIT.load(Ordering::SeqCst), 4);
+        assert_eq!(PROBED_COUNT_CALLS.load(Ordering::SeqCst), 0);
+    }
+
+    #[rocket::async_test]
+    async fn total_count_matches_across_page_sizes() {
+        let schema = schema();
+
+        for first in [1, 5, 10, 30] {
+            let query = format!(
+                "{{ keysetItems(first: {first}, orderBy: CREATED_AT_ASC) {{ totalCount }} }}"
             );
+            let data = schema.execute(query).await.data.into_json().unwrap();
+
+            assert_eq!(data["keysetItems"]["totalCount"], 25);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn deferred_total_count_is_resolved_only_when_selected() {
+        let schema = schema();
+
+        schema
+            .execute("{ countedItems(first: 2) { edges { node { id } } } }")
+            .await;
+        assert_eq!(COUNT_CALLS.load(Ordering::SeqCst), 0);
+
+        let data = schema
+            .execute("{ countedItems(first: 2) { totalCount } }")
+            .await
+            .data
+            .into_json()
+            .unwrap();
 
-            connection.append(
-                (start..end)
-                    .into_iter()
-                    .zip(iter.skip(start))
-                    .map(|(cursor, node)| Edge::new(Base64Cursor::new(cursor), node)),
+        assert_eq!(data["countedItems"]["totalCount"], 25);
+        assert_eq!(COUNT_CALLS.load(Ordering::SeqCst), 1);
+    }
+
+    #[rocket::async_test]
+    async fn reports_malformed_cursors_on_their_argument() {
+        let error = keyset_page(Params::new(Some(String::from("$$$")), None, None, None))
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.code, ErrorCode::Base64CursorError);
+        assert_eq!(error.field, Some(String::from("after")));
+        assert!(error.message.unwrap().contains("base64"));
+
+        let before = encode_config("Cursor:NaN", URL_SAFE_NO_PAD);
+        let error = keyset_page(Params::new(None, Some(before), None, None))
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.field, Some(String::from("before")));
+        assert_eq!(
+            error.message,
+            Some(String::from("Invalid cursor, it doesn't point at a node"))
+        );
+    }
+
+    #[test]
+    fn page_size_clamps_to_max() {
+        let config = PageSizeConfig {
+            default: 10,
+            max: 100,
+            policy: PageSizePolicy::Clamp,
+        };
+
+        assert_eq!(
+            page_size("first", Some(100_000), config).unwrap(),
+            Some(100)
+        );
+        assert_eq!(page_size("first", Some(20), config).unwrap(), Some(20));
+        assert_eq!(page_size("first", None, config).unwrap(), None);
+    }
+
+    #[test]
+    fn page_size_rejects_sizes_over_max() {
+        let config = PageSizeConfig {
+            default: 10,
+            max: 100,
+            policy: PageSizePolicy::Reject,
+        };
+        let error = page_size("last", Some(101), config).err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::PageSizeExceeded);
+        assert_eq!(error.field.as_deref(), Some("last"));
+        assert_eq!(page_size("last", Some(100), config).unwrap(), Some(100));
+    }
+
+    #[test]
+    fn page_sizes_reject_negative_values() {
+        for (params, field) in [
+            (Params::new(None, None, Some(-1), None), "first"),
+            (Params::new(None, None, None, Some(-5)), "last"),
+        ] {
+            let error = page_sizes(&params, PageSizeConfig::default())
+                .err()
+                .unwrap();
+
+            assert_eq!(error.code, ErrorCode::ValidationError);
+            assert_eq!(error.field.as_deref(), Some(field));
+        }
+    }
+
+    #[test]
+    fn page_sizes_reject_first_and_last_together() {
+        let params = Params::new(None, None, Some(2), Some(3));
+        let error = page_sizes(&params, PageSizeConfig::default())
+            .err()
+            .unwrap();
+
+        assert_eq!(error.code, ErrorCode::ValidationError);
+        assert_eq!(error.field.as_deref(), Some("last"));
+        assert_eq!(
+            error.message.as_deref(),
+            Some("The \"first\" and \"last\" parameters must not be provided together")
+        );
+    }
+
+    #[rocket::async_test]
+    async fn omitted_page_size_uses_the_default() {
+        let data = schema()
+            .execute("{ keysetItems(orderBy: CREATED_AT_ASC) { edges { cursor } } }")
+            .await
+            .data
+            .into_json()
+            .unwrap();
+
+        assert_eq!(
+            data["keysetItems"]["edges"].as_array().unwrap().len(),
+            PageSizeConfig::default().default
+        );
+    }
+
+    #[rocket::async_test]
+    async fn connections_clamp_page_size_by_default() {
+        let data = schema()
+            .execute("{ keysetItems(first: 100000, orderBy: CREATED_AT_ASC) { edges { cursor } } }")
+            .await
+            .data
+            .into_json()
+            .unwrap();
+
+        assert_eq!(data["keysetItems"]["edges"].as_array().unwrap().len(), 25);
+
+        let error = keyset_page(Params::new(None, None, Some(-1), None))
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.code, ErrorCode::ValidationError);
+    }
+
+    #[test]
+    fn keyset_cursor_round_trips() {
+        for sort_value in [
+            SortValue::Timestamp(Utc::now()),
+            SortValue::Text(String::from("with:colons")),
+        ] {
+            let cursor = KeysetCursor::new("CREATED_AT_ASC", sort_value, Uuid::new_v4());
+            let decoded = KeysetCursor::decode_cursor(&cursor.encode_cursor()).unwrap();
+
+            assert_eq!(decoded, cursor);
+        }
+
+        assert!(KeysetCursor::decode_cursor(&encode_config("Cursor:1", URL_SAFE_NO_PAD)).is_err());
+    }
+
+    /// Walks every page of `keysetItems` sorted by `order`, forward when
+    /// `forward` is set and backward otherwise, returning the visited ids
+    async fn walk_keyset_items(order: &str, forward: bool) -> Vec<Uuid> {
+        let schema = schema();
+        let mut cursor: Option<String> = None;
+        let mut ids = Vec::new();
+        let (size_arg, cursor_arg, has_more, next_cursor) = if forward {
+            ("first", "after", "hasNextPage", "endCursor")
+        } else {
+            ("last", "before", "hasPreviousPage", "startCursor")
+        };
+
+        loop {
+            let cursor_arg = cursor
+                .as_deref()
+                .map(|cursor| format!(", {cursor_arg}: \"{cursor}\""))
+                .unwrap_or_default();
+            let query = format!(
+                "{{ keysetItems({size_arg}: 3, orderBy: {order}{cursor_arg}) {{ edges {{ node {{ id }} }} pageInfo {{ {has_more} {next_cursor} }} }} }}"
             );
+            let data = schema.execute(query).await.data.into_json().unwrap();
+            let page = &data["keysetItems"];
+            let page_ids = page["edges"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|edge| Uuid::parse_str(edge["node"]["id"].as_str().unwrap()).unwrap())
+                .collect::<Vec<Uuid>>();
+
+            if forward {
+                ids.extend(page_ids);
+            } else {
+                ids.splice(0..0, page_ids);
+            }
+
+            if !page["pageInfo"][has_more].as_bool().unwrap() {
+                break;
+            }
+
+            cursor = page["pageInfo"][next_cursor].as_str().map(String::from);
+        }
+
+        ids
+    }
+
+    #[rocket::async_test]
+    async fn keyset_pages_visit_rows_sharing_sort_value_once() {
+        for order in [KeysetItemOrder::CreatedAtAsc, KeysetItemOrder::NameDesc] {
+            let expected = sorted_keyset_items(order)
+                .iter()
+                .map(|item| item.id)
+                .collect::<Vec<Uuid>>();
+
+            assert_eq!(walk_keyset_items(order.name(), true).await, expected);
+            assert_eq!(walk_keyset_items(order.name(), false).await, expected);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn keyset_cursor_from_another_order_is_rejected() {
+        let schema = schema();
+        let data = schema
+            .execute(
+                "{ keysetItems(first: 2, orderBy: CREATED_AT_ASC) { pageInfo { endCursor } } }",
+            )
+            .await
+            .data
+            .into_json()
+            .unwrap();
+        let cursor = data["keysetItems"]["pageInfo"]["endCursor"]
+            .as_str()
+            .unwrap();
+        let response = schema
+            .execute(format!(
+                "{{ keysetItems(first: 2, orderBy: NAME_DESC, after: \"{cursor}\") {{ edges {{ cursor }} }} }}"
+            ))
+            .await;
+        let extensions = response.errors[0].extensions.as_ref().unwrap();
 
-            Ok(connection)
-        },
-    )
-    .await
-    .map_err(|_err| Error::server_error())
+        assert_eq!(
+            extensions.get("code"),
+            Some(&async_graphql::Value::from("BASE64_CURSOR_ERROR"))
+        );
+        assert_eq!(
+            extensions.get("field"),
+            Some(&async_graphql::Value::from("after"))
+        );
+    }
+
+    #[test]
+    fn global_ids_round_trip() {
+        for type_name in ["User", "Post"] {
+            let global_id = GlobalId::new(type_name, uuid::Uuid::new_v4());
+            let encoded = global_id.encode();
+
+            assert_eq!(GlobalId::decode(&encoded), Some(global_id));
+        }
+    }
+
+    #[test]
+    fn global_id_is_none_when_malformed() {
+        let without_uuid = encode_config("User:1", URL_SAFE_NO_PAD);
+
+        assert_eq!(GlobalId::decode(&without_uuid), None);
+        assert_eq!(GlobalId::decode("not base64!"), None);
+    }
+
+    #[test]
+    fn keyset_queries_follow_the_filter_parameters() {
+        let cursor =
+            KeysetCursor::new("NAME_DESC", SortValue::Text(String::from("b")), Uuid::nil());
+        let page = KeysetPage {
+            after: Some(cursor),
+            before: None,
+            limit: 11,
+            backward: false,
+        };
+        let query = KeysetQuery::new(
+            "SELECT * FROM items WHERE owner_id = $1",
+            1,
+            KeysetItemOrder::NameDesc,
+            page,
+        );
+
+        assert_eq!(
+            query.sql(),
+            "SELECT * FROM items WHERE owner_id = $1 \
+            AND ($2::text IS NULL OR (name, id) < ($2::text::text, $3)) \
+            AND ($4::text IS NULL OR (name, id) > ($4::text::text, $5)) \
+            ORDER BY name DESC, id DESC LIMIT $6"
+        );
+    }
+
+    #[test]
+    fn backward_keyset_queries_walk_from_the_end() {
+        let page = KeysetPage {
+            after: None,
+            before: None,
+            limit: 11,
+            backward: true,
+        };
+        let query = KeysetQuery::new(
+            "SELECT * FROM items WHERE TRUE",
+            0,
+            KeysetItemOrder::CreatedAtAsc,
+            page,
+        );
+
+        assert!(query
+            .sql()
+            .contains("(created_at, id) > ($1::text::timestamptz, $2)"));
+        assert!(query
+            .sql()
+            .ends_with("ORDER BY created_at DESC, id DESC LIMIT $5"));
+    }
 }
@@ -0,0 +1,111 @@
+use std::path::{Path, PathBuf};
+
+use super::{Mutation, Query, Schema, Subscription};
+
+const USAGE: &str = "Usage: nexus-api schema [--out <file> | --check <file>]";
+
+/// SDL of the whole schema. It doesn't depend on the configuration, limits
+/// and introspection only apply to the execution of operations.
+pub fn sdl() -> String {
+    Schema::build(
+        Query::default(),
+        Mutation::default(),
+        Subscription::default(),
+    )
+    .finish()
+    .sdl()
+}
+
+/// Compares the SDL committed at `path` with the current one
+fn check(path: &Path, sdl: &str) -> Result<(), String> {
+    let committed = std::fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+
+    if committed.trim_end() != sdl.trim_end() {
+        return Err(format!(
+            "The schema differs from {}, run `nexus-api schema --out {}` to update it",
+            path.display(),
+            path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Prints the SDL of the schema, or writes it to `--out`. With `--check`
+/// exits with a non-zero status if the SDL at the provided path is outdated.
+pub fn run(mut args: impl Iterator<Item = String>) {
+    let mut out = None;
+    let mut check_path = None;
+
+    while let Some(arg) = args.next() {
+        let target = match arg.as_str() {
+            "--out" => &mut out,
+            "--check" => &mut check_path,
+            _ => panic!("Unknown argument: {}\n{}", arg, USAGE),
+        };
+        let path = args
+            .next()
+            .unwrap_or_else(|| panic!("{} expects a file\n{}", arg, USAGE));
+
+        *target = Some(PathBuf::from(path));
+    }
+
+    let sdl = sdl();
+
+    match (out, check_path) {
+        (Some(_), Some(_)) => panic!("--out and --check are exclusive\n{}", USAGE),
+        (Some(path), None) => {
+            std::fs::write(&path, sdl)
+                .unwrap_or_else(|err| panic!("Failed to write {}: {}", path.display(), err));
+        }
+        (None, Some(path)) => {
+            if let Err(message) = check(&path, &sdl) {
+                eprintln!("{message}");
+                std::process::exit(1);
+            }
+        }
+        (None, None) => print!("{sdl}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, sdl};
+
+    #[test]
+    fn dumps_the_whole_schema() {
+        let sdl = sdl();
+
+        for definition in [
+            "type Query",
+            "type Mutation",
+            "type Subscription",
+            "type User",
+            "enum Role",
+            "scalar DateTime",
+            "interface Node",
+        ] {
+            assert!(sdl.contains(definition), "missing {definition}");
+        }
+
+        assert!(sdl.contains("tokenCreate("));
+        assert!(sdl.contains("usersByIds("));
+    }
+
+    #[test]
+    fn detects_schema_drift() {
+        let path =
+            std::env::temp_dir().join(format!("nexus-schema-{}.graphql", std::process::id()));
+        let sdl = sdl();
+
+        std::fs::write(&path, &sdl).unwrap();
+        assert!(check(&path, &sdl).is_ok());
+
+        std::fs::write(&path, sdl.replace("type User", "type Member")).unwrap();
+        assert!(check(&path, &sdl).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(check(&path, &sdl).is_err());
+    }
+}
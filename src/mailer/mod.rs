@@ -0,0 +1,83 @@
+mod smtp;
+
+pub use smtp::SmtpMailer;
+
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::error::Result;
+
+/// Delivers the emails sent by mutations, which retrieve it from the schema
+/// data as an `Arc<dyn Mailer>` so the transport can be replaced
+#[rocket::async_trait]
+pub trait Mailer: Send + Sync {
+    /// Sends a plain text email to the `to` address
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<()>;
+}
+
+/// Logs emails instead of delivering them, for local development and tests
+pub struct LogMailer;
+
+#[rocket::async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<()> {
+        tracing::debug!(%to, %subject, %body, "email not delivered, no SMTP server is configured");
+
+        Ok(())
+    }
+}
+
+/// Delivers through the configured SMTP server, falling back to logging
+/// emails when none is
+pub fn from_config(config: &Config) -> Arc<dyn Mailer> {
+    match &config.smtp {
+        Some(smtp) => Arc::new(SmtpMailer::new(smtp.clone())),
+        None => {
+            tracing::warn!("SMTP_HOST is not set, emails will only be logged");
+
+            Arc::new(LogMailer)
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod testing {
+    use std::sync::Mutex;
+
+    use crate::error::Result;
+
+    use super::Mailer;
+
+    /// Email recorded by `RecordingMailer`
+    #[derive(Clone, Debug)]
+    pub struct SentEmail {
+        pub to: String,
+        pub subject: String,
+        pub body: String,
+    }
+
+    /// Records sent emails so tests can assert on them
+    #[derive(Default)]
+    pub struct RecordingMailer {
+        sent: Mutex<Vec<SentEmail>>,
+    }
+
+    impl RecordingMailer {
+        pub fn sent(&self) -> Vec<SentEmail> {
+            self.sent.lock().unwrap().clone()
+        }
+    }
+
+    #[rocket::async_trait]
+    impl Mailer for RecordingMailer {
+        async fn send(&self, to: &str, subject: &str, body: &str) -> Result<()> {
+            self.sent.lock().unwrap().push(SentEmail {
+                to: String::from(to),
+                subject: String::from(subject),
+                body: String::from(body),
+            });
+
+            Ok(())
+        }
+    }
+}
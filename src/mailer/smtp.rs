@@ -0,0 +1,391 @@
+//! SMTP client submitting the emails of `SmtpMailer`. `lettre` isn't
+//! available to the offline builds of this project, so the few commands
+//! submitting a plain text email are spoken here: `EHLO`, `STARTTLS`,
+//! `AUTH PLAIN`, `MAIL FROM`, `RCPT TO` and `DATA`.
+
+use chrono::Utc;
+use rocket::tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use rocket::tokio::net::TcpStream;
+use rocket::tokio::time::timeout;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::webpki::DNSNameRef;
+use tokio_rustls::TlsConnector;
+
+use crate::config::SmtpConfig;
+use crate::error::{Error, Result};
+
+use super::Mailer;
+
+/// Time a whole SMTP transaction is given before it's abandoned
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Name the server is greeted with
+const EHLO_DOMAIN: &str = "nexus";
+
+#[derive(Debug, thiserror::Error)]
+enum SmtpError {
+    #[error("SMTP connection failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("SMTP server replied {code}: {message}")]
+    Rejected { code: u16, message: String },
+    #[error("SMTP server sent a malformed reply: {0}")]
+    MalformedReply(String),
+    #[error("SMTP host is not a valid DNS name: {0}")]
+    InvalidHost(String),
+    #[error("email headers mustn't contain line breaks")]
+    InvalidHeader,
+    #[error("SMTP transaction timed out")]
+    Timeout,
+}
+
+/// Submits emails to an SMTP server, one connection per email
+pub struct SmtpMailer {
+    config: SmtpConfig,
+    tls: TlsConnector,
+}
+
+impl SmtpMailer {
+    pub fn new(config: SmtpConfig) -> Self {
+        let mut tls = ClientConfig::new();
+
+        tls.root_store
+            .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+
+        Self {
+            config,
+            tls: TlsConnector::from(Arc::new(tls)),
+        }
+    }
+
+    async fn deliver(&self, message: &Message<'_>) -> std::result::Result<(), SmtpError> {
+        let stream = TcpStream::connect((self.config.host.as_str(), self.config.port)).await?;
+        let mut session = Session::new(stream);
+
+        session.expect(220).await?;
+        session.command(&format!("EHLO {EHLO_DOMAIN}"), 250).await?;
+
+        if !self.config.starttls {
+            return session.transact(&self.config, message).await;
+        }
+
+        session.command("STARTTLS", 220).await?;
+
+        let domain = DNSNameRef::try_from_ascii_str(&self.config.host)
+            .map_err(|_| SmtpError::InvalidHost(self.config.host.clone()))?;
+        let stream = self.tls.connect(domain, session.into_inner()).await?;
+        let mut session = Session::new(stream);
+
+        session.command(&format!("EHLO {EHLO_DOMAIN}"), 250).await?;
+        session.transact(&self.config, message).await
+    }
+}
+
+#[rocket::async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<()> {
+        let message = Message {
+            from: &self.config.from,
+            to,
+            subject,
+            body,
+        };
+
+        let delivered = match timeout(SMTP_TIMEOUT, self.deliver(&message)).await {
+            Ok(delivered) => delivered,
+            Err(_) => Err(SmtpError::Timeout),
+        };
+
+        delivered.map_err(|err| Error::unhandled(Box::new(err)))
+    }
+}
+
+struct Message<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    body: &'a str,
+}
+
+impl Message<'_> {
+    /// Formats the message as the content of a `DATA` command, terminator
+    /// included. Line breaks are normalized to CRLF and lines starting with a
+    /// dot are escaped so they can't end the data early.
+    fn data(&self) -> std::result::Result<String, SmtpError> {
+        let headers = [self.from, self.to, self.subject];
+
+        if headers.iter().any(|header| header.contains(['\r', '\n'])) {
+            return Err(SmtpError::InvalidHeader);
+        }
+
+        let mut data = format!(
+            "From: <{}>\r\nTo: <{}>\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+            self.from,
+            self.to,
+            self.subject,
+            Utc::now().to_rfc2822(),
+        );
+
+        for line in self.body.lines() {
+            if line.starts_with('.') {
+                data.push('.');
+            }
+
+            data.push_str(line);
+            data.push_str("\r\n");
+        }
+
+        data.push_str(".\r\n");
+
+        Ok(data)
+    }
+}
+
+struct Session<S> {
+    stream: BufReader<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Session<S> {
+    fn new(stream: S) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    /// Reads a possibly multiline reply, failing unless its code is of the
+    /// same class as `expected`
+    async fn expect(&mut self, expected: u16) -> std::result::Result<(), SmtpError> {
+        let mut message = String::new();
+
+        loop {
+            let mut line = String::new();
+
+            if self.stream.read_line(&mut line).await? == 0 {
+                return Err(SmtpError::MalformedReply(message));
+            }
+
+            let line = line.trim_end();
+            let code = line
+                .get(..3)
+                .and_then(|code| code.parse::<u16>().ok())
+                .ok_or_else(|| SmtpError::MalformedReply(String::from(line)))?;
+
+            message.push_str(line.get(4..).unwrap_or_default());
+
+            // Every line but the last one has a `-` after the code
+            if line.as_bytes().get(3) == Some(&b'-') {
+                message.push(' ');
+                continue;
+            }
+
+            if code / 100 != expected / 100 {
+                return Err(SmtpError::Rejected { code, message });
+            }
+
+            return Ok(());
+        }
+    }
+
+    async fn command(
+        &mut self,
+        command: &str,
+        expected: u16,
+    ) -> std::result::Result<(), SmtpError> {
+        self.write(&format!("{command}\r\n")).await?;
+        self.expect(expected).await
+    }
+
+    async fn write(&mut self, data: &str) -> std::result::Result<(), SmtpError> {
+        let stream = self.stream.get_mut();
+
+        stream.write_all(data.as_bytes()).await?;
+        stream.flush().await?;
+
+        Ok(())
+    }
+
+    /// Authenticates if credentials are configured and sends the message
+    async fn transact(
+        mut self,
+        config: &SmtpConfig,
+        message: &Message<'_>,
+    ) -> std::result::Result<(), SmtpError> {
+        let data = message.data()?;
+
+        if let Some((username, password)) = &config.credentials {
+            let credentials = base64::encode(format!("\0{username}\0{password}"));
+
+            self.command(&format!("AUTH PLAIN {credentials}"), 235)
+                .await?;
+        }
+
+        self.command(&format!("MAIL FROM:<{}>", message.from), 250)
+            .await?;
+        self.command(&format!("RCPT TO:<{}>", message.to), 250)
+            .await?;
+        self.command("DATA", 354).await?;
+        self.write(&data).await?;
+        self.expect(250).await?;
+
+        // The message is accepted already, a failing `QUIT` doesn't matter
+        let _ = self.command("QUIT", 221).await;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::tokio::io::{
+        duplex, AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, ReadHalf, WriteHalf,
+    };
+
+    use crate::config::SmtpConfig;
+
+    use super::{Message, Session, SmtpError};
+
+    fn message(body: &str) -> Message<'_> {
+        Message {
+            from: "nexus@example.com",
+            to: "esteban@example.com",
+            subject: "Reset your password",
+            body,
+        }
+    }
+
+    #[test]
+    fn escapes_lines_starting_with_a_dot() {
+        let data = message("Hello\n.\n..leading dots\r\nBye").data().unwrap();
+        let (headers, body) = data.split_once("\r\n\r\n").unwrap();
+
+        assert!(headers.contains("To: <esteban@example.com>\r\n"));
+        assert_eq!(body, "Hello\r\n..\r\n...leading dots\r\nBye\r\n.\r\n");
+    }
+
+    #[test]
+    fn rejects_line_breaks_in_headers() {
+        let message = Message {
+            to: "esteban@example.com\r\nBcc: everyone@example.com",
+            ..message("Hello")
+        };
+
+        assert!(matches!(message.data(), Err(SmtpError::InvalidHeader)));
+    }
+
+    /// Replies to each line the client sends with the next scripted reply
+    async fn serve(
+        mut reader: BufReader<ReadHalf<DuplexStream>>,
+        mut writer: WriteHalf<DuplexStream>,
+        replies: &[&str],
+    ) -> Vec<String> {
+        let mut received = Vec::new();
+        let mut in_data = false;
+
+        writer
+            .write_all(b"220 smtp.example.com ready\r\n")
+            .await
+            .unwrap();
+
+        for reply in replies {
+            loop {
+                let mut line = String::new();
+
+                if reader.read_line(&mut line).await.unwrap() == 0 {
+                    return received;
+                }
+
+                let line = String::from(line.trim_end());
+                let done = !in_data || line == ".";
+
+                in_data = line == "DATA" || (in_data && line != ".");
+                received.push(line);
+
+                if done {
+                    break;
+                }
+            }
+
+            writer.write_all(reply.as_bytes()).await.unwrap();
+        }
+
+        received
+    }
+
+    #[rocket::async_test]
+    async fn submits_the_message() {
+        let (client, server) = duplex(4096);
+        let (reader, writer) = rocket::tokio::io::split(server);
+        let server = rocket::tokio::spawn(async move {
+            serve(
+                BufReader::new(reader),
+                writer,
+                &[
+                    "250-smtp.example.com\r\n250 AUTH PLAIN\r\n",
+                    "235 Authenticated\r\n",
+                    "250 OK\r\n",
+                    "250 OK\r\n",
+                    "354 Go ahead\r\n",
+                    "250 Queued\r\n",
+                    "221 Bye\r\n",
+                ],
+            )
+            .await
+        });
+        let config = SmtpConfig {
+            host: String::from("smtp.example.com"),
+            port: 25,
+            credentials: Some((String::from("nexus"), String::from("secret"))),
+            from: String::from("nexus@example.com"),
+            starttls: false,
+        };
+        let mut session = Session::new(client);
+
+        session.expect(220).await.unwrap();
+        session.command("EHLO nexus", 250).await.unwrap();
+        session.transact(&config, &message("Hello")).await.unwrap();
+
+        let received = server.await.unwrap();
+
+        assert_eq!(received[1], "AUTH PLAIN AG5leHVzAHNlY3JldA==");
+        assert_eq!(received[2], "MAIL FROM:<nexus@example.com>");
+        assert_eq!(received[3], "RCPT TO:<esteban@example.com>");
+        assert_eq!(received[4], "DATA");
+        assert!(received.contains(&String::from("Hello")));
+        assert_eq!(received.last().unwrap(), "QUIT");
+    }
+
+    #[rocket::async_test]
+    async fn fails_on_rejected_commands() {
+        let (client, server) = duplex(4096);
+        let (reader, writer) = rocket::tokio::io::split(server);
+        let server = rocket::tokio::spawn(async move {
+            serve(
+                BufReader::new(reader),
+                writer,
+                &["250 smtp.example.com\r\n", "550 No such user\r\n"],
+            )
+            .await
+        });
+        let config = SmtpConfig {
+            host: String::from("smtp.example.com"),
+            port: 25,
+            credentials: None,
+            from: String::from("nexus@example.com"),
+            starttls: false,
+        };
+        let mut session = Session::new(client);
+
+        session.expect(220).await.unwrap();
+        session.command("EHLO nexus", 250).await.unwrap();
+
+        let err = session.transact(&config, &message("Hello")).await;
+
+        assert!(matches!(err, Err(SmtpError::Rejected { code: 550, .. })));
+        drop(server);
+    }
+}
@@ -4,29 +4,50 @@ mod database;
 mod error;
 mod fairings;
 mod graphql;
+mod mailer;
+mod metrics;
 mod modules;
 mod responders;
+// Rocket's route codegen re-exports a URI macro per handler, which newer
+// compilers report as an unused import.
+#[allow(unused_imports)]
 mod routes;
+mod seed;
 mod services;
+#[cfg(feature = "otel")]
+mod telemetry;
+mod ttl_map;
 
 use async_graphql::dataloader::DataLoader;
-use async_graphql::EmptySubscription;
 use dotenv::dotenv;
-use rocket::routes;
+use rocket::{routes, Build, Rocket};
 use std::env;
 use std::sync::Arc;
+use tracing_subscriber::EnvFilter;
 
 use self::config::Config;
 use self::database::Database;
 use self::graphql::loaders::UserLoader;
-use self::graphql::{Mutation, Query, Schema};
-use self::routes::{cors_preflight, graphql_playground, graphql_request};
+use self::graphql::websocket::server::SubscriptionServer;
 use self::services::Services;
 
-#[rocket::launch]
-async fn rocket() -> _ {
+#[rocket::main]
+async fn main() {
     env::set_var("RUST_BACKTRACE", "1");
 
+    let args: Vec<String> = env::args().collect();
+
+    // Generating the allowlist only reads `.graphql` files and the schema
+    // doesn't depend on the configuration, neither must require it nor a
+    // `.env` file
+    match args.get(1).map(String::as_str) {
+        Some("allowlist-gen") => {
+            return graphql::allowlist::run_generator(args.into_iter().skip(2))
+        }
+        Some("schema") => return graphql::sdl::run(args.into_iter().skip(2)),
+        _ => {}
+    }
+
     if cfg!(debug_assertions) {
         dotenv().expect("No \".env\" file found. Copy the current \".env.sample\" file into a \".env\" file and run the server again.");
     } else {
@@ -39,26 +60,133 @@ async fn rocket() -> _ {
         ));
     }
 
-    let config = Config::new();
+    init_tracing();
+
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    error::expose_internal_errors(config.expose_internal_errors);
+    graphql::relay::limit_page_size(config.graphql.page_size);
+    modules::user::reserve_usernames(config.reserved_usernames.clone());
+
+    match args.get(1).map(String::as_str) {
+        Some("migrate") => return migrate(&Database::new(&config).await).await,
+        Some("seed") => return seed::run(&config, args.into_iter().skip(2)).await,
+        _ => {}
+    }
+
+    if let Err(err) = rocket(config).await.launch().await {
+        panic!("Failed to launch the server: {}", err);
+    }
+}
+
+/// Applies pending migrations, the process must not go on against a partially
+/// migrated schema if any fails.
+async fn migrate(database: &Database) {
+    match database.migrate().await {
+        Ok(applied) => tracing::info!(applied, "database is up to date"),
+        Err(err) => panic!("Failed to run migrations: {}", err),
+    }
+}
+
+/// Builds the server from the provided configuration
+async fn rocket(config: Config) -> Rocket<Build> {
     let database = Database::new(&config).await;
+
+    if config.run_migrations_on_start {
+        migrate(&database).await;
+    }
+
     let database = Arc::new(database);
     let services = Services::new(&config, Arc::clone(&database));
     let services = Arc::new(services);
-    let graphql_schema = Schema::build(Query::default(), Mutation::default(), EmptySubscription)
+    let graphql_schema = graphql::schema_builder(&config.graphql)
         .data(Arc::clone(&services))
+        .data(mailer::from_config(&config))
+        .data(config.auth_cookie.clone())
         .data(DataLoader::new(
             UserLoader::new(Arc::clone(&database)),
             rocket::tokio::spawn,
         ))
         .finish();
 
+    let subscriptions = SubscriptionServer {
+        schema: graphql_schema.clone(),
+        auth_service: Arc::clone(&services.auth),
+        keepalive: config.graphql.subscription_keepalive,
+        max_message_bytes: config.graphql.max_request_bytes as usize,
+    };
+
+    let mut routes = routes![
+        routes::cors_preflight,
+        routes::export_users,
+        routes::export_users_jsonl,
+        routes::graphql_query,
+        routes::graphql_request,
+        routes::health,
+        routes::health_live,
+        routes::health_ready,
+        routes::metrics
+    ];
+
+    // The playground is only useful along with introspection
+    if !config.graphql.is_introspection_disabled() {
+        routes.extend(routes![routes::graphql_playground]);
+    }
+
     rocket::custom(&config.server_config)
-        .attach(fairings::cors::Cors)
+        .attach(fairings::cors::Cors::new(config.cors.clone()))
+        .attach(fairings::request_id::RequestIdFairing)
+        .attach(fairings::drain::Drain::new(
+            config.shutdown_timeout,
+            Arc::clone(&database),
+        ))
+        .attach(fairings::token_cleanup::TokenCleanup::new(
+            config.token_cleanup_interval,
+            Arc::clone(&services.auth),
+        ))
+        .attach(fairings::subscriptions::Subscriptions::new(
+            config.graphql.subscription_port,
+            subscriptions,
+        ))
+        .manage(Arc::clone(&database))
         .manage(Arc::clone(&services))
         .manage(graphql_schema)
-        .mount(
+        .manage(config.trusted_proxies.clone())
+        .manage(config.auth_cookie.clone())
+        .manage(config.error_format)
+        .mount("/", routes)
+        .register(
             "/",
-            routes![cors_preflight, graphql_playground, graphql_request],
+            rocket::catchers![catchers::not_found, catchers::payload_too_large],
         )
-        .register("/", rocket::catchers![catchers::not_found])
+}
+
+/// Installs the global `tracing` subscriber. Verbosity is controlled through
+/// the `RUST_LOG` environment variable.
+fn init_tracing() {
+    let default_level = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "info"
+    };
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_level));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter).finish();
+    #[cfg(feature = "otel")]
+    let subscriber = {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let config = config::TelemetryConfig::from_env();
+
+        subscriber.with(telemetry::OtlpLayer::from_config(config))
+    };
+
+    tracing::subscriber::set_global_default(subscriber)
+        .expect("Failed to install the tracing subscriber");
 }
@@ -0,0 +1,251 @@
+//! Prometheus metrics of the process, rendered in the text exposition
+//! format by hand. The `metrics` crate and its Prometheus exporter aren't
+//! available to the offline builds of this project, and the handful of
+//! counters, gauges and the latency histogram don't need more.
+
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::database::PoolStats;
+use crate::error::ErrorCode;
+
+/// Upper bounds in seconds of the request latency histogram buckets
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Application wide metrics, exposed in the Prometheus text format through
+/// the `/metrics` route
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::default);
+
+#[derive(Default)]
+pub struct Metrics {
+    operations: AtomicU64,
+    latency: Histogram,
+    errors: Mutex<BTreeMap<String, u64>>,
+    deprecated_fields: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Metrics {
+    /// Records an executed GraphQL operation and the time it took
+    pub fn record_operation(&self, elapsed: Duration) {
+        self.operations.fetch_add(1, Ordering::Relaxed);
+        self.latency.observe(elapsed);
+    }
+
+    /// Records an error reported to clients
+    pub fn record_error(&self, code: ErrorCode) {
+        let mut errors = self.errors.lock().unwrap_or_else(|err| err.into_inner());
+
+        *errors.entry(code.to_string()).or_default() += 1;
+    }
+
+    /// Records `count` resolutions of a deprecated field, named
+    /// `Type.field`
+    pub fn record_deprecated_field(&self, field: &str, count: u64) {
+        let mut fields = self
+            .deprecated_fields
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+
+        *fields.entry(String::from(field)).or_default() += count;
+    }
+
+    /// Renders every metric in the Prometheus text exposition format
+    pub fn render(&self, pool: Option<PoolStats>) -> String {
+        let mut output = String::new();
+
+        writeln!(
+            output,
+            "# HELP nexus_graphql_operations_total Total GraphQL operations executed"
+        )
+        .unwrap();
+        writeln!(output, "# TYPE nexus_graphql_operations_total counter").unwrap();
+        writeln!(
+            output,
+            "nexus_graphql_operations_total {}",
+            self.operations.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        self.latency.render(
+            &mut output,
+            "nexus_graphql_request_duration_seconds",
+            "GraphQL request latency in seconds",
+        );
+
+        writeln!(
+            output,
+            "# HELP nexus_graphql_errors_total GraphQL errors reported by error code"
+        )
+        .unwrap();
+        writeln!(output, "# TYPE nexus_graphql_errors_total counter").unwrap();
+
+        for (code, count) in self
+            .errors
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+        {
+            writeln!(
+                output,
+                "nexus_graphql_errors_total{{code=\"{code}\"}} {count}"
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            output,
+            "# HELP nexus_graphql_deprecated_field_resolutions_total Resolutions of deprecated fields by field"
+        )
+        .unwrap();
+        writeln!(
+            output,
+            "# TYPE nexus_graphql_deprecated_field_resolutions_total counter"
+        )
+        .unwrap();
+
+        for (field, count) in self
+            .deprecated_fields
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+        {
+            writeln!(
+                output,
+                "nexus_graphql_deprecated_field_resolutions_total{{field=\"{field}\"}} {count}"
+            )
+            .unwrap();
+        }
+
+        if let Some(pool) = pool {
+            writeln!(
+                output,
+                "# HELP nexus_database_connections Database pool connections by state"
+            )
+            .unwrap();
+            writeln!(output, "# TYPE nexus_database_connections gauge").unwrap();
+            writeln!(
+                output,
+                "nexus_database_connections{{state=\"idle\"}} {}",
+                pool.idle_connections
+            )
+            .unwrap();
+            writeln!(
+                output,
+                "nexus_database_connections{{state=\"active\"}} {}",
+                pool.active_connections
+            )
+            .unwrap();
+        }
+
+        output
+    }
+}
+
+/// Histogram over `LATENCY_BUCKETS`, each bucket holds the observations
+/// falling under its bound only and are accumulated when rendered
+#[derive(Default)]
+struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, output: &mut String, name: &str, help: &str) {
+        let mut cumulative = 0;
+
+        writeln!(output, "# HELP {name} {help}").unwrap();
+        writeln!(output, "# TYPE {name} histogram").unwrap();
+
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(self.buckets.iter()) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            writeln!(output, "{name}_bucket{{le=\"{bound}\"}} {cumulative}").unwrap();
+        }
+
+        let count = self.count.load(Ordering::Relaxed);
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+
+        writeln!(output, "{name}_bucket{{le=\"+Inf\"}} {count}").unwrap();
+        writeln!(output, "{name}_sum {sum}").unwrap();
+        writeln!(output, "{name}_count {count}").unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::database::PoolStats;
+    use crate::error::ErrorCode;
+
+    use super::Metrics;
+
+    #[test]
+    fn renders_accumulated_latency_buckets() {
+        let metrics = Metrics::default();
+
+        metrics.record_operation(Duration::from_millis(3));
+        metrics.record_operation(Duration::from_millis(40));
+        metrics.record_operation(Duration::from_secs(30));
+
+        let output = metrics.render(None);
+
+        assert!(output.contains("nexus_graphql_operations_total 3\n"));
+        assert!(output.contains("nexus_graphql_request_duration_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(output.contains("nexus_graphql_request_duration_seconds_bucket{le=\"0.05\"} 2\n"));
+        assert!(output.contains("nexus_graphql_request_duration_seconds_bucket{le=\"10\"} 2\n"));
+        assert!(output.contains("nexus_graphql_request_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(output.contains("nexus_graphql_request_duration_seconds_count 3\n"));
+    }
+
+    #[test]
+    fn renders_errors_by_code_and_pool_gauges() {
+        let metrics = Metrics::default();
+
+        metrics.record_error(ErrorCode::ServerError);
+        metrics.record_error(ErrorCode::ServerError);
+        metrics.record_error(ErrorCode::InvalidCredentials);
+
+        let output = metrics.render(Some(PoolStats {
+            idle_connections: 2,
+            active_connections: 3,
+        }));
+
+        assert!(output.contains("nexus_graphql_errors_total{code=\"SERVER_ERROR\"} 2\n"));
+        assert!(output.contains("nexus_graphql_errors_total{code=\"INVALID_CREDENTIALS\"} 1\n"));
+        assert!(output.contains("nexus_database_connections{state=\"idle\"} 2\n"));
+        assert!(output.contains("nexus_database_connections{state=\"active\"} 3\n"));
+    }
+
+    #[test]
+    fn renders_deprecated_field_resolutions_by_field() {
+        let metrics = Metrics::default();
+
+        metrics.record_deprecated_field("User.fullName", 2);
+        metrics.record_deprecated_field("User.fullName", 3);
+
+        let output = metrics.render(None);
+
+        assert!(output.contains(
+            "nexus_graphql_deprecated_field_resolutions_total{field=\"User.fullName\"} 5\n"
+        ));
+    }
+}
@@ -0,0 +1,128 @@
+use async_graphql::{Context, Enum, SimpleObject};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use uuid::Uuid;
+
+use crate::graphql::relay::{Keyset, KeysetColumn, KeysetOrder, SortValue};
+use crate::routes::ClientIp;
+
+/// Security-sensitive actions recorded in the audit log
+#[derive(Copy, Clone, Debug, Deserialize, Enum, PartialEq, Eq, Serialize, sqlx::Type)]
+#[sqlx(type_name = "audit_action", rename_all = "snake_case")]
+pub enum AuditAction {
+    UserCreate,
+    TokenCreate,
+    PasswordChange,
+    UserDelete,
+    RoleUpdate,
+    UserUnlock,
+    ApiTokenCreate,
+    ApiTokenRevoke,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Enum, PartialEq, Eq, Serialize, sqlx::Type)]
+#[sqlx(type_name = "audit_outcome", rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+/// Sorting applied when browsing the audit log
+#[derive(Copy, Clone, Debug, Default, Deserialize, Enum, PartialEq, Eq, Serialize)]
+pub enum AuditLogOrder {
+    CreatedAtAsc,
+    #[default]
+    CreatedAtDesc,
+}
+
+impl KeysetOrder for AuditLogOrder {
+    fn name(&self) -> &'static str {
+        match self {
+            AuditLogOrder::CreatedAtAsc => "CREATED_AT_ASC",
+            AuditLogOrder::CreatedAtDesc => "CREATED_AT_DESC",
+        }
+    }
+
+    fn descending(&self) -> bool {
+        *self == AuditLogOrder::CreatedAtDesc
+    }
+
+    fn column(&self) -> KeysetColumn {
+        KeysetColumn {
+            name: "created_at",
+            sql_type: "timestamptz",
+        }
+    }
+}
+
+/// Who performs an audited action and from where. `actor_id` is `None` for
+/// anonymous callers, e.g. when registering or logging in.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AuditContext {
+    pub actor_id: Option<Uuid>,
+    pub ip: Option<IpAddr>,
+}
+
+impl AuditContext {
+    pub fn new(actor_id: Option<Uuid>, ip: Option<IpAddr>) -> Self {
+        Self { actor_id, ip }
+    }
+
+    /// Uses the client IP of the GraphQL request being resolved
+    pub fn from_ctx(ctx: &Context<'_>, actor_id: Option<Uuid>) -> Self {
+        let ip = ctx.data_opt::<ClientIp>().and_then(|client_ip| client_ip.0);
+
+        Self::new(actor_id, ip)
+    }
+
+    /// Builds the entry recording `action` performed on `target_id`
+    pub fn entry(
+        &self,
+        action: AuditAction,
+        target_id: Option<Uuid>,
+        outcome: AuditOutcome,
+    ) -> NewAuditEntry {
+        NewAuditEntry {
+            actor_id: self.actor_id,
+            target_id,
+            action,
+            outcome,
+            ip_address: self.ip.map(|ip| ip.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NewAuditEntry {
+    pub actor_id: Option<Uuid>,
+    pub target_id: Option<Uuid>,
+    pub action: AuditAction,
+    pub outcome: AuditOutcome,
+    pub ip_address: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct AuditLogEntry {
+    pub id: Uuid,
+    /// User performing the action, `null` for anonymous callers
+    pub actor_id: Option<Uuid>,
+    /// User the action was performed on, if known
+    pub target_id: Option<Uuid>,
+    pub action: AuditAction,
+    pub outcome: AuditOutcome,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Keyset for AuditLogEntry {
+    type Order = AuditLogOrder;
+
+    fn sort_value(&self, _: AuditLogOrder) -> SortValue {
+        SortValue::Timestamp(self.created_at)
+    }
+
+    fn keyset_id(&self) -> Uuid {
+        self.id
+    }
+}
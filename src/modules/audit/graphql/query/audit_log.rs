@@ -0,0 +1,166 @@
+use async_graphql::{Context, Enum, InputObject, SimpleObject};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::graphql::guards::current_user;
+use crate::graphql::relay::{self, KeysetConnection};
+use crate::modules::audit::{AuditAction, AuditLogEntry, AuditLogFilter, AuditLogOrder};
+use crate::services::Services;
+
+#[derive(SimpleObject)]
+pub struct AuditLog {
+    audit_log: Option<KeysetConnection<AuditLogEntry>>,
+    error: Option<AuditLogError>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct AuditLogError {
+    field: Option<String>,
+    message: Option<String>,
+    code: AuditLogErrorCode,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
+pub enum AuditLogErrorCode {
+    InvalidRange,
+}
+
+impl TryFrom<Error> for AuditLogError {
+    type Error = Error;
+
+    fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
+        match value.code {
+            ErrorCode::ValidationError => Ok(AuditLogError {
+                field: value.field,
+                message: value.message,
+                code: AuditLogErrorCode::InvalidRange,
+            }),
+            _ => Err(value),
+        }
+    }
+}
+
+/// Entries recorded within `[from, to)`, optionally only for `action`
+#[derive(Default, InputObject)]
+pub struct AuditLogFilterInput {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub action: Option<AuditAction>,
+}
+
+impl AuditLogFilterInput {
+    /// Builds the filter for the log of the provided organization
+    fn into_filter(self, organization_id: Uuid) -> Result<AuditLogFilter> {
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from >= to {
+                return Err(Error::new(
+                    "to",
+                    "Must be later than `from`",
+                    ErrorCode::ValidationError,
+                ));
+            }
+        }
+
+        Ok(AuditLogFilter {
+            organization_id,
+            from: self.from,
+            to: self.to,
+            action: self.action,
+        })
+    }
+}
+
+impl AuditLog {
+    pub async fn exec(
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+        filter: Option<AuditLogFilterInput>,
+        order_by: Option<AuditLogOrder>,
+    ) -> Result<AuditLog> {
+        let services = ctx.data_unchecked::<Arc<Services>>();
+        let organization_id = current_user(ctx).await?.organization_id;
+        let filter = match filter.unwrap_or_default().into_filter(organization_id) {
+            Ok(filter) => Arc::new(filter),
+            Err(err) => {
+                let audit_log_error = AuditLogError::try_from(err)?;
+
+                return Ok(AuditLog {
+                    audit_log: None,
+                    error: Some(audit_log_error),
+                });
+            }
+        };
+        let order = order_by.unwrap_or_default();
+        let count_filter = Arc::clone(&filter);
+        let count_services = Arc::clone(services);
+        let audit_log = relay::query_keyset(
+            relay::Params::new(after, before, first, last),
+            order,
+            Arc::new(move || {
+                let services = Arc::clone(&count_services);
+                let filter = Arc::clone(&count_filter);
+
+                Box::pin(async move { services.audit.count(&filter).await })
+            }),
+            |page| async move { services.audit.find_page(&filter, order, page).await },
+        )
+        .await;
+
+        match audit_log {
+            Ok(audit_log) => Ok(AuditLog {
+                audit_log: Some(audit_log),
+                error: None,
+            }),
+            Err(err) => {
+                let audit_log_error = AuditLogError::try_from(err)?;
+
+                Ok(AuditLog {
+                    audit_log: None,
+                    error: Some(audit_log_error),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    use super::AuditLogFilterInput;
+
+    #[test]
+    fn rejects_empty_date_ranges() {
+        let now = Utc::now();
+        let error = AuditLogFilterInput {
+            from: Some(now),
+            to: Some(now - Duration::days(1)),
+            action: None,
+        }
+        .into_filter(Uuid::new_v4())
+        .err()
+        .unwrap();
+
+        assert_eq!(error.field.as_deref(), Some("to"));
+    }
+
+    #[test]
+    fn accepts_open_date_ranges() {
+        let filter = AuditLogFilterInput {
+            from: Some(Utc::now()),
+            to: None,
+            action: None,
+        }
+        .into_filter(Uuid::new_v4())
+        .unwrap();
+
+        assert!(filter.to.is_none());
+    }
+}
@@ -0,0 +1,35 @@
+pub mod audit_log;
+
+use async_graphql::{Context, Object};
+
+use crate::error::Result;
+use crate::graphql::guards::{RoleGuard, ScopeGuard};
+use crate::modules::audit::AuditLogOrder;
+use crate::modules::auth::ApiScope;
+use crate::modules::user::Role;
+
+use self::audit_log::{AuditLog, AuditLogFilterInput};
+
+#[derive(Default)]
+pub struct AuditQuery;
+
+#[Object]
+impl AuditQuery {
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(
+        name = "auditLog",
+        guard = "RoleGuard::new(Role::Admin).and(ScopeGuard::new(ApiScope::AuditLogRead))"
+    )]
+    async fn audit_log(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+        filter: Option<AuditLogFilterInput>,
+        order_by: Option<AuditLogOrder>,
+    ) -> Result<AuditLog> {
+        AuditLog::exec(ctx, after, before, first, last, filter, order_by).await
+    }
+}
@@ -0,0 +1,9 @@
+mod entity;
+mod repository;
+mod service;
+
+pub mod graphql;
+
+pub use entity::*;
+pub use repository::*;
+pub use service::*;
@@ -0,0 +1,141 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::database::Database;
+use crate::error::Result;
+use crate::graphql::relay::{KeysetPage, KeysetQuery};
+
+use super::{AuditAction, AuditLogEntry, AuditLogOrder, AuditOutcome, NewAuditEntry};
+
+#[derive(Debug, Deserialize, FromRow, Serialize)]
+pub struct AuditLogTableRow {
+    pub id: Uuid,
+    pub actor_id: Option<Uuid>,
+    pub target_id: Option<Uuid>,
+    pub action: AuditAction,
+    pub outcome: AuditOutcome,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<AuditLogTableRow> for AuditLogEntry {
+    fn from(dto: AuditLogTableRow) -> Self {
+        Self {
+            id: dto.id,
+            actor_id: dto.actor_id,
+            target_id: dto.target_id,
+            action: dto.action,
+            outcome: dto.outcome,
+            ip_address: dto.ip_address,
+            created_at: dto.created_at,
+        }
+    }
+}
+
+/// `WHERE` conditions for `AuditLogFilter`, bound to the first four
+/// parameters
+const LIST_FILTER: &str = "($1::timestamptz IS NULL OR created_at >= $1) \
+    AND ($2::timestamptz IS NULL OR created_at < $2) \
+    AND ($3::audit_action IS NULL OR action = $3::audit_action) \
+    AND (actor_id IN (SELECT id FROM users WHERE organization_id = $4) \
+        OR target_id IN (SELECT id FROM users WHERE organization_id = $4))";
+
+/// Conditions applied when browsing the audit log, entries are within
+/// `[from, to)` and performed by or on users of `organization_id`
+#[derive(Debug, Default)]
+pub struct AuditLogFilter {
+    pub organization_id: Uuid,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub action: Option<AuditAction>,
+}
+
+/// Writes an audit entry through the provided connection, allows recording
+/// actions within the transaction performing them
+pub async fn insert_audit_entry(conn: &mut PgConnection, entry: &NewAuditEntry) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log (
+            actor_id,
+            target_id,
+            action,
+            outcome,
+            ip_address
+        ) VALUES (
+            $1,
+            $2,
+            $3::audit_action,
+            $4::audit_outcome,
+            $5
+        )"#,
+    )
+    .bind(entry.actor_id)
+    .bind(entry.target_id)
+    .bind(entry.action)
+    .bind(entry.outcome)
+    .bind(entry.ip_address.as_deref())
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub struct AuditRepository {
+    database: Arc<Database>,
+}
+
+impl AuditRepository {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self { database }
+    }
+
+    /// Records an action which doesn't modify other tables, such as a login
+    pub async fn insert(&self, entry: &NewAuditEntry) -> Result<()> {
+        let mut conn = self.database.conn_pool.acquire().await?;
+
+        insert_audit_entry(&mut conn, entry).await
+    }
+
+    /// Retrieves a page of the entries matching `filter` sorted by `order`,
+    /// ties are sorted by id so keyset cursors point to a single row.
+    pub async fn find_page(
+        &self,
+        filter: &AuditLogFilter,
+        order: AuditLogOrder,
+        page: KeysetPage,
+    ) -> Result<Vec<AuditLogEntry>> {
+        let query = KeysetQuery::new(
+            &format!("SELECT * FROM audit_log WHERE {LIST_FILTER}"),
+            4,
+            order,
+            page,
+        );
+        let result: Vec<AuditLogTableRow> = query
+            .fetch_all(&self.database.conn_pool, |query| {
+                query
+                    .bind(filter.from)
+                    .bind(filter.to)
+                    .bind(filter.action)
+                    .bind(filter.organization_id)
+            })
+            .await?;
+
+        Ok(result.into_iter().map(AuditLogEntry::from).collect())
+    }
+
+    pub async fn count(&self, filter: &AuditLogFilter) -> Result<i64> {
+        let query = format!("SELECT COUNT(*) FROM audit_log WHERE {LIST_FILTER}");
+        let count: i64 = sqlx::query_scalar(&query)
+            .bind(filter.from)
+            .bind(filter.to)
+            .bind(filter.action)
+            .bind(filter.organization_id)
+            .fetch_one(&self.database.conn_pool)
+            .await?;
+
+        Ok(count)
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::graphql::relay::KeysetPage;
+
+use super::{AuditLogEntry, AuditLogFilter, AuditLogOrder, AuditRepository, NewAuditEntry};
+
+pub struct AuditService {
+    repository: Arc<AuditRepository>,
+}
+
+impl AuditService {
+    pub fn new(repository: Arc<AuditRepository>) -> Self {
+        Self { repository }
+    }
+
+    /// Records an action performed outside of a transaction. Actions
+    /// modifying data are recorded by the repository performing them
+    /// instead, so both are committed or rolled back together.
+    pub async fn record(&self, entry: NewAuditEntry) -> Result<()> {
+        self.repository.insert(&entry).await
+    }
+
+    pub async fn find_page(
+        &self,
+        filter: &AuditLogFilter,
+        order: AuditLogOrder,
+        page: KeysetPage,
+    ) -> Result<Vec<AuditLogEntry>> {
+        self.repository.find_page(filter, order, page).await
+    }
+
+    pub async fn count(&self, filter: &AuditLogFilter) -> Result<usize> {
+        let count = self.repository.count(filter).await?;
+
+        Ok(count as usize)
+    }
+}
@@ -1,7 +1,194 @@
-use async_graphql::SimpleObject;
+use async_graphql::{Enum, SimpleObject};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::modules::user::User;
 
 #[derive(Debug, Deserialize, Serialize, SimpleObject)]
 pub struct Tokens {
     pub access_token: String,
+    pub refresh_token: String,
+}
+
+/// Tokens issued by a successful login, along with the user they were issued
+/// for
+#[derive(Debug)]
+pub struct Login {
+    pub tokens: Tokens,
+    pub user: User,
+}
+
+/// Permissions granted to an API token. Session tokens are granted every
+/// scope, within the limits of the user's role.
+#[derive(Copy, Clone, Debug, Deserialize, Enum, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    /// Browse the audit log
+    AuditLogRead,
+    /// Read posts and feeds
+    PostsRead,
+    /// Create posts
+    PostsWrite,
+    /// List, read and export users
+    UsersRead,
+    /// Update, delete, unlock and change the role of users
+    UsersWrite,
+}
+
+impl ApiScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::AuditLogRead => "audit_log_read",
+            ApiScope::PostsRead => "posts_read",
+            ApiScope::PostsWrite => "posts_write",
+            ApiScope::UsersRead => "users_read",
+            ApiScope::UsersWrite => "users_write",
+        }
+    }
+}
+
+impl FromStr for ApiScope {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "audit_log_read" => Ok(ApiScope::AuditLogRead),
+            "posts_read" => Ok(ApiScope::PostsRead),
+            "posts_write" => Ok(ApiScope::PostsWrite),
+            "users_read" => Ok(ApiScope::UsersRead),
+            "users_write" => Ok(ApiScope::UsersWrite),
+            _ => Err(format!("Unknown API scope: {value}")),
+        }
+    }
+}
+
+/// Long-lived token for scripts and integrations, stored so it can be listed
+/// and revoked. The signed token itself is only handed out once.
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct ApiToken {
+    pub id: Uuid,
+    #[graphql(skip)]
+    pub user_id: Uuid,
+    /// Label chosen by the owner to tell its tokens apart
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    /// Tokens without expiry remain valid until revoked
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiToken {
+    /// Checks whether the token is accepted at `now`
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+/// Caller identified by a session or an API token
+#[derive(Clone, Debug)]
+pub struct Authenticated {
+    pub user: User,
+    pub expires_at: DateTime<Utc>,
+    /// Scopes granted to the API token the caller authenticated with, `None`
+    /// for session tokens
+    pub scopes: Option<Vec<ApiScope>>,
+}
+
+impl Authenticated {
+    /// Checks whether the caller's token grants `scope`
+    pub fn allows(&self, scope: ApiScope) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.contains(&scope))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RefreshToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// Every refresh token issued by rotating a token shares the `family_id`
+    /// of the token it was issued from.
+    pub family_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Amount of rows deleted by a purge of expired tokens
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PurgedTokens {
+    pub refresh_tokens: u64,
+    pub revoked_tokens: u64,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
+pub enum SessionEventKind {
+    Login,
+    Logout,
+    PasswordChanged,
+}
+
+/// Notifies a user's sessions about activity in any of its sessions
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct SessionEvent {
+    pub user_id: Uuid,
+    pub kind: SessionEventKind,
+    pub occurred_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    use super::{ApiScope, ApiToken};
+
+    fn api_token() -> ApiToken {
+        ApiToken {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            name: String::from("ci"),
+            scopes: vec![ApiScope::UsersRead],
+            expires_at: None,
+            revoked_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn api_scopes_are_stored_by_name() {
+        for scope in [
+            ApiScope::AuditLogRead,
+            ApiScope::PostsRead,
+            ApiScope::PostsWrite,
+            ApiScope::UsersRead,
+            ApiScope::UsersWrite,
+        ] {
+            assert_eq!(scope.as_str().parse::<ApiScope>(), Ok(scope));
+        }
+
+        assert!("admin".parse::<ApiScope>().is_err());
+    }
+
+    #[test]
+    fn api_tokens_are_active_until_revoked_or_expired() {
+        let now = Utc::now();
+        let expired = ApiToken {
+            expires_at: Some(now - Duration::seconds(1)),
+            ..api_token()
+        };
+        let revoked = ApiToken {
+            revoked_at: Some(now),
+            ..api_token()
+        };
+
+        assert!(api_token().is_active(now));
+        assert!(!expired.is_active(now));
+        assert!(!revoked.is_active(now));
+    }
 }
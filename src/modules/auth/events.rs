@@ -0,0 +1,77 @@
+use async_graphql::futures_util::stream::{self, Stream};
+use chrono::Utc;
+use rocket::tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+use super::{SessionEvent, SessionEventKind};
+
+/// Amount of events buffered per subscriber, slower subscribers miss the
+/// oldest events
+const SESSION_EVENTS_CAPACITY: usize = 64;
+
+/// Broadcasts session events to every subscribed session
+pub struct SessionEvents {
+    sender: broadcast::Sender<SessionEvent>,
+}
+
+impl Default for SessionEvents {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(SESSION_EVENTS_CAPACITY);
+
+        Self { sender }
+    }
+}
+
+impl SessionEvents {
+    pub fn publish(&self, user_id: Uuid, kind: SessionEventKind) {
+        let event = SessionEvent {
+            user_id,
+            kind,
+            occurred_at: Utc::now(),
+        };
+
+        // Sending only fails when nobody is subscribed
+        let _ = self.sender.send(event);
+    }
+
+    /// Streams the events published for the provided user from now on
+    pub fn subscribe(&self, user_id: Uuid) -> impl Stream<Item = SessionEvent> {
+        stream::unfold(self.sender.subscribe(), move |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if event.user_id == user_id => return Some((event, receiver)),
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(%user_id, skipped, "session events subscriber lagged");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::futures_util::StreamExt;
+    use uuid::Uuid;
+
+    use crate::modules::auth::SessionEventKind;
+
+    use super::SessionEvents;
+
+    #[rocket::async_test]
+    async fn streams_events_of_the_subscribed_user() {
+        let events = SessionEvents::default();
+        let user_id = Uuid::new_v4();
+        let mut stream = Box::pin(events.subscribe(user_id));
+
+        events.publish(Uuid::new_v4(), SessionEventKind::Login);
+        events.publish(user_id, SessionEventKind::PasswordChanged);
+
+        let event = stream.next().await.unwrap();
+
+        assert_eq!(event.user_id, user_id);
+        assert_eq!(event.kind, SessionEventKind::PasswordChanged);
+    }
+}
@@ -1,3 +1,7 @@
 mod mutation;
+mod query;
+mod subscription;
 
 pub use mutation::*;
+pub use query::*;
+pub use subscription::*;
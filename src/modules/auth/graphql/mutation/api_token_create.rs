@@ -0,0 +1,99 @@
+use async_graphql::{Context, Enum, InputObject, SimpleObject};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::graphql::duration::HumanDuration;
+use crate::graphql::guards::current_user;
+use crate::graphql::relay::MutationPayload;
+use crate::modules::audit::AuditContext;
+use crate::modules::auth::{ApiScope, ApiToken};
+use crate::services::Services;
+
+#[derive(Debug, InputObject)]
+pub struct ApiTokenCreateInput {
+    /// Label telling the token apart from the caller's other tokens
+    pub name: String,
+    /// Scopes granted to the token, at least one
+    pub scopes: Vec<ApiScope>,
+    /// How long the token remains valid, until revoked when omitted
+    pub expires_in: Option<HumanDuration>,
+    /// Echoed unchanged by the payload, matching it to the request
+    pub client_mutation_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, SimpleObject)]
+pub struct ApiTokenCreate {
+    api_token: Option<ApiToken>,
+    /// Signed token to send in the `Authorization` header, it can't be
+    /// retrieved again
+    token: Option<String>,
+    error: Option<ApiTokenCreateError>,
+    /// `clientMutationId` of the input, unchanged
+    client_mutation_id: Option<String>,
+}
+
+impl MutationPayload for ApiTokenCreate {
+    fn client_mutation_id(&mut self) -> &mut Option<String> {
+        &mut self.client_mutation_id
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct ApiTokenCreateError {
+    field: Option<String>,
+    message: Option<String>,
+    code: ApiTokenCreateErrorCode,
+}
+
+impl TryFrom<Error> for ApiTokenCreateError {
+    type Error = Error;
+
+    fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
+        match value.code {
+            ErrorCode::ValidationError => Ok(ApiTokenCreateError {
+                field: value.field,
+                message: value.message,
+                code: ApiTokenCreateErrorCode::ValidationError,
+            }),
+            _ => Err(value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
+pub enum ApiTokenCreateErrorCode {
+    ValidationError,
+}
+
+impl ApiTokenCreate {
+    pub async fn exec(ctx: &Context<'_>, input: ApiTokenCreateInput) -> Result<ApiTokenCreate> {
+        let services = ctx.data_unchecked::<Arc<Services>>();
+        let caller = current_user(ctx).await?;
+        let audit = AuditContext::from_ctx(ctx, Some(caller.id));
+        let expires_in = input.expires_in.map(HumanDuration::to_chrono);
+
+        match services
+            .auth
+            .issue_api_token(&caller, input.name, input.scopes, expires_in, audit)
+            .await
+        {
+            Ok((api_token, token)) => Ok(ApiTokenCreate {
+                api_token: Some(api_token),
+                token: Some(token),
+                error: None,
+                client_mutation_id: None,
+            }),
+            Err(err) => {
+                let api_token_create_error = ApiTokenCreateError::try_from(err)?;
+
+                Ok(ApiTokenCreate {
+                    api_token: None,
+                    token: None,
+                    error: Some(api_token_create_error),
+                    client_mutation_id: None,
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use async_graphql::{Context, Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::graphql::guards::current_user;
+use crate::modules::audit::AuditContext;
+use crate::modules::auth::ApiToken;
+use crate::services::Services;
+
+#[derive(Debug, Deserialize, Serialize, SimpleObject)]
+pub struct ApiTokenRevoke {
+    api_token: Option<ApiToken>,
+    error: Option<ApiTokenRevokeError>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct ApiTokenRevokeError {
+    field: Option<String>,
+    message: Option<String>,
+    code: ApiTokenRevokeErrorCode,
+}
+
+impl TryFrom<Error> for ApiTokenRevokeError {
+    type Error = Error;
+
+    fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
+        match value.code {
+            ErrorCode::NotFound => Ok(ApiTokenRevokeError {
+                field: value.field,
+                message: value.message,
+                code: ApiTokenRevokeErrorCode::NotFound,
+            }),
+            ErrorCode::Conflict => Ok(ApiTokenRevokeError {
+                field: value.field,
+                message: value.message,
+                code: ApiTokenRevokeErrorCode::AlreadyRevoked,
+            }),
+            _ => Err(value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
+pub enum ApiTokenRevokeErrorCode {
+    NotFound,
+    AlreadyRevoked,
+}
+
+impl ApiTokenRevoke {
+    pub async fn exec(ctx: &Context<'_>, id: Uuid) -> Result<ApiTokenRevoke> {
+        let services = ctx.data_unchecked::<Arc<Services>>();
+        let caller = current_user(ctx).await?;
+        let audit = AuditContext::from_ctx(ctx, Some(caller.id));
+
+        match services.auth.revoke_api_token(&caller, id, audit).await {
+            Ok(api_token) => Ok(ApiTokenRevoke {
+                api_token: Some(api_token),
+                error: None,
+            }),
+            Err(err) => {
+                let api_token_revoke_error = ApiTokenRevokeError::try_from(err)?;
+
+                Ok(ApiTokenRevoke {
+                    api_token: None,
+                    error: Some(api_token_revoke_error),
+                })
+            }
+        }
+    }
+}
@@ -1,10 +1,29 @@
+pub mod api_token_create;
+pub mod api_token_revoke;
+pub mod password_change;
+pub mod password_reset_confirm;
+pub mod password_reset_request;
+pub mod refresh_token;
 pub mod token_create;
+pub mod token_revoke;
+pub mod verify_email;
 
 use async_graphql::{Context, Object};
+use uuid::Uuid;
 
 use crate::error::Result;
+use crate::graphql::guards::SessionGuard;
+use crate::graphql::relay::echo_client_mutation_id;
 
+use self::api_token_create::{ApiTokenCreate, ApiTokenCreateInput};
+use self::api_token_revoke::ApiTokenRevoke;
+use self::password_change::{PasswordChange, PasswordChangeInput};
+use self::password_reset_confirm::PasswordResetConfirm;
+use self::password_reset_request::PasswordResetRequest;
+use self::refresh_token::RefreshToken;
 use self::token_create::TokenCreate;
+use self::token_revoke::TokenRevoke;
+use self::verify_email::VerifyEmail;
 
 #[derive(Default)]
 pub struct AuthMutation;
@@ -17,7 +36,76 @@ impl AuthMutation {
         ctx: &Context<'_>,
         username: String,
         password: String,
+        #[graphql(default = false)] set_cookie: bool,
     ) -> Result<TokenCreate> {
-        TokenCreate::exec(ctx, username, password).await
+        TokenCreate::exec(ctx, username, password, set_cookie).await
+    }
+
+    #[graphql(name = "tokenRevoke")]
+    pub async fn token_revoke(&self, ctx: &Context<'_>) -> Result<TokenRevoke> {
+        TokenRevoke::exec(ctx).await
+    }
+
+    #[graphql(name = "refreshToken")]
+    pub async fn refresh_token(
+        &self,
+        ctx: &Context<'_>,
+        refresh_token: String,
+    ) -> Result<RefreshToken> {
+        RefreshToken::exec(ctx, refresh_token).await
+    }
+
+    #[graphql(name = "passwordChange", guard = "SessionGuard")]
+    pub async fn password_change(
+        &self,
+        ctx: &Context<'_>,
+        mut input: PasswordChangeInput,
+    ) -> async_graphql::Result<PasswordChange> {
+        let client_mutation_id = input.client_mutation_id.take();
+
+        echo_client_mutation_id(client_mutation_id, PasswordChange::exec(ctx, input).await)
+    }
+
+    #[graphql(name = "verifyEmail")]
+    pub async fn verify_email(&self, ctx: &Context<'_>, token: String) -> Result<VerifyEmail> {
+        VerifyEmail::exec(ctx, token).await
+    }
+
+    #[graphql(name = "passwordResetRequest")]
+    pub async fn password_reset_request(
+        &self,
+        ctx: &Context<'_>,
+        email: String,
+    ) -> Result<PasswordResetRequest> {
+        PasswordResetRequest::exec(ctx, email).await
+    }
+
+    #[graphql(name = "passwordResetConfirm")]
+    pub async fn password_reset_confirm(
+        &self,
+        ctx: &Context<'_>,
+        token: String,
+        new_password: String,
+    ) -> async_graphql::Result<PasswordResetConfirm> {
+        PasswordResetConfirm::exec(ctx, token, new_password).await
+    }
+
+    /// Issues a scoped API token for the caller, meant for scripts and
+    /// integrations
+    #[graphql(name = "apiTokenCreate", guard = "SessionGuard")]
+    pub async fn api_token_create(
+        &self,
+        ctx: &Context<'_>,
+        mut input: ApiTokenCreateInput,
+    ) -> Result<ApiTokenCreate> {
+        let client_mutation_id = input.client_mutation_id.take();
+
+        echo_client_mutation_id(client_mutation_id, ApiTokenCreate::exec(ctx, input).await)
+    }
+
+    /// Revokes one of the caller's API tokens
+    #[graphql(name = "apiTokenRevoke", guard = "SessionGuard")]
+    pub async fn api_token_revoke(&self, ctx: &Context<'_>, id: Uuid) -> Result<ApiTokenRevoke> {
+        ApiTokenRevoke::exec(ctx, id).await
     }
 }
@@ -0,0 +1,96 @@
+use async_graphql::{Context, Enum, InputObject, SimpleObject};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::error::{Error, ErrorCode};
+use crate::graphql::guards::current_user;
+use crate::graphql::relay::MutationPayload;
+use crate::modules::audit::AuditContext;
+use crate::modules::user::User;
+use crate::services::Services;
+
+#[derive(Debug, Deserialize, Serialize, SimpleObject)]
+pub struct PasswordChange {
+    user: Option<User>,
+    error: Option<PasswordChangeError>,
+    /// `clientMutationId` of the input, unchanged
+    client_mutation_id: Option<String>,
+}
+
+impl MutationPayload for PasswordChange {
+    fn client_mutation_id(&mut self) -> &mut Option<String> {
+        &mut self.client_mutation_id
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct PasswordChangeError {
+    field: Option<String>,
+    message: Option<String>,
+    code: PasswordChangeErrorCode,
+}
+
+impl TryFrom<Error> for PasswordChangeError {
+    type Error = Error;
+
+    fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
+        match value.code {
+            ErrorCode::InvalidCredentials => Ok(PasswordChangeError {
+                field: Some(String::from("currentPassword")),
+                message: Some(String::from("Current password is not valid")),
+                code: PasswordChangeErrorCode::InvalidCredentials,
+            }),
+            _ => Err(value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
+pub enum PasswordChangeErrorCode {
+    InvalidCredentials,
+}
+
+#[derive(Deserialize, Serialize, InputObject)]
+#[graphql(input_name = "PasswordChangeInput")]
+pub struct PasswordChangeInput {
+    pub current_password: String,
+    pub new_password: String,
+    /// Revokes every refresh token issued to the user, logging out other
+    /// sessions once their access token expires
+    #[graphql(default)]
+    pub revoke_other_sessions: bool,
+    /// Echoed unchanged by the payload, matching it to the request
+    pub client_mutation_id: Option<String>,
+}
+
+impl PasswordChange {
+    pub async fn exec(
+        ctx: &Context<'_>,
+        input: PasswordChangeInput,
+    ) -> async_graphql::Result<PasswordChange> {
+        let services = ctx.data_unchecked::<Arc<Services>>();
+        let user = current_user(ctx).await?;
+        let audit = AuditContext::from_ctx(ctx, Some(user.id));
+
+        services
+            .user
+            .validate_password("newPassword", &input.new_password)?;
+
+        match services.auth.change_password(user, input, audit).await {
+            Ok(user) => Ok(PasswordChange {
+                user: Some(user),
+                error: None,
+                client_mutation_id: None,
+            }),
+            Err(err) => {
+                let password_change_error = PasswordChangeError::try_from(err)?;
+
+                Ok(PasswordChange {
+                    user: None,
+                    error: Some(password_change_error),
+                    client_mutation_id: None,
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use async_graphql::{Context, Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::error::{Error, ErrorCode};
+use crate::modules::user::User;
+use crate::services::Services;
+
+#[derive(Debug, Deserialize, Serialize, SimpleObject)]
+pub struct PasswordResetConfirm {
+    user: Option<User>,
+    error: Option<PasswordResetConfirmError>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct PasswordResetConfirmError {
+    field: Option<String>,
+    message: Option<String>,
+    code: PasswordResetConfirmErrorCode,
+}
+
+impl TryFrom<Error> for PasswordResetConfirmError {
+    type Error = Error;
+
+    fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
+        match value.code {
+            ErrorCode::InvalidJsonWebToken => Ok(PasswordResetConfirmError {
+                field: Some(String::from("token")),
+                message: None,
+                code: PasswordResetConfirmErrorCode::InvalidToken,
+            }),
+            ErrorCode::ExpiredJsonWebToken => Ok(PasswordResetConfirmError {
+                field: Some(String::from("token")),
+                message: None,
+                code: PasswordResetConfirmErrorCode::ExpiredToken,
+            }),
+            _ => Err(value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
+pub enum PasswordResetConfirmErrorCode {
+    InvalidToken,
+    ExpiredToken,
+}
+
+impl PasswordResetConfirm {
+    pub async fn exec(
+        ctx: &Context<'_>,
+        token: String,
+        new_password: String,
+    ) -> async_graphql::Result<PasswordResetConfirm> {
+        let services = ctx.data_unchecked::<Arc<Services>>();
+
+        services
+            .user
+            .validate_password("newPassword", &new_password)?;
+
+        match services
+            .auth
+            .confirm_password_reset(token, new_password)
+            .await
+        {
+            Ok(user) => Ok(PasswordResetConfirm {
+                user: Some(user),
+                error: None,
+            }),
+            Err(err) => {
+                let password_reset_confirm_error = PasswordResetConfirmError::try_from(err)?;
+
+                Ok(PasswordResetConfirm {
+                    user: None,
+                    error: Some(password_reset_confirm_error),
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use async_graphql::{Context, SimpleObject};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::mailer::Mailer;
+use crate::services::Services;
+
+/// Always succeeds regardless of the provided email being registered, so this
+/// mutation can't be used to find out which emails exist.
+#[derive(Debug, Deserialize, Serialize, SimpleObject)]
+pub struct PasswordResetRequest {
+    success: bool,
+}
+
+impl PasswordResetRequest {
+    pub async fn exec(ctx: &Context<'_>, email: String) -> Result<PasswordResetRequest> {
+        let services = ctx.data_unchecked::<Arc<Services>>();
+        let mailer = ctx.data_unchecked::<Arc<dyn Mailer>>();
+
+        if let Some(token) = services.auth.request_password_reset(&email).await? {
+            let body = format!(
+                "A password reset was requested for your account. Use the following token to choose a new password:\n\n{token}\n\nIf you didn't request it, you can ignore this email."
+            );
+
+            // Failing to deliver is logged, reporting it would reveal the
+            // email is registered
+            let _ = mailer.send(&email, "Reset your password", &body).await;
+        }
+
+        Ok(PasswordResetRequest { success: true })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::Request;
+    use sqlx::postgres::PgPoolOptions;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::graphql::schema_builder;
+    use crate::mailer::testing::RecordingMailer;
+    use crate::mailer::{LogMailer, Mailer};
+    use crate::routes::AuthToken;
+    use crate::services::Services;
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn mails_the_reset_token() {
+        let config = Config::testing(&std::env::var("DATABASE_URL").unwrap());
+        let database = Arc::new(Database {
+            conn_pool: PgPoolOptions::new()
+                .connect(&config.database_url)
+                .await
+                .unwrap(),
+        });
+        let services = Arc::new(Services::new(&config, Arc::clone(&database)));
+        let execute = |mailer: Arc<dyn Mailer>, query: String| {
+            let schema = schema_builder(&config.graphql)
+                .data(Arc::clone(&services))
+                .data(mailer)
+                .finish();
+
+            async move {
+                let response = schema
+                    .execute(Request::new(query).data(AuthToken::empty()))
+                    .await;
+
+                assert!(response.errors.is_empty(), "{:?}", response.errors);
+            }
+        };
+        let username = format!("reset{}", &Uuid::new_v4().to_simple().to_string()[..16]);
+        let email = format!("{username}@nexus.dev");
+
+        execute(
+            Arc::new(LogMailer),
+            format!(
+                r#"mutation {{
+                    accountRegister(input: {{
+                        name: "Reset", lastName: "Test", email: "{email}",
+                        username: "{username}", password: "Correct horse battery staple 1",
+                        birthdate: "1990-01-01T00:00:00Z", gender: CUSTOM, pronoun: THEY
+                    }}) {{ user {{ username }} }}
+                }}"#
+            ),
+        )
+        .await;
+
+        let mailer = Arc::new(RecordingMailer::default());
+        let request = |email: &str| {
+            format!(r#"mutation {{ passwordResetRequest(email: "{email}") {{ success }} }}"#)
+        };
+
+        execute(mailer.clone(), request("nobody@nexus.dev")).await;
+        execute(mailer.clone(), request(&email)).await;
+
+        let sent = mailer.sent();
+
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, email);
+        assert_eq!(sent[0].subject, "Reset your password");
+
+        let token = sent[0]
+            .body
+            .lines()
+            .find(|line| line.split('.').count() == 3)
+            .unwrap();
+
+        services
+            .auth
+            .confirm_password_reset(String::from(token), String::from("Another passw0rd"))
+            .await
+            .unwrap();
+
+        // The reset moved the registered password into the history
+        for query in [
+            "DELETE FROM password_history WHERE user_id IN (SELECT id FROM users WHERE username = $1)",
+            "DELETE FROM audit_log WHERE target_id IN (SELECT id FROM users WHERE username = $1)",
+            "DELETE FROM users WHERE username = $1",
+        ] {
+            sqlx::query(query)
+                .bind(&username)
+                .execute(&database.conn_pool)
+                .await
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use async_graphql::{Context, Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::modules::auth::Tokens;
+use crate::services::Services;
+
+#[derive(Debug, Deserialize, Serialize, SimpleObject)]
+pub struct RefreshToken {
+    tokens: Option<Tokens>,
+    error: Option<RefreshTokenError>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct RefreshTokenError {
+    field: Option<String>,
+    message: Option<String>,
+    code: RefreshTokenErrorCode,
+}
+
+impl TryFrom<Error> for RefreshTokenError {
+    type Error = Error;
+
+    fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
+        match value.code {
+            ErrorCode::InvalidJsonWebToken => Ok(RefreshTokenError {
+                field: Some(String::from("refreshToken")),
+                message: None,
+                code: RefreshTokenErrorCode::InvalidToken,
+            }),
+            ErrorCode::ExpiredJsonWebToken => Ok(RefreshTokenError {
+                field: Some(String::from("refreshToken")),
+                message: None,
+                code: RefreshTokenErrorCode::ExpiredToken,
+            }),
+            _ => Err(value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
+pub enum RefreshTokenErrorCode {
+    InvalidToken,
+    ExpiredToken,
+}
+
+impl RefreshToken {
+    pub async fn exec(ctx: &Context<'_>, refresh_token: String) -> Result<RefreshToken> {
+        let services = ctx.data_unchecked::<Arc<Services>>();
+
+        match services.auth.refresh_token(refresh_token).await {
+            Ok(tokens) => Ok(RefreshToken {
+                tokens: Some(tokens),
+                error: None,
+            }),
+            Err(err) => {
+                let refresh_token_error = RefreshTokenError::try_from(err)?;
+
+                Ok(RefreshToken {
+                    tokens: None,
+                    error: Some(refresh_token_error),
+                })
+            }
+        }
+    }
+}
@@ -2,13 +2,18 @@ use async_graphql::{Context, Enum, SimpleObject};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
+use crate::config::AuthCookieConfig;
 use crate::error::{Error, ErrorCode, Result};
 use crate::modules::auth::Tokens;
+use crate::modules::user::User;
+use crate::routes::ClientIp;
 use crate::services::Services;
 
 #[derive(Debug, Deserialize, Serialize, SimpleObject)]
 pub struct TokenCreate {
     tokens: Option<Tokens>,
+    /// User the tokens were issued for, saving a `me` query after login
+    user: Option<User>,
     error: Option<TokenCreateError>,
 }
 
@@ -17,6 +22,9 @@ pub struct TokenCreateError {
     field: Option<String>,
     message: Option<String>,
     code: TokenCreateErrorCode,
+    /// Seconds to wait before retrying, set when `RATE_LIMITED` or
+    /// `ACCOUNT_LOCKED`
+    retry_after_secs: Option<u64>,
 }
 
 impl TryFrom<Error> for TokenCreateError {
@@ -24,10 +32,23 @@ impl TryFrom<Error> for TokenCreateError {
 
     fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
         match value.code {
+            ErrorCode::AccountLocked => Ok(TokenCreateError {
+                field: None,
+                message: value.message,
+                code: TokenCreateErrorCode::AccountLocked,
+                retry_after_secs: value.retry_after_secs,
+            }),
             ErrorCode::InvalidCredentials => Ok(TokenCreateError {
                 field: None,
                 message: None,
                 code: TokenCreateErrorCode::InvalidCredentials,
+                retry_after_secs: None,
+            }),
+            ErrorCode::RateLimited => Ok(TokenCreateError {
+                field: None,
+                message: value.message,
+                code: TokenCreateErrorCode::RateLimited,
+                retry_after_secs: value.retry_after_secs,
             }),
             _ => Err(value),
         }
@@ -36,7 +57,9 @@ impl TryFrom<Error> for TokenCreateError {
 
 #[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
 pub enum TokenCreateErrorCode {
+    AccountLocked,
     InvalidCredentials,
+    RateLimited,
 }
 
 impl TokenCreate {
@@ -44,22 +67,136 @@ impl TokenCreate {
         ctx: &Context<'_>,
         username: String,
         password: String,
+        set_cookie: bool,
     ) -> Result<TokenCreate> {
+        let auth_cookie = ctx
+            .data_opt::<AuthCookieConfig>()
+            .filter(|auth_cookie| auth_cookie.name.is_some());
+
+        if set_cookie && auth_cookie.is_none() {
+            return Err(Error::new(
+                "setCookie",
+                "Cookie authentication is not enabled on this server",
+                ErrorCode::ValidationError,
+            ));
+        }
+
         let services = ctx.data_unchecked::<Arc<Services>>();
+        let client_ip = ctx.data_opt::<ClientIp>().and_then(|client_ip| client_ip.0);
 
-        match services.auth.create_token(username, password).await {
-            Ok(tokens) => Ok(TokenCreate {
-                tokens: Some(tokens),
-                error: None,
-            }),
+        match services
+            .auth
+            .create_token(username, password, client_ip)
+            .await
+        {
+            Ok(login) => {
+                if let Some(cookie) = auth_cookie
+                    .filter(|_| set_cookie)
+                    .and_then(|auth_cookie| auth_cookie.cookie(&login.tokens.access_token))
+                {
+                    ctx.append_http_header("Set-Cookie", cookie.to_string());
+                }
+
+                Ok(TokenCreate {
+                    tokens: Some(login.tokens),
+                    user: Some(login.user),
+                    error: None,
+                })
+            }
             Err(err) => {
                 let token_create_error = TokenCreateError::try_from(err)?;
 
                 Ok(TokenCreate {
                     tokens: None,
+                    user: None,
                     error: Some(token_create_error),
                 })
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::Request;
+    use sqlx::postgres::PgPoolOptions;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use crate::config::{Config, GraphQLConfig};
+    use crate::database::Database;
+    use crate::graphql::schema_builder;
+    use crate::mailer::{LogMailer, Mailer};
+    use crate::routes::AuthToken;
+    use crate::services::Services;
+
+    #[rocket::async_test]
+    async fn rejects_cookies_unless_enabled() {
+        let schema = schema_builder(&GraphQLConfig::default()).finish();
+        let request = Request::new(
+            r#"mutation { tokenCreate(username: "esteban", password: "secret", setCookie: true) { error { code } } }"#,
+        )
+        .data(AuthToken::empty());
+        let response = schema.execute(request).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(error["extensions"]["code"], "VALIDATION_ERROR");
+        assert_eq!(error["extensions"]["field"], "setCookie");
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn returns_the_tokens_along_with_the_user() {
+        let config = Config::testing(&std::env::var("DATABASE_URL").unwrap());
+        let database = Arc::new(Database {
+            conn_pool: PgPoolOptions::new()
+                .connect(&config.database_url)
+                .await
+                .unwrap(),
+        });
+        let services = Arc::new(Services::new(&config, Arc::clone(&database)));
+        let schema = schema_builder(&config.graphql)
+            .data(services)
+            .data(Arc::new(LogMailer) as Arc<dyn Mailer>)
+            .finish();
+        let execute = |query: String| {
+            let request = Request::new(query).data(AuthToken::empty());
+
+            async {
+                let response = schema.execute(request).await;
+
+                assert!(response.errors.is_empty(), "{:?}", response.errors);
+                response.data.into_json().unwrap()
+            }
+        };
+        let username = format!("login{}", &Uuid::new_v4().to_simple().to_string()[..16]);
+
+        execute(format!(
+            r#"mutation {{
+                accountRegister(input: {{
+                    name: "Login", lastName: "Test", email: "{username}@nexus.dev",
+                    username: "{username}", password: "Correct horse battery staple 1",
+                    birthdate: "1990-01-01T00:00:00Z", gender: CUSTOM, pronoun: THEY
+                }}) {{ user {{ username }} }}
+            }}"#
+        ))
+        .await;
+
+        let data = execute(format!(
+            r#"mutation {{
+                tokenCreate(username: "{username}", password: "Correct horse battery staple 1") {{
+                    tokens {{ accessToken refreshToken }}
+                    user {{ username }}
+                    error {{ code }}
+                }}
+            }}"#
+        ))
+        .await;
+        let token_create = &data["tokenCreate"];
+
+        assert_eq!(token_create["error"], serde_json::Value::Null);
+        assert!(token_create["tokens"]["accessToken"].is_string());
+        assert!(token_create["tokens"]["refreshToken"].is_string());
+        assert_eq!(token_create["user"]["username"], username);
+    }
+}
@@ -0,0 +1,75 @@
+use async_graphql::{Context, Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::routes::AuthToken;
+use crate::services::Services;
+
+/// Revokes the access token used to authenticate the request
+#[derive(Debug, Deserialize, Serialize, SimpleObject)]
+pub struct TokenRevoke {
+    success: bool,
+    error: Option<TokenRevokeError>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct TokenRevokeError {
+    field: Option<String>,
+    message: Option<String>,
+    code: TokenRevokeErrorCode,
+}
+
+impl TryFrom<Error> for TokenRevokeError {
+    type Error = Error;
+
+    fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
+        match value.code {
+            ErrorCode::InvalidJsonWebToken => Ok(TokenRevokeError {
+                field: None,
+                message: None,
+                code: TokenRevokeErrorCode::InvalidToken,
+            }),
+            ErrorCode::ExpiredJsonWebToken => Ok(TokenRevokeError {
+                field: None,
+                message: None,
+                code: TokenRevokeErrorCode::ExpiredToken,
+            }),
+            ErrorCode::Conflict => Ok(TokenRevokeError {
+                field: None,
+                message: value.message,
+                code: TokenRevokeErrorCode::AlreadyRevoked,
+            }),
+            _ => Err(value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
+pub enum TokenRevokeErrorCode {
+    InvalidToken,
+    ExpiredToken,
+    AlreadyRevoked,
+}
+
+impl TokenRevoke {
+    pub async fn exec(ctx: &Context<'_>) -> Result<TokenRevoke> {
+        let token = ctx.data_unchecked::<AuthToken>().token()?;
+        let services = ctx.data_unchecked::<Arc<Services>>();
+
+        match services.auth.revoke_token(token).await {
+            Ok(()) => Ok(TokenRevoke {
+                success: true,
+                error: None,
+            }),
+            Err(err) => {
+                let token_revoke_error = TokenRevokeError::try_from(err)?;
+
+                Ok(TokenRevoke {
+                    success: false,
+                    error: Some(token_revoke_error),
+                })
+            }
+        }
+    }
+}
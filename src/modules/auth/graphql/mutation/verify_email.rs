@@ -0,0 +1,73 @@
+use async_graphql::{Context, Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::modules::user::User;
+use crate::services::Services;
+
+#[derive(Debug, Deserialize, Serialize, SimpleObject)]
+pub struct VerifyEmail {
+    user: Option<User>,
+    error: Option<VerifyEmailError>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct VerifyEmailError {
+    field: Option<String>,
+    message: Option<String>,
+    code: VerifyEmailErrorCode,
+}
+
+impl TryFrom<Error> for VerifyEmailError {
+    type Error = Error;
+
+    fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
+        match value.code {
+            ErrorCode::InvalidJsonWebToken => Ok(VerifyEmailError {
+                field: Some(String::from("token")),
+                message: None,
+                code: VerifyEmailErrorCode::InvalidToken,
+            }),
+            ErrorCode::ExpiredJsonWebToken => Ok(VerifyEmailError {
+                field: Some(String::from("token")),
+                message: None,
+                code: VerifyEmailErrorCode::ExpiredToken,
+            }),
+            ErrorCode::Conflict => Ok(VerifyEmailError {
+                field: None,
+                message: value.message,
+                code: VerifyEmailErrorCode::AlreadyVerified,
+            }),
+            _ => Err(value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
+pub enum VerifyEmailErrorCode {
+    InvalidToken,
+    ExpiredToken,
+    AlreadyVerified,
+}
+
+impl VerifyEmail {
+    pub async fn exec(ctx: &Context<'_>, token: String) -> Result<VerifyEmail> {
+        let services = ctx.data_unchecked::<Arc<Services>>();
+
+        match services.auth.verify_email(token).await {
+            Ok(user) => Ok(VerifyEmail {
+                user: Some(user),
+                error: None,
+            }),
+            Err(err) => {
+                let verify_email_error = VerifyEmailError::try_from(err)?;
+
+                Ok(VerifyEmail {
+                    user: None,
+                    error: Some(verify_email_error),
+                })
+            }
+        }
+    }
+}
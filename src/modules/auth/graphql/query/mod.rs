@@ -0,0 +1,32 @@
+pub mod token_verify;
+
+use async_graphql::{Context, Object};
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::graphql::guards::{current_user, SessionGuard};
+use crate::modules::auth::ApiToken;
+use crate::services::Services;
+
+use self::token_verify::TokenVerify;
+
+#[derive(Default)]
+pub struct AuthQuery;
+
+#[Object]
+impl AuthQuery {
+    #[graphql(name = "tokenVerify")]
+    async fn token_verify(&self, ctx: &Context<'_>, token: String) -> Result<TokenVerify> {
+        TokenVerify::exec(ctx, token).await
+    }
+
+    /// Lists the caller's API tokens, revoked ones included. Signed tokens
+    /// are only returned when created.
+    #[graphql(name = "apiTokenList", guard = "SessionGuard")]
+    async fn api_token_list(&self, ctx: &Context<'_>) -> Result<Vec<ApiToken>> {
+        let services = ctx.data_unchecked::<Arc<Services>>();
+        let caller = current_user(ctx).await?;
+
+        services.auth.list_api_tokens(&caller).await
+    }
+}
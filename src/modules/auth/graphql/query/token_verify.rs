@@ -0,0 +1,94 @@
+use async_graphql::{Context, Enum, SimpleObject, ID};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::graphql::relay::GlobalId;
+use crate::services::Services;
+
+/// Checks whether an access token is still accepted, without failing the
+/// operation when it isn't
+#[derive(Debug, Deserialize, Serialize, SimpleObject)]
+pub struct TokenVerify {
+    valid: bool,
+    expires_at: Option<DateTime<Utc>>,
+    user_id: Option<ID>,
+    reason: Option<TokenVerifyReason>,
+}
+
+/// Why a token is not valid, named after the corresponding `ErrorCode`
+#[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
+pub enum TokenVerifyReason {
+    #[graphql(name = "INVALID_JWT")]
+    Invalid,
+    #[graphql(name = "EXPIRED_JWT")]
+    Expired,
+    #[graphql(name = "IMMATURE_JWT")]
+    Immature,
+}
+
+impl TryFrom<Error> for TokenVerifyReason {
+    type Error = Error;
+
+    fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
+        match value.code {
+            ErrorCode::InvalidJsonWebToken => Ok(TokenVerifyReason::Invalid),
+            ErrorCode::ExpiredJsonWebToken => Ok(TokenVerifyReason::Expired),
+            ErrorCode::ImmatureJsonWebToken => Ok(TokenVerifyReason::Immature),
+            _ => Err(value),
+        }
+    }
+}
+
+impl TokenVerify {
+    pub async fn exec(ctx: &Context<'_>, token: String) -> Result<TokenVerify> {
+        let services = ctx.data_unchecked::<Arc<Services>>();
+
+        match services.auth.verify_token(&token).await {
+            Ok(authenticated) => Ok(TokenVerify {
+                valid: true,
+                expires_at: Some(authenticated.expires_at),
+                user_id: Some(GlobalId::new("User", authenticated.user.id).encode()),
+                reason: None,
+            }),
+            Err(err) => {
+                let reason = TokenVerifyReason::try_from(err)?;
+
+                Ok(TokenVerify {
+                    valid: false,
+                    expires_at: None,
+                    user_id: None,
+                    reason: Some(reason),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{InputType, Name, Value};
+
+    use crate::error::{Error, ErrorCode};
+
+    use super::TokenVerifyReason;
+
+    #[test]
+    fn reasons_match_error_codes() {
+        for code in [
+            ErrorCode::InvalidJsonWebToken,
+            ErrorCode::ExpiredJsonWebToken,
+            ErrorCode::ImmatureJsonWebToken,
+        ] {
+            let reason = TokenVerifyReason::try_from(Error::code(code)).unwrap();
+
+            assert_eq!(reason.to_value(), Value::Enum(Name::new(code.to_string())));
+        }
+    }
+
+    #[test]
+    fn other_errors_are_not_reasons() {
+        assert!(TokenVerifyReason::try_from(Error::code(ErrorCode::ServerError)).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use async_graphql::futures_util::Stream;
+use async_graphql::{Context, Subscription};
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::graphql::guards::current_user;
+use crate::modules::auth::SessionEvent;
+use crate::services::Services;
+
+#[derive(Default)]
+pub struct AuthSubscription;
+
+#[Subscription]
+impl AuthSubscription {
+    /// Streams logins, logouts and password changes of the authenticated user
+    #[graphql(name = "sessionEvents")]
+    async fn session_events(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = SessionEvent>> {
+        let user = current_user(ctx).await?;
+        let services = ctx.data_unchecked::<Arc<Services>>();
+
+        Ok(services.auth.session_events(&user))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::futures_util::{Stream, StreamExt};
+    use async_graphql::{Request, Response};
+    use rocket::tokio::time::timeout;
+    use sqlx::postgres::PgPoolOptions;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::graphql::schema_builder;
+    use crate::mailer::{LogMailer, Mailer};
+    use crate::routes::AuthToken;
+    use crate::services::Services;
+
+    async fn next_event<S: Stream<Item = Response> + Unpin>(events: &mut S) -> serde_json::Value {
+        let response = timeout(Duration::from_secs(5), events.next())
+            .await
+            .expect("no session event was received")
+            .unwrap();
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        response.data.into_json().unwrap()["sessionEvents"].clone()
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn streams_logins_and_logouts_of_the_user() {
+        let config = Config::testing(&std::env::var("DATABASE_URL").unwrap());
+        let database = Arc::new(Database {
+            conn_pool: PgPoolOptions::new()
+                .connect(&config.database_url)
+                .await
+                .unwrap(),
+        });
+        let services = Arc::new(Services::new(&config, Arc::clone(&database)));
+        let schema = schema_builder(&config.graphql)
+            .data(services)
+            .data(Arc::new(LogMailer) as Arc<dyn Mailer>)
+            .finish();
+        let execute = |query: String, auth: AuthToken| {
+            let request = Request::new(query).data(auth);
+
+            async {
+                let response = schema.execute(request).await;
+
+                assert!(response.errors.is_empty(), "{:?}", response.errors);
+                response.data.into_json().unwrap()
+            }
+        };
+        let username = format!("events{}", &Uuid::new_v4().to_simple().to_string()[..16]);
+        let token_create = format!(
+            r#"mutation {{
+                tokenCreate(username: "{username}", password: "Correct horse battery staple 1") {{
+                    tokens {{ accessToken }}
+                }}
+            }}"#
+        );
+        let data = execute(
+            format!(
+                r#"mutation {{
+                    accountRegister(input: {{
+                        name: "Events", lastName: "Test", email: "{username}@nexus.dev",
+                        username: "{username}", password: "Correct horse battery staple 1",
+                        birthdate: "1990-01-01T00:00:00Z", gender: CUSTOM, pronoun: THEY
+                    }}) {{ user {{ uuid }} }}
+                }}"#
+            ),
+            AuthToken::empty(),
+        )
+        .await;
+        let user_id = data["accountRegister"]["user"]["uuid"].clone();
+        let data = execute(token_create.clone(), AuthToken::empty()).await;
+        let token = data["tokenCreate"]["tokens"]["accessToken"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let mut events = schema.execute_stream(
+            Request::new("subscription { sessionEvents { userId kind } }")
+                .data(AuthToken::new(&token)),
+        );
+        // Resolves the subscription so it listens before events are published
+        assert!(timeout(Duration::from_millis(100), events.next())
+            .await
+            .is_err());
+
+        execute(token_create, AuthToken::empty()).await;
+
+        let event = next_event(&mut events).await;
+
+        assert_eq!(event["userId"], user_id);
+        assert_eq!(event["kind"], "LOGIN");
+
+        let data = execute(
+            String::from("mutation { tokenRevoke { success } }"),
+            AuthToken::new(&token),
+        )
+        .await;
+
+        assert_eq!(data["tokenRevoke"]["success"], true);
+
+        let event = next_event(&mut events).await;
+
+        assert_eq!(event["userId"], user_id);
+        assert_eq!(event["kind"], "LOGOUT");
+
+        let user_id = Uuid::parse_str(user_id.as_str().unwrap()).unwrap();
+
+        sqlx::query("DELETE FROM audit_log WHERE actor_id = $1 OR target_id = $1")
+            .bind(user_id)
+            .execute(&database.conn_pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM refresh_tokens WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&database.conn_pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&database.conn_pool)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,431 @@
+use chrono::{Duration, Utc};
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::config::{JwtConfig, DEFAULT_JWT_KEY_ID};
+use crate::error::{Error, ErrorCode, Result};
+use crate::modules::auth::ApiScope;
+use crate::modules::user::{Role, User, DEFAULT_ORGANIZATION_ID};
+
+/// Purpose of a signed token, prevents tokens issued for a flow from being
+/// accepted by another, e.g. using an email verification token as access
+/// token.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenType {
+    #[default]
+    Access,
+    /// Long-lived token restricted to the `scp` scopes
+    Api,
+    EmailVerification,
+    PasswordReset,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Claims {
+    pub sub: String,
+    pub iat: usize,
+    pub exp: usize,
+    pub iss: String,
+    pub aud: String,
+    /// Unique token identifier, used to revoke the token before it expires
+    pub jti: Uuid,
+    /// Id of the user the token was issued for. Usernames can change and be
+    /// taken by another user afterwards, tokens must never identify users by
+    /// them.
+    pub uid: Uuid,
+    #[serde(default)]
+    pub role: Role,
+    /// Organization of the user, tokens issued before organizations existed
+    /// belong to the default one
+    #[serde(default = "default_organization_id")]
+    pub org: Uuid,
+    #[serde(default)]
+    pub token_type: TokenType,
+    /// Email address being verified by an `EmailVerification` token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// User's `token_version` when a `PasswordReset` token was issued
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ver: Option<i32>,
+    /// Scopes granted to an `Api` token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scp: Option<Vec<ApiScope>>,
+}
+
+fn default_organization_id() -> Uuid {
+    DEFAULT_ORGANIZATION_ID
+}
+
+/// Signs and validates JSON Web Tokens
+pub struct Jwt {
+    algorithm: Algorithm,
+    current_key_id: String,
+    encoding_key: EncodingKey,
+    decoding_keys: HashMap<String, DecodingKey>,
+    expiry: Duration,
+    issuer: String,
+    audience: String,
+}
+
+impl Jwt {
+    pub fn new(config: &JwtConfig) -> Self {
+        let current_key = config
+            .keys
+            .iter()
+            .find(|key| key.id == config.current_key_id)
+            .expect("Current JWT key is missing from the keyring");
+
+        Self {
+            algorithm: config.algorithm,
+            current_key_id: config.current_key_id.clone(),
+            encoding_key: EncodingKey::from_secret(current_key.secret.as_bytes()),
+            decoding_keys: config
+                .keys
+                .iter()
+                .map(|key| {
+                    (
+                        key.id.clone(),
+                        DecodingKey::from_secret(key.secret.as_bytes()),
+                    )
+                })
+                .collect(),
+            expiry: Duration::from_std(config.expiry).expect("JWT expiry out of range"),
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+        }
+    }
+
+    /// Builds the claims for a token identifying the provided user, valid for
+    /// the configured expiry
+    pub fn access_claims(&self, user: &User) -> Claims {
+        self.claims(user, TokenType::Access, self.expiry)
+    }
+
+    /// Builds the claims for a token of the provided type identifying the
+    /// provided user, valid for `ttl`
+    pub fn claims(&self, user: &User, token_type: TokenType, ttl: Duration) -> Claims {
+        let now = Utc::now();
+
+        Claims {
+            sub: String::from("nexus"),
+            iat: now.timestamp() as usize,
+            exp: (now + ttl).timestamp() as usize,
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            jti: Uuid::new_v4(),
+            uid: user.id,
+            role: user.role,
+            org: user.organization_id,
+            token_type,
+            email: None,
+            ver: None,
+            scp: None,
+        }
+    }
+
+    /// Signs the provided claims with the current key, naming it in the
+    /// `kid` header
+    pub fn sign(&self, claims: &Claims) -> Result<String> {
+        let header = Header {
+            kid: Some(self.current_key_id.clone()),
+            ..Header::new(self.algorithm)
+        };
+        let token = encode(&header, claims, &self.encoding_key)?;
+
+        Ok(token)
+    }
+
+    /// Decodes and validates the provided token with the key named by its
+    /// `kid` header, rejecting tokens not issued for the `expected` purpose.
+    /// Tokens without `kid` were issued before keys were named and are
+    /// validated with the `DEFAULT_JWT_KEY_ID` key.
+    pub fn decode(&self, token: &str, expected: TokenType) -> Result<Claims> {
+        self.decode_as(token, &[expected])
+    }
+
+    /// Decodes and validates the provided token as `decode` does, accepting
+    /// tokens issued for any of the `expected` purposes
+    pub fn decode_as(&self, token: &str, expected: &[TokenType]) -> Result<Claims> {
+        let header = decode_header(token)?;
+        let key_id = header.kid.as_deref().unwrap_or(DEFAULT_JWT_KEY_ID);
+        let decoding_key = self
+            .decoding_keys
+            .get(key_id)
+            .ok_or_else(|| Error::code(ErrorCode::InvalidJsonWebToken))?;
+        let mut validation = Validation::new(self.algorithm);
+
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+
+        let token = decode::<Claims>(token, decoding_key, &validation)?;
+
+        if !expected.contains(&token.claims.token_type) {
+            return Err(Error::code(ErrorCode::InvalidJsonWebToken));
+        }
+
+        Ok(token.claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    use crate::config::{JwtConfig, JwtKey, DEFAULT_JWT_AUDIENCE, DEFAULT_JWT_ISSUER};
+    use crate::error::ErrorCode;
+    use crate::modules::auth::ApiScope;
+    use crate::modules::user::{Role, DEFAULT_ORGANIZATION_ID};
+
+    use super::{Claims, Jwt, TokenType};
+
+    fn claims(token_type: TokenType) -> Claims {
+        let now = Utc::now();
+
+        Claims {
+            sub: String::from("nexus"),
+            iat: now.timestamp() as usize,
+            exp: (now + Duration::hours(1)).timestamp() as usize,
+            iss: String::from(DEFAULT_JWT_ISSUER),
+            aud: String::from(DEFAULT_JWT_AUDIENCE),
+            jti: Uuid::new_v4(),
+            uid: Uuid::nil(),
+            role: Role::User,
+            org: DEFAULT_ORGANIZATION_ID,
+            token_type,
+            email: None,
+            ver: None,
+            scp: None,
+        }
+    }
+
+    #[test]
+    fn decodes_signed_token() {
+        let jwt = Jwt::new(&JwtConfig::new("secret"));
+        let claims = claims(TokenType::Access);
+        let token = jwt.sign(&claims).unwrap();
+        let decoded = jwt.decode(&token, TokenType::Access).unwrap();
+
+        assert_eq!(decoded.uid, Uuid::nil());
+        assert_eq!(decoded.jti, claims.jti);
+    }
+
+    #[test]
+    fn rejects_token_without_jti() {
+        #[derive(serde::Serialize)]
+        struct LegacyClaims {
+            sub: String,
+            iat: usize,
+            exp: usize,
+            iss: String,
+            aud: String,
+            uid: Uuid,
+        }
+
+        let jwt = Jwt::new(&JwtConfig::new("secret"));
+        let claims = claims(TokenType::Access);
+        let legacy = LegacyClaims {
+            sub: claims.sub,
+            iat: claims.iat,
+            exp: claims.exp,
+            iss: claims.iss,
+            aud: claims.aud,
+            uid: claims.uid,
+        };
+        let token = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &legacy,
+            &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        let error = jwt.decode(&token, TokenType::Access).err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::InvalidJsonWebToken);
+    }
+
+    #[test]
+    fn token_without_organization_belongs_to_default_one() {
+        #[derive(serde::Serialize)]
+        struct LegacyClaims {
+            sub: String,
+            iat: usize,
+            exp: usize,
+            iss: String,
+            aud: String,
+            jti: Uuid,
+            uid: Uuid,
+        }
+
+        let jwt = Jwt::new(&JwtConfig::new("secret"));
+        let claims = claims(TokenType::Access);
+        let legacy = LegacyClaims {
+            sub: claims.sub,
+            iat: claims.iat,
+            exp: claims.exp,
+            iss: claims.iss,
+            aud: claims.aud,
+            jti: claims.jti,
+            uid: claims.uid,
+        };
+        let token = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &legacy,
+            &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        let decoded = jwt.decode(&token, TokenType::Access).unwrap();
+
+        assert_eq!(decoded.org, DEFAULT_ORGANIZATION_ID);
+    }
+
+    #[test]
+    fn rejects_expired_token() {
+        let jwt = Jwt::new(&JwtConfig::new("secret"));
+        let mut expired = claims(TokenType::PasswordReset);
+
+        expired.exp = (Utc::now() - Duration::hours(1)).timestamp() as usize;
+
+        let token = jwt.sign(&expired).unwrap();
+        let error = jwt.decode(&token, TokenType::PasswordReset).err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::ExpiredJsonWebToken);
+    }
+
+    #[test]
+    fn rejects_email_verification_token_as_access_token() {
+        let jwt = Jwt::new(&JwtConfig::new("secret"));
+        let token = jwt.sign(&claims(TokenType::EmailVerification)).unwrap();
+        let error = jwt.decode(&token, TokenType::Access).err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::InvalidJsonWebToken);
+    }
+
+    #[test]
+    fn api_tokens_carry_their_scopes() {
+        let jwt = Jwt::new(&JwtConfig::new("secret"));
+        let token = jwt
+            .sign(&Claims {
+                scp: Some(vec![ApiScope::UsersRead]),
+                ..claims(TokenType::Api)
+            })
+            .unwrap();
+        let decoded = jwt
+            .decode_as(&token, &[TokenType::Access, TokenType::Api])
+            .unwrap();
+
+        assert_eq!(decoded.token_type, TokenType::Api);
+        assert_eq!(decoded.scp, Some(vec![ApiScope::UsersRead]));
+        assert_eq!(
+            jwt.decode(&token, TokenType::Access).err().unwrap().code,
+            ErrorCode::InvalidJsonWebToken
+        );
+    }
+
+    #[test]
+    fn rejects_token_for_another_audience() {
+        let staging = Jwt::new(&JwtConfig {
+            audience: String::from("nexus-staging"),
+            ..JwtConfig::new("secret")
+        });
+        let production = Jwt::new(&JwtConfig::new("secret"));
+        let token = staging
+            .sign(&Claims {
+                aud: String::from("nexus-staging"),
+                ..claims(TokenType::Access)
+            })
+            .unwrap();
+        let error = production.decode(&token, TokenType::Access).err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::InvalidJsonWebToken);
+    }
+
+    #[test]
+    fn rejects_token_from_another_issuer() {
+        let jwt = Jwt::new(&JwtConfig::new("secret"));
+        let token = jwt
+            .sign(&Claims {
+                iss: String::from("someone-else"),
+                ..claims(TokenType::Access)
+            })
+            .unwrap();
+        let error = jwt.decode(&token, TokenType::Access).err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::InvalidJsonWebToken);
+    }
+
+    fn key(id: &str, secret: &str) -> JwtKey {
+        JwtKey {
+            id: id.to_string(),
+            secret: secret.to_string(),
+        }
+    }
+
+    #[test]
+    fn signs_with_current_key_id() {
+        let jwt = Jwt::new(&JwtConfig::with_keys(
+            vec![key("2022-01", "old"), key("2022-06", "new")],
+            "2022-06",
+        ));
+        let token = jwt.sign(&claims(TokenType::Access)).unwrap();
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+
+        assert_eq!(header.kid.as_deref(), Some("2022-06"));
+    }
+
+    #[test]
+    fn decodes_tokens_signed_with_retired_keys() {
+        let before = Jwt::new(&JwtConfig::with_keys(
+            vec![key("2022-01", "old")],
+            "2022-01",
+        ));
+        let after = Jwt::new(&JwtConfig::with_keys(
+            vec![key("2022-01", "old"), key("2022-06", "new")],
+            "2022-06",
+        ));
+        let token = before.sign(&claims(TokenType::Access)).unwrap();
+
+        assert!(after.decode(&token, TokenType::Access).is_ok());
+    }
+
+    #[test]
+    fn rejects_token_with_unknown_key_id() {
+        let removed = Jwt::new(&JwtConfig::with_keys(
+            vec![key("2022-01", "old")],
+            "2022-01",
+        ));
+        let jwt = Jwt::new(&JwtConfig::with_keys(
+            vec![key("2022-06", "new")],
+            "2022-06",
+        ));
+        let token = removed.sign(&claims(TokenType::Access)).unwrap();
+        let error = jwt.decode(&token, TokenType::Access).err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::InvalidJsonWebToken);
+    }
+
+    #[test]
+    fn rejects_token_with_another_algorithm() {
+        let hs512 = Jwt::new(&JwtConfig {
+            algorithm: jsonwebtoken::Algorithm::HS512,
+            ..JwtConfig::new("secret")
+        });
+        let jwt = Jwt::new(&JwtConfig::new("secret"));
+        let token = hs512.sign(&claims(TokenType::Access)).unwrap();
+        let error = jwt.decode(&token, TokenType::Access).err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::InvalidJsonWebToken);
+    }
+
+    #[test]
+    fn rejects_malformed_token() {
+        let jwt = Jwt::new(&JwtConfig::new("secret"));
+        let error = jwt.decode("not-a-token", TokenType::Access).err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::InvalidJsonWebToken);
+    }
+}
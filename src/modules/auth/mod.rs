@@ -1,7 +1,14 @@
 mod entity;
+mod events;
+mod jwt;
+mod repository;
 mod service;
+mod throttle;
 
 pub mod graphql;
 
 pub use entity::*;
+pub use events::*;
+pub use repository::*;
 pub use service::*;
+pub use throttle::*;
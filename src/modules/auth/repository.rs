@@ -0,0 +1,573 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::database::Database;
+use crate::error::Result;
+use crate::modules::audit::{insert_audit_entry, NewAuditEntry};
+
+use super::entity::{ApiScope, ApiToken, PurgedTokens, RefreshToken};
+
+#[derive(Debug, Deserialize, FromRow, Serialize)]
+pub struct RefreshTokensTableRow {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub family_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<RefreshTokensTableRow> for RefreshToken {
+    fn from(dto: RefreshTokensTableRow) -> Self {
+        Self {
+            id: dto.id,
+            user_id: dto.user_id,
+            family_id: dto.family_id,
+            token_hash: dto.token_hash,
+            expires_at: dto.expires_at,
+            revoked_at: dto.revoked_at,
+            created_at: dto.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InsertRefreshTokenTableRow {
+    pub user_id: Uuid,
+    pub family_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, FromRow, Serialize)]
+pub struct ApiTokensTableRow {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<ApiTokensTableRow> for ApiToken {
+    fn from(dto: ApiTokensTableRow) -> Self {
+        Self {
+            id: dto.id,
+            user_id: dto.user_id,
+            name: dto.name,
+            // Scopes are only written from `ApiScope`, unknown ones were
+            // removed since and are no longer granted
+            scopes: dto
+                .scopes
+                .iter()
+                .filter_map(|scope| scope.parse().ok())
+                .collect(),
+            expires_at: dto.expires_at,
+            revoked_at: dto.revoked_at,
+            created_at: dto.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InsertApiTokenTableRow {
+    pub user_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Inserts a refresh token through the provided connection, allows inserting
+/// it within the transaction of a larger operation
+async fn insert_refresh_token_row(
+    conn: &mut PgConnection,
+    dto: &InsertRefreshTokenTableRow,
+) -> Result<RefreshTokensTableRow> {
+    let result: RefreshTokensTableRow = sqlx::query_as(
+        r#"
+        INSERT INTO refresh_tokens (
+            user_id,
+            family_id,
+            token_hash,
+            expires_at
+        ) VALUES (
+            $1,
+            $2,
+            $3,
+            $4
+        ) RETURNING *"#,
+    )
+    .bind(dto.user_id)
+    .bind(dto.family_id)
+    .bind(&dto.token_hash)
+    .bind(dto.expires_at)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(result)
+}
+
+pub struct AuthRepository {
+    database: Arc<Database>,
+}
+
+impl AuthRepository {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self { database }
+    }
+
+    pub async fn find_refresh_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>> {
+        let result: Option<RefreshTokensTableRow> =
+            sqlx::query_as("SELECT * FROM refresh_tokens WHERE token_hash = $1")
+                .bind(token_hash)
+                .fetch_optional(&self.database.conn_pool)
+                .await?;
+
+        Ok(result.map(RefreshToken::from))
+    }
+
+    /// Inserts the refresh token and records `audit` in a single
+    /// transaction, neither is stored if the other fails
+    pub async fn insert_refresh_token(
+        &self,
+        dto: InsertRefreshTokenTableRow,
+        audit: NewAuditEntry,
+    ) -> Result<RefreshToken> {
+        let result = self
+            .database
+            .with_transaction(|tx| {
+                Box::pin(async move {
+                    let result = insert_refresh_token_row(tx, &dto).await?;
+
+                    insert_audit_entry(tx, &audit).await?;
+
+                    Ok(result)
+                })
+            })
+            .await?;
+
+        Ok(RefreshToken::from(result))
+    }
+
+    /// Revokes the refresh token with the provided `id` and inserts its
+    /// replacement in a single transaction.
+    ///
+    /// Returns `None` if the token was already revoked, which means it has
+    /// been used before.
+    pub async fn rotate_refresh_token(
+        &self,
+        id: Uuid,
+        dto: InsertRefreshTokenTableRow,
+    ) -> Result<Option<RefreshToken>> {
+        let mut tx = self.database.conn_pool.begin().await?;
+        let revoked = sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP WHERE id = $1 AND revoked_at IS NULL",
+        )
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+
+        if revoked.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
+        let result = insert_refresh_token_row(&mut tx, &dto).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(RefreshToken::from(result)))
+    }
+
+    /// Revokes every refresh token sharing the provided `family_id`
+    pub async fn revoke_refresh_token_family(&self, family_id: Uuid) -> Result<()> {
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP WHERE family_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(family_id)
+        .execute(&self.database.conn_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Revokes every active refresh token issued to the provided user
+    pub async fn revoke_user_refresh_tokens(&self, user_id: Uuid) -> Result<()> {
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .execute(&self.database.conn_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Adds the access token identified by `jti` to the denylist until it
+    /// expires. Entries past their expiry are pruned on every insert, so the
+    /// denylist only holds tokens which would otherwise still be valid.
+    /// Returns `false` if the token was revoked already.
+    pub async fn revoke_token(&self, jti: Uuid, expires_at: DateTime<Utc>) -> Result<bool> {
+        let mut tx = self.database.conn_pool.begin().await?;
+
+        sqlx::query("DELETE FROM revoked_tokens WHERE expires_at < CURRENT_TIMESTAMP")
+            .execute(&mut tx)
+            .await?;
+        let inserted = sqlx::query(
+            "INSERT INTO revoked_tokens (jti, expires_at) VALUES ($1, $2) ON CONFLICT (jti) DO NOTHING",
+        )
+        .bind(jti)
+        .bind(expires_at)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+
+        Ok(inserted == 1)
+    }
+
+    /// Deletes refresh tokens past their expiry, whether revoked or not, and
+    /// denylist entries past the expiry of the token they revoked. Neither
+    /// would be accepted anymore.
+    pub async fn purge_expired_tokens(&self) -> Result<PurgedTokens> {
+        let refresh_tokens =
+            sqlx::query("DELETE FROM refresh_tokens WHERE expires_at < CURRENT_TIMESTAMP")
+                .execute(&self.database.conn_pool)
+                .await?
+                .rows_affected();
+        let revoked_tokens =
+            sqlx::query("DELETE FROM revoked_tokens WHERE expires_at < CURRENT_TIMESTAMP")
+                .execute(&self.database.conn_pool)
+                .await?
+                .rows_affected();
+
+        Ok(PurgedTokens {
+            refresh_tokens,
+            revoked_tokens,
+        })
+    }
+
+    /// Inserts the API token and records `audit` in a single transaction,
+    /// neither is stored if the other fails
+    pub async fn insert_api_token(
+        &self,
+        dto: InsertApiTokenTableRow,
+        audit: NewAuditEntry,
+    ) -> Result<ApiToken> {
+        let result: ApiTokensTableRow = self
+            .database
+            .with_transaction(|tx| {
+                Box::pin(async move {
+                    let scopes = dto
+                        .scopes
+                        .iter()
+                        .map(|scope| scope.as_str())
+                        .collect::<Vec<_>>();
+                    let result = sqlx::query_as(
+                        r#"
+                        INSERT INTO api_tokens (
+                            user_id,
+                            name,
+                            scopes,
+                            expires_at
+                        ) VALUES (
+                            $1,
+                            $2,
+                            $3,
+                            $4
+                        ) RETURNING *"#,
+                    )
+                    .bind(dto.user_id)
+                    .bind(&dto.name)
+                    .bind(&scopes)
+                    .bind(dto.expires_at)
+                    .fetch_one(&mut *tx)
+                    .await?;
+
+                    insert_audit_entry(tx, &audit).await?;
+
+                    Ok(result)
+                })
+            })
+            .await?;
+
+        Ok(ApiToken::from(result))
+    }
+
+    pub async fn find_api_token(&self, id: Uuid) -> Result<Option<ApiToken>> {
+        let result: Option<ApiTokensTableRow> =
+            sqlx::query_as("SELECT * FROM api_tokens WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&self.database.conn_pool)
+                .await?;
+
+        Ok(result.map(ApiToken::from))
+    }
+
+    /// Lists the API tokens of the provided user, revoked ones included,
+    /// newest first
+    pub async fn list_api_tokens(&self, user_id: Uuid) -> Result<Vec<ApiToken>> {
+        let result: Vec<ApiTokensTableRow> = sqlx::query_as(
+            "SELECT * FROM api_tokens WHERE user_id = $1 ORDER BY created_at DESC, id DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.database.conn_pool)
+        .await?;
+
+        Ok(result.into_iter().map(ApiToken::from).collect())
+    }
+
+    /// Revokes the API token with the provided `id` owned by `user_id` and
+    /// records `audit` in a single transaction.
+    ///
+    /// Returns `None` if the user owns no such token or it was already
+    /// revoked.
+    pub async fn revoke_api_token(
+        &self,
+        id: Uuid,
+        user_id: Uuid,
+        audit: NewAuditEntry,
+    ) -> Result<Option<ApiToken>> {
+        let result: Option<ApiTokensTableRow> = self
+            .database
+            .with_transaction(|tx| {
+                Box::pin(async move {
+                    let result = sqlx::query_as(
+                        r#"
+                        UPDATE api_tokens SET revoked_at = CURRENT_TIMESTAMP
+                        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+                        RETURNING *"#,
+                    )
+                    .bind(id)
+                    .bind(user_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+
+                    if result.is_some() {
+                        insert_audit_entry(tx, &audit).await?;
+                    }
+
+                    Ok(result)
+                })
+            })
+            .await?;
+
+        Ok(result.map(ApiToken::from))
+    }
+
+    pub async fn is_token_revoked(&self, jti: Uuid) -> Result<bool> {
+        let (revoked,): (bool,) =
+            sqlx::query_as("SELECT EXISTS (SELECT 1 FROM revoked_tokens WHERE jti = $1)")
+                .bind(jti)
+                .fetch_one(&self.database.conn_pool)
+                .await?;
+
+        Ok(revoked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use sqlx::postgres::PgPoolOptions;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use crate::database::Database;
+    use crate::modules::audit::{AuditAction, AuditContext, AuditOutcome};
+    use crate::modules::auth::ApiScope;
+
+    use super::{AuthRepository, InsertApiTokenTableRow};
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn purges_expired_tokens_only() {
+        let database_url = std::env::var("DATABASE_URL").unwrap();
+        let database = Arc::new(Database {
+            conn_pool: PgPoolOptions::new().connect(&database_url).await.unwrap(),
+        });
+        let pool = &database.conn_pool;
+        let repository = AuthRepository::new(Arc::clone(&database));
+        let marker = Uuid::new_v4().to_simple().to_string();
+        let (user_id,): (Uuid,) = sqlx::query_as(
+            r#"
+            INSERT INTO users (name, last_name, email, username, password_hash, birthdate, gender, pronoun)
+            VALUES ('Purge', 'Test', $1 || '@nexus.dev', $1, '', CURRENT_TIMESTAMP, 'custom', 'they')
+            RETURNING id"#,
+        )
+        .bind(&marker)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let expired_at = Utc::now() - Duration::hours(1);
+        let expires_at = Utc::now() + Duration::hours(1);
+        let expired_jti = Uuid::new_v4();
+        let valid_jti = Uuid::new_v4();
+
+        for (index, expires_at) in [expired_at, expires_at].into_iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(user_id)
+            .bind(Uuid::new_v4())
+            .bind(format!("{marker}{index}"))
+            .bind(expires_at)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+
+        for (jti, expires_at) in [(expired_jti, expired_at), (valid_jti, expires_at)] {
+            sqlx::query("INSERT INTO revoked_tokens (jti, expires_at) VALUES ($1, $2)")
+                .bind(jti)
+                .bind(expires_at)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+
+        let purged = repository.purge_expired_tokens().await.unwrap();
+        let (refresh_tokens,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM refresh_tokens WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_one(pool)
+                .await
+                .unwrap();
+
+        // Revoking a token also prunes the expired denylist entries, so tests
+        // revoking tokens concurrently may have purged this one already
+        assert!(purged.refresh_tokens >= 1);
+        assert_eq!(refresh_tokens, 1);
+        assert!(!repository.is_token_revoked(expired_jti).await.unwrap());
+        assert!(repository.is_token_revoked(valid_jti).await.unwrap());
+
+        sqlx::query("DELETE FROM refresh_tokens WHERE user_id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM revoked_tokens WHERE jti = $1")
+            .bind(valid_jti)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn reports_tokens_revoked_already() {
+        let database_url = std::env::var("DATABASE_URL").unwrap();
+        let database = Arc::new(Database {
+            conn_pool: PgPoolOptions::new().connect(&database_url).await.unwrap(),
+        });
+        let repository = AuthRepository::new(Arc::clone(&database));
+        let jti = Uuid::new_v4();
+        let expires_at = Utc::now() + Duration::hours(1);
+
+        assert!(repository.revoke_token(jti, expires_at).await.unwrap());
+        assert!(!repository.revoke_token(jti, expires_at).await.unwrap());
+
+        sqlx::query("DELETE FROM revoked_tokens WHERE jti = $1")
+            .bind(jti)
+            .execute(&database.conn_pool)
+            .await
+            .unwrap();
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn api_tokens_are_revoked_by_their_owner_only() {
+        let database_url = std::env::var("DATABASE_URL").unwrap();
+        let database = Arc::new(Database {
+            conn_pool: PgPoolOptions::new().connect(&database_url).await.unwrap(),
+        });
+        let pool = &database.conn_pool;
+        let repository = AuthRepository::new(Arc::clone(&database));
+        let marker = Uuid::new_v4().to_simple().to_string();
+        let (user_id,): (Uuid,) = sqlx::query_as(
+            r#"
+            INSERT INTO users (name, last_name, email, username, password_hash, birthdate, gender, pronoun)
+            VALUES ('Api', 'Token', $1 || '@nexus.dev', $1, '', CURRENT_TIMESTAMP, 'custom', 'they')
+            RETURNING id"#,
+        )
+        .bind(&marker)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let audit = |action| {
+            AuditContext::new(Some(user_id), None).entry(
+                action,
+                Some(user_id),
+                AuditOutcome::Success,
+            )
+        };
+        let api_token = repository
+            .insert_api_token(
+                InsertApiTokenTableRow {
+                    user_id,
+                    name: String::from("ci"),
+                    scopes: vec![ApiScope::UsersRead, ApiScope::PostsRead],
+                    expires_at: None,
+                },
+                audit(AuditAction::ApiTokenCreate),
+            )
+            .await
+            .unwrap();
+        let found = repository
+            .find_api_token(api_token.id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(found.scopes, vec![ApiScope::UsersRead, ApiScope::PostsRead]);
+        assert_eq!(repository.list_api_tokens(user_id).await.unwrap().len(), 1);
+        assert!(repository
+            .revoke_api_token(
+                api_token.id,
+                Uuid::new_v4(),
+                audit(AuditAction::ApiTokenRevoke)
+            )
+            .await
+            .unwrap()
+            .is_none());
+
+        let revoked = repository
+            .revoke_api_token(api_token.id, user_id, audit(AuditAction::ApiTokenRevoke))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(!revoked.is_active(Utc::now()));
+        assert!(repository
+            .revoke_api_token(api_token.id, user_id, audit(AuditAction::ApiTokenRevoke))
+            .await
+            .unwrap()
+            .is_none());
+
+        for table in [
+            "audit_log WHERE actor_id",
+            "api_tokens WHERE user_id",
+            "users WHERE id",
+        ] {
+            sqlx::query(&format!("DELETE FROM {table} = $1"))
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+    }
+}
@@ -1,86 +1,733 @@
-use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
-use serde::{Deserialize, Serialize};
+use async_graphql::futures_util::Stream;
+use chrono::{Duration, TimeZone, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
 use std::sync::Arc;
+use uuid::Uuid;
 
 use crate::config::Config;
 use crate::error::{Error, ErrorCode, Result};
+use crate::modules::audit::{AuditAction, AuditContext, AuditOutcome, AuditService, NewAuditEntry};
+use crate::modules::auth::graphql::password_change::PasswordChangeInput;
 use crate::modules::user::{User, UserService};
 
-use super::Tokens;
+use super::jwt::{Claims, Jwt, TokenType};
+use super::{
+    ApiScope, ApiToken, AuthRepository, Authenticated, InsertApiTokenTableRow,
+    InsertRefreshTokenTableRow, Login, LoginThrottle, LoginThrottleKey, PurgedTokens, SessionEvent,
+    SessionEventKind, SessionEvents, Tokens,
+};
+
+/// Amount of days a refresh token remains valid
+const REFRESH_TOKEN_TTL_DAYS: i64 = 60;
+
+/// Length of the random string used as refresh token
+const REFRESH_TOKEN_LENGTH: usize = 64;
+
+/// Amount of days the signature of an API token created without expiry
+/// remains valid, tokens are stored so they are revoked well before that
+const NON_EXPIRING_API_TOKEN_TTL_DAYS: i64 = 36_500;
+
+/// Maximum length of the name of an API token
+const MAX_API_TOKEN_NAME_LENGTH: usize = 64;
+
+/// Amount of hours an email verification token remains valid
+const EMAIL_VERIFICATION_TOKEN_TTL_HOURS: i64 = 24;
+
+/// Amount of minutes a password reset token remains valid
+const PASSWORD_RESET_TOKEN_TTL_MINUTES: i64 = 30;
 
 pub struct AuthService {
-    jwt_secret: Vec<u8>,
+    jwt: Jwt,
+    events: SessionEvents,
+    throttle: LoginThrottle,
+    repository: Arc<AuthRepository>,
+    audit_service: Arc<AuditService>,
     user_service: Arc<UserService>,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-struct Claims {
-    sub: String,
-    iat: usize,
-    exp: usize,
-    uid: String,
-}
-
 impl AuthService {
-    pub fn new(config: &Config, user_service: Arc<UserService>) -> Self {
+    pub fn new(
+        config: &Config,
+        repository: Arc<AuthRepository>,
+        audit_service: Arc<AuditService>,
+        user_service: Arc<UserService>,
+    ) -> Self {
         Self {
-            jwt_secret: config.jwt_secret.clone().into_bytes(),
+            jwt: Jwt::new(&config.jwt),
+            events: SessionEvents::default(),
+            throttle: LoginThrottle::new(config.login_throttle),
+            repository,
+            audit_service,
             user_service,
         }
     }
 
     /// Validate provided `username` and `password`. If valid, fetches the
     /// corresponding user and signs a JSON Web Token.
-    pub async fn create_token(&self, username: String, password: String) -> Result<Tokens> {
+    ///
+    /// Once too many attempts failed for the `username` or the `client_ip`,
+    /// attempts are rejected with `RATE_LIMITED` until the cooldown ends,
+    /// without checking the credentials.
+    ///
+    /// Once too many consecutive attempts failed for an existing user, its
+    /// account is locked and attempts are rejected with `ACCOUNT_LOCKED`
+    /// until the lock expires or an admin lifts it, whatever the password.
+    ///
+    /// Both successful and failed attempts are recorded in the audit log.
+    pub async fn create_token(
+        &self,
+        username: String,
+        password: String,
+        client_ip: Option<IpAddr>,
+    ) -> Result<Login> {
+        let throttle_keys = LoginThrottleKey::keys(&username, client_ip);
+
+        if let Err(retry_after) = self.throttle.check(&throttle_keys) {
+            tracing::warn!(?client_ip, ?retry_after, "login throttled");
+            return Err(Error::rate_limited(retry_after));
+        }
+
         let find_user_by_username = self.user_service.find_by_username(&username).await?;
+        let target_id = find_user_by_username.as_ref().map(|user| user.id);
 
-        if let Some(user) = find_user_by_username {
-            let is_valid_password =
-                argon2::verify_encoded(&user.password_hash, password.as_bytes())?;
-
-            if is_valid_password {
-                let iat = Utc::now().timestamp() as usize;
-                let exp = Utc::now()
-                    .checked_add_signed(Duration::days(30))
-                    .unwrap()
-                    .timestamp() as usize;
-
-                let claims = Claims {
-                    sub: String::from("nexus"),
-                    iat,
-                    exp,
-                    uid: username,
-                };
-
-                let access_token = encode(
-                    &Header::default(),
-                    &claims,
-                    &EncodingKey::from_secret(&self.jwt_secret),
-                )?;
-
-                return Ok(Tokens { access_token });
+        if let Some(user) = &find_user_by_username {
+            if let Some(locked_until) = user.active_lock(Utc::now()) {
+                // The password is checked all the same, its outcome ignored,
+                // so locked accounts take as long to answer as any other
+                // attempt
+                self.user_service.check_password(user, &password)?;
+                self.record_failed_login(client_ip, target_id).await?;
+
+                return Err(Error::account_locked(locked_until));
+            }
+        }
+
+        // Unknown usernames are verified against a dummy hash so they take as
+        // long as wrong passwords, both end up in the same failure below.
+        // Known users are kept on failure to count it towards their lockout.
+        let authenticated = match find_user_by_username {
+            Some(user) if self.user_service.verify_password(&user, &password).await? => Ok(user),
+            Some(user) => Err(Some(user)),
+            None => {
+                self.user_service.check_dummy_password(&password)?;
+                Err(None)
+            }
+        };
+
+        if let Ok(user) = authenticated {
+            self.user_service.reset_login_failures(&user).await?;
+
+            let access_token = self.sign_access_token(&user)?;
+            let audit = AuditContext::new(Some(user.id), client_ip).entry(
+                AuditAction::TokenCreate,
+                Some(user.id),
+                AuditOutcome::Success,
+            );
+            let refresh_token = self
+                .issue_refresh_token(&user, Uuid::new_v4(), audit)
+                .await?;
+
+            self.throttle.record_success(&throttle_keys);
+            self.events.publish(user.id, SessionEventKind::Login);
+
+            return Ok(Login {
+                tokens: Tokens {
+                    access_token,
+                    refresh_token,
+                },
+                user,
+            });
+        }
+
+        self.throttle.record_failure(&throttle_keys);
+        self.record_failed_login(client_ip, target_id).await?;
+
+        if let Err(Some(user)) = authenticated {
+            if let Some(locked_until) = self.user_service.record_login_failure(&user).await? {
+                return Err(Error::account_locked(locked_until));
             }
         }
 
         Err(Error::code(ErrorCode::InvalidCredentials))
     }
 
-    /// Retrieves the User Data for the provided token
-    pub async fn whoami(&self, token: String) -> Result<User> {
-        let token = decode::<Claims>(
-            &token,
-            &DecodingKey::from_secret(&self.jwt_secret),
-            &Validation::default(),
-        )?;
+    /// Records a failed login targeting the user with the given id, if known
+    async fn record_failed_login(
+        &self,
+        client_ip: Option<IpAddr>,
+        target_id: Option<Uuid>,
+    ) -> Result<()> {
+        self.audit_service
+            .record(AuditContext::new(None, client_ip).entry(
+                AuditAction::TokenCreate,
+                target_id,
+                AuditOutcome::Failure,
+            ))
+            .await
+    }
 
-        let username = token.claims.uid;
-        let find_user_by_username = self.user_service.find_by_username(&username).await?;
+    /// Exchanges a refresh token for a new pair of tokens. The provided
+    /// refresh token is revoked and replaced by the new one.
+    ///
+    /// Presenting an already rotated refresh token revokes every token in
+    /// its family, given that this token was probably stolen.
+    pub async fn refresh_token(&self, refresh_token: String) -> Result<Tokens> {
+        let token_hash = AuthService::hash_refresh_token(&refresh_token);
+        let stored = self
+            .repository
+            .find_refresh_token_by_hash(&token_hash)
+            .await?
+            .ok_or_else(|| Error::code(ErrorCode::InvalidJsonWebToken))?;
+
+        if stored.revoked_at.is_some() {
+            tracing::warn!(
+                family_id = %stored.family_id,
+                user_id = %stored.user_id,
+                "refresh token reuse detected, revoking token family"
+            );
+            self.repository
+                .revoke_refresh_token_family(stored.family_id)
+                .await?;
 
-        if let Some(user) = find_user_by_username {
-            return Ok(user);
+            return Err(Error::code(ErrorCode::InvalidJsonWebToken));
         }
 
-        Err(Error::code(ErrorCode::InvalidCredentials))
+        if stored.expires_at < Utc::now() {
+            return Err(Error::code(ErrorCode::ExpiredJsonWebToken));
+        }
+
+        let user = self
+            .user_service
+            .find_by_id(stored.user_id)
+            .await?
+            .ok_or_else(|| Error::code(ErrorCode::InvalidJsonWebToken))?;
+        let (refresh_token, dto) = AuthService::new_refresh_token(&user, stored.family_id);
+        let rotated = self.repository.rotate_refresh_token(stored.id, dto).await?;
+
+        if rotated.is_none() {
+            // Another request rotated this token first
+            self.repository
+                .revoke_refresh_token_family(stored.family_id)
+                .await?;
+
+            return Err(Error::code(ErrorCode::InvalidJsonWebToken));
+        }
+
+        let access_token = self.sign_access_token(&user)?;
+
+        Ok(Tokens {
+            access_token,
+            refresh_token,
+        })
+    }
+
+    /// Verifies the `current_password` of the provided user before replacing
+    /// it with the `new_password`.
+    pub async fn change_password(
+        &self,
+        user: User,
+        payload: PasswordChangeInput,
+        audit: AuditContext,
+    ) -> Result<User> {
+        let is_valid_password = self
+            .user_service
+            .check_password(&user, &payload.current_password)?;
+
+        if !is_valid_password {
+            return Err(Error::code(ErrorCode::InvalidCredentials));
+        }
+
+        self.user_service
+            .ensure_password_not_reused("newPassword", &user, &payload.new_password)
+            .await?;
+
+        let user = self
+            .user_service
+            .update_password(user.id, &payload.new_password, audit)
+            .await?;
+
+        if payload.revoke_other_sessions {
+            self.repository.revoke_user_refresh_tokens(user.id).await?;
+        }
+
+        self.events
+            .publish(user.id, SessionEventKind::PasswordChanged);
+
+        Ok(user)
+    }
+
+    /// Stores an API token named `name` for the provided user, granted
+    /// `scopes` and valid for `expires_in` or until revoked. Returns the
+    /// stored token along with the signed one, which is not stored and can't
+    /// be retrieved again.
+    pub async fn issue_api_token(
+        &self,
+        user: &User,
+        name: String,
+        scopes: Vec<ApiScope>,
+        expires_in: Option<Duration>,
+        audit: AuditContext,
+    ) -> Result<(ApiToken, String)> {
+        let name = validate_api_token(&name, &scopes)?;
+        let ttl = expires_in.unwrap_or_else(|| Duration::days(NON_EXPIRING_API_TOKEN_TTL_DAYS));
+        let claims = Claims {
+            scp: Some(scopes.clone()),
+            ..self.jwt.claims(user, TokenType::Api, ttl)
+        };
+        let dto = InsertApiTokenTableRow {
+            user_id: user.id,
+            name,
+            scopes,
+            expires_at: expires_in.map(|_| Utc.timestamp(claims.exp as i64, 0)),
+        };
+        let api_token = self
+            .repository
+            .insert_api_token(
+                dto,
+                audit.entry(
+                    AuditAction::ApiTokenCreate,
+                    Some(user.id),
+                    AuditOutcome::Success,
+                ),
+            )
+            .await?;
+        // The stored token is looked up by the `jti` of the signed one
+        let token = self.jwt.sign(&Claims {
+            jti: api_token.id,
+            ..claims
+        })?;
+
+        Ok((api_token, token))
+    }
+
+    /// Lists the API tokens of the provided user, revoked ones included
+    pub async fn list_api_tokens(&self, user: &User) -> Result<Vec<ApiToken>> {
+        self.repository.list_api_tokens(user.id).await
+    }
+
+    /// Revokes the API token with the provided `id`, it's rejected from now
+    /// on. Users can only revoke their own tokens.
+    pub async fn revoke_api_token(
+        &self,
+        user: &User,
+        id: Uuid,
+        audit: AuditContext,
+    ) -> Result<ApiToken> {
+        let revoked = self
+            .repository
+            .revoke_api_token(
+                id,
+                user.id,
+                audit.entry(
+                    AuditAction::ApiTokenRevoke,
+                    Some(user.id),
+                    AuditOutcome::Success,
+                ),
+            )
+            .await?;
+
+        match revoked {
+            Some(api_token) => Ok(api_token),
+            None => Err(unrevocable_api_token(
+                self.repository.find_api_token(id).await?,
+                user,
+                id,
+            )),
+        }
+    }
+
+    /// Signs a token proving ownership of the user's current email address
+    pub fn issue_email_verification_token(&self, user: &User) -> Result<String> {
+        let claims = Claims {
+            email: Some(user.email.clone()),
+            ..self.jwt.claims(
+                user,
+                TokenType::EmailVerification,
+                Duration::hours(EMAIL_VERIFICATION_TOKEN_TTL_HOURS),
+            )
+        };
+
+        self.jwt.sign(&claims)
+    }
+
+    /// Marks the email address the provided token was issued for as
+    /// verified. Fails if the user changed its email address since.
+    pub async fn verify_email(&self, token: String) -> Result<User> {
+        let claims = self.jwt.decode(&token, TokenType::EmailVerification)?;
+        let user = self
+            .user_service
+            .find_by_id(claims.uid)
+            .await?
+            .ok_or_else(|| Error::code(ErrorCode::InvalidJsonWebToken))?;
+
+        if claims.email.as_deref() != Some(user.email.as_str()) {
+            return Err(Error::code(ErrorCode::InvalidJsonWebToken));
+        }
+
+        ensure_email_unverified(&user)?;
+
+        self.user_service.mark_email_verified(user.id).await
+    }
+
+    /// Issues a password reset token for the user owning the provided email.
+    /// Returns `None` if no user owns it, callers must not disclose this.
+    pub async fn request_password_reset(&self, email: &str) -> Result<Option<String>> {
+        let user = match self.user_service.find_by_email(email).await? {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+        let claims = Claims {
+            ver: Some(user.token_version),
+            ..self.jwt.claims(
+                &user,
+                TokenType::PasswordReset,
+                Duration::minutes(PASSWORD_RESET_TOKEN_TTL_MINUTES),
+            )
+        };
+
+        self.jwt.sign(&claims).map(Some)
+    }
+
+    /// Sets a new password for the user the reset token was issued for.
+    ///
+    /// Tokens are single-use, changing the password increments the user's
+    /// `token_version` so replaying a token fails.
+    pub async fn confirm_password_reset(
+        &self,
+        token: String,
+        new_password: String,
+    ) -> Result<User> {
+        let claims = self.jwt.decode(&token, TokenType::PasswordReset)?;
+        let user = self
+            .user_service
+            .find_by_id(claims.uid)
+            .await?
+            .ok_or_else(|| Error::code(ErrorCode::InvalidJsonWebToken))?;
+
+        ensure_token_version(&claims, user.token_version)?;
+        self.user_service
+            .ensure_password_not_reused("newPassword", &user, &new_password)
+            .await?;
+
+        let user = self
+            .user_service
+            .reset_password(user.id, &new_password, user.token_version)
+            .await?
+            .ok_or_else(|| Error::code(ErrorCode::InvalidJsonWebToken))?;
+
+        self.events
+            .publish(user.id, SessionEventKind::PasswordChanged);
+
+        Ok(user)
+    }
+
+    /// Revokes the provided access token, it's rejected from now on even if
+    /// it didn't expire yet
+    pub async fn revoke_token(&self, token: String) -> Result<()> {
+        let claims = self.jwt.decode(&token, TokenType::Access)?;
+        let expires_at = Utc.timestamp(claims.exp as i64, 0);
+
+        if !self.repository.revoke_token(claims.jti, expires_at).await? {
+            return Err(Error::conflict("The token is already revoked"));
+        }
+
+        if let Some(user) = self.user_service.find_by_id(claims.uid).await? {
+            self.events.publish(user.id, SessionEventKind::Logout);
+        }
+
+        Ok(())
+    }
+
+    /// Purges tokens which can't be used anymore, see
+    /// `AuthRepository::purge_expired_tokens`
+    pub async fn purge_expired_tokens(&self) -> Result<PurgedTokens> {
+        self.repository.purge_expired_tokens().await
+    }
+
+    /// Streams the session events of the provided user
+    pub fn session_events(&self, user: &User) -> impl Stream<Item = SessionEvent> {
+        self.events.subscribe(user.id)
+    }
+
+    /// Retrieves the id of the user the provided access or API token was
+    /// issued for, without checking it against the database
+    pub fn token_user_id(&self, token: &str) -> Option<Uuid> {
+        self.jwt
+            .decode_as(token, &[TokenType::Access, TokenType::Api])
+            .ok()
+            .map(|claims| claims.uid)
+    }
+
+    /// Validates the provided access or API token's signature, expiry,
+    /// issuer and audience, and checks it's neither revoked nor issued for a
+    /// user that no longer exists. Returns the user along with when the
+    /// token expires and, for API tokens, the scopes it grants.
+    pub async fn verify_token(&self, token: &str) -> Result<Authenticated> {
+        let claims = self
+            .jwt
+            .decode_as(token, &[TokenType::Access, TokenType::Api])?;
+        let api_token = match claims.token_type {
+            TokenType::Api => Some(self.find_active_api_token(&claims).await?),
+            _ if self.repository.is_token_revoked(claims.jti).await? => {
+                return Err(Error::code(ErrorCode::InvalidJsonWebToken));
+            }
+            _ => None,
+        };
+
+        let find_user_by_id = self.user_service.find_by_id(claims.uid).await?;
+
+        if let Some(user) = find_user_by_id {
+            // The user moved to another organization since the token was
+            // issued, its claims no longer scope the caller
+            if user.organization_id != claims.org {
+                return Err(Error::code(ErrorCode::InvalidJsonWebToken));
+            }
+
+            if api_token
+                .as_ref()
+                .is_some_and(|api_token| api_token.user_id != user.id)
+            {
+                return Err(Error::code(ErrorCode::InvalidJsonWebToken));
+            }
+
+            return Ok(Authenticated {
+                user,
+                expires_at: Utc.timestamp(claims.exp as i64, 0),
+                scopes: api_token.map(|api_token| api_token.scopes),
+            });
+        }
+
+        // The token is well signed but no longer identifies a user
+        Err(Error::code(ErrorCode::InvalidJsonWebToken))
+    }
+
+    /// Retrieves the stored API token a signed one was issued for, failing
+    /// if it has been revoked or deleted since
+    async fn find_active_api_token(&self, claims: &Claims) -> Result<ApiToken> {
+        self.repository
+            .find_api_token(claims.jti)
+            .await?
+            .filter(|api_token| api_token.is_active(Utc::now()))
+            .ok_or_else(|| Error::code(ErrorCode::InvalidJsonWebToken))
+    }
+
+    fn sign_access_token(&self, user: &User) -> Result<String> {
+        self.jwt.sign(&self.jwt.access_claims(user))
+    }
+
+    /// Stores a new refresh token along with the `audit` entry of the login
+    /// it's issued for
+    async fn issue_refresh_token(
+        &self,
+        user: &User,
+        family_id: Uuid,
+        audit: NewAuditEntry,
+    ) -> Result<String> {
+        let (refresh_token, dto) = AuthService::new_refresh_token(user, family_id);
+
+        self.repository.insert_refresh_token(dto, audit).await?;
+
+        Ok(refresh_token)
+    }
+
+    /// Generates a random refresh token, returns the raw token to hand to
+    /// the client along with the row to store, which only keeps its hash.
+    fn new_refresh_token(user: &User, family_id: Uuid) -> (String, InsertRefreshTokenTableRow) {
+        let refresh_token: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(REFRESH_TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+        let dto = InsertRefreshTokenTableRow {
+            user_id: user.id,
+            family_id,
+            token_hash: AuthService::hash_refresh_token(&refresh_token),
+            expires_at: Utc::now() + Duration::days(REFRESH_TOKEN_TTL_DAYS),
+        };
+
+        (refresh_token, dto)
+    }
+
+    fn hash_refresh_token(refresh_token: &str) -> String {
+        hex::encode(Sha256::digest(refresh_token.as_bytes()))
+    }
+}
+
+/// Checks an API token is named and grants at least a scope, returns the
+/// trimmed name
+fn validate_api_token(name: &str, scopes: &[ApiScope]) -> Result<String> {
+    let name = name.trim();
+
+    if name.is_empty() || name.chars().count() > MAX_API_TOKEN_NAME_LENGTH {
+        return Err(Error::new(
+            "name",
+            &format!("Name must be between 1 and {MAX_API_TOKEN_NAME_LENGTH} characters"),
+            ErrorCode::ValidationError,
+        ));
+    }
+
+    if scopes.is_empty() {
+        return Err(Error::new(
+            "scopes",
+            "API tokens must be granted at least a scope",
+            ErrorCode::ValidationError,
+        ));
+    }
+
+    Ok(name.to_string())
+}
+
+/// Checks the token was issued for the user's current `token_version`
+fn ensure_token_version(claims: &Claims, token_version: i32) -> Result<()> {
+    if claims.ver != Some(token_version) {
+        return Err(Error::code(ErrorCode::InvalidJsonWebToken));
+    }
+
+    Ok(())
+}
+
+/// Fails if the user's email address is verified already
+fn ensure_email_unverified(user: &User) -> Result<()> {
+    if user.email_verified {
+        return Err(Error::conflict("The email address is already verified"));
+    }
+
+    Ok(())
+}
+
+/// Tells apart the API tokens of the user which were revoked already from
+/// those which don't exist or belong to other users, which are not found
+fn unrevocable_api_token(api_token: Option<ApiToken>, user: &User, id: Uuid) -> Error {
+    match api_token {
+        Some(api_token) if api_token.user_id == user.id && api_token.revoked_at.is_some() => {
+            Error::conflict("The API token is already revoked")
+        }
+        _ => Error::not_found("API token", &id.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    use crate::config::{JwtConfig, DEFAULT_JWT_AUDIENCE, DEFAULT_JWT_ISSUER};
+    use crate::error::ErrorCode;
+    use crate::modules::user::{Role, User, DEFAULT_ORGANIZATION_ID};
+
+    use super::{
+        ensure_email_unverified, ensure_token_version, unrevocable_api_token, validate_api_token,
+        ApiScope, ApiToken, Claims, Jwt, TokenType,
+    };
+
+    fn password_reset_claims(ver: i32) -> Claims {
+        let now = Utc::now();
+
+        Claims {
+            sub: String::from("nexus"),
+            iat: now.timestamp() as usize,
+            exp: (now + Duration::minutes(30)).timestamp() as usize,
+            iss: String::from(DEFAULT_JWT_ISSUER),
+            aud: String::from(DEFAULT_JWT_AUDIENCE),
+            jti: Uuid::new_v4(),
+            uid: Uuid::new_v4(),
+            role: Role::User,
+            org: DEFAULT_ORGANIZATION_ID,
+            token_type: TokenType::PasswordReset,
+            email: None,
+            ver: Some(ver),
+            scp: None,
+        }
+    }
+
+    #[test]
+    fn accepts_password_reset_token_for_current_version() {
+        let jwt = Jwt::new(&JwtConfig::new("secret"));
+        let token = jwt.sign(&password_reset_claims(3)).unwrap();
+        let claims = jwt.decode(&token, TokenType::PasswordReset).unwrap();
+
+        assert!(ensure_token_version(&claims, 3).is_ok());
+    }
+
+    #[test]
+    fn rejects_reused_password_reset_token() {
+        let claims = password_reset_claims(3);
+        // A successful reset increments the user's token version
+        let error = ensure_token_version(&claims, 4).err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::InvalidJsonWebToken);
+    }
+
+    #[test]
+    fn rejects_password_reset_token_without_version() {
+        let mut claims = password_reset_claims(0);
+
+        claims.ver = None;
+
+        let error = ensure_token_version(&claims, 0).err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::InvalidJsonWebToken);
+    }
+
+    #[test]
+    fn api_tokens_are_named_and_scoped() {
+        let scopes = [ApiScope::UsersRead];
+
+        assert_eq!(validate_api_token(" ci ", &scopes).unwrap(), "ci");
+
+        for (name, scopes, field) in [
+            ("  ", &scopes[..], "name"),
+            (&"a".repeat(65)[..], &scopes[..], "name"),
+            ("ci", &[][..], "scopes"),
+        ] {
+            let error = validate_api_token(name, scopes).err().unwrap();
+
+            assert_eq!(error.code, ErrorCode::ValidationError);
+            assert_eq!(error.field.as_deref(), Some(field));
+        }
+    }
+
+    #[test]
+    fn rejects_verifying_a_verified_email() {
+        let mut user = User::fixture("esteban");
+
+        assert!(ensure_email_unverified(&user).is_ok());
+
+        user.email_verified = true;
+
+        let error = ensure_email_unverified(&user).err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::Conflict);
+    }
+
+    #[test]
+    fn revoked_api_tokens_conflict_for_their_owner_only() {
+        let owner = User::fixture("esteban");
+        let api_token = ApiToken {
+            id: Uuid::new_v4(),
+            user_id: owner.id,
+            name: String::from("ci"),
+            scopes: vec![ApiScope::UsersRead],
+            expires_at: None,
+            revoked_at: Some(Utc::now()),
+            created_at: Utc::now(),
+        };
+        let id = api_token.id;
+
+        assert_eq!(
+            unrevocable_api_token(Some(api_token.clone()), &owner, id).code,
+            ErrorCode::Conflict
+        );
+        assert_eq!(
+            unrevocable_api_token(Some(api_token.clone()), &User::fixture("esteban"), id).code,
+            ErrorCode::NotFound
+        );
+        assert_eq!(
+            unrevocable_api_token(None, &owner, id).code,
+            ErrorCode::NotFound
+        );
     }
 }
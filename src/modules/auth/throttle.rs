@@ -0,0 +1,230 @@
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::LoginThrottleConfig;
+use crate::ttl_map::TtlMap;
+
+/// Amount of keys whose failures are tracked at most, the key whose
+/// failures expire the soonest is forgotten to make room for another
+const MAX_TRACKED_KEYS: usize = 100_000;
+
+/// Identifies the source of failed login attempts
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum LoginThrottleKey {
+    Username(String),
+    Ip(IpAddr),
+}
+
+impl LoginThrottleKey {
+    /// Keys a login attempt is counted against, the attempted username and
+    /// the client's IP if known
+    pub fn keys(username: &str, client_ip: Option<IpAddr>) -> Vec<Self> {
+        let mut keys = vec![Self::Username(username.trim().to_lowercase())];
+
+        if let Some(ip) = client_ip {
+            keys.push(Self::Ip(ip));
+        }
+
+        keys
+    }
+}
+
+struct Attempts {
+    failures: u32,
+    window_started_at: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Counts failed logins per key within a window. Once a key reaches the
+/// maximum amount of failures, logins for it are rejected during a cooldown
+/// period regardless of the provided credentials.
+pub struct LoginThrottle {
+    config: LoginThrottleConfig,
+    attempts: Mutex<TtlMap<LoginThrottleKey, Attempts>>,
+}
+
+impl LoginThrottle {
+    pub fn new(config: LoginThrottleConfig) -> Self {
+        Self {
+            config,
+            attempts: Mutex::new(TtlMap::new(MAX_TRACKED_KEYS)),
+        }
+    }
+
+    /// Returns how long to wait until logging in is allowed again if any of
+    /// the provided keys is locked
+    pub fn check(&self, keys: &[LoginThrottleKey]) -> Result<(), Duration> {
+        self.check_at(keys, Instant::now())
+    }
+
+    pub fn record_failure(&self, keys: &[LoginThrottleKey]) {
+        self.record_failure_at(keys, Instant::now())
+    }
+
+    /// Forgets the failures of the username among the provided keys. Those
+    /// of the IP are kept, or an attacker spraying passwords from it could
+    /// reset its count by logging into an account of their own.
+    pub fn record_success(&self, keys: &[LoginThrottleKey]) {
+        let mut attempts = self.attempts.lock().unwrap();
+
+        for key in keys {
+            if let LoginThrottleKey::Username(_) = key {
+                attempts.remove(key, Instant::now());
+            }
+        }
+    }
+
+    fn check_at(&self, keys: &[LoginThrottleKey], now: Instant) -> Result<(), Duration> {
+        let attempts = self.attempts.lock().unwrap();
+        let retry_after = keys
+            .iter()
+            .filter_map(|key| attempts.get(key, now)?.locked_until)
+            .map(|locked_until| locked_until.saturating_duration_since(now))
+            .filter(|remaining| !remaining.is_zero())
+            .max();
+
+        match retry_after {
+            Some(retry_after) => Err(retry_after),
+            None => Ok(()),
+        }
+    }
+
+    fn record_failure_at(&self, keys: &[LoginThrottleKey], now: Instant) {
+        let mut tracked = self.attempts.lock().unwrap();
+
+        for key in keys {
+            let mut attempts = tracked.remove(key, now).unwrap_or(Attempts {
+                failures: 0,
+                window_started_at: now,
+                locked_until: None,
+            });
+
+            attempts.failures += 1;
+
+            if attempts.failures >= self.config.max_failures {
+                attempts.locked_until = Some(now + self.config.cooldown);
+            }
+
+            let expires_at = self.expires_at(&attempts);
+
+            tracked.insert(key.clone(), attempts, expires_at, now);
+        }
+    }
+
+    /// When both the failures window and the cooldown are over, the failures
+    /// are forgotten then
+    fn expires_at(&self, attempts: &Attempts) -> Instant {
+        let window_ends_at = attempts.window_started_at + self.config.window;
+
+        attempts
+            .locked_until
+            .map_or(window_ends_at, |locked_until| {
+                locked_until.max(window_ends_at)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::{Duration, Instant};
+
+    use crate::config::LoginThrottleConfig;
+
+    use super::{LoginThrottle, LoginThrottleKey};
+
+    fn throttle() -> LoginThrottle {
+        LoginThrottle::new(LoginThrottleConfig {
+            max_failures: 3,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(300),
+        })
+    }
+
+    #[test]
+    fn locks_after_max_failures_until_cooldown_ends() {
+        let throttle = throttle();
+        let keys = LoginThrottleKey::keys("Esteban", None);
+        let now = Instant::now();
+
+        for _ in 0..2 {
+            throttle.record_failure_at(&keys, now);
+        }
+
+        assert!(throttle.check_at(&keys, now).is_ok());
+
+        throttle.record_failure_at(&keys, now);
+
+        assert_eq!(
+            throttle.check_at(&keys, now + Duration::from_secs(100)),
+            Err(Duration::from_secs(200))
+        );
+        assert!(throttle
+            .check_at(&keys, now + Duration::from_secs(300))
+            .is_ok());
+    }
+
+    #[test]
+    fn failures_outside_the_window_are_forgotten() {
+        let throttle = throttle();
+        let keys = LoginThrottleKey::keys("esteban", None);
+        let now = Instant::now();
+
+        throttle.record_failure_at(&keys, now);
+        throttle.record_failure_at(&keys, now);
+        throttle.record_failure_at(&keys, now + Duration::from_secs(61));
+
+        assert!(throttle
+            .check_at(&keys, now + Duration::from_secs(61))
+            .is_ok());
+    }
+
+    #[test]
+    fn ip_is_locked_across_usernames() {
+        let throttle = throttle();
+        let ip = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let now = Instant::now();
+
+        for username in ["alice", "bob", "carol"] {
+            throttle.record_failure_at(&LoginThrottleKey::keys(username, ip), now);
+        }
+
+        assert!(throttle
+            .check_at(&LoginThrottleKey::keys("dave", ip), now)
+            .is_err());
+        assert!(throttle
+            .check_at(&LoginThrottleKey::keys("dave", None), now)
+            .is_ok());
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let throttle = throttle();
+        let keys = LoginThrottleKey::keys("esteban", None);
+        let now = Instant::now();
+
+        throttle.record_failure_at(&keys, now);
+        throttle.record_failure_at(&keys, now);
+        throttle.record_success(&keys);
+        throttle.record_failure_at(&keys, now);
+
+        assert!(throttle.check_at(&keys, now).is_ok());
+    }
+
+    #[test]
+    fn success_keeps_the_failures_of_the_ip() {
+        let throttle = throttle();
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let now = Instant::now();
+
+        throttle.record_failure_at(&LoginThrottleKey::keys("ana", Some(ip)), now);
+        throttle.record_failure_at(&LoginThrottleKey::keys("bob", Some(ip)), now);
+        throttle.record_success(&LoginThrottleKey::keys("mallory", Some(ip)));
+        throttle.record_failure_at(&LoginThrottleKey::keys("carol", Some(ip)), now);
+
+        assert!(throttle
+            .check_at(&LoginThrottleKey::keys("dave", Some(ip)), now)
+            .is_err());
+    }
+}
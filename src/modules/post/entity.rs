@@ -1,8 +1,11 @@
 use async_graphql::Enum;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
 use uuid::Uuid;
 
+use crate::graphql::relay::{KeysetColumn, KeysetOrder};
+
 #[derive(Copy, Clone, Debug, Deserialize, Enum, PartialEq, Eq, Serialize, sqlx::Type)]
 #[sqlx(rename_all = "lowercase")]
 pub enum Scope {
@@ -10,11 +13,35 @@ pub enum Scope {
     Private,
 }
 
-impl ToString for Scope {
-    fn to_string(&self) -> String {
+impl Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
-            Self::Public => String::from("public"),
-            Self::Private => String::from("private"),
+            Self::Public => write!(f, "public"),
+            Self::Private => write!(f, "private"),
+        }
+    }
+}
+
+/// Sorting applied when listing posts, the latest ones first
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PostOrder {
+    #[default]
+    CreatedAtDesc,
+}
+
+impl KeysetOrder for PostOrder {
+    fn name(&self) -> &'static str {
+        "CREATED_AT_DESC"
+    }
+
+    fn descending(&self) -> bool {
+        true
+    }
+
+    fn column(&self) -> KeysetColumn {
+        KeysetColumn {
+            name: "created_at",
+            sql_type: "timestamptz",
         }
     }
 }
@@ -3,6 +3,9 @@ pub mod post_create;
 use async_graphql::{Context, Object};
 
 use crate::error::Result;
+use crate::graphql::guards::ScopeGuard;
+use crate::graphql::relay::echo_client_mutation_id;
+use crate::modules::auth::ApiScope;
 
 use self::post_create::{PostCreate, PostCreateInput};
 
@@ -11,8 +14,14 @@ pub struct PostMutation;
 
 #[Object]
 impl PostMutation {
-    #[graphql(name = "postCreate")]
-    async fn post_create(&self, ctx: &Context<'_>, input: PostCreateInput) -> Result<PostCreate> {
-        PostCreate::exec(ctx, input).await
+    #[graphql(name = "postCreate", guard = "ScopeGuard::new(ApiScope::PostsWrite)")]
+    async fn post_create(
+        &self,
+        ctx: &Context<'_>,
+        mut input: PostCreateInput,
+    ) -> Result<PostCreate> {
+        let client_mutation_id = input.client_mutation_id.take();
+
+        echo_client_mutation_id(client_mutation_id, PostCreate::exec(ctx, input).await)
     }
 }
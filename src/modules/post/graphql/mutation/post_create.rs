@@ -3,15 +3,24 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 use crate::error::Result;
+use crate::graphql::guards::current_user;
+use crate::graphql::relay::MutationPayload;
 use crate::modules::post::graphql::{Post, PostError};
 use crate::modules::post::Scope;
-use crate::routes::AuthToken;
 use crate::services::Services;
 
 #[derive(Debug, Deserialize, Serialize, SimpleObject)]
 pub struct PostCreate {
     post: Option<Post>,
     error: Option<PostError>,
+    /// `clientMutationId` of the input, unchanged
+    client_mutation_id: Option<String>,
+}
+
+impl MutationPayload for PostCreate {
+    fn client_mutation_id(&mut self) -> &mut Option<String> {
+        &mut self.client_mutation_id
+    }
 }
 
 #[derive(Deserialize, Serialize, InputObject)]
@@ -19,26 +28,20 @@ pub struct PostCreate {
 pub struct PostCreateInput {
     pub content: String,
     pub scope: Scope,
+    /// Echoed unchanged by the payload, matching it to the request
+    pub client_mutation_id: Option<String>,
 }
 
 impl PostCreate {
     pub async fn exec(ctx: &Context<'_>, input: PostCreateInput) -> Result<PostCreate> {
-        let auth = ctx.data_unchecked::<AuthToken>();
         let services = ctx.data::<Arc<Services>>().unwrap();
-        let token = auth.token()?;
-        let user = services.auth.whoami(token).await?;
+        let user = current_user(ctx).await?;
 
         match services.post.create(user.clone(), input).await {
             Ok(post) => Ok(PostCreate {
-                post: Some(Post {
-                    id: post.id,
-                    content: post.content,
-                    user,
-                    scope: post.scope,
-                    created_at: post.created_at,
-                    updated_at: post.updated_at,
-                }),
+                post: Some(Post::new(post, Some(user))),
                 error: None,
+                client_mutation_id: None,
             }),
             Err(err) => {
                 let post_error = PostError::try_from(err)?;
@@ -46,6 +49,7 @@ impl PostCreate {
                 Ok(PostCreate {
                     post: None,
                     error: Some(post_error),
+                    client_mutation_id: None,
                 })
             }
         }
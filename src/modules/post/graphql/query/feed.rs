@@ -1,16 +1,16 @@
-use async_graphql::dataloader::DataLoader;
 use async_graphql::{Context, SimpleObject};
 use std::sync::Arc;
 
 use crate::error::Result;
-use crate::graphql::loaders::UserLoader;
-use crate::graphql::relay::{self, RelayConnection};
+use crate::graphql::guards::current_user;
+use crate::graphql::relay::{self, KeysetConnection};
 use crate::modules::post::graphql::{Post, PostError};
+use crate::modules::post::PostOrder;
 use crate::services::Services;
 
 #[derive(SimpleObject)]
 pub struct Feed {
-    feed: Option<RelayConnection<Post>>,
+    feed: Option<KeysetConnection<Post>>,
     error: Option<PostError>,
 }
 
@@ -23,29 +23,32 @@ impl Feed {
         last: Option<i32>,
     ) -> Result<Self> {
         let services = ctx.data::<Arc<Services>>().unwrap();
+        let organization_id = current_user(ctx).await?.organization_id;
+        let params = relay::Params::new(after, before, first, last);
+        let count_services = Arc::clone(services);
+        let posts_connection = relay::query_keyset(
+            params,
+            PostOrder::CreatedAtDesc,
+            Arc::new(move || {
+                let services = Arc::clone(&count_services);
 
-        match services.post.find_public_posts(first).await {
-            Ok(posts) => {
-                let user_loader = ctx.data_unchecked::<DataLoader<UserLoader>>();
-                let users = user_loader
-                    .load_many(posts.iter().map(|p| p.user_id))
-                    .await
-                    .unwrap();
-                let posts = posts.iter().map(|p| Post {
-                    id: p.id,
-                    content: p.content.clone(),
-                    user: users.get(&p.user_id).unwrap().to_owned(),
-                    scope: p.scope,
-                    created_at: p.created_at,
-                    updated_at: p.updated_at,
-                });
-                let posts_connection =
-                    relay::query(posts, relay::Params::new(after, before, first, last), 10).await?;
-                Ok(Feed {
-                    feed: Some(posts_connection),
-                    error: None,
-                })
-            }
+                Box::pin(async move { services.post.count_public_posts(organization_id).await })
+            }),
+            |page| async move {
+                let posts = services
+                    .post
+                    .find_public_page(organization_id, page)
+                    .await?;
+                Post::with_authors(ctx, organization_id, posts).await
+            },
+        )
+        .await;
+
+        match posts_connection {
+            Ok(posts_connection) => Ok(Feed {
+                feed: Some(posts_connection),
+                error: None,
+            }),
             Err(err) => {
                 let post_error = PostError::try_from(err)?;
 
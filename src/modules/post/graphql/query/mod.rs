@@ -4,6 +4,8 @@ pub mod posts;
 use async_graphql::{Context, Object};
 
 use crate::error::Result;
+use crate::graphql::guards::ScopeGuard;
+use crate::modules::auth::ApiScope;
 
 use self::feed::Feed;
 use self::posts::Posts;
@@ -13,7 +15,7 @@ pub struct PostQuery;
 
 #[Object]
 impl PostQuery {
-    #[graphql(name = "feed")]
+    #[graphql(name = "feed", guard = "ScopeGuard::new(ApiScope::PostsRead)")]
     async fn feed(
         &self,
         ctx: &Context<'_>,
@@ -25,7 +27,7 @@ impl PostQuery {
         Feed::exec(ctx, after, before, first, last).await
     }
 
-    #[graphql(name = "posts")]
+    #[graphql(name = "posts", guard = "ScopeGuard::new(ApiScope::PostsRead)")]
     async fn posts(
         &self,
         ctx: &Context<'_>,
@@ -1,17 +1,16 @@
-use async_graphql::dataloader::DataLoader;
 use async_graphql::{Context, SimpleObject};
 use std::sync::Arc;
 
 use crate::error::Result;
-use crate::graphql::loaders::UserLoader;
-use crate::graphql::relay::{self, RelayConnection};
+use crate::graphql::guards::current_user;
+use crate::graphql::relay::{self, KeysetConnection};
 use crate::modules::post::graphql::{Post, PostError};
-use crate::routes::AuthToken;
+use crate::modules::post::PostOrder;
 use crate::services::Services;
 
 #[derive(SimpleObject)]
 pub struct Posts {
-    posts: Option<RelayConnection<Post>>,
+    posts: Option<KeysetConnection<Post>>,
     error: Option<PostError>,
 }
 
@@ -23,33 +22,33 @@ impl Posts {
         first: Option<i32>,
         last: Option<i32>,
     ) -> Result<Self> {
-        let auth = ctx.data_unchecked::<AuthToken>();
-        let token = auth.token()?;
         let services = ctx.data::<Arc<Services>>().unwrap();
-        let user = services.auth.whoami(token).await?;
+        let user = current_user(ctx).await?;
+        let organization_id = user.organization_id;
+        let user_id = user.id;
+        let params = relay::Params::new(after, before, first, last);
+        let count_services = Arc::clone(services);
+        let posts_connection = relay::query_keyset(
+            params,
+            PostOrder::CreatedAtDesc,
+            Arc::new(move || {
+                let services = Arc::clone(&count_services);
 
-        match services.post.find_by_author(user).await {
-            Ok(posts) => {
-                let user_loader = ctx.data_unchecked::<DataLoader<UserLoader>>();
-                let users = user_loader
-                    .load_many(posts.iter().map(|p| p.user_id))
-                    .await
-                    .unwrap();
-                let posts = posts.iter().map(|p| Post {
-                    id: p.id,
-                    content: p.content.clone(),
-                    user: users.get(&p.user_id).unwrap().to_owned(),
-                    scope: p.scope,
-                    created_at: p.created_at,
-                    updated_at: p.updated_at,
-                });
-                let posts_connection =
-                    relay::query(posts, relay::Params::new(after, before, first, last), 10).await?;
-                Ok(Posts {
-                    posts: Some(posts_connection),
-                    error: None,
-                })
-            }
+                Box::pin(async move { services.post.count_by_author(user_id).await })
+            }),
+            |page| async move {
+                let posts = services.post.find_author_page(&user, page).await?;
+
+                Post::with_authors(ctx, organization_id, posts).await
+            },
+        )
+        .await;
+
+        match posts_connection {
+            Ok(posts_connection) => Ok(Posts {
+                posts: Some(posts_connection),
+                error: None,
+            }),
             Err(err) => {
                 let post_error = PostError::try_from(err)?;
 
@@ -1,22 +1,110 @@
-use async_graphql::{Enum, SimpleObject};
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{ComplexObject, Context, Enum, Result, SimpleObject, ID};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::error::{Error, ErrorCode};
-use crate::modules::post::Scope;
+use crate::graphql::loaders::{UserKey, UserLoader};
+use crate::graphql::relay::{GlobalId, Keyset, SortValue};
+use crate::modules::post::{self, PostOrder, Scope};
 use crate::modules::user::User;
 
 #[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+#[graphql(complex)]
 pub struct Post {
+    #[graphql(skip)]
     pub id: Uuid,
     pub content: String,
-    pub user: User,
+    #[graphql(skip)]
+    pub user_id: Uuid,
+    /// Resolved as `user`, `None` when the author couldn't be loaded
+    #[graphql(skip)]
+    pub author: Option<User>,
     pub scope: Scope,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl Post {
+    /// Pairs `posts` with their authors, which belong to `organization_id`,
+    /// loading them in a single batch. Posts whose author is missing are
+    /// kept, their `user` failing on its own.
+    pub async fn with_authors(
+        ctx: &Context<'_>,
+        organization_id: Uuid,
+        posts: Vec<post::Post>,
+    ) -> crate::error::Result<Vec<Post>> {
+        let user_loader = ctx.data_unchecked::<DataLoader<UserLoader>>();
+        let users = user_loader
+            .load_many(
+                posts
+                    .iter()
+                    .map(|p| UserKey::new(organization_id, p.user_id)),
+            )
+            .await?;
+
+        let posts = posts
+            .into_iter()
+            .map(|p| {
+                let author = users
+                    .get(&UserKey::new(organization_id, p.user_id))
+                    .cloned();
+
+                Post::new(p, author)
+            })
+            .collect();
+
+        Ok(posts)
+    }
+
+    pub fn new(post: post::Post, author: Option<User>) -> Self {
+        Post {
+            id: post.id,
+            content: post.content,
+            user_id: post.user_id,
+            author,
+            scope: post.scope,
+            created_at: post.created_at,
+            updated_at: post.updated_at,
+        }
+    }
+}
+
+impl Keyset for Post {
+    type Order = PostOrder;
+
+    fn sort_value(&self, _: PostOrder) -> SortValue {
+        SortValue::Timestamp(self.created_at)
+    }
+
+    fn keyset_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+#[ComplexObject]
+impl Post {
+    /// Relay global object identifier
+    pub async fn id(&self, _ctx: &Context<'_>) -> Result<ID> {
+        Ok(GlobalId::new("Post", self.id).encode())
+    }
+
+    /// Identifier expected by mutations
+    async fn uuid(&self) -> Uuid {
+        self.id
+    }
+
+    /// Author of the post. When it can't be loaded the field is `null` and
+    /// an error is reported, the other posts of a connection are unaffected.
+    async fn user(&self) -> crate::error::Result<Option<User>> {
+        match &self.author {
+            Some(author) => Ok(Some(author.clone())),
+            None => Err(Error::not_found("user", &self.user_id.to_string())),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
 pub struct PostError {
     field: Option<String>,
@@ -43,3 +131,73 @@ impl TryFrom<Error> for PostError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::connection::{Connection, Edge};
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use crate::graphql::error_path::ErrorPath;
+    use crate::graphql::partial_results::PartialResults;
+    use crate::modules::post::{self, Scope};
+    use crate::modules::user::User;
+
+    use super::Post;
+
+    /// Page of three posts, the author of the second one is missing
+    struct TestQuery;
+
+    #[Object]
+    impl TestQuery {
+        async fn posts(&self) -> Connection<usize, Post> {
+            let author = User::fixture("esteban");
+            let mut connection = Connection::new(false, false);
+
+            connection.append((0..3).map(|index| {
+                let post = post::Post {
+                    id: Uuid::new_v4(),
+                    content: format!("post-{index}"),
+                    user_id: author.id,
+                    scope: Scope::Public,
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                };
+
+                Edge::new(index, Post::new(post, (index != 1).then(|| author.clone())))
+            }));
+            connection
+        }
+    }
+
+    #[rocket::async_test]
+    async fn missing_authors_only_null_their_own_field() {
+        let schema = Schema::build(TestQuery, EmptyMutation, EmptySubscription)
+            .extension(ErrorPath)
+            .extension(PartialResults)
+            .finish();
+        let response = schema
+            .execute("{ posts { edges { node { content user { username } } } } }")
+            .await;
+        let data = response.data.into_json().unwrap();
+        let edges = data["posts"]["edges"].as_array().unwrap();
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(edges.len(), 3);
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(
+            error["path"],
+            serde_json::json!(["posts", "edges", 1, "node", "user"])
+        );
+        assert_eq!(error["extensions"]["code"], "NOT_FOUND");
+
+        for (index, edge) in edges.iter().enumerate() {
+            assert_eq!(edge["node"]["content"], format!("post-{index}"));
+        }
+
+        assert_eq!(edges[0]["node"]["user"]["username"], "esteban");
+        assert!(edges[1]["node"]["user"].is_null());
+        assert_eq!(edges[2]["node"]["user"]["username"], "esteban");
+    }
+}
@@ -6,10 +6,11 @@ use uuid::{self, Uuid};
 
 use crate::database::Database;
 use crate::error::Result;
+use crate::graphql::relay::{KeysetPage, KeysetQuery};
 use crate::modules::user::User;
 
 use super::entity::Post;
-use super::Scope;
+use super::{PostOrder, Scope};
 
 #[derive(Debug, Deserialize, FromRow, Serialize)]
 pub struct PostsTableRow {
@@ -50,24 +51,37 @@ impl PostRepository {
         Self { database }
     }
 
-    pub async fn find_by_author(&self, user_id: &Uuid) -> Result<Vec<Post>> {
-        let result: Vec<PostsTableRow> = sqlx::query_as("SELECT * FROM posts WHERE user_id = $1")
+    pub async fn find_by_id(&self, id: Uuid) -> Result<Option<Post>> {
+        let result: Option<PostsTableRow> = sqlx::query_as("SELECT * FROM posts WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.database.conn_pool)
+            .await?;
+
+        Ok(result.map(Post::from))
+    }
+
+    /// Retrieves a page of the posts authored by the provided user
+    pub async fn find_author_page(&self, user_id: Uuid, page: KeysetPage) -> Result<Vec<Post>> {
+        let query = KeysetQuery::new(
+            "SELECT * FROM posts WHERE user_id = $1",
+            1,
+            PostOrder::CreatedAtDesc,
+            page,
+        );
+        let result: Vec<PostsTableRow> = query
+            .fetch_all(&self.database.conn_pool, |query| query.bind(user_id))
+            .await?;
+
+        Ok(result.into_iter().map(Post::from).collect())
+    }
+
+    pub async fn count_by_author(&self, user_id: Uuid) -> Result<i64> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM posts WHERE user_id = $1")
             .bind(user_id)
-            .fetch_all(&self.database.conn_pool)
+            .fetch_one(&self.database.conn_pool)
             .await?;
-        let posts = result
-            .into_iter()
-            .map(|row| Post {
-                id: row.id,
-                user_id: row.user_id,
-                content: row.content,
-                scope: row.scope,
-                created_at: row.created_at,
-                updated_at: row.updated_at,
-            })
-            .collect::<Vec<Post>>();
 
-        Ok(posts)
+        Ok(count)
     }
 
     pub async fn insert(&self, user: User, dto: InsertPostTableRow) -> Result<Post> {
@@ -99,25 +113,42 @@ impl PostRepository {
         })
     }
 
-    pub async fn find_public_posts(&self, first: i32) -> Result<Vec<Post>> {
-        let result: Vec<PostsTableRow> = sqlx::query_as(
-            "SELECT * FROM posts WHERE scope = 'public' ORDER BY created_at DESC LIMIT $1",
+    /// Retrieves a page of the public posts authored by users of the
+    /// provided organization
+    pub async fn find_public_page(
+        &self,
+        organization_id: Uuid,
+        page: KeysetPage,
+    ) -> Result<Vec<Post>> {
+        let query = KeysetQuery::new(
+            r#"
+            SELECT * FROM posts
+            WHERE scope = 'public'
+            AND user_id IN (SELECT id FROM users WHERE organization_id = $1)"#,
+            1,
+            PostOrder::CreatedAtDesc,
+            page,
+        );
+        let result: Vec<PostsTableRow> = query
+            .fetch_all(&self.database.conn_pool, |query| {
+                query.bind(organization_id)
+            })
+            .await?;
+
+        Ok(result.into_iter().map(Post::from).collect())
+    }
+
+    pub async fn count_public_posts(&self, organization_id: Uuid) -> Result<i64> {
+        let count: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM posts
+            JOIN users ON users.id = posts.user_id
+            WHERE posts.scope = 'public' AND users.organization_id = $1"#,
         )
-        .bind(first)
-        .fetch_all(&self.database.conn_pool)
+        .bind(organization_id)
+        .fetch_one(&self.database.conn_pool)
         .await?;
-        let posts = result
-            .into_iter()
-            .map(|row| Post {
-                id: row.id,
-                user_id: row.user_id,
-                content: row.content,
-                scope: row.scope,
-                created_at: row.created_at,
-                updated_at: row.updated_at,
-            })
-            .collect::<Vec<Post>>();
 
-        Ok(posts)
+        Ok(count)
     }
 }
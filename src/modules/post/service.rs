@@ -1,6 +1,8 @@
 use std::sync::Arc;
+use uuid::Uuid;
 
 use crate::error::Result;
+use crate::graphql::relay::KeysetPage;
 use crate::modules::post::graphql::post_create::PostCreateInput;
 use crate::modules::user::User;
 
@@ -31,38 +33,33 @@ impl PostService {
         Ok(inserted)
     }
 
-    pub async fn find_by_author(&self, user: User) -> Result<Vec<Post>> {
-        let posts = self.repository.find_by_author(&user.id).await?;
-        let posts: Vec<Post> = posts
-            .into_iter()
-            .map(|post| Post {
-                id: post.id,
-                content: post.content,
-                scope: post.scope,
-                user_id: user.id,
-                created_at: post.created_at,
-                updated_at: post.updated_at,
-            })
-            .collect();
+    pub async fn find_by_id(&self, id: Uuid) -> Result<Option<Post>> {
+        self.repository.find_by_id(id).await
+    }
+
+    pub async fn find_author_page(&self, user: &User, page: KeysetPage) -> Result<Vec<Post>> {
+        self.repository.find_author_page(user.id, page).await
+    }
+
+    pub async fn count_by_author(&self, user_id: Uuid) -> Result<usize> {
+        let count = self.repository.count_by_author(user_id).await?;
+
+        Ok(count as usize)
+    }
 
-        Ok(posts)
+    pub async fn find_public_page(
+        &self,
+        organization_id: Uuid,
+        page: KeysetPage,
+    ) -> Result<Vec<Post>> {
+        self.repository
+            .find_public_page(organization_id, page)
+            .await
     }
 
-    pub async fn find_public_posts(&self, first: Option<i32>) -> Result<Vec<Post>> {
-        let first = first.unwrap_or(10);
-        let posts = self.repository.find_public_posts(first).await?;
-        let posts: Vec<Post> = posts
-            .into_iter()
-            .map(|post| Post {
-                id: post.id,
-                content: post.content,
-                scope: post.scope,
-                user_id: post.user_id,
-                created_at: post.created_at,
-                updated_at: post.updated_at,
-            })
-            .collect();
+    pub async fn count_public_posts(&self, organization_id: Uuid) -> Result<usize> {
+        let count = self.repository.count_public_posts(organization_id).await?;
 
-        Ok(posts)
+        Ok(count as usize)
     }
 }
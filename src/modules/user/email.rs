@@ -0,0 +1,153 @@
+use async_graphql::{InputValueError, InputValueResult, Scalar, ScalarType, Value};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::error::{Error, ErrorCode};
+
+/// Maximum length of an email address as per RFC 5321
+const MAX_EMAIL_LENGTH: usize = 254;
+
+const INVALID_EMAIL_MESSAGE: &str = "Invalid email address";
+
+/// Matches addresses accepted by the HTML `email` input type, additionally
+/// requiring a dot in the domain.
+static EMAIL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^[a-z0-9.!#$%&'*+/=?^_`{|}~-]+@[a-z0-9](?:[a-z0-9-]{0,61}[a-z0-9])?(?:\.[a-z0-9](?:[a-z0-9-]{0,61}[a-z0-9])?)+$").unwrap()
+});
+
+/// A valid email address, normalized to lowercase
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Email(String);
+
+impl Email {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for Email {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let email = value.trim().to_lowercase();
+
+        if email.len() > MAX_EMAIL_LENGTH || !EMAIL_RE.is_match(&email) {
+            return Err(Error::new(
+                "email",
+                INVALID_EMAIL_MESSAGE,
+                ErrorCode::ValidationError,
+            ));
+        }
+
+        Ok(Self(email))
+    }
+}
+
+impl FromStr for Email {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Email::try_from(value.to_string())
+    }
+}
+
+impl Display for Email {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<Email> for String {
+    fn from(email: Email) -> Self {
+        email.0
+    }
+}
+
+/// Email address, validated and normalized to lowercase
+#[Scalar]
+impl ScalarType for Email {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        match value {
+            Value::String(value) => {
+                Email::try_from(value).map_err(|_| InputValueError::custom(INVALID_EMAIL_MESSAGE))
+            }
+            _ => Err(InputValueError::expected_type(value)),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{Pos, ScalarType, Value};
+
+    use crate::error::ErrorCode;
+
+    use super::Email;
+
+    #[test]
+    fn accepts_valid_addresses() {
+        for email in [
+            "esteban@example.com",
+            "first.last+tag@sub.example.co",
+            "o'brien@example.org",
+            "user_1@my-domain.io",
+        ] {
+            assert!(email.parse::<Email>().is_ok(), "{email} should be valid");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        for email in [
+            "",
+            "plainaddress",
+            "@example.com",
+            "user@",
+            "user@localhost",
+            "user@exa mple.com",
+            "user@-example.com",
+            "user@example..com",
+            "user@@example.com",
+        ] {
+            let error = email.parse::<Email>().err().unwrap();
+
+            assert_eq!(error.code, ErrorCode::ValidationError, "{email}");
+            assert_eq!(error.field.as_deref(), Some("email"));
+        }
+    }
+
+    #[test]
+    fn normalizes_case_and_whitespace() {
+        let email = " User@Example.COM ".parse::<Email>().unwrap();
+
+        assert_eq!(email.as_str(), "user@example.com");
+    }
+
+    #[test]
+    fn rejects_too_long_addresses() {
+        let email = format!("{}@example.com", "a".repeat(250));
+
+        assert!(email.parse::<Email>().is_err());
+    }
+
+    #[test]
+    fn scalar_reports_clean_message() {
+        let error = <Email as ScalarType>::parse(Value::from("not an email"))
+            .err()
+            .unwrap()
+            .into_server_error(Pos::default());
+
+        assert_eq!(
+            error.message,
+            r#"Failed to parse "Email": Invalid email address"#
+        );
+    }
+}
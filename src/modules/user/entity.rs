@@ -1,8 +1,14 @@
-use async_graphql::{Enum, SimpleObject};
+use async_graphql::{ComplexObject, Context, Enum, Result, SimpleObject, ID};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::graphql::relay::{GlobalId, Keyset, KeysetColumn, KeysetOrder, SortValue};
+
+/// Organization existing users were migrated to, self-registered users join
+/// it as well
+pub const DEFAULT_ORGANIZATION_ID: Uuid = Uuid::nil();
+
 #[derive(Copy, Clone, Debug, Deserialize, Enum, PartialEq, Eq, Serialize, sqlx::Type)]
 #[sqlx(rename_all = "lowercase")]
 pub enum Gender {
@@ -19,19 +25,179 @@ pub enum Pronoun {
     They,
 }
 
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Deserialize,
+    Enum,
+    PartialEq,
+    Eq,
+    Serialize,
+    sqlx::Type,
+    rocket::FromFormField,
+)]
+#[sqlx(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    Admin,
+}
+
+/// Sorting applied when listing users
+#[derive(Copy, Clone, Debug, Default, Deserialize, Enum, PartialEq, Eq, Serialize)]
+pub enum UserOrder {
+    #[default]
+    CreatedAtAsc,
+    CreatedAtDesc,
+    UsernameAsc,
+    UsernameDesc,
+}
+
+impl KeysetOrder for UserOrder {
+    fn name(&self) -> &'static str {
+        match self {
+            UserOrder::CreatedAtAsc => "CREATED_AT_ASC",
+            UserOrder::CreatedAtDesc => "CREATED_AT_DESC",
+            UserOrder::UsernameAsc => "USERNAME_ASC",
+            UserOrder::UsernameDesc => "USERNAME_DESC",
+        }
+    }
+
+    fn descending(&self) -> bool {
+        matches!(self, UserOrder::CreatedAtDesc | UserOrder::UsernameDesc)
+    }
+
+    fn column(&self) -> KeysetColumn {
+        match self {
+            UserOrder::CreatedAtAsc | UserOrder::CreatedAtDesc => KeysetColumn {
+                name: "created_at",
+                sql_type: "timestamptz",
+            },
+            UserOrder::UsernameAsc | UserOrder::UsernameDesc => KeysetColumn {
+                name: "username",
+                sql_type: "text",
+            },
+        }
+    }
+}
+
+impl Role {
+    /// Checks whether this role grants the permissions of the `required`
+    /// role. The `Admin` role grants every permission.
+    pub fn satisfies(&self, required: Role) -> bool {
+        matches!(
+            (self, required),
+            (Role::Admin, _) | (Role::User, Role::User)
+        )
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+#[graphql(complex)]
 pub struct User {
+    #[graphql(skip)]
     pub id: Uuid,
     pub name: String,
     pub last_name: String,
     pub email: String,
+    pub email_verified: bool,
     pub username: String,
     #[graphql(skip)]
     pub password_hash: String,
+    /// Incremented every time the password changes, invalidating password
+    /// reset tokens issued before
+    #[graphql(skip)]
+    pub token_version: i32,
+    /// Consecutive failed logins since the last successful one or lock
+    #[graphql(skip)]
+    pub failed_login_count: i32,
+    /// Logins are rejected until then, even with valid credentials
+    #[graphql(skip)]
+    pub locked_until: Option<DateTime<Utc>>,
     pub gender: Gender,
     pub pronoun: Pronoun,
     pub custom_gender: Option<String>,
+    pub role: Role,
+    /// Tenant the user belongs to, users only see and act on users and
+    /// posts within their organization
+    pub organization_id: Uuid,
+    /// Incremented by every `userUpdate`, which must provide the version
+    /// the changes are based on
+    pub version: i32,
     pub birthdate: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Set when the account is deleted, deleted accounts are kept to
+    /// preserve references to them
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl User {
+    /// When the account is locked until, `None` if it's not locked at `now`.
+    /// Locks expire on their own.
+    pub fn active_lock(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.locked_until.filter(|locked_until| *locked_until > now)
+    }
+
+    /// Unverified user of the default organization, for tests to adjust
+    /// through struct update syntax
+    #[cfg(test)]
+    pub fn fixture(username: &str) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: String::from("Esteban"),
+            last_name: String::from("Borai"),
+            email: format!("{username}@example.com"),
+            email_verified: false,
+            username: String::from(username),
+            password_hash: String::new(),
+            token_version: 0,
+            failed_login_count: 0,
+            locked_until: None,
+            gender: Gender::Male,
+            pronoun: Pronoun::He,
+            custom_gender: None,
+            role: Role::User,
+            organization_id: DEFAULT_ORGANIZATION_ID,
+            version: 1,
+            birthdate: Utc::now(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }
+    }
+}
+
+impl Keyset for User {
+    type Order = UserOrder;
+
+    fn sort_value(&self, order: UserOrder) -> SortValue {
+        match order {
+            UserOrder::CreatedAtAsc | UserOrder::CreatedAtDesc => {
+                SortValue::Timestamp(self.created_at)
+            }
+            UserOrder::UsernameAsc | UserOrder::UsernameDesc => {
+                SortValue::Text(self.username.clone())
+            }
+        }
+    }
+
+    fn keyset_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+#[ComplexObject]
+impl User {
+    /// Relay global object identifier
+    pub async fn id(&self, _ctx: &Context<'_>) -> Result<ID> {
+        Ok(GlobalId::new("User", self.id).encode())
+    }
+
+    /// Identifier expected by mutations
+    async fn uuid(&self) -> Uuid {
+        self.id
+    }
 }
@@ -0,0 +1,162 @@
+use std::borrow::Cow;
+
+use chrono::SecondsFormat;
+use futures::future;
+use futures::stream::{self, Stream, StreamExt};
+
+use crate::error::Result;
+
+use super::{Role, User};
+
+/// Header line of the users CSV export
+pub const USERS_CSV_HEADER: &str = "id,username,email,role,createdAt\n";
+
+/// Maximum rows sent at once, rows already received from the database are
+/// sent together instead of waiting for this many
+const EXPORT_CHUNK_ROWS: usize = 256;
+
+/// Renders the `users` of an export with `record`, preceded by `header`.
+/// Rows received from the database are batched into chunks sent as soon as
+/// the database lags behind, so clients process rows while the query runs
+/// and slow clients hold back the query. The export is truncated at the
+/// first failing row, as the response is already underway.
+pub fn export_chunks<S>(
+    header: Option<&'static str>,
+    users: S,
+    record: fn(&User) -> String,
+) -> impl Stream<Item = String>
+where
+    S: Stream<Item = Result<User>>,
+{
+    let rows = users
+        .take_while(|user| {
+            if let Err(err) = user {
+                tracing::error!(?err, "failed to export users");
+            }
+
+            future::ready(user.is_ok())
+        })
+        .filter_map(move |user| future::ready(user.ok().map(|user| record(&user))))
+        .ready_chunks(EXPORT_CHUNK_ROWS)
+        .map(|rows| rows.concat());
+
+    stream::iter(header.map(String::from)).chain(rows)
+}
+
+fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::Admin => "ADMIN",
+        Role::User => "USER",
+    }
+}
+
+/// Renders a user as a line of the users CSV export
+pub fn users_csv_record(user: &User) -> String {
+    format!(
+        "{},{},{},{},{}\n",
+        user.id,
+        csv_field(&user.username),
+        csv_field(&user.email),
+        role_name(user.role),
+        user.created_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+    )
+}
+
+/// Renders a user as a line of the users JSON Lines export, holding the
+/// columns of the CSV export
+pub fn users_json_record(user: &User) -> String {
+    let record = serde_json::json!({
+        "id": user.id,
+        "username": user.username,
+        "email": user.email,
+        "role": role_name(user.role),
+        "createdAt": user.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+    });
+
+    format!("{record}\n")
+}
+
+/// Quotes values containing separators, quotes or line breaks. Values which
+/// spreadsheets would evaluate as formulas are prefixed with a quote.
+fn csv_field(value: &str) -> Cow<'_, str> {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        Cow::Owned(format!("'{value}"))
+    } else {
+        Cow::Borrowed(value)
+    };
+
+    if value.contains([',', '"', '\n', '\r']) {
+        return Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")));
+    }
+
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream::{self, StreamExt};
+
+    use crate::error::{Error, ErrorCode};
+    use crate::modules::user::User;
+
+    use super::{csv_field, export_chunks, users_json_record, EXPORT_CHUNK_ROWS};
+
+    fn user(index: usize) -> User {
+        User::fixture(&format!("user\"{index}"))
+    }
+
+    #[test]
+    fn plain_values_are_kept() {
+        assert_eq!(csv_field("esteban@nexus.dev"), "esteban@nexus.dev");
+    }
+
+    #[test]
+    fn values_with_separators_are_quoted() {
+        assert_eq!(csv_field("a,b"), r#""a,b""#);
+        assert_eq!(csv_field(r#"say "hi""#), r#""say ""hi""""#);
+        assert_eq!(csv_field("a\nb"), "\"a\nb\"");
+    }
+
+    #[test]
+    fn formulas_are_neutralized() {
+        assert_eq!(csv_field("=1+1"), "'=1+1");
+        assert_eq!(csv_field("@sum,1"), r#""'@sum,1""#);
+    }
+
+    #[rocket::async_test]
+    async fn json_lines_export_holds_a_record_per_line() {
+        let count = EXPORT_CHUNK_ROWS * 2 + 1;
+        let users = stream::iter((0..count).map(|index| Ok(user(index))));
+        let chunks = export_chunks(None, users, users_json_record)
+            .collect::<Vec<String>>()
+            .await;
+        let export = chunks.concat();
+
+        assert!(chunks.len() >= 3);
+        assert!(export.ends_with('\n'));
+        assert_eq!(export.lines().count(), count);
+
+        for (index, line) in export.lines().enumerate() {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+
+            assert_eq!(record["username"], format!("user\"{index}"));
+            assert_eq!(record["role"], "USER");
+        }
+    }
+
+    #[rocket::async_test]
+    async fn exports_are_truncated_at_the_first_failing_row() {
+        let users = stream::iter([
+            Ok(user(0)),
+            Err(Error::new("user", "Failed", ErrorCode::ServerError)),
+            Ok(user(2)),
+        ]);
+        let export = export_chunks(Some("header\n"), users, users_json_record)
+            .collect::<Vec<String>>()
+            .await
+            .concat();
+
+        assert_eq!(export.lines().count(), 2);
+        assert!(export.starts_with("header\n"));
+    }
+}
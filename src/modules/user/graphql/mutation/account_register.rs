@@ -1,16 +1,39 @@
-use async_graphql::{Context, Enum, InputObject, SimpleObject};
+use async_graphql::{ComplexObject, Context, Enum, InputObject, SimpleObject};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::error::{Error, ErrorCode, Result};
-use crate::modules::user::{Gender, Pronoun, User};
+use crate::error::{Error, ErrorCode};
+use crate::graphql::relay::{self, KeysetEdge, MutationPayload};
+use crate::mailer::Mailer;
+use crate::modules::audit::AuditContext;
+use crate::modules::user::{Email, Gender, Pronoun, User, UserOrder, Username};
 use crate::services::Services;
 
 #[derive(Debug, Deserialize, Serialize, SimpleObject)]
+#[graphql(complex)]
 pub struct AccountRegister {
     user: Option<User>,
     error: Option<AccountRegisterError>,
+    /// `clientMutationId` of the input, unchanged
+    client_mutation_id: Option<String>,
+}
+
+impl MutationPayload for AccountRegister {
+    fn client_mutation_id(&mut self) -> &mut Option<String> {
+        &mut self.client_mutation_id
+    }
+}
+
+#[ComplexObject]
+impl AccountRegister {
+    /// Edge of the registered user within the `users` connection sorted by
+    /// `orderBy`
+    async fn edge(&self, order_by: Option<UserOrder>) -> Option<KeysetEdge<User>> {
+        self.user
+            .as_ref()
+            .map(|user| relay::keyset_edge(user, order_by.unwrap_or_default()))
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
@@ -22,6 +45,7 @@ pub struct AccountRegisterError {
 
 #[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
 pub enum AccountRegisterErrorCode {
+    EmailTaken,
     UsernameTaken,
 }
 
@@ -30,14 +54,27 @@ impl TryFrom<Error> for AccountRegister {
 
     fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
         match value.code {
-            ErrorCode::Unique => Ok(AccountRegister {
-                user: None,
-                error: Some(AccountRegisterError {
-                    field: String::from("username"),
-                    message: String::from("Username is already taken"),
-                    code: AccountRegisterErrorCode::UsernameTaken,
+            ErrorCode::Unique => match value.field.as_deref() {
+                Some("email") => Ok(AccountRegister {
+                    user: None,
+                    error: Some(AccountRegisterError {
+                        field: String::from("email"),
+                        message: String::from("Email is already taken"),
+                        code: AccountRegisterErrorCode::EmailTaken,
+                    }),
+                    client_mutation_id: None,
+                }),
+                Some("username") => Ok(AccountRegister {
+                    user: None,
+                    error: Some(AccountRegisterError {
+                        field: String::from("username"),
+                        message: String::from("Username is already taken"),
+                        code: AccountRegisterErrorCode::UsernameTaken,
+                    }),
+                    client_mutation_id: None,
                 }),
-            }),
+                _ => Err(value),
+            },
             _ => Err(value),
         }
     }
@@ -48,23 +85,62 @@ impl TryFrom<Error> for AccountRegister {
 pub struct AccountRegisterInput {
     pub name: String,
     pub last_name: String,
-    pub email: String,
-    pub username: String,
+    pub email: Email,
+    pub username: Username,
     pub password: String,
     pub birthdate: DateTime<Utc>,
     pub gender: Gender,
     pub pronoun: Pronoun,
     pub custom_gender: Option<String>,
+    /// Echoed unchanged by the payload, matching it to the request
+    /// Left out of the idempotency fingerprint, as retries may send another
+    #[serde(skip)]
+    pub client_mutation_id: Option<String>,
 }
 
-pub async fn exec(ctx: &Context<'_>, input: AccountRegisterInput) -> Result<AccountRegister> {
+pub async fn exec(
+    ctx: &Context<'_>,
+    input: AccountRegisterInput,
+    idempotency_key: Option<String>,
+) -> async_graphql::Result<AccountRegister> {
     let services = ctx.data_unchecked::<Arc<Services>>();
 
-    match services.user.create(input).await {
-        Ok(user) => Ok(AccountRegister {
-            user: Some(user),
-            error: None,
-        }),
+    services
+        .user
+        .validate_password("password", &input.password)?;
+
+    let audit = AuditContext::from_ctx(ctx, None);
+    let created = match idempotency_key.as_deref() {
+        Some(idempotency_key) => {
+            services
+                .user
+                .create_idempotent(input, idempotency_key, audit)
+                .await
+        }
+        None => services.user.create(input, audit).await,
+    };
+
+    match created {
+        Ok(user) => {
+            let verification_token = services.auth.issue_email_verification_token(&user)?;
+            let body = format!(
+                "Welcome to Nexus, {}! Use the following token to verify your email address:\n\n{verification_token}\n",
+                user.name
+            );
+
+            // The account is created already, failing to deliver is only
+            // logged
+            let _ = ctx
+                .data_unchecked::<Arc<dyn Mailer>>()
+                .send(&user.email, "Verify your email address", &body)
+                .await;
+
+            Ok(AccountRegister {
+                user: Some(user),
+                error: None,
+                client_mutation_id: None,
+            })
+        }
         Err(err) => {
             let account_register = AccountRegister::try_from(err)?;
 
@@ -72,3 +148,133 @@ pub async fn exec(ctx: &Context<'_>, input: AccountRegisterInput) -> Result<Acco
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::connection::CursorType;
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Request, Schema};
+    use sqlx::postgres::PgPoolOptions;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::graphql::relay::{KeysetCursor, SortValue};
+    use crate::graphql::schema_builder;
+    use crate::mailer::{LogMailer, Mailer};
+    use crate::modules::user::User;
+    use crate::routes::AuthToken;
+    use crate::services::Services;
+
+    use super::AccountRegister;
+
+    struct TestQuery(User);
+
+    #[Object]
+    impl TestQuery {
+        async fn account_register(&self) -> AccountRegister {
+            AccountRegister {
+                user: Some(self.0.clone()),
+                error: None,
+                client_mutation_id: None,
+            }
+        }
+    }
+
+    async fn edge_cursor(user: &User, order_by: &str) -> KeysetCursor {
+        let schema = Schema::new(TestQuery(user.clone()), EmptyMutation, EmptySubscription);
+        let query = format!(
+            "{{ accountRegister {{ edge({order_by}) {{ cursor node {{ username }} }} }} }}"
+        );
+        let response = schema.execute(query).await;
+        let data = response.data.into_json().unwrap();
+        let edge = &data["accountRegister"]["edge"];
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(edge["node"]["username"], "esteban");
+
+        KeysetCursor::decode_cursor(edge["cursor"].as_str().unwrap()).unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn edge_cursor_points_at_the_registered_user() {
+        let user = User::fixture("esteban");
+
+        assert_eq!(
+            edge_cursor(&user, "orderBy: CREATED_AT_ASC").await,
+            KeysetCursor::new(
+                "CREATED_AT_ASC",
+                SortValue::Timestamp(user.created_at),
+                user.id
+            )
+        );
+        assert_eq!(
+            edge_cursor(&user, "orderBy: USERNAME_DESC").await,
+            KeysetCursor::new(
+                "USERNAME_DESC",
+                SortValue::Text(String::from("esteban")),
+                user.id
+            )
+        );
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn client_mutation_id_round_trips() {
+        let config = Config::testing(&std::env::var("DATABASE_URL").unwrap());
+        let database = Arc::new(Database {
+            conn_pool: PgPoolOptions::new()
+                .connect(&config.database_url)
+                .await
+                .unwrap(),
+        });
+        let services = Arc::new(Services::new(&config, Arc::clone(&database)));
+        let schema = schema_builder(&config.graphql)
+            .data(Arc::clone(&services))
+            .data(Arc::new(LogMailer) as Arc<dyn Mailer>)
+            .finish();
+        let username = format!("relay{}", &Uuid::new_v4().to_simple().to_string()[..16]);
+        let mutation = format!(
+            r#"mutation {{
+                accountRegister(input: {{
+                    name: "Relay", lastName: "Test", email: "{username}@nexus.dev",
+                    username: "{username}", password: "Correct horse battery staple 1",
+                    birthdate: "1990-01-01T00:00:00Z", gender: CUSTOM, pronoun: THEY,
+                    clientMutationId: "relay-1"
+                }}) {{ clientMutationId user {{ username }} error {{ code }} }}
+            }}"#
+        );
+        let register = || async {
+            let response = schema
+                .execute(Request::new(&mutation).data(AuthToken::empty()))
+                .await;
+
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            response.data.into_json().unwrap()["accountRegister"].clone()
+        };
+
+        let registered = register().await;
+
+        assert_eq!(registered["clientMutationId"], "relay-1");
+        assert_eq!(registered["user"]["username"], username.as_str());
+
+        // Payloads carrying an error echo it as well
+        let rejected = register().await;
+
+        assert_eq!(rejected["clientMutationId"], "relay-1");
+        assert_eq!(rejected["error"]["code"], "EMAIL_TAKEN");
+
+        sqlx::query(
+            "DELETE FROM audit_log WHERE target_id IN (SELECT id FROM users WHERE username = $1)",
+        )
+        .bind(&username)
+        .execute(&database.conn_pool)
+        .await
+        .unwrap();
+        sqlx::query("DELETE FROM users WHERE username = $1")
+            .bind(&username)
+            .execute(&database.conn_pool)
+            .await
+            .unwrap();
+    }
+}
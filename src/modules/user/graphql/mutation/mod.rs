@@ -1,22 +1,82 @@
 pub mod account_register;
+pub mod user_delete;
+pub mod user_role_update;
+pub mod user_unlock;
+pub mod user_update;
 
 use async_graphql::{Context, Object};
+use uuid::Uuid;
 
 use crate::error::Result;
+use crate::graphql::guards::{RoleGuard, ScopeGuard};
+use crate::graphql::relay::echo_client_mutation_id;
+use crate::modules::auth::ApiScope;
+use crate::modules::user::Role;
 
 use self::account_register::{AccountRegister, AccountRegisterInput};
+use self::user_delete::UserDelete;
+use self::user_role_update::UserRoleUpdate;
+use self::user_unlock::UserUnlock;
+use self::user_update::{UserUpdate, UserUpdateInput};
 
 #[derive(Default)]
 pub struct UserMutation;
 
 #[Object]
 impl UserMutation {
+    /// Registers a new account. Retried requests carrying the same
+    /// `idempotencyKey` and input return the user created by the first one.
     #[graphql(name = "accountRegister")]
     async fn account_register(
         &self,
         ctx: &Context<'_>,
-        input: AccountRegisterInput,
-    ) -> Result<AccountRegister> {
-        account_register::exec(ctx, input).await
+        mut input: AccountRegisterInput,
+        idempotency_key: Option<String>,
+    ) -> async_graphql::Result<AccountRegister> {
+        let client_mutation_id = input.client_mutation_id.take();
+
+        echo_client_mutation_id(
+            client_mutation_id,
+            account_register::exec(ctx, input, idempotency_key).await,
+        )
+    }
+
+    #[graphql(name = "userUpdate", guard = "ScopeGuard::new(ApiScope::UsersWrite)")]
+    async fn user_update(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        mut input: UserUpdateInput,
+    ) -> Result<UserUpdate> {
+        let client_mutation_id = input.client_mutation_id.take();
+
+        echo_client_mutation_id(client_mutation_id, UserUpdate::exec(ctx, id, input).await)
+    }
+
+    #[graphql(name = "userDelete", guard = "ScopeGuard::new(ApiScope::UsersWrite)")]
+    async fn user_delete(&self, ctx: &Context<'_>, id: Uuid) -> Result<UserDelete> {
+        UserDelete::exec(ctx, id).await
+    }
+
+    #[graphql(
+        name = "userRoleUpdate",
+        guard = "RoleGuard::new(Role::Admin).and(ScopeGuard::new(ApiScope::UsersWrite))"
+    )]
+    async fn user_role_update(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        role: Role,
+    ) -> Result<UserRoleUpdate> {
+        UserRoleUpdate::exec(ctx, id, role).await
+    }
+
+    /// Lifts the lock placed on an account after repeated failed logins
+    #[graphql(
+        name = "userUnlock",
+        guard = "RoleGuard::new(Role::Admin).and(ScopeGuard::new(ApiScope::UsersWrite))"
+    )]
+    async fn user_unlock(&self, ctx: &Context<'_>, id: Uuid) -> Result<UserUnlock> {
+        UserUnlock::exec(ctx, id).await
     }
 }
@@ -0,0 +1,89 @@
+use async_graphql::{Context, Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::graphql::guards::current_user;
+use crate::modules::audit::AuditContext;
+use crate::modules::user::{Role, User};
+use crate::services::Services;
+
+#[derive(Debug, Deserialize, Serialize, SimpleObject)]
+pub struct UserDelete {
+    user: Option<User>,
+    error: Option<UserDeleteError>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct UserDeleteError {
+    field: Option<String>,
+    message: Option<String>,
+    code: UserDeleteErrorCode,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
+pub enum UserDeleteErrorCode {
+    Forbidden,
+    NotFound,
+}
+
+impl TryFrom<Error> for UserDeleteError {
+    type Error = Error;
+
+    fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
+        match value.code {
+            ErrorCode::Forbidden => Ok(UserDeleteError {
+                field: Some(String::from("id")),
+                message: value.message,
+                code: UserDeleteErrorCode::Forbidden,
+            }),
+            ErrorCode::NotFound => Ok(UserDeleteError {
+                field: Some(String::from("id")),
+                message: value.message,
+                code: UserDeleteErrorCode::NotFound,
+            }),
+            _ => Err(value),
+        }
+    }
+}
+
+impl UserDelete {
+    pub async fn exec(ctx: &Context<'_>, id: Uuid) -> Result<UserDelete> {
+        let services = ctx.data_unchecked::<Arc<Services>>();
+        let caller = current_user(ctx).await?;
+
+        if caller.id != id && !caller.role.satisfies(Role::Admin) {
+            let user_delete_error =
+                UserDeleteError::try_from(Error::forbidden("delete this user"))?;
+
+            return Ok(UserDelete {
+                user: None,
+                error: Some(user_delete_error),
+            });
+        }
+
+        match services
+            .user
+            .delete(
+                id,
+                caller.organization_id,
+                AuditContext::from_ctx(ctx, Some(caller.id)),
+            )
+            .await
+        {
+            Ok(user) => Ok(UserDelete {
+                user: Some(user),
+                error: None,
+            }),
+            Err(err) => {
+                let user_delete_error = UserDeleteError::try_from(err)?;
+
+                Ok(UserDelete {
+                    user: None,
+                    error: Some(user_delete_error),
+                })
+            }
+        }
+    }
+}
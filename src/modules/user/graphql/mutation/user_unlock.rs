@@ -0,0 +1,44 @@
+use async_graphql::{Context, SimpleObject};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::error::Result;
+use crate::graphql::guards::current_user;
+use crate::modules::audit::AuditContext;
+use crate::modules::user::graphql::UserError;
+use crate::modules::user::User;
+use crate::services::Services;
+
+#[derive(Debug, Deserialize, Serialize, SimpleObject)]
+pub struct UserUnlock {
+    user: Option<User>,
+    error: Option<UserError>,
+}
+
+impl UserUnlock {
+    pub async fn exec(ctx: &Context<'_>, id: Uuid) -> Result<UserUnlock> {
+        let services = ctx.data_unchecked::<Arc<Services>>();
+        let caller = current_user(ctx).await?;
+        let audit = AuditContext::from_ctx(ctx, Some(caller.id));
+
+        match services
+            .user
+            .unlock(id, caller.organization_id, audit)
+            .await
+        {
+            Ok(user) => Ok(UserUnlock {
+                user: Some(user),
+                error: None,
+            }),
+            Err(err) => {
+                let user_error = UserError::try_from(err)?;
+
+                Ok(UserUnlock {
+                    user: None,
+                    error: Some(user_error),
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use async_graphql::{Context, Enum, InputObject, MaybeUndefined, SimpleObject};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::graphql::guards::current_user;
+use crate::graphql::relay::MutationPayload;
+use crate::modules::user::{Email, Role, User, Username};
+use crate::services::Services;
+
+#[derive(Debug, Deserialize, Serialize, SimpleObject)]
+pub struct UserUpdate {
+    user: Option<User>,
+    error: Option<UserUpdateError>,
+    /// `clientMutationId` of the input, unchanged
+    client_mutation_id: Option<String>,
+}
+
+impl MutationPayload for UserUpdate {
+    fn client_mutation_id(&mut self) -> &mut Option<String> {
+        &mut self.client_mutation_id
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, SimpleObject)]
+pub struct UserUpdateError {
+    field: Option<String>,
+    message: Option<String>,
+    code: UserUpdateErrorCode,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
+pub enum UserUpdateErrorCode {
+    EmailTaken,
+    UsernameTaken,
+    Forbidden,
+    /// The user was updated since it was fetched, refetch it and try again
+    Conflict,
+}
+
+impl TryFrom<Error> for UserUpdateError {
+    type Error = Error;
+
+    fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
+        match value.code {
+            ErrorCode::Unique => match value.field.as_deref() {
+                Some("email") => Ok(UserUpdateError {
+                    field: Some(String::from("email")),
+                    message: Some(String::from("Email is already taken")),
+                    code: UserUpdateErrorCode::EmailTaken,
+                }),
+                Some("username") => Ok(UserUpdateError {
+                    field: Some(String::from("username")),
+                    message: Some(String::from("Username is already taken")),
+                    code: UserUpdateErrorCode::UsernameTaken,
+                }),
+                _ => Err(value),
+            },
+            ErrorCode::Conflict => Ok(UserUpdateError {
+                field: value.field,
+                message: value.message,
+                code: UserUpdateErrorCode::Conflict,
+            }),
+            ErrorCode::Forbidden => Ok(UserUpdateError {
+                field: Some(String::from("id")),
+                message: value.message,
+                code: UserUpdateErrorCode::Forbidden,
+            }),
+            _ => Err(value),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, InputObject)]
+#[graphql(input_name = "UserUpdateInput")]
+pub struct UserUpdateInput {
+    pub username: Option<Username>,
+    pub email: Option<Email>,
+    /// Left unchanged when omitted, cleared when `null`
+    #[serde(default)]
+    pub custom_gender: MaybeUndefined<String>,
+    /// `version` of the user the changes are based on
+    pub version: i32,
+    /// Echoed unchanged by the payload, matching it to the request
+    pub client_mutation_id: Option<String>,
+}
+
+impl UserUpdate {
+    pub async fn exec(ctx: &Context<'_>, id: Uuid, input: UserUpdateInput) -> Result<UserUpdate> {
+        let caller = current_user(ctx).await?;
+
+        // Admins update users of their own organization only, users of other
+        // organizations are not found by the update
+        if caller.id != id && !caller.role.satisfies(Role::Admin) {
+            let user_update_error =
+                UserUpdateError::try_from(Error::forbidden("update this user"))?;
+
+            return Ok(UserUpdate {
+                user: None,
+                error: Some(user_update_error),
+                client_mutation_id: None,
+            });
+        }
+
+        let services = ctx.data_unchecked::<Arc<Services>>();
+
+        match services
+            .user
+            .update(id, caller.organization_id, input)
+            .await
+        {
+            Ok(user) => Ok(UserUpdate {
+                user: Some(user),
+                error: None,
+                client_mutation_id: None,
+            }),
+            Err(err) => {
+                let user_update_error = UserUpdateError::try_from(err)?;
+
+                Ok(UserUpdate {
+                    user: None,
+                    error: Some(user_update_error),
+                    client_mutation_id: None,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{value, InputType, Request};
+    use chrono::Utc;
+    use sqlx::postgres::PgPoolOptions;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use crate::config::{Config, GraphQLConfig};
+    use crate::database::Database;
+    use crate::graphql::schema_builder;
+    use crate::mailer::{LogMailer, Mailer};
+    use crate::modules::auth::Authenticated;
+    use crate::modules::user::User;
+    use crate::routes::AuthToken;
+    use crate::services::Services;
+
+    use super::UserUpdateInput;
+
+    fn custom_gender(input: async_graphql::Value) -> Option<Option<String>> {
+        UserUpdateInput::parse(Some(input))
+            .unwrap_or_else(|err| panic!("{}", err.into_server_error(Default::default()).message))
+            .custom_gender
+            .into()
+    }
+
+    #[test]
+    fn omitted_fields_are_left_unchanged() {
+        assert_eq!(custom_gender(value!({ "version": 1 })), None);
+    }
+
+    #[test]
+    fn null_fields_are_cleared() {
+        assert_eq!(
+            custom_gender(value!({ "version": 1, "customGender": null })),
+            Some(None)
+        );
+    }
+
+    #[test]
+    fn provided_fields_are_updated() {
+        assert_eq!(
+            custom_gender(value!({ "version": 1, "customGender": "Non-binary" })),
+            Some(Some(String::from("Non-binary")))
+        );
+    }
+
+    #[rocket::async_test]
+    async fn rejects_updates_of_other_users_by_non_admins() {
+        let schema = schema_builder(&GraphQLConfig::default()).finish();
+        let query = format!(
+            r#"mutation {{
+                userUpdate(id: "{}", input: {{ username: "taken", version: 1 }}) {{
+                    user {{ username }}
+                    error {{ field code }}
+                }}
+            }}"#,
+            Uuid::new_v4()
+        );
+        let request = Request::new(query)
+            .data(AuthToken::empty())
+            .data(Authenticated {
+                user: User::fixture("esteban"),
+                expires_at: Utc::now(),
+                scopes: None,
+            });
+        let response = schema.execute(request).await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let data = response.data.into_json().unwrap();
+
+        assert_eq!(data["userUpdate"]["user"], serde_json::Value::Null);
+        assert_eq!(data["userUpdate"]["error"]["code"], "FORBIDDEN");
+        assert_eq!(data["userUpdate"]["error"]["field"], "id");
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn renamed_users_keep_their_tokens_and_versions() {
+        let config = Config::testing(&std::env::var("DATABASE_URL").unwrap());
+        let database = Arc::new(Database {
+            conn_pool: PgPoolOptions::new()
+                .connect(&config.database_url)
+                .await
+                .unwrap(),
+        });
+        let services = Arc::new(Services::new(&config, Arc::clone(&database)));
+        let schema = schema_builder(&config.graphql)
+            .data(services)
+            .data(Arc::new(LogMailer) as Arc<dyn Mailer>)
+            .finish();
+        let execute = |query: String, token: Option<&str>| {
+            let auth = token.map_or_else(AuthToken::empty, AuthToken::new);
+            let request = Request::new(query).data(auth);
+
+            async {
+                let response = schema.execute(request).await;
+
+                assert!(response.errors.is_empty(), "{:?}", response.errors);
+                response.data.into_json().unwrap()
+            }
+        };
+        // Renames keep the email, registrations get one of their own
+        let register = |username: &str| {
+            let email = format!("{}@nexus.dev", Uuid::new_v4().to_simple());
+            let query = format!(
+                r#"mutation {{
+                    accountRegister(input: {{
+                        name: "Rename", lastName: "Test", email: "{email}",
+                        username: "{username}", password: "Correct horse battery staple 1",
+                        birthdate: "1990-01-01T00:00:00Z", gender: CUSTOM, pronoun: THEY
+                    }}) {{ user {{ uuid }} }}
+                }}"#
+            );
+
+            async {
+                let data = execute(query, None).await;
+
+                Uuid::parse_str(data["accountRegister"]["user"]["uuid"].as_str().unwrap()).unwrap()
+            }
+        };
+        let marker = &Uuid::new_v4().to_simple().to_string()[..16];
+        let (old_name, new_name) = (format!("old{marker}"), format!("new{marker}"));
+        let id = register(&old_name).await;
+        let data = execute(
+            format!(
+                r#"mutation {{
+                    tokenCreate(username: "{old_name}", password: "Correct horse battery staple 1") {{
+                        tokens {{ accessToken }}
+                    }}
+                }}"#
+            ),
+            None,
+        )
+        .await;
+        let token = data["tokenCreate"]["tokens"]["accessToken"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let rename = |version: i32| {
+            format!(
+                r#"mutation {{
+                    userUpdate(id: "{id}", input: {{ username: "{new_name}", version: {version} }}) {{
+                        user {{ username version }}
+                        error {{ field code }}
+                    }}
+                }}"#
+            )
+        };
+        let data = execute(rename(1), Some(&token)).await;
+
+        assert_eq!(data["userUpdate"]["user"]["username"], new_name.as_str());
+        assert_eq!(data["userUpdate"]["user"]["version"], 2);
+
+        // The freed username is taken by someone else, the token issued
+        // before the rename must still identify the renamed user
+        let other_id = register(&old_name).await;
+        let data = execute(
+            String::from("{ me { me { uuid username } } }"),
+            Some(&token),
+        )
+        .await;
+
+        assert_eq!(data["me"]["me"]["uuid"], id.to_string());
+        assert_eq!(data["me"]["me"]["username"], new_name.as_str());
+
+        let data = execute(rename(1), Some(&token)).await;
+
+        assert_eq!(data["userUpdate"]["user"], serde_json::Value::Null);
+        assert_eq!(data["userUpdate"]["error"]["code"], "CONFLICT");
+        assert_eq!(data["userUpdate"]["error"]["field"], "version");
+
+        let ids = vec![id, other_id];
+
+        sqlx::query("DELETE FROM audit_log WHERE actor_id = ANY($1) OR target_id = ANY($1)")
+            .bind(&ids)
+            .execute(&database.conn_pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM refresh_tokens WHERE user_id = ANY($1)")
+            .bind(&ids)
+            .execute(&database.conn_pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = ANY($1)")
+            .bind(&ids)
+            .execute(&database.conn_pool)
+            .await
+            .unwrap();
+    }
+}
@@ -1,12 +1,10 @@
 use async_graphql::{Context, SimpleObject};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
 
 use crate::error::Result;
-use crate::modules::user::graphql::types::UserError;
+use crate::graphql::guards::current_user;
+use crate::modules::user::graphql::UserError;
 use crate::modules::user::User;
-use crate::routes::AuthToken;
-use crate::services::Services;
 
 #[derive(Debug, Deserialize, Serialize, SimpleObject)]
 pub struct Me {
@@ -16,11 +14,7 @@ pub struct Me {
 
 impl Me {
     pub async fn exec(ctx: &Context<'_>) -> Result<Self> {
-        let auth = ctx.data_unchecked::<AuthToken>();
-        let services = ctx.data_unchecked::<Arc<Services>>();
-        let token = auth.token()?;
-
-        match services.auth.whoami(token).await {
+        match current_user(ctx).await {
             Ok(user) => Ok(Me {
                 me: Some(user),
                 error: None,
@@ -1,9 +1,13 @@
 pub mod me;
 pub mod users;
+pub mod users_by_ids;
 
-use async_graphql::{Context, Object};
+use async_graphql::{Context, Object, ID};
 
 use crate::error::Result;
+use crate::graphql::guards::{RoleGuard, ScopeGuard};
+use crate::modules::auth::ApiScope;
+use crate::modules::user::{Role, User, UserOrder};
 
 use self::me::Me;
 use self::users::{Users, UsersFilter};
@@ -18,6 +22,10 @@ impl UserQuery {
         Me::exec(ctx).await
     }
 
+    /// Lists the users of the caller's organization. Only available to
+    /// admins, as users expose their email and birthdate.
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(guard = "RoleGuard::new(Role::Admin).and(ScopeGuard::new(ApiScope::UsersRead))")]
     async fn users(
         &self,
         ctx: &Context<'_>,
@@ -26,7 +34,27 @@ impl UserQuery {
         first: Option<i32>,
         last: Option<i32>,
         filter: Option<UsersFilter>,
+        #[graphql(default = false)] include_deleted: bool,
+        order_by: Option<UserOrder>,
     ) -> Result<Users> {
-        Users::exec(ctx, after, before, first, last, filter).await
+        Users::exec(
+            ctx,
+            after,
+            before,
+            first,
+            last,
+            filter,
+            include_deleted,
+            order_by,
+        )
+        .await
+    }
+
+    /// Users with the provided global ids, in the same order. Ids of users
+    /// which don't exist or belong to another organization resolve to `null`.
+    /// Only available to admins, as users expose their email and birthdate.
+    #[graphql(guard = "RoleGuard::new(Role::Admin).and(ScopeGuard::new(ApiScope::UsersRead))")]
+    async fn users_by_ids(&self, ctx: &Context<'_>, ids: Vec<ID>) -> Result<Vec<Option<User>>> {
+        users_by_ids::exec(ctx, ids).await
     }
 }
@@ -2,24 +2,30 @@ use async_graphql::{Context, InputObject, SimpleObject};
 use std::sync::Arc;
 
 use crate::error::Result;
-use crate::graphql::relay::{self, RelayConnection};
-use crate::modules::user::graphql::types::UserError;
-use crate::modules::user::User;
-use crate::routes::AuthToken;
+use crate::graphql::guards::current_user;
+use crate::graphql::relay::{self, KeysetConnection};
+use crate::modules::user::graphql::UserError;
+use crate::modules::user::{search_pattern, Role, User, UserListFilter, UserOrder};
 use crate::services::Services;
 
 #[derive(SimpleObject)]
 pub struct Users {
-    users: Option<RelayConnection<User>>,
+    users: Option<KeysetConnection<User>>,
     error: Option<UserError>,
 }
 
 #[derive(InputObject)]
 pub struct UsersFilter {
+    /// Matches the user with exactly this username, combined with the other
+    /// conditions
     pub username: Option<String>,
+    /// Matches users whose username or email contains the provided term
+    pub search: Option<String>,
+    pub role: Option<Role>,
 }
 
 impl Users {
+    #[allow(clippy::too_many_arguments)]
     pub async fn exec(
         ctx: &Context<'_>,
         after: Option<String>,
@@ -27,61 +33,47 @@ impl Users {
         first: Option<i32>,
         last: Option<i32>,
         filter: Option<UsersFilter>,
+        include_deleted: bool,
+        order_by: Option<UserOrder>,
     ) -> Result<Users> {
-        let auth = ctx.data_unchecked::<AuthToken>();
         let services = ctx.data_unchecked::<Arc<Services>>();
-        let token = auth.token()?;
+        let caller = current_user(ctx).await?;
+        let params = relay::Params::new(after, before, first, last);
+        let order = order_by.unwrap_or_default();
 
-        services.auth.whoami(token).await?;
+        let mut list_filter = UserListFilter {
+            organization_id: caller.organization_id,
+            include_deleted,
+            ..UserListFilter::default()
+        };
 
         if let Some(filter) = filter {
-            if let Some(username) = filter.username {
-                return match services.user.find_by_username(username.as_str()).await {
-                    Ok(find_by_username) => {
-                        // The result is wrapped into a `Vec<User>` to keep
-                        // responses consistent.
-                        let res = if let Some(user) = find_by_username {
-                            vec![user]
-                        } else {
-                            Vec::default()
-                        };
+            list_filter.search = filter.search.as_deref().and_then(search_pattern);
+            list_filter.role = filter.role;
+            list_filter.username = filter.username;
+        }
 
-                        let users_connection = relay::query(
-                            res.into_iter(),
-                            relay::Params::new(after, before, first, last),
-                            10,
-                        )
-                        .await?;
-                        Ok(Users {
-                            users: Some(users_connection),
-                            error: None,
-                        })
-                    }
-                    Err(err) => {
-                        let user_error = UserError::try_from(err)?;
+        let list_filter = Arc::new(list_filter);
+        let count_filter = Arc::clone(&list_filter);
+        let count_services = Arc::clone(services);
+        let users_connection = relay::query_keyset(
+            params,
+            order,
+            Arc::new(move || {
+                let services = Arc::clone(&count_services);
+                let filter = Arc::clone(&count_filter);
 
-                        Ok(Users {
-                            users: None,
-                            error: Some(user_error),
-                        })
-                    }
-                };
-            }
-        }
+                Box::pin(async move { services.user.count(&filter).await })
+            }),
+            |page| async move { services.user.find_page(&list_filter, order, page).await },
+        )
+        .await;
 
-        match services.user.find_all().await {
-            Ok(users) => {
-                let users_connection = relay::query(
-                    users.into_iter(),
-                    relay::Params::new(after, before, first, last),
-                    10,
-                )
-                .await?;
-                Ok(Users {
-                    users: Some(users_connection),
-                    error: None,
-                })
-            }
+        match users_connection {
+            Ok(users_connection) => Ok(Users {
+                users: Some(users_connection),
+                error: None,
+            }),
             Err(err) => {
                 let user_error = UserError::try_from(err)?;
 
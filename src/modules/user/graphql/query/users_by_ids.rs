@@ -0,0 +1,183 @@
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{Context, ID};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::graphql::guards::current_user;
+use crate::graphql::loaders::{UserKey, UserLoader};
+use crate::graphql::relay::GlobalId;
+use crate::modules::user::User;
+
+/// Maximum amount of ids `usersByIds` accepts, from
+/// `GraphQLConfig::max_batch_ids`
+#[derive(Clone, Copy, Debug)]
+pub struct MaxBatchIds(pub usize);
+
+/// Resolves the users of the caller's organization with the provided global
+/// ids in a single batch, in the requested order. Ids of users which don't
+/// exist, were deleted or aren't users are resolved to `null`.
+pub async fn exec(ctx: &Context<'_>, ids: Vec<ID>) -> Result<Vec<Option<User>>> {
+    let MaxBatchIds(max_batch_ids) = *ctx.data_unchecked::<MaxBatchIds>();
+
+    if ids.len() > max_batch_ids {
+        return Err(Error::new(
+            "ids",
+            &format!("At most {max_batch_ids} ids can be requested at once"),
+            ErrorCode::ValidationError,
+        ));
+    }
+
+    let caller = current_user(ctx).await?;
+    let user_ids = decode_user_ids(&ids);
+    let keys = user_ids
+        .iter()
+        .flatten()
+        .map(|id| UserKey::new(caller.organization_id, *id))
+        .collect::<HashSet<UserKey>>();
+    let users = ctx
+        .data_unchecked::<DataLoader<UserLoader>>()
+        .load_many(keys)
+        .await?
+        .into_iter()
+        .map(|(key, user)| (key.id, user))
+        .collect();
+
+    Ok(in_requested_order(&user_ids, users))
+}
+
+/// Decodes the global ids of users, `None` for ids of other types or which
+/// can't be decoded
+fn decode_user_ids(ids: &[ID]) -> Vec<Option<Uuid>> {
+    ids.iter()
+        .map(|id| {
+            GlobalId::decode(id)
+                .filter(|global_id| global_id.type_name == "User")
+                .map(|global_id| global_id.id)
+        })
+        .collect()
+}
+
+/// Places every loaded user at the position of each id it was requested by,
+/// duplicated ids resolving to the same user
+fn in_requested_order(ids: &[Option<Uuid>], users: HashMap<Uuid, User>) -> Vec<Option<User>> {
+    ids.iter()
+        .map(|id| {
+            id.and_then(|id| users.get(&id))
+                .filter(|user| user.deleted_at.is_none())
+                .cloned()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{Request, ID};
+    use chrono::Utc;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    use crate::config::GraphQLConfig;
+    use crate::graphql::relay::GlobalId;
+    use crate::graphql::schema_builder;
+    use crate::modules::auth::Authenticated;
+    use crate::modules::user::{Role, User};
+    use crate::routes::AuthToken;
+
+    use super::{decode_user_ids, in_requested_order};
+
+    fn resolve(ids: &[Option<Uuid>], users: &[&User]) -> Vec<Option<String>> {
+        let users = users
+            .iter()
+            .map(|user| (user.id, (*user).clone()))
+            .collect::<HashMap<Uuid, User>>();
+
+        in_requested_order(ids, users)
+            .into_iter()
+            .map(|user| user.map(|user| user.username))
+            .collect()
+    }
+
+    #[test]
+    fn resolves_users_in_the_requested_order() {
+        let (ana, bob) = (User::fixture("ana"), User::fixture("bob"));
+
+        assert_eq!(
+            resolve(&[Some(bob.id), Some(ana.id)], &[&ana, &bob]),
+            [Some(String::from("bob")), Some(String::from("ana"))]
+        );
+    }
+
+    #[test]
+    fn missing_users_are_null() {
+        let ana = User::fixture("ana");
+        let mut deleted = User::fixture("deleted");
+
+        deleted.deleted_at = Some(Utc::now());
+
+        assert_eq!(
+            resolve(
+                &[Some(Uuid::new_v4()), Some(ana.id), None, Some(deleted.id)],
+                &[&ana, &deleted]
+            ),
+            [None, Some(String::from("ana")), None, None]
+        );
+    }
+
+    #[test]
+    fn duplicated_ids_keep_their_positions() {
+        let (ana, bob) = (User::fixture("ana"), User::fixture("bob"));
+
+        assert_eq!(
+            resolve(&[Some(ana.id), Some(bob.id), Some(ana.id)], &[&ana, &bob]),
+            [
+                Some(String::from("ana")),
+                Some(String::from("bob")),
+                Some(String::from("ana"))
+            ]
+        );
+    }
+
+    #[test]
+    fn only_decodes_user_ids() {
+        let id = Uuid::new_v4();
+
+        assert_eq!(
+            decode_user_ids(&[
+                GlobalId::new("User", id).encode(),
+                GlobalId::new("Post", id).encode(),
+                ID::from("not a global id"),
+            ]),
+            [Some(id), None, None]
+        );
+    }
+
+    #[rocket::async_test]
+    async fn rejects_more_ids_than_allowed() {
+        let config = GraphQLConfig {
+            max_batch_ids: 2,
+            ..GraphQLConfig::default()
+        };
+        let schema = schema_builder(&config).finish();
+        let ids = (0..3)
+            .map(|_| format!("\"{}\"", GlobalId::new("User", Uuid::new_v4()).encode().0))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let request = Request::new(format!("{{ usersByIds(ids: [{ids}]) {{ id }} }}"))
+            .data(AuthToken::empty())
+            .data(Authenticated {
+                user: User {
+                    role: Role::Admin,
+                    ..User::fixture("ana")
+                },
+                expires_at: Utc::now(),
+                scopes: None,
+            });
+        let response = schema.execute(request).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(error["extensions"]["code"], "VALIDATION_ERROR");
+        assert_eq!(error["extensions"]["field"], "ids");
+    }
+}
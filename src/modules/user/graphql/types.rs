@@ -13,6 +13,7 @@ pub struct UserError {
 #[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, PartialEq, Serialize)]
 pub enum UserErrorCode {
     Unauthorized,
+    Forbidden,
 }
 
 impl TryFrom<Error> for UserError {
@@ -20,11 +21,16 @@ impl TryFrom<Error> for UserError {
 
     fn try_from(value: Error) -> std::result::Result<Self, Self::Error> {
         match value.code {
-            ErrorCode::InvalidJsonWebToken => Ok(UserError {
+            ErrorCode::InvalidJsonWebToken | ErrorCode::Unauthorized => Ok(UserError {
                 field: None,
                 message: None,
                 code: UserErrorCode::Unauthorized,
             }),
+            ErrorCode::Forbidden => Ok(UserError {
+                field: None,
+                message: value.message,
+                code: UserErrorCode::Forbidden,
+            }),
             _ => Err(value),
         }
     }
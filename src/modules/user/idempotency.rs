@@ -0,0 +1,256 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::ttl_map::TtlMap;
+
+/// Amount of keys remembered at most, the key expiring the soonest is
+/// forgotten to make room for another
+const MAX_TRACKED_KEYS: usize = 100_000;
+
+/// Amount of minutes a processed request is remembered by its key
+pub const IDEMPOTENCY_KEY_TTL_MINUTES: u64 = 60;
+
+/// Outcome of claiming an idempotency key
+#[derive(Debug, Eq, PartialEq)]
+pub enum IdempotencyClaim {
+    /// The key wasn't seen before, the request must be processed
+    Fresh,
+    /// A request with the same key and payload already created this user
+    Replay(Uuid),
+}
+
+struct IdempotencyEntry {
+    fingerprint: String,
+    /// `None` while the first request is being processed
+    user_id: Option<Uuid>,
+}
+
+/// Remembers the user created by requests carrying an idempotency key, so
+/// retried requests return the original user instead of creating another.
+///
+/// Keys are kept in memory, so requests are only deduplicated within one
+/// process: retries reaching another instance, or sent after a restart,
+/// create another user.
+pub struct IdempotencyKeys {
+    ttl: Duration,
+    entries: Mutex<TtlMap<String, IdempotencyEntry>>,
+}
+
+impl Default for IdempotencyKeys {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(IDEMPOTENCY_KEY_TTL_MINUTES * 60))
+    }
+}
+
+impl IdempotencyKeys {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(TtlMap::new(MAX_TRACKED_KEYS)),
+        }
+    }
+
+    /// Claims `key` for a request whose payload hashes to `fingerprint`.
+    /// Reusing a key with another payload, or while the request which
+    /// claimed it is still being processed, is rejected.
+    pub fn claim(&self, key: &str, fingerprint: &str) -> Result<IdempotencyClaim> {
+        self.claim_at(key, fingerprint, Instant::now())
+    }
+
+    /// Records the user created by the request which claimed `key`
+    pub fn complete(&self, key: &str, user_id: Uuid) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(key, Instant::now()) {
+            entry.user_id = Some(user_id);
+        }
+    }
+
+    /// Forgets `key` after the request which claimed it failed, so it can be
+    /// retried
+    pub fn release(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key, Instant::now());
+    }
+
+    /// Claims `key` like `claim`, the returned guard releases it unless the
+    /// request completes. Requests whose future is dropped between the claim
+    /// and their completion, e.g. because the client went away, don't keep
+    /// their key in-flight for good.
+    pub fn claim_guarded<'a>(
+        &'a self,
+        key: &'a str,
+        fingerprint: &str,
+    ) -> Result<GuardedClaim<'a>> {
+        Ok(match self.claim(key, fingerprint)? {
+            IdempotencyClaim::Fresh => GuardedClaim::Fresh(ClaimedKey {
+                keys: self,
+                key,
+                completed: false,
+            }),
+            IdempotencyClaim::Replay(user_id) => GuardedClaim::Replay(user_id),
+        })
+    }
+
+    fn claim_at(&self, key: &str, fingerprint: &str, now: Instant) -> Result<IdempotencyClaim> {
+        let mut entries = self.entries.lock().unwrap();
+
+        if let Some(entry) = entries.get(key, now) {
+            if entry.fingerprint != fingerprint {
+                return Err(Error::new(
+                    "idempotencyKey",
+                    "The idempotency key was already used with a different payload",
+                    ErrorCode::ValidationError,
+                ));
+            }
+
+            return match entry.user_id {
+                Some(user_id) => Ok(IdempotencyClaim::Replay(user_id)),
+                None => Err(Error::new(
+                    "idempotencyKey",
+                    "A request with this idempotency key is still being processed",
+                    ErrorCode::ValidationError,
+                )),
+            };
+        }
+
+        entries.insert(
+            key.to_string(),
+            IdempotencyEntry {
+                fingerprint: fingerprint.to_string(),
+                user_id: None,
+            },
+            now + self.ttl,
+            now,
+        );
+
+        Ok(IdempotencyClaim::Fresh)
+    }
+}
+
+/// Outcome of claiming an idempotency key through `claim_guarded`
+pub enum GuardedClaim<'a> {
+    Fresh(ClaimedKey<'a>),
+    Replay(Uuid),
+}
+
+/// Key claimed by a request being processed, released when dropped before
+/// the request completes
+pub struct ClaimedKey<'a> {
+    keys: &'a IdempotencyKeys,
+    key: &'a str,
+    completed: bool,
+}
+
+impl ClaimedKey<'_> {
+    /// Records the user created by the request, keeping the key claimed
+    pub fn complete(mut self, user_id: Uuid) {
+        self.keys.complete(self.key, user_id);
+        self.completed = true;
+    }
+}
+
+impl Drop for ClaimedKey<'_> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.keys.release(self.key);
+        }
+    }
+}
+
+/// Hashes the provided payload, requests are only replayed for an identical
+/// payload
+pub fn fingerprint<T: Serialize>(payload: &T) -> Result<String> {
+    let json = serde_json::to_vec(payload).map_err(|err| Error::unhandled(Box::new(err)))?;
+
+    Ok(hex::encode(Sha256::digest(&json)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use uuid::Uuid;
+
+    use crate::error::ErrorCode;
+
+    use super::{GuardedClaim, IdempotencyClaim, IdempotencyKeys};
+
+    #[test]
+    fn first_request_is_processed() {
+        let keys = IdempotencyKeys::default();
+
+        assert_eq!(
+            keys.claim("signup-1", "payload").unwrap(),
+            IdempotencyClaim::Fresh
+        );
+    }
+
+    #[test]
+    fn replay_with_same_payload_returns_the_original_user() {
+        let keys = IdempotencyKeys::default();
+        let user_id = Uuid::new_v4();
+
+        keys.claim("signup-1", "payload").unwrap();
+        keys.complete("signup-1", user_id);
+
+        assert_eq!(
+            keys.claim("signup-1", "payload").unwrap(),
+            IdempotencyClaim::Replay(user_id)
+        );
+    }
+
+    #[test]
+    fn replay_with_different_payload_is_rejected() {
+        let keys = IdempotencyKeys::default();
+
+        keys.claim("signup-1", "payload").unwrap();
+        keys.complete("signup-1", Uuid::new_v4());
+
+        let error = keys.claim("signup-1", "another payload").err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::ValidationError);
+        assert_eq!(error.field.as_deref(), Some("idempotencyKey"));
+        assert!(error.message.unwrap().contains("different payload"));
+    }
+
+    #[test]
+    fn released_and_expired_keys_can_be_reused() {
+        let keys = IdempotencyKeys::new(Duration::from_secs(60));
+        let now = Instant::now();
+
+        keys.claim_at("signup-1", "payload", now).unwrap();
+        assert!(keys.claim_at("signup-1", "payload", now).is_err());
+
+        keys.release("signup-1");
+        keys.claim_at("signup-1", "payload", now).unwrap();
+        keys.complete("signup-1", Uuid::new_v4());
+
+        assert_eq!(
+            keys.claim_at("signup-1", "other", now + Duration::from_secs(61))
+                .unwrap(),
+            IdempotencyClaim::Fresh
+        );
+    }
+
+    #[test]
+    fn guarded_claims_are_released_unless_completed() {
+        let keys = IdempotencyKeys::default();
+        let user_id = Uuid::new_v4();
+
+        match keys.claim_guarded("signup-1", "payload").unwrap() {
+            GuardedClaim::Fresh(claimed) => drop(claimed),
+            GuardedClaim::Replay(_) => panic!("the key wasn't claimed yet"),
+        }
+
+        match keys.claim_guarded("signup-1", "payload").unwrap() {
+            GuardedClaim::Fresh(claimed) => claimed.complete(user_id),
+            GuardedClaim::Replay(_) => panic!("the dropped claim wasn't released"),
+        }
+
+        assert_eq!(
+            keys.claim("signup-1", "payload").unwrap(),
+            IdempotencyClaim::Replay(user_id)
+        );
+    }
+}
@@ -1,9 +1,19 @@
+mod email;
 mod entity;
+mod export;
+mod idempotency;
+mod password;
 mod repository;
 mod service;
+mod username;
 
 pub mod graphql;
 
+pub use email::*;
 pub use entity::*;
+pub use export::*;
+pub use idempotency::*;
+pub use password::*;
 pub use repository::*;
 pub use service::*;
+pub use username::*;
@@ -0,0 +1,439 @@
+use argon2::{self, Config};
+use hmac::{Hmac, Mac};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sha2::Sha256;
+
+use crate::config::{Argon2Config, PasswordPolicyConfig};
+use crate::error::{Error, ErrorCode, Result, ValidationError};
+
+/// Length of the random salt used when hashing a password
+const SALT_LENGTH: usize = 30;
+
+/// Plain text of the hash verified against when there is no user
+const DUMMY_PASSWORD: &str = "dummy password";
+
+/// Outcome of verifying a password against a stored hash
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PasswordMatch {
+    Mismatch,
+    Match,
+    /// The hash was created before the pepper was configured, the password
+    /// must be hashed again
+    Unpeppered,
+}
+
+impl PasswordMatch {
+    pub fn is_match(self) -> bool {
+        self != PasswordMatch::Mismatch
+    }
+}
+
+/// Verifies `candidate` against an encoded argon2 `hash`. A mismatch is
+/// `Ok(false)`, only failing to verify at all, e.g. because the stored hash
+/// is malformed, is an error. It's logged and reported as `SERVER_ERROR`,
+/// so a corrupt hash is never mistaken for invalid credentials.
+fn verify_password(hash: &str, candidate: &[u8]) -> Result<bool> {
+    argon2::verify_encoded(hash, candidate).map_err(|err| {
+        Error::server_error(format_args!(
+            "failed to verify a password against its hash: {err}"
+        ))
+    })
+}
+
+/// Hashes and verifies passwords using the configured argon2 parameters.
+/// When a pepper is configured, passwords are keyed with it through
+/// HMAC-SHA256 before being hashed.
+pub struct PasswordHasher {
+    config: Argon2Config,
+    dummy_hash: String,
+}
+
+impl PasswordHasher {
+    /// Creates a hasher along with its dummy hash, computed upfront so the
+    /// first verification without a user doesn't take longer than others.
+    pub fn new(config: Argon2Config) -> Self {
+        let mut hasher = Self {
+            config,
+            dummy_hash: String::new(),
+        };
+
+        hasher.dummy_hash = hasher
+            .hash(DUMMY_PASSWORD)
+            .expect("Failed to hash the dummy password");
+        hasher
+    }
+
+    pub fn hash(&self, raw: &str) -> Result<String> {
+        let salt: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SALT_LENGTH)
+            .map(char::from)
+            .collect();
+        let config = Config {
+            mem_cost: self.config.memory_kib,
+            time_cost: self.config.iterations,
+            lanes: self.config.parallelism,
+            ..Config::default()
+        };
+        let hash = argon2::hash_encoded(&self.peppered(raw), salt.as_bytes(), &config)?;
+
+        Ok(hash)
+    }
+
+    pub fn verify(&self, hash: &str, raw: &str) -> Result<bool> {
+        Ok(self.check(hash, raw)?.is_match())
+    }
+
+    /// Verifies `raw` against `hash`. Hashes created before the pepper was
+    /// configured are verified without it, so existing passwords keep
+    /// working until they are hashed again.
+    pub fn check(&self, hash: &str, raw: &str) -> Result<PasswordMatch> {
+        if verify_password(hash, &self.peppered(raw))? {
+            return Ok(PasswordMatch::Match);
+        }
+
+        if self.config.pepper.is_some() && verify_password(hash, raw.as_bytes())? {
+            return Ok(PasswordMatch::Unpeppered);
+        }
+
+        Ok(PasswordMatch::Mismatch)
+    }
+
+    /// Keys `raw` with the configured pepper, as is without one
+    fn peppered(&self, raw: &str) -> Vec<u8> {
+        let pepper = match &self.config.pepper {
+            Some(pepper) => pepper,
+            None => return raw.as_bytes().to_vec(),
+        };
+        let mut mac = Hmac::<Sha256>::new_from_slice(pepper.as_bytes())
+            .expect("HMAC accepts keys of any length");
+
+        mac.update(raw.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    /// Verifies `raw` against a hash created with the configured parameters,
+    /// used to take as long as a real verification when there is no hash to
+    /// verify against, e.g. for unknown usernames.
+    pub fn verify_dummy(&self, raw: &str) -> Result<()> {
+        self.verify(&self.dummy_hash, raw)?;
+
+        Ok(())
+    }
+
+    /// Hashes `raw` again if the provided `hash` was created with weaker
+    /// parameters than the configured ones or without the pepper. Expects
+    /// `raw` to be already verified against `hash`, `matched` being the
+    /// outcome.
+    pub fn rehash_if_outdated(
+        &self,
+        hash: &str,
+        raw: &str,
+        matched: PasswordMatch,
+    ) -> Result<Option<String>> {
+        if matched != PasswordMatch::Unpeppered && !self.is_outdated(hash) {
+            return Ok(None);
+        }
+
+        self.hash(raw).map(Some)
+    }
+
+    /// Checks the `m`, `t` and `p` parameters of an encoded hash such as
+    /// `$argon2i$v=19$m=4096,t=3,p=1$<salt>$<hash>` against the configured
+    /// ones. Hashes which can't be parsed are considered outdated.
+    fn is_outdated(&self, hash: &str) -> bool {
+        let params = match hash.split('$').find(|segment| segment.starts_with("m=")) {
+            Some(params) => params,
+            None => return true,
+        };
+        let mut memory_kib = 0;
+        let mut iterations = 0;
+        let mut parallelism = 0;
+
+        for param in params.split(',') {
+            let (key, value) = match param.split_once('=') {
+                Some(pair) => pair,
+                None => return true,
+            };
+            let value = match value.parse::<u32>() {
+                Ok(value) => value,
+                Err(_) => return true,
+            };
+
+            match key {
+                "m" => memory_kib = value,
+                "t" => iterations = value,
+                "p" => parallelism = value,
+                _ => {}
+            }
+        }
+
+        memory_kib < self.config.memory_kib
+            || iterations < self.config.iterations
+            || parallelism < self.config.parallelism
+    }
+}
+
+/// Checks new passwords against the configured rules
+pub struct PasswordPolicy {
+    config: PasswordPolicyConfig,
+}
+
+impl PasswordPolicy {
+    pub fn new(config: PasswordPolicyConfig) -> Self {
+        Self { config }
+    }
+
+    /// Collects an error for every rule `raw` doesn't comply with, reported
+    /// on the provided input `field`
+    pub fn validate(&self, field: &str, raw: &str) -> std::result::Result<(), ValidationError> {
+        let mut validation_error = ValidationError::new();
+        let mut fail = |message: String| {
+            validation_error.push(Error::new(field, &message, ErrorCode::ValidationError));
+        };
+
+        if raw.chars().count() < self.config.min_length {
+            fail(format!(
+                "Password must have at least {} characters",
+                self.config.min_length
+            ));
+        }
+
+        if self.config.require_mixed_case
+            && !(raw.chars().any(char::is_lowercase) && raw.chars().any(char::is_uppercase))
+        {
+            fail(String::from(
+                "Password must have both lowercase and uppercase letters",
+            ));
+        }
+
+        if self.config.require_digit && !raw.chars().any(|ch| ch.is_ascii_digit()) {
+            fail(String::from("Password must have at least one digit"));
+        }
+
+        if self.config.require_symbol && raw.chars().all(char::is_alphanumeric) {
+            fail(String::from("Password must have at least one symbol"));
+        }
+
+        validation_error.into_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Argon2Config, PasswordPolicyConfig};
+
+    use crate::error::ErrorCode;
+
+    use super::{verify_password, PasswordHasher, PasswordMatch, PasswordPolicy};
+
+    fn strict_policy() -> PasswordPolicy {
+        PasswordPolicy::new(PasswordPolicyConfig {
+            min_length: 10,
+            require_mixed_case: true,
+            require_digit: true,
+            require_symbol: true,
+            ..PasswordPolicyConfig::default()
+        })
+    }
+
+    fn failures(raw: &str) -> Vec<String> {
+        strict_policy()
+            .validate("password", raw)
+            .err()
+            .map(|validation_error| {
+                validation_error
+                    .errors
+                    .into_iter()
+                    .map(|error| {
+                        assert_eq!(error.field.as_deref(), Some("password"));
+                        error.message.unwrap()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn accepts_compliant_password() {
+        assert!(failures("Correct-Horse-9").is_empty());
+    }
+
+    #[test]
+    fn rejects_short_password() {
+        assert_eq!(
+            failures("Sh0rt!"),
+            vec![String::from("Password must have at least 10 characters")]
+        );
+    }
+
+    #[test]
+    fn rejects_password_without_mixed_case() {
+        assert_eq!(
+            failures("lowercase-only-9"),
+            vec![String::from(
+                "Password must have both lowercase and uppercase letters"
+            )]
+        );
+    }
+
+    #[test]
+    fn rejects_password_without_digit() {
+        assert_eq!(
+            failures("Without-Digits"),
+            vec![String::from("Password must have at least one digit")]
+        );
+    }
+
+    #[test]
+    fn rejects_password_without_symbol() {
+        assert_eq!(
+            failures("WithoutSymbols9"),
+            vec![String::from("Password must have at least one symbol")]
+        );
+    }
+
+    #[test]
+    fn collects_every_failed_rule() {
+        assert_eq!(failures("a").len(), 4);
+    }
+
+    fn weak_config() -> Argon2Config {
+        Argon2Config {
+            memory_kib: 1024,
+            iterations: 1,
+            parallelism: 1,
+            pepper: None,
+        }
+    }
+
+    fn strong_config() -> Argon2Config {
+        Argon2Config {
+            memory_kib: 2048,
+            iterations: 2,
+            parallelism: 1,
+            pepper: None,
+        }
+    }
+
+    fn peppered_config(pepper: &str) -> Argon2Config {
+        Argon2Config {
+            pepper: Some(pepper.to_string()),
+            ..weak_config()
+        }
+    }
+
+    #[test]
+    fn upgrades_hash_created_with_weaker_params() {
+        let old_hash = PasswordHasher::new(weak_config()).hash("secret").unwrap();
+        let hasher = PasswordHasher::new(strong_config());
+
+        assert!(hasher.verify(&old_hash, "secret").unwrap());
+
+        let new_hash = hasher
+            .rehash_if_outdated(&old_hash, "secret", PasswordMatch::Match)
+            .unwrap()
+            .expect("expected the hash to be upgraded");
+
+        assert!(new_hash.contains("m=2048,t=2,p=1"));
+        assert!(hasher.verify(&new_hash, "secret").unwrap());
+        assert!(hasher
+            .rehash_if_outdated(&new_hash, "secret", PasswordMatch::Match)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn keeps_hash_created_with_stronger_params() {
+        let hash = PasswordHasher::new(strong_config()).hash("secret").unwrap();
+        let hasher = PasswordHasher::new(weak_config());
+
+        assert!(hasher
+            .rehash_if_outdated(&hash, "secret", PasswordMatch::Match)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn rejects_wrong_password() {
+        let hasher = PasswordHasher::new(weak_config());
+        let hash = hasher.hash("secret").unwrap();
+
+        assert!(!hasher.verify(&hash, "not-the-secret").unwrap());
+    }
+
+    #[test]
+    fn verifies_passwords_against_their_hash() {
+        let hash = PasswordHasher::new(weak_config()).hash("secret").unwrap();
+
+        assert!(verify_password(&hash, b"secret").unwrap());
+        assert!(!verify_password(&hash, b"not-the-secret").unwrap());
+    }
+
+    #[test]
+    fn corrupt_hashes_are_server_errors() {
+        let hasher = PasswordHasher::new(weak_config());
+
+        for hash in ["", "not a hash", "$argon2i$v=19$m=1024,t=1,p=1$c2FsdA"] {
+            let error = verify_password(hash, b"secret").err().unwrap();
+
+            assert_eq!(error.code, ErrorCode::ServerError);
+            assert_eq!(
+                hasher.verify(hash, "secret").err().unwrap().code,
+                ErrorCode::ServerError
+            );
+        }
+    }
+
+    #[test]
+    fn dummy_hash_uses_configured_params() {
+        let hasher = PasswordHasher::new(weak_config());
+
+        assert!(hasher.dummy_hash.contains("m=1024,t=1,p=1"));
+        assert!(hasher.verify_dummy("not-the-secret").is_ok());
+    }
+
+    #[test]
+    fn peppered_hashes_require_the_pepper() {
+        let hash = PasswordHasher::new(peppered_config("pepper"))
+            .hash("secret")
+            .unwrap();
+
+        assert!(PasswordHasher::new(peppered_config("pepper"))
+            .verify(&hash, "secret")
+            .unwrap());
+        assert!(!PasswordHasher::new(weak_config())
+            .verify(&hash, "secret")
+            .unwrap());
+        assert!(!PasswordHasher::new(peppered_config("another-pepper"))
+            .verify(&hash, "secret")
+            .unwrap());
+    }
+
+    #[test]
+    fn upgrades_hash_created_without_pepper() {
+        let old_hash = PasswordHasher::new(weak_config()).hash("secret").unwrap();
+        let hasher = PasswordHasher::new(peppered_config("pepper"));
+        let matched = hasher.check(&old_hash, "secret").unwrap();
+
+        assert_eq!(matched, PasswordMatch::Unpeppered);
+        assert_eq!(
+            hasher.check(&old_hash, "not-the-secret").unwrap(),
+            PasswordMatch::Mismatch
+        );
+
+        let new_hash = hasher
+            .rehash_if_outdated(&old_hash, "secret", matched)
+            .unwrap()
+            .expect("expected the hash to be peppered");
+
+        assert_eq!(
+            hasher.check(&new_hash, "secret").unwrap(),
+            PasswordMatch::Match
+        );
+        assert!(!PasswordHasher::new(weak_config())
+            .verify(&new_hash, "secret")
+            .unwrap());
+    }
+}
@@ -1,15 +1,19 @@
 use chrono::{DateTime, Utc};
+use futures::stream::{BoxStream, StreamExt};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use sqlx::pool::Pool;
-use sqlx::{FromRow, Postgres};
+use sqlx::{FromRow, PgConnection, Postgres};
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::database::Database;
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::graphql::relay::{KeysetPage, KeysetQuery};
+use crate::modules::audit::{insert_audit_entry, NewAuditEntry};
 
 use super::entity::User;
-use super::{Gender, Pronoun};
+use super::{Gender, Pronoun, Role, UserOrder};
 
 #[derive(Debug, Deserialize, FromRow, Serialize)]
 pub struct UsersTableRow {
@@ -17,14 +21,22 @@ pub struct UsersTableRow {
     pub name: String,
     pub last_name: String,
     pub email: String,
+    pub email_verified: bool,
     pub username: String,
     pub password_hash: String,
+    pub token_version: i32,
+    pub failed_login_count: i32,
+    pub locked_until: Option<DateTime<Utc>>,
     pub gender: Gender,
     pub pronoun: Pronoun,
     pub custom_gender: Option<String>,
+    pub role: Role,
+    pub organization_id: Uuid,
+    pub version: i32,
     pub birthdate: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 impl From<UsersTableRow> for User {
@@ -34,14 +46,22 @@ impl From<UsersTableRow> for User {
             name: dto.name,
             last_name: dto.last_name,
             email: dto.email,
+            email_verified: dto.email_verified,
             username: dto.username,
             password_hash: dto.password_hash,
+            token_version: dto.token_version,
+            failed_login_count: dto.failed_login_count,
+            locked_until: dto.locked_until,
             gender: dto.gender,
             pronoun: dto.pronoun,
             custom_gender: dto.custom_gender,
+            role: dto.role,
+            organization_id: dto.organization_id,
+            version: dto.version,
             birthdate: dto.birthdate,
             created_at: dto.created_at,
             updated_at: dto.updated_at,
+            deleted_at: dto.deleted_at,
         }
     }
 }
@@ -59,6 +79,39 @@ pub struct InsertUserTableRow {
     pub custom_gender: Option<String>,
 }
 
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct UpdateUserTableRow {
+    pub username: Option<String>,
+    pub email: Option<String>,
+    /// `Some(None)` clears the column
+    pub custom_gender: Option<Option<String>>,
+}
+
+/// `WHERE` conditions for `UserListFilter`, bound to the first five
+/// parameters
+const LIST_FILTER: &str = "($1 OR deleted_at IS NULL) \
+    AND ($2::text IS NULL OR username ILIKE $2 OR email ILIKE $2) \
+    AND ($3::role IS NULL OR role = $3::role) \
+    AND organization_id = $4 \
+    AND ($5::text IS NULL OR username = $5)";
+
+/// Selects every user matching `UserListFilter` in creation order
+static EXPORT_QUERY: Lazy<String> = Lazy::new(|| {
+    format!("SELECT * FROM users WHERE {LIST_FILTER} ORDER BY created_at ASC, id ASC")
+});
+
+/// Conditions applied when listing users. `search` is an `ILIKE` pattern
+/// matched against usernames and emails, `username` must match exactly.
+/// Only users of `organization_id` are listed.
+#[derive(Debug, Default)]
+pub struct UserListFilter {
+    pub organization_id: Uuid,
+    pub include_deleted: bool,
+    pub search: Option<String>,
+    pub role: Option<Role>,
+    pub username: Option<String>,
+}
+
 pub struct UserRepository {
     database: Arc<Database>,
 }
@@ -68,18 +121,102 @@ impl UserRepository {
         Self { database }
     }
 
-    pub async fn find_all(&self) -> Result<Vec<User>> {
-        let result: Vec<UsersTableRow> = sqlx::query_as("SELECT * FROM users")
-            .fetch_all(&self.database.conn_pool)
+    /// Retrieves a page of the users matching `filter` sorted by `order`,
+    /// ties are sorted by id so keyset cursors point to a single row.
+    pub async fn find_page(
+        &self,
+        filter: &UserListFilter,
+        order: UserOrder,
+        page: KeysetPage,
+    ) -> Result<Vec<User>> {
+        let query = KeysetQuery::new(
+            &format!("SELECT * FROM users WHERE {LIST_FILTER}"),
+            5,
+            order,
+            page,
+        );
+        let result: Vec<UsersTableRow> = self
+            .database
+            .timed_query("users.find_page", |mut conn| async move {
+                query
+                    .fetch_all(&mut conn, |query| {
+                        query
+                            .bind(filter.include_deleted)
+                            .bind(filter.search.as_deref())
+                            .bind(filter.role)
+                            .bind(filter.organization_id)
+                            .bind(filter.username.as_deref())
+                    })
+                    .await
+            })
             .await?;
         let users = result.into_iter().map(User::from).collect::<Vec<User>>();
 
         Ok(users)
     }
 
+    /// Streams every user matching `filter`, rows are decoded as they are
+    /// received instead of being collected.
+    pub fn stream<'a>(&'a self, filter: &'a UserListFilter) -> BoxStream<'a, Result<User>> {
+        sqlx::query_as::<_, UsersTableRow>(&EXPORT_QUERY)
+            .bind(filter.include_deleted)
+            .bind(filter.search.as_deref())
+            .bind(filter.role)
+            .bind(filter.organization_id)
+            .bind(filter.username.as_deref())
+            .fetch(&self.database.conn_pool)
+            .map(|row| row.map(User::from).map_err(Error::from))
+            .boxed()
+    }
+
+    pub async fn count(&self, filter: &UserListFilter) -> Result<i64> {
+        let query = format!("SELECT COUNT(*) FROM users WHERE {LIST_FILTER}");
+        let count: i64 = self
+            .database
+            .timed_query("users.count", |mut conn| async move {
+                let count = sqlx::query_scalar(&query)
+                    .bind(filter.include_deleted)
+                    .bind(filter.search.as_deref())
+                    .bind(filter.role)
+                    .bind(filter.organization_id)
+                    .bind(filter.username.as_deref())
+                    .fetch_one(&mut conn)
+                    .await?;
+
+                Ok(count)
+            })
+            .await?;
+
+        Ok(count)
+    }
+
+    /// Finds the user regardless of its organization. Only for ids that
+    /// didn't come from clients, such as those of signed tokens: users
+    /// requested through the API are loaded by `UserLoader`, scoped to the
+    /// caller's organization.
+    pub async fn find_by_id(&self, id: Uuid) -> Result<Option<User>> {
+        let result: Option<UsersTableRow> =
+            sqlx::query_as("SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL")
+                .bind(id)
+                .fetch_optional(&self.database.conn_pool)
+                .await?;
+
+        Ok(result.map(User::from))
+    }
+
+    pub async fn find_by_email(&self, email: &str) -> Result<Option<User>> {
+        let result: Option<UsersTableRow> =
+            sqlx::query_as("SELECT * FROM users WHERE email = $1 AND deleted_at IS NULL")
+                .bind(email)
+                .fetch_optional(&self.database.conn_pool)
+                .await?;
+
+        Ok(result.map(User::from))
+    }
+
     pub async fn find_by_username(&self, username: &str) -> Result<Option<User>> {
         let result: Option<UsersTableRow> =
-            sqlx::query_as("SELECT * FROM users WHERE username = $1")
+            sqlx::query_as("SELECT * FROM users WHERE username = $1 AND deleted_at IS NULL")
                 .bind(username)
                 .fetch_optional::<&Pool<Postgres>>(&self.database.conn_pool)
                 .await?;
@@ -91,7 +228,10 @@ impl UserRepository {
         Ok(None)
     }
 
-    pub async fn insert(&self, dto: InsertUserTableRow) -> Result<User> {
+    /// Inserts the user and records `audit` targeting it within the same
+    /// transaction
+    pub async fn insert(&self, dto: InsertUserTableRow, mut audit: NewAuditEntry) -> Result<User> {
+        let mut tx = self.database.conn_pool.begin().await?;
         let result: UsersTableRow = sqlx::query_as(
             r#"
             INSERT INTO users (
@@ -125,9 +265,744 @@ impl UserRepository {
         .bind(dto.gender)
         .bind(dto.pronoun)
         .bind(dto.custom_gender)
-        .fetch_one(&self.database.conn_pool)
+        .fetch_one(&mut tx)
         .await?;
 
+        audit.target_id = Some(result.id);
+        insert_audit_entry(&mut tx, &audit).await?;
+        tx.commit().await?;
+
         Ok(User::from(result))
     }
+
+    /// Updates the provided columns of the user with the given `id`, columns
+    /// set to `None` are left untouched while nullable ones set to
+    /// `Some(None)` are cleared. Fails with `CONFLICT` unless the
+    /// user is still at `version`, which is incremented. Users of other
+    /// organizations than `organization_id` are not found.
+    pub async fn update(
+        &self,
+        id: Uuid,
+        organization_id: Uuid,
+        version: i32,
+        dto: UpdateUserTableRow,
+    ) -> Result<User> {
+        let result: Option<UsersTableRow> = sqlx::query_as(
+            r#"
+            UPDATE users SET
+                username = COALESCE($2, username),
+                email_verified = email_verified AND ($3 IS NULL OR $3 = email),
+                email = COALESCE($3, email),
+                custom_gender = CASE WHEN $5 THEN $6 ELSE custom_gender END,
+                version = version + 1,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND organization_id = $7 AND deleted_at IS NULL AND version = $4
+            RETURNING *"#,
+        )
+        .bind(id)
+        .bind(dto.username)
+        .bind(dto.email)
+        .bind(version)
+        .bind(dto.custom_gender.is_some())
+        .bind(dto.custom_gender.flatten())
+        .bind(organization_id)
+        .fetch_optional(&self.database.conn_pool)
+        .await?;
+
+        if let Some(row) = result {
+            return Ok(User::from(row));
+        }
+
+        let exists: bool = sqlx::query_scalar(
+            r#"SELECT EXISTS (
+                SELECT 1 FROM users WHERE id = $1 AND organization_id = $2 AND deleted_at IS NULL
+            )"#,
+        )
+        .bind(id)
+        .bind(organization_id)
+        .fetch_one(&self.database.conn_pool)
+        .await?;
+
+        if exists {
+            return Err(Error::stale_version("user"));
+        }
+
+        Err(Error::not_found("user", &id.to_string()))
+    }
+
+    /// Marks the user as deleted instead of removing its row, which would
+    /// break references to it. Users of other organizations are not found.
+    pub async fn soft_delete(
+        &self,
+        id: Uuid,
+        organization_id: Uuid,
+        audit: NewAuditEntry,
+    ) -> Result<User> {
+        let mut tx = self.database.conn_pool.begin().await?;
+        let result: Option<UsersTableRow> = sqlx::query_as(
+            r#"
+            UPDATE users SET
+                deleted_at = CURRENT_TIMESTAMP,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND organization_id = $2 AND deleted_at IS NULL
+            RETURNING *"#,
+        )
+        .bind(id)
+        .bind(organization_id)
+        .fetch_optional(&mut tx)
+        .await?;
+        let user = result
+            .map(User::from)
+            .ok_or_else(|| Error::not_found("user", &id.to_string()))?;
+
+        insert_audit_entry(&mut tx, &audit).await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    /// Replaces the password hash, moving the current one into the user's
+    /// password history which keeps the `history_size` most recent hashes.
+    /// Only for the authenticated user's own id, which is why organizations
+    /// aren't checked. Deleted users are not found.
+    pub async fn update_password_hash(
+        &self,
+        id: Uuid,
+        password_hash: &str,
+        history_size: usize,
+        audit: NewAuditEntry,
+    ) -> Result<User> {
+        let mut tx = self.database.conn_pool.begin().await?;
+
+        push_password_history(&mut tx, id, history_size).await?;
+
+        let result: Option<UsersTableRow> = sqlx::query_as(
+            r#"
+            UPDATE users SET
+                password_hash = $2,
+                token_version = token_version + 1,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING *"#,
+        )
+        .bind(id)
+        .bind(password_hash)
+        .fetch_optional(&mut tx)
+        .await?;
+        let user = result
+            .map(User::from)
+            .ok_or_else(|| Error::not_found("user", &id.to_string()))?;
+
+        insert_audit_entry(&mut tx, &audit).await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    /// Replaces the password hash with an equivalent one for the same
+    /// password, tokens bound to the current password remain valid.
+    pub async fn upgrade_password_hash(&self, id: Uuid, password_hash: &str) -> Result<()> {
+        sqlx::query("UPDATE users SET password_hash = $2 WHERE id = $1")
+            .bind(id)
+            .bind(password_hash)
+            .execute(&self.database.conn_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Replaces the password hash only if the user's `token_version` still
+    /// matches the provided one. Returns `None` otherwise. The password
+    /// history is kept as by `update_password_hash`.
+    pub async fn reset_password_hash(
+        &self,
+        id: Uuid,
+        password_hash: &str,
+        token_version: i32,
+        history_size: usize,
+    ) -> Result<Option<User>> {
+        let mut tx = self.database.conn_pool.begin().await?;
+
+        push_password_history(&mut tx, id, history_size).await?;
+
+        let result: Option<UsersTableRow> = sqlx::query_as(
+            r#"
+            UPDATE users SET
+                password_hash = $2,
+                token_version = token_version + 1,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND token_version = $3
+            RETURNING *"#,
+        )
+        .bind(id)
+        .bind(password_hash)
+        .bind(token_version)
+        .fetch_optional(&mut tx)
+        .await?;
+
+        // Dropping the transaction discards the history entry
+        if result.is_some() {
+            tx.commit().await?;
+        }
+
+        Ok(result.map(User::from))
+    }
+
+    /// Hashes the user's password was replaced from, most recent first
+    pub async fn find_password_history(&self, id: Uuid) -> Result<Vec<String>> {
+        let hashes: Vec<(String,)> = sqlx::query_as(
+            "SELECT password_hash FROM password_history WHERE user_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(id)
+        .fetch_all(&self.database.conn_pool)
+        .await?;
+
+        Ok(hashes.into_iter().map(|(hash,)| hash).collect())
+    }
+
+    /// Deleted users and users of other organizations than `organization_id`
+    /// are not found
+    pub async fn update_role(
+        &self,
+        id: Uuid,
+        organization_id: Uuid,
+        role: Role,
+        audit: NewAuditEntry,
+    ) -> Result<User> {
+        let mut tx = self.database.conn_pool.begin().await?;
+        let result: Option<UsersTableRow> = sqlx::query_as(
+            r#"
+            UPDATE users SET
+                role = $2::role,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND organization_id = $3 AND deleted_at IS NULL
+            RETURNING *"#,
+        )
+        .bind(id)
+        .bind(role)
+        .bind(organization_id)
+        .fetch_optional(&mut tx)
+        .await?;
+        let user = result
+            .map(User::from)
+            .ok_or_else(|| Error::not_found("user", &id.to_string()))?;
+
+        insert_audit_entry(&mut tx, &audit).await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    /// Counts a failed login of the user, locking it for `lock_secs` once
+    /// `threshold` consecutive logins failed. The counter restarts from zero
+    /// when the account locks. Returns when the account is locked until, if
+    /// this failure locked it.
+    pub async fn record_login_failure(
+        &self,
+        id: Uuid,
+        threshold: i32,
+        lock_secs: i64,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let locked_until: Option<Option<DateTime<Utc>>> = sqlx::query_scalar(
+            r#"
+            UPDATE users SET
+                failed_login_count = CASE
+                    WHEN failed_login_count + 1 >= $2 THEN 0
+                    ELSE failed_login_count + 1
+                END,
+                locked_until = CASE
+                    WHEN failed_login_count + 1 >= $2
+                    THEN CURRENT_TIMESTAMP + $3 * INTERVAL '1 second'
+                    ELSE locked_until
+                END
+            WHERE id = $1
+            RETURNING CASE WHEN failed_login_count = 0 THEN locked_until END"#,
+        )
+        .bind(id)
+        .bind(threshold)
+        .bind(lock_secs)
+        .fetch_optional(&self.database.conn_pool)
+        .await?;
+
+        Ok(locked_until.flatten())
+    }
+
+    /// Clears the failed logins of the user after a successful login, rows
+    /// without any are left untouched
+    pub async fn reset_login_failures(&self, id: Uuid) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE users SET
+                failed_login_count = 0,
+                locked_until = NULL
+            WHERE id = $1 AND (failed_login_count > 0 OR locked_until IS NOT NULL)"#,
+        )
+        .bind(id)
+        .execute(&self.database.conn_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Lifts the lock and clears the failed logins of the user. Users of
+    /// other organizations than `organization_id` are not found.
+    pub async fn unlock(
+        &self,
+        id: Uuid,
+        organization_id: Uuid,
+        audit: NewAuditEntry,
+    ) -> Result<User> {
+        let mut tx = self.database.conn_pool.begin().await?;
+        let result: Option<UsersTableRow> = sqlx::query_as(
+            r#"
+            UPDATE users SET
+                failed_login_count = 0,
+                locked_until = NULL
+            WHERE id = $1 AND organization_id = $2 AND deleted_at IS NULL
+            RETURNING *"#,
+        )
+        .bind(id)
+        .bind(organization_id)
+        .fetch_optional(&mut tx)
+        .await?;
+        let user = result
+            .map(User::from)
+            .ok_or_else(|| Error::not_found("user", &id.to_string()))?;
+
+        insert_audit_entry(&mut tx, &audit).await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    /// Only for the ids of signed verification tokens, which is why
+    /// organizations aren't checked. Deleted users are not found.
+    pub async fn set_email_verified(&self, id: Uuid) -> Result<User> {
+        let result: Option<UsersTableRow> = sqlx::query_as(
+            r#"
+            UPDATE users SET
+                email_verified = TRUE,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING *"#,
+        )
+        .bind(id)
+        .fetch_optional(&self.database.conn_pool)
+        .await?;
+
+        result
+            .map(User::from)
+            .ok_or_else(|| Error::not_found("user", &id.to_string()))
+    }
+}
+
+/// Copies the current password hash of the user `id` into its history and
+/// evicts the oldest entries beyond `history_size`. Must run before the hash
+/// is replaced, within the same transaction.
+async fn push_password_history(
+    conn: &mut PgConnection,
+    id: Uuid,
+    history_size: usize,
+) -> Result<()> {
+    if history_size == 0 {
+        return Ok(());
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO password_history (user_id, password_hash)
+        SELECT id, password_hash FROM users WHERE id = $1"#,
+    )
+    .bind(id)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(
+        r#"
+        DELETE FROM password_history
+        WHERE user_id = $1 AND id NOT IN (
+            SELECT id FROM password_history
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+        )"#,
+    )
+    .bind(id)
+    .bind(history_size as i64)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use sqlx::postgres::PgPoolOptions;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use crate::database::Database;
+    use crate::error::ErrorCode;
+    use crate::graphql::relay::{Keyset, KeysetOrder, KeysetPage};
+    use crate::modules::audit::{AuditAction, AuditContext, AuditOutcome};
+    use crate::modules::user::{search_pattern, Role, UserOrder, DEFAULT_ORGANIZATION_ID};
+
+    use super::{UpdateUserTableRow, UserListFilter, UserRepository};
+
+    async fn repository() -> (Arc<Database>, UserRepository) {
+        let database_url = std::env::var("DATABASE_URL").unwrap();
+        let database = Arc::new(Database {
+            conn_pool: PgPoolOptions::new().connect(&database_url).await.unwrap(),
+        });
+
+        (Arc::clone(&database), UserRepository::new(database))
+    }
+
+    async fn insert_user(database: &Database) -> Uuid {
+        let marker = Uuid::new_v4().to_simple().to_string();
+        let (id,): (Uuid,) = sqlx::query_as(
+            r#"
+            INSERT INTO users (name, last_name, email, username, password_hash, birthdate, gender, pronoun)
+            VALUES ('Lockout', 'Test', $1 || '@nexus.dev', $1, '', CURRENT_TIMESTAMP, 'custom', 'they')
+            RETURNING id"#,
+        )
+        .bind(&marker)
+        .fetch_one(&database.conn_pool)
+        .await
+        .unwrap();
+
+        id
+    }
+
+    async fn delete_user(database: &Database, id: Uuid) {
+        sqlx::query("DELETE FROM audit_log WHERE target_id = $1")
+            .bind(id)
+            .execute(&database.conn_pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(id)
+            .execute(&database.conn_pool)
+            .await
+            .unwrap();
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn pages_through_users_sharing_a_sort_value_once() {
+        let (database, repository) = repository().await;
+        let marker = format!("tie{}", &Uuid::new_v4().to_simple().to_string()[..12]);
+        let created_at = Utc::now();
+        let mut expected = Vec::new();
+
+        for index in 0..12 {
+            let (id,): (Uuid,) = sqlx::query_as(
+                r#"
+                INSERT INTO users (name, last_name, email, username, password_hash, birthdate, gender, pronoun, created_at)
+                VALUES ('Tie', 'Test', $1 || '@nexus.dev', $1, '', CURRENT_TIMESTAMP, 'custom', 'they', $2)
+                RETURNING id"#,
+            )
+            .bind(format!("{marker}{index:02}"))
+            .bind(created_at)
+            .fetch_one(&database.conn_pool)
+            .await
+            .unwrap();
+
+            expected.push(id);
+        }
+
+        let filter = UserListFilter {
+            organization_id: DEFAULT_ORGANIZATION_ID,
+            search: search_pattern(&marker),
+            ..UserListFilter::default()
+        };
+
+        for order in [UserOrder::CreatedAtAsc, UserOrder::CreatedAtDesc] {
+            let mut visited = Vec::new();
+            let mut after = None;
+
+            loop {
+                let page = KeysetPage {
+                    after: after.take(),
+                    before: None,
+                    limit: 5,
+                    backward: false,
+                };
+                let users = repository.find_page(&filter, order, page).await.unwrap();
+
+                visited.extend(users.iter().map(|user| user.id));
+
+                match users.last() {
+                    Some(last) if users.len() == 5 => after = Some(last.keyset_cursor(order)),
+                    _ => break,
+                }
+            }
+
+            let mut sorted = expected.clone();
+
+            sorted.sort();
+
+            if order.descending() {
+                sorted.reverse();
+            }
+
+            assert_eq!(visited, sorted);
+        }
+
+        for id in expected {
+            delete_user(&database, id).await;
+        }
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn locks_account_after_consecutive_failures() {
+        let (database, repository) = repository().await;
+        let id = insert_user(&database).await;
+
+        for _ in 0..2 {
+            assert_eq!(
+                repository.record_login_failure(id, 3, 60).await.unwrap(),
+                None
+            );
+        }
+
+        let locked_until = repository
+            .record_login_failure(id, 3, 60)
+            .await
+            .unwrap()
+            .unwrap();
+        let user = repository.find_by_id(id).await.unwrap().unwrap();
+
+        assert!(locked_until > Utc::now());
+        assert_eq!(user.failed_login_count, 0);
+        assert_eq!(user.active_lock(Utc::now()), Some(locked_until));
+
+        repository.reset_login_failures(id).await.unwrap();
+
+        let user = repository.find_by_id(id).await.unwrap().unwrap();
+
+        assert_eq!(user.locked_until, None);
+
+        delete_user(&database, id).await;
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn lock_expires_after_its_duration() {
+        let (database, repository) = repository().await;
+        let id = insert_user(&database).await;
+        let locked_until = repository
+            .record_login_failure(id, 1, 60)
+            .await
+            .unwrap()
+            .unwrap();
+        let user = repository.find_by_id(id).await.unwrap().unwrap();
+
+        assert!(user.active_lock(Utc::now()).is_some());
+        assert_eq!(user.active_lock(locked_until), None);
+        assert_eq!(user.active_lock(Utc::now() + Duration::minutes(2)), None);
+
+        delete_user(&database, id).await;
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn unlock_lifts_the_lock_within_the_organization() {
+        let (database, repository) = repository().await;
+        let id = insert_user(&database).await;
+        let audit = AuditContext::new(None, None).entry(
+            AuditAction::UserUnlock,
+            Some(id),
+            AuditOutcome::Success,
+        );
+
+        repository.record_login_failure(id, 1, 60).await.unwrap();
+
+        let error = repository
+            .unlock(id, Uuid::new_v4(), audit.clone())
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.code, ErrorCode::NotFound);
+
+        let user = repository
+            .unlock(id, DEFAULT_ORGANIZATION_ID, audit)
+            .await
+            .unwrap();
+
+        assert_eq!(user.failed_login_count, 0);
+        assert_eq!(user.active_lock(Utc::now()), None);
+
+        delete_user(&database, id).await;
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn usernames_are_filtered_along_the_other_conditions() {
+        let (database, repository) = repository().await;
+        let id = insert_user(&database).await;
+        let (username,): (String,) = sqlx::query_as("SELECT username FROM users WHERE id = $1")
+            .bind(id)
+            .fetch_one(&database.conn_pool)
+            .await
+            .unwrap();
+        let filter = |role, include_deleted| UserListFilter {
+            organization_id: DEFAULT_ORGANIZATION_ID,
+            include_deleted,
+            role,
+            username: Some(username.clone()),
+            ..UserListFilter::default()
+        };
+
+        assert_eq!(repository.count(&filter(None, false)).await.unwrap(), 1);
+        assert_eq!(
+            repository
+                .count(&filter(Some(Role::Admin), false))
+                .await
+                .unwrap(),
+            0
+        );
+
+        sqlx::query("UPDATE users SET deleted_at = CURRENT_TIMESTAMP WHERE id = $1")
+            .bind(id)
+            .execute(&database.conn_pool)
+            .await
+            .unwrap();
+
+        assert_eq!(repository.count(&filter(None, false)).await.unwrap(), 0);
+        assert_eq!(repository.count(&filter(None, true)).await.unwrap(), 1);
+
+        delete_user(&database, id).await;
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn roles_of_deleted_users_are_not_updated() {
+        let (database, repository) = repository().await;
+        let id = insert_user(&database).await;
+        let audit = AuditContext::new(None, None).entry(
+            AuditAction::RoleUpdate,
+            Some(id),
+            AuditOutcome::Success,
+        );
+
+        sqlx::query("UPDATE users SET deleted_at = CURRENT_TIMESTAMP WHERE id = $1")
+            .bind(id)
+            .execute(&database.conn_pool)
+            .await
+            .unwrap();
+
+        let error = repository
+            .update_role(id, DEFAULT_ORGANIZATION_ID, Role::Admin, audit)
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.code, ErrorCode::NotFound);
+
+        delete_user(&database, id).await;
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn updates_at_the_expected_version() {
+        let (database, repository) = repository().await;
+        let id = insert_user(&database).await;
+        let username = format!("v{}", &Uuid::new_v4().to_simple().to_string()[..16]);
+        let user = repository
+            .update(
+                id,
+                DEFAULT_ORGANIZATION_ID,
+                1,
+                UpdateUserTableRow {
+                    username: Some(username.clone()),
+                    email: None,
+                    custom_gender: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(user.username, username);
+        assert_eq!(user.version, 2);
+
+        delete_user(&database, id).await;
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn stale_versions_conflict() {
+        let (database, repository) = repository().await;
+        let id = insert_user(&database).await;
+        let unchanged = UpdateUserTableRow::default;
+
+        repository
+            .update(id, DEFAULT_ORGANIZATION_ID, 1, unchanged())
+            .await
+            .unwrap();
+
+        let error = repository
+            .update(id, DEFAULT_ORGANIZATION_ID, 1, unchanged())
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.code, ErrorCode::Conflict);
+        assert_eq!(error.field.as_deref(), Some("version"));
+
+        let error = repository
+            .update(Uuid::new_v4(), DEFAULT_ORGANIZATION_ID, 1, unchanged())
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.code, ErrorCode::NotFound);
+
+        // Users of other organizations are not found, whatever their version
+        let error = repository
+            .update(id, Uuid::new_v4(), 2, unchanged())
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(error.code, ErrorCode::NotFound);
+
+        delete_user(&database, id).await;
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn keeps_clears_or_sets_nullable_columns() {
+        let (database, repository) = repository().await;
+        let id = insert_user(&database).await;
+        let update = |version, custom_gender| {
+            repository.update(
+                id,
+                DEFAULT_ORGANIZATION_ID,
+                version,
+                UpdateUserTableRow {
+                    custom_gender,
+                    ..UpdateUserTableRow::default()
+                },
+            )
+        };
+
+        let user = update(1, Some(Some(String::from("Non-binary"))))
+            .await
+            .unwrap();
+
+        assert_eq!(user.custom_gender.as_deref(), Some("Non-binary"));
+
+        let user = update(2, None).await.unwrap();
+
+        assert_eq!(user.custom_gender.as_deref(), Some("Non-binary"));
+
+        let user = update(3, Some(None)).await.unwrap();
+
+        assert_eq!(user.custom_gender, None);
+
+        delete_user(&database, id).await;
+    }
 }
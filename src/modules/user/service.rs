@@ -1,64 +1,559 @@
-use argon2::{self, Config};
-use rand::distributions::Alphanumeric;
-use rand::{thread_rng, Rng};
+use chrono::{DateTime, Utc};
+use futures::stream::BoxStream;
 use std::sync::Arc;
+use uuid::Uuid;
 
-use crate::error::Result;
+use crate::config::{AccountLockoutConfig, Config};
+use crate::error::{Error, ErrorCode, Result, ValidationError};
+use crate::graphql::relay::KeysetPage;
+use crate::modules::audit::{AuditAction, AuditContext, AuditOutcome};
 use crate::modules::user::graphql::account_register::AccountRegisterInput;
+use crate::modules::user::graphql::user_update::UserUpdateInput;
 
-use super::{InsertUserTableRow, User, UserRepository};
+use super::{
+    fingerprint, GuardedClaim, IdempotencyKeys, InsertUserTableRow, PasswordHasher, PasswordPolicy,
+    Role, UpdateUserTableRow, User, UserListFilter, UserOrder, UserRepository,
+};
+
+/// Maximum amount of characters taken from a search term
+pub const MAX_SEARCH_LENGTH: usize = 64;
 
 pub struct UserService {
+    lockout: AccountLockoutConfig,
+    hasher: PasswordHasher,
+    idempotency_keys: IdempotencyKeys,
+    policy: PasswordPolicy,
+    password_history_size: usize,
     repository: Arc<UserRepository>,
 }
 
 impl UserService {
-    pub fn new(repository: Arc<UserRepository>) -> Self {
-        Self { repository }
+    pub fn new(config: &Config, repository: Arc<UserRepository>) -> Self {
+        Self {
+            lockout: config.account_lockout,
+            hasher: PasswordHasher::new(config.argon2.clone()),
+            idempotency_keys: IdempotencyKeys::default(),
+            policy: PasswordPolicy::new(config.password_policy),
+            password_history_size: config.password_policy.history_size,
+            repository,
+        }
     }
 
-    pub async fn find_all(&self) -> Result<Vec<User>> {
-        let users = self.repository.find_all().await?;
+    pub async fn find_page(
+        &self,
+        filter: &UserListFilter,
+        order: UserOrder,
+        page: KeysetPage,
+    ) -> Result<Vec<User>> {
+        let users = self.repository.find_page(filter, order, page).await?;
 
         Ok(users)
     }
 
-    pub async fn find_by_username(&self, username: &str) -> Result<Option<User>> {
-        let username = username.to_string().to_lowercase();
+    pub fn stream<'a>(&'a self, filter: &'a UserListFilter) -> BoxStream<'a, Result<User>> {
+        self.repository.stream(filter)
+    }
+
+    pub async fn count(&self, filter: &UserListFilter) -> Result<usize> {
+        let count = self.repository.count(filter).await?;
 
-        self.repository.find_by_username(&username).await
+        Ok(count as usize)
     }
 
-    pub async fn create(&self, payload: AccountRegisterInput) -> Result<User> {
-        let password_hash = self.hash_password(&payload.password)?;
-        let username = payload.username.to_lowercase();
+    pub async fn find_by_id(&self, id: Uuid) -> Result<Option<User>> {
+        self.repository.find_by_id(id).await
+    }
+
+    pub async fn find_by_email(&self, email: &str) -> Result<Option<User>> {
+        self.repository.find_by_email(&normalize_email(email)).await
+    }
+
+    pub async fn find_by_username(&self, username: &str) -> Result<Option<User>> {
+        self.repository
+            .find_by_username(&normalize_username(username))
+            .await
+    }
+
+    pub async fn create(&self, payload: AccountRegisterInput, audit: AuditContext) -> Result<User> {
+        let password_hash = self.hasher.hash(&payload.password)?;
         let inserted = self
             .repository
-            .insert(InsertUserTableRow {
-                name: payload.name,
-                last_name: payload.last_name,
-                email: payload.email,
-                username,
-                gender: payload.gender,
-                pronoun: payload.pronoun,
-                custom_gender: payload.custom_gender,
-                password_hash,
-                birthdate: payload.birthdate,
-            })
+            .insert(
+                InsertUserTableRow {
+                    name: payload.name,
+                    last_name: payload.last_name,
+                    email: String::from(payload.email),
+                    username: String::from(payload.username),
+                    gender: payload.gender,
+                    pronoun: payload.pronoun,
+                    custom_gender: payload.custom_gender,
+                    password_hash,
+                    birthdate: payload.birthdate,
+                },
+                audit.entry(AuditAction::UserCreate, None, AuditOutcome::Success),
+            )
             .await?;
 
         Ok(inserted)
     }
 
-    fn hash_password(&self, raw: &str) -> Result<String> {
-        let salt: String = thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(30)
-            .map(char::from)
-            .collect();
-        let hash =
-            argon2::hash_encoded(raw.as_bytes(), salt.as_bytes(), &Config::default()).unwrap();
+    /// Creates a user once per `idempotency_key`, retries carrying the same
+    /// key and payload return the user created by the first request. Keys
+    /// are held in memory, so retries are only deduplicated within this
+    /// process.
+    pub async fn create_idempotent(
+        &self,
+        payload: AccountRegisterInput,
+        idempotency_key: &str,
+        audit: AuditContext,
+    ) -> Result<User> {
+        let fingerprint = fingerprint(&payload)?;
+        let claimed = match self
+            .idempotency_keys
+            .claim_guarded(idempotency_key, &fingerprint)?
+        {
+            GuardedClaim::Fresh(claimed) => claimed,
+            GuardedClaim::Replay(user_id) => {
+                return self
+                    .find_by_id(user_id)
+                    .await?
+                    .ok_or_else(|| Error::not_found("user", &user_id.to_string()));
+            }
+        };
+        // The key is released if creating the user fails or this future is
+        // dropped before it completes
+        let user = self.create(payload, audit).await?;
+
+        claimed.complete(user.id);
+
+        Ok(user)
+    }
+
+    /// Updates the user with the given `id` if it belongs to
+    /// `organization_id`
+    pub async fn update(
+        &self,
+        id: Uuid,
+        organization_id: Uuid,
+        payload: UserUpdateInput,
+    ) -> Result<User> {
+        let updated = self
+            .repository
+            .update(
+                id,
+                organization_id,
+                payload.version,
+                UpdateUserTableRow {
+                    username: payload.username.map(String::from),
+                    email: payload.email.map(String::from),
+                    custom_gender: payload.custom_gender.into(),
+                },
+            )
+            .await?;
+
+        Ok(updated)
+    }
+
+    pub async fn update_password(&self, id: Uuid, raw: &str, audit: AuditContext) -> Result<User> {
+        let password_hash = self.hasher.hash(raw)?;
+
+        self.repository
+            .update_password_hash(
+                id,
+                &password_hash,
+                self.password_history_size,
+                audit.entry(AuditAction::PasswordChange, Some(id), AuditOutcome::Success),
+            )
+            .await
+    }
+
+    /// Sets a new password as long as the user's `token_version` matches the
+    /// provided one, returns `None` otherwise.
+    pub async fn reset_password(
+        &self,
+        id: Uuid,
+        raw: &str,
+        token_version: i32,
+    ) -> Result<Option<User>> {
+        let password_hash = self.hasher.hash(raw)?;
+
+        self.repository
+            .reset_password_hash(
+                id,
+                &password_hash,
+                token_version,
+                self.password_history_size,
+            )
+            .await
+    }
+
+    /// Fails if `raw` matches the user's current password or one of the
+    /// previous ones kept in its password history, reported on the provided
+    /// input `field`
+    pub async fn ensure_password_not_reused(
+        &self,
+        field: &str,
+        user: &User,
+        raw: &str,
+    ) -> Result<()> {
+        let history = if self.password_history_size == 0 {
+            Vec::new()
+        } else {
+            self.repository.find_password_history(user.id).await?
+        };
+        let recent = std::iter::once(&user.password_hash)
+            .chain(history.iter().take(self.password_history_size));
+
+        for password_hash in recent {
+            if self.hasher.verify(password_hash, raw)? {
+                return Err(Error::new(
+                    field,
+                    "You cannot reuse a recent password",
+                    ErrorCode::ValidationError,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks a new password against the password policy, failures are
+    /// reported on the provided input `field`
+    pub fn validate_password(
+        &self,
+        field: &str,
+        raw: &str,
+    ) -> std::result::Result<(), ValidationError> {
+        self.policy.validate(field, raw)
+    }
+
+    /// Checks the provided password against the user's password hash
+    pub fn check_password(&self, user: &User, raw: &str) -> Result<bool> {
+        self.hasher.verify(&user.password_hash, raw)
+    }
+
+    /// Spends as long as `check_password` without a user, so responses don't
+    /// reveal whether a username exists
+    pub fn check_dummy_password(&self, raw: &str) -> Result<()> {
+        self.hasher.verify_dummy(raw)
+    }
+
+    /// Checks the provided password against the user's password hash. When
+    /// valid and the hash was created with weaker parameters than the
+    /// configured ones or without the pepper, the password is hashed again
+    /// and stored.
+    pub async fn verify_password(&self, user: &User, raw: &str) -> Result<bool> {
+        let matched = self.hasher.check(&user.password_hash, raw)?;
+
+        if !matched.is_match() {
+            return Ok(false);
+        }
+
+        if let Some(password_hash) =
+            self.hasher
+                .rehash_if_outdated(&user.password_hash, raw, matched)?
+        {
+            self.repository
+                .upgrade_password_hash(user.id, &password_hash)
+                .await?;
+        }
+
+        Ok(true)
+    }
+
+    /// Deletes the user with the given `id` if it belongs to
+    /// `organization_id`
+    pub async fn delete(
+        &self,
+        id: Uuid,
+        organization_id: Uuid,
+        audit: AuditContext,
+    ) -> Result<User> {
+        self.repository
+            .soft_delete(
+                id,
+                organization_id,
+                audit.entry(AuditAction::UserDelete, Some(id), AuditOutcome::Success),
+            )
+            .await
+    }
+
+    /// Updates the role of the user with the given `id` if it belongs to
+    /// `organization_id`
+    pub async fn update_role(
+        &self,
+        id: Uuid,
+        organization_id: Uuid,
+        role: Role,
+        audit: AuditContext,
+    ) -> Result<User> {
+        self.repository
+            .update_role(
+                id,
+                organization_id,
+                role,
+                audit.entry(AuditAction::RoleUpdate, Some(id), AuditOutcome::Success),
+            )
+            .await
+    }
+
+    /// Counts a failed login of the provided user, locking the account once
+    /// too many consecutive logins failed. Returns when the account is
+    /// locked until, if this failure locked it.
+    pub async fn record_login_failure(&self, user: &User) -> Result<Option<DateTime<Utc>>> {
+        let locked_until = self
+            .repository
+            .record_login_failure(
+                user.id,
+                self.lockout.threshold as i32,
+                self.lockout.duration.as_secs() as i64,
+            )
+            .await?;
+
+        if let Some(locked_until) = locked_until {
+            tracing::warn!(user_id = %user.id, %locked_until, "account locked after failed logins");
+        }
+
+        Ok(locked_until)
+    }
+
+    /// Clears the failed logins of the provided user
+    pub async fn reset_login_failures(&self, user: &User) -> Result<()> {
+        if user.failed_login_count == 0 && user.locked_until.is_none() {
+            return Ok(());
+        }
+
+        self.repository.reset_login_failures(user.id).await
+    }
+
+    /// Lifts the lock of the user with the given `id` if it belongs to
+    /// `organization_id`
+    pub async fn unlock(
+        &self,
+        id: Uuid,
+        organization_id: Uuid,
+        audit: AuditContext,
+    ) -> Result<User> {
+        self.repository
+            .unlock(
+                id,
+                organization_id,
+                audit.entry(AuditAction::UserUnlock, Some(id), AuditOutcome::Success),
+            )
+            .await
+    }
+
+    pub async fn mark_email_verified(&self, id: Uuid) -> Result<User> {
+        self.repository.set_email_verified(id).await
+    }
+}
+
+/// Emails and usernames are unique regardless of their casing, these are
+/// stored lowercased to match the database's case-insensitive indexes. See
+/// `Email` for emails provided through inputs.
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+fn normalize_username(username: &str) -> String {
+    username.trim().to_lowercase()
+}
+
+/// Builds the `ILIKE` pattern matching values containing `search`. Returns
+/// `None` for blank terms. Wildcards in the term are matched literally and
+/// long terms are truncated to keep patterns cheap to evaluate.
+pub fn search_pattern(search: &str) -> Option<String> {
+    let search = search.trim();
+
+    if search.is_empty() {
+        return None;
+    }
+
+    let escaped = search
+        .chars()
+        .take(MAX_SEARCH_LENGTH)
+        .fold(String::new(), |mut pattern, ch| {
+            if matches!(ch, '%' | '_' | '\\') {
+                pattern.push('\\');
+            }
+
+            pattern.push(ch);
+            pattern
+        });
+
+    Some(format!("%{escaped}%"))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::postgres::PgPoolOptions;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use crate::config::{Argon2Config, Config};
+    use crate::database::Database;
+    use crate::error::ErrorCode;
+    use crate::modules::audit::AuditContext;
+    use crate::modules::user::{PasswordHasher, User, UserRepository};
+
+    use super::{
+        normalize_email, normalize_username, search_pattern, UserService, MAX_SEARCH_LENGTH,
+    };
+
+    /// Inserts a user whose password is `password`, passwords are then
+    /// changed through `UserService` to fill its history
+    async fn password_history_fixture(
+        history_size: usize,
+        password: &str,
+    ) -> (Arc<Database>, UserService, User) {
+        let mut config = Config::testing(&std::env::var("DATABASE_URL").unwrap());
+
+        config.password_policy.history_size = history_size;
+
+        let database = Arc::new(Database {
+            conn_pool: PgPoolOptions::new()
+                .connect(&config.database_url)
+                .await
+                .unwrap(),
+        });
+        let service = UserService::new(
+            &config,
+            Arc::new(UserRepository::new(Arc::clone(&database))),
+        );
+        let marker = Uuid::new_v4().to_simple().to_string();
+        let password_hash = PasswordHasher::new(Argon2Config::default())
+            .hash(password)
+            .unwrap();
+        let (id,): (Uuid,) = sqlx::query_as(
+            r#"
+            INSERT INTO users (name, last_name, email, username, password_hash, birthdate, gender, pronoun)
+            VALUES ('History', 'Test', $1 || '@nexus.dev', $1, $2, CURRENT_TIMESTAMP, 'custom', 'they')
+            RETURNING id"#,
+        )
+        .bind(&marker)
+        .bind(&password_hash)
+        .fetch_one(&database.conn_pool)
+        .await
+        .unwrap();
+        let user = service.find_by_id(id).await.unwrap().unwrap();
+
+        (database, service, user)
+    }
+
+    async fn change_password(service: &UserService, user: &User, password: &str) -> User {
+        service
+            .update_password(user.id, password, AuditContext::new(None, None))
+            .await
+            .unwrap()
+    }
+
+    async fn delete_user(database: &Database, id: Uuid) {
+        for query in [
+            "DELETE FROM password_history WHERE user_id = $1",
+            "DELETE FROM audit_log WHERE target_id = $1",
+            "DELETE FROM users WHERE id = $1",
+        ] {
+            sqlx::query(query)
+                .bind(id)
+                .execute(&database.conn_pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn rejects_the_previous_password() {
+        let (database, service, user) = password_history_fixture(5, "First passw0rd").await;
+        let user = change_password(&service, &user, "Second passw0rd").await;
+
+        for password in ["First passw0rd", "Second passw0rd"] {
+            let error = service
+                .ensure_password_not_reused("newPassword", &user, password)
+                .await
+                .err()
+                .unwrap();
+
+            assert_eq!(error.code, ErrorCode::ValidationError);
+            assert_eq!(error.field.as_deref(), Some("newPassword"));
+            assert_eq!(
+                error.message.as_deref(),
+                Some("You cannot reuse a recent password")
+            );
+        }
+
+        delete_user(&database, user.id).await;
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn accepts_fresh_passwords() {
+        let (database, service, user) = password_history_fixture(5, "First passw0rd").await;
+        let user = change_password(&service, &user, "Second passw0rd").await;
+
+        assert!(service
+            .ensure_password_not_reused("newPassword", &user, "Third passw0rd")
+            .await
+            .is_ok());
+
+        delete_user(&database, user.id).await;
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a migrated database at DATABASE_URL"]
+    async fn evicts_the_oldest_password_beyond_the_history_size() {
+        let (database, service, user) = password_history_fixture(1, "First passw0rd").await;
+        let user = change_password(&service, &user, "Second passw0rd").await;
+        let user = change_password(&service, &user, "Third passw0rd").await;
+
+        assert_eq!(
+            service
+                .repository
+                .find_password_history(user.id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(service
+            .ensure_password_not_reused("newPassword", &user, "First passw0rd")
+            .await
+            .is_ok());
+        assert!(service
+            .ensure_password_not_reused("newPassword", &user, "Second passw0rd")
+            .await
+            .is_err());
+
+        delete_user(&database, user.id).await;
+    }
+
+    #[test]
+    fn emails_differing_in_casing_collide() {
+        assert_eq!(
+            normalize_email("User@Example.com"),
+            normalize_email("user@example.com")
+        );
+    }
+
+    #[test]
+    fn usernames_differing_in_casing_collide() {
+        assert_eq!(normalize_username("Esteban"), normalize_username("esteban"));
+    }
+
+    #[test]
+    fn search_pattern_is_trimmed_and_escaped() {
+        assert_eq!(search_pattern("  este "), Some(String::from("%este%")));
+        assert_eq!(
+            search_pattern("100%_\\"),
+            Some(String::from("%100\\%\\_\\\\%"))
+        );
+        assert_eq!(search_pattern("   "), None);
+    }
+
+    #[test]
+    fn search_pattern_is_length_limited() {
+        let pattern = search_pattern(&"a".repeat(1000)).unwrap();
 
-        Ok(hash)
+        assert_eq!(pattern.len(), MAX_SEARCH_LENGTH + 2);
     }
 }
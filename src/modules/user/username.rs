@@ -0,0 +1,171 @@
+use async_graphql::{InputValueError, InputValueResult, Scalar, ScalarType, Value};
+use once_cell::sync::{Lazy, OnceCell};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::config::ReservedUsernames;
+use crate::error::{Error, ErrorCode};
+
+pub const MIN_USERNAME_LENGTH: usize = 3;
+
+pub const MAX_USERNAME_LENGTH: usize = 32;
+
+static USERNAME_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[a-z0-9_]+$").unwrap());
+
+/// Usernames which can't be registered, set once on startup from
+/// `Config::reserved_usernames`
+static RESERVED_USERNAMES: OnceCell<ReservedUsernames> = OnceCell::new();
+
+/// Sets the usernames which can't be registered. Has no effect after the
+/// first call.
+pub fn reserve_usernames(reserved: ReservedUsernames) {
+    let _ = RESERVED_USERNAMES.set(reserved);
+}
+
+fn is_reserved(username: &str) -> bool {
+    match RESERVED_USERNAMES.get() {
+        Some(reserved) => reserved.contains(username),
+        None => ReservedUsernames::default().contains(username),
+    }
+}
+
+/// A valid username, normalized to lowercase
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Username(String);
+
+impl Username {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for Username {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let username = value.trim().to_lowercase();
+        let length = username.chars().count();
+        let message = if !(MIN_USERNAME_LENGTH..=MAX_USERNAME_LENGTH).contains(&length) {
+            format!(
+                "Username must have between {MIN_USERNAME_LENGTH} and {MAX_USERNAME_LENGTH} characters"
+            )
+        } else if !USERNAME_RE.is_match(&username) {
+            String::from("Username may only contain letters, digits and underscores")
+        } else if is_reserved(&username) {
+            String::from("Username is reserved")
+        } else {
+            return Ok(Self(username));
+        };
+
+        Err(Error::new("username", &message, ErrorCode::ValidationError))
+    }
+}
+
+impl FromStr for Username {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Username::try_from(value.to_string())
+    }
+}
+
+impl Display for Username {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<Username> for String {
+    fn from(username: Username) -> Self {
+        username.0
+    }
+}
+
+/// Username of 3 to 32 letters, digits or underscores, normalized to
+/// lowercase
+#[Scalar]
+impl ScalarType for Username {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        match value {
+            Value::String(value) => Username::try_from(value)
+                .map_err(|err| InputValueError::custom(err.message.unwrap_or_default())),
+            _ => Err(InputValueError::expected_type(value)),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{Pos, ScalarType, Value};
+
+    use crate::error::ErrorCode;
+
+    use super::Username;
+
+    fn failure(username: &str) -> String {
+        let error = username.parse::<Username>().err().unwrap();
+
+        assert_eq!(error.code, ErrorCode::ValidationError);
+        assert_eq!(error.field.as_deref(), Some("username"));
+
+        error.message.unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_usernames() {
+        for username in ["esteban", "user_1", "abc", &"a".repeat(32)] {
+            assert!(username.parse::<Username>().is_ok(), "{username}");
+        }
+    }
+
+    #[test]
+    fn normalizes_case_and_whitespace() {
+        let username = " Esteban_Borai ".parse::<Username>().unwrap();
+
+        assert_eq!(username.as_str(), "esteban_borai");
+    }
+
+    #[test]
+    fn rejects_too_short_and_too_long_usernames() {
+        let message = "Username must have between 3 and 32 characters";
+
+        assert_eq!(failure("ab"), message);
+        assert_eq!(failure(&"a".repeat(33)), message);
+    }
+
+    #[test]
+    fn rejects_illegal_characters() {
+        for username in ["esteban.borai", "este ban", "esteban!", "estébán"] {
+            assert_eq!(
+                failure(username),
+                "Username may only contain letters, digits and underscores"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_reserved_usernames() {
+        assert_eq!(failure("admin"), "Username is reserved");
+        assert_eq!(failure("Root"), "Username is reserved");
+    }
+
+    #[test]
+    fn scalar_reports_clean_message() {
+        let error = <Username as ScalarType>::parse(Value::from("admin"))
+            .err()
+            .unwrap()
+            .into_server_error(Pos::default());
+
+        assert_eq!(
+            error.message,
+            r#"Failed to parse "Username": Username is reserved"#
+        );
+    }
+}
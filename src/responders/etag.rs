@@ -0,0 +1,122 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{Responder, Response};
+use sha2::{Digest, Sha256};
+
+const CACHE_CONTROL: &str = "Cache-Control";
+const ETAG: &str = "ETag";
+const IF_NONE_MATCH: &str = "If-None-Match";
+
+/// Entity tags provided through the `If-None-Match` header
+#[derive(Debug, Default)]
+pub struct IfNoneMatch(Option<String>);
+
+impl IfNoneMatch {
+    pub fn new(value: &str) -> Self {
+        Self(Some(value.to_string()))
+    }
+
+    /// Checks whether `etag` is one of the provided tags, compared weakly as
+    /// required for `If-None-Match`
+    pub fn matches(&self, etag: &str) -> bool {
+        let value = match &self.0 {
+            Some(value) => value,
+            None => return false,
+        };
+
+        value.split(',').map(str::trim).any(|candidate| {
+            candidate == "*" || candidate.trim_start_matches("W/") == etag.trim_start_matches("W/")
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = ();
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(
+            request
+                .headers()
+                .get_one(IF_NONE_MATCH)
+                .map(IfNoneMatch::new)
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// Quoted hex encoded SHA-256 hash of `body`
+pub fn etag(body: &[u8]) -> String {
+    format!("\"{}\"", hex::encode(Sha256::digest(body)))
+}
+
+/// Sets the `ETag` header when the response is cacheable, responding with
+/// `304 Not Modified` and no body if the client already holds it. Responses
+/// without an `ETag` are marked as not to be stored.
+pub struct ETagged<R> {
+    responder: R,
+    etag: Option<String>,
+    not_modified: bool,
+}
+
+impl<R> ETagged<R> {
+    /// Wraps a response which must not be cached
+    pub fn uncached(responder: R) -> Self {
+        ETagged {
+            responder,
+            etag: None,
+            not_modified: false,
+        }
+    }
+
+    /// Wraps a cacheable response identified by `etag`
+    pub fn cached(responder: R, etag: String, if_none_match: &IfNoneMatch) -> Self {
+        ETagged {
+            responder,
+            not_modified: if_none_match.matches(&etag),
+            etag: Some(etag),
+        }
+    }
+}
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for ETagged<R> {
+    fn respond_to(self, request: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
+        let etag = match self.etag {
+            Some(etag) => etag,
+            None => {
+                let mut response = self.responder.respond_to(request)?;
+
+                response.set_raw_header(CACHE_CONTROL, "no-store");
+
+                return Ok(response);
+            }
+        };
+
+        let mut response = if self.not_modified {
+            Response::build().status(Status::NotModified).finalize()
+        } else {
+            self.responder.respond_to(request)?
+        };
+
+        response.set_raw_header(ETAG, etag);
+        response.set_raw_header(CACHE_CONTROL, "no-cache");
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{etag, IfNoneMatch};
+
+    #[test]
+    fn if_none_match_compares_tags_weakly() {
+        let etag = etag(b"{}");
+
+        assert!(IfNoneMatch::new(&etag).matches(&etag));
+        assert!(IfNoneMatch::new(&format!("\"other\", W/{etag}")).matches(&etag));
+        assert!(IfNoneMatch::new("*").matches(&etag));
+        assert!(!IfNoneMatch::new("\"other\"").matches(&etag));
+        assert!(!IfNoneMatch::default().matches(&etag));
+    }
+}
@@ -1 +1,3 @@
-pub mod cors;
+pub mod etag;
+pub mod negotiated;
+pub mod rate_limited;
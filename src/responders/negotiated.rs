@@ -0,0 +1,132 @@
+use rocket::http::{Accept, ContentType, MediaType, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{Responder, Response};
+
+const VARY: &str = "Vary";
+
+/// Media type of GraphQL responses, negotiated through the `Accept` header as
+/// described by the GraphQL over HTTP specification
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GraphQLMediaType {
+    /// `application/json`, answered with `200 OK` even for request errors as
+    /// clients predating the specification expect
+    Json,
+    /// `application/graphql-response+json`, request errors are answered with
+    /// `400 Bad Request`
+    GraphQLResponseJson,
+}
+
+impl GraphQLMediaType {
+    fn media_type() -> MediaType {
+        MediaType::new("application", "graphql-response+json")
+    }
+
+    /// Prefers `application/graphql-response+json` unless `application/json`
+    /// is given a higher weight. Requests without an `Accept` header, or
+    /// accepting neither, get the legacy `application/json`.
+    pub fn negotiate(accept: Option<&Accept>) -> Self {
+        let weight_of = |media_type: &MediaType| {
+            accept
+                .into_iter()
+                .flat_map(Accept::iter)
+                .filter(|accepted| accepted.media_type() == media_type)
+                .map(|accepted| accepted.weight_or(1.0))
+                .fold(0.0, f32::max)
+        };
+        let graphql_response_weight = weight_of(&Self::media_type());
+
+        if graphql_response_weight > 0.0 && graphql_response_weight >= weight_of(&MediaType::JSON) {
+            return GraphQLMediaType::GraphQLResponseJson;
+        }
+
+        GraphQLMediaType::Json
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for GraphQLMediaType {
+    type Error = ();
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(GraphQLMediaType::negotiate(request.accept()))
+    }
+}
+
+/// Sets the `Content-Type` and status of a GraphQL response according to the
+/// negotiated media type. Request errors are those which prevented the
+/// operation from being executed, e.g. failing to parse or validate.
+pub struct Negotiated<R> {
+    responder: R,
+    media_type: GraphQLMediaType,
+    request_error: bool,
+}
+
+impl<R> Negotiated<R> {
+    pub fn new(responder: R, media_type: GraphQLMediaType, request_error: bool) -> Self {
+        Negotiated {
+            responder,
+            media_type,
+            request_error,
+        }
+    }
+}
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for Negotiated<R> {
+    fn respond_to(self, request: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
+        let mut response = Response::build_from(self.responder.respond_to(request)?).finalize();
+
+        response.set_raw_header(VARY, "Accept");
+
+        if self.media_type == GraphQLMediaType::GraphQLResponseJson {
+            response.set_header(ContentType(GraphQLMediaType::media_type()));
+
+            if self.request_error {
+                response.set_status(Status::BadRequest);
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Accept;
+
+    use super::GraphQLMediaType;
+
+    fn negotiate(accept: &str) -> GraphQLMediaType {
+        GraphQLMediaType::negotiate(Some(&accept.parse::<Accept>().unwrap()))
+    }
+
+    #[test]
+    fn negotiates_graphql_response_json_when_preferred() {
+        assert_eq!(
+            negotiate("application/graphql-response+json"),
+            GraphQLMediaType::GraphQLResponseJson
+        );
+        assert_eq!(
+            negotiate("application/graphql-response+json, application/json;q=0.9"),
+            GraphQLMediaType::GraphQLResponseJson
+        );
+        assert_eq!(
+            negotiate("application/json, application/graphql-response+json"),
+            GraphQLMediaType::GraphQLResponseJson
+        );
+    }
+
+    #[test]
+    fn falls_back_to_json() {
+        assert_eq!(GraphQLMediaType::negotiate(None), GraphQLMediaType::Json);
+        assert_eq!(negotiate("application/json"), GraphQLMediaType::Json);
+        assert_eq!(negotiate("*/*"), GraphQLMediaType::Json);
+        assert_eq!(
+            negotiate("application/graphql-response+json;q=0.5, application/json"),
+            GraphQLMediaType::Json
+        );
+        assert_eq!(
+            negotiate("application/graphql-response+json;q=0"),
+            GraphQLMediaType::Json
+        );
+    }
+}
@@ -0,0 +1,33 @@
+use rocket::http::Status;
+use rocket::response::{Responder, Response};
+
+const RETRY_AFTER: &str = "Retry-After";
+
+/// Responds with `429 Too Many Requests` and a `Retry-After` header when
+/// `retry_after_secs` is set, otherwise the inner responder is left as is.
+pub struct RateLimited<R> {
+    responder: R,
+    retry_after_secs: Option<u64>,
+}
+
+impl<R> RateLimited<R> {
+    pub fn new(responder: R, retry_after_secs: Option<u64>) -> Self {
+        RateLimited {
+            responder,
+            retry_after_secs,
+        }
+    }
+}
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for RateLimited<R> {
+    fn respond_to(self, request: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
+        let mut response = Response::build_from(self.responder.respond_to(request)?).finalize();
+
+        if let Some(retry_after_secs) = self.retry_after_secs {
+            response.set_status(Status::TooManyRequests);
+            response.set_raw_header(RETRY_AFTER, retry_after_secs.to_string());
+        }
+
+        Ok(response)
+    }
+}
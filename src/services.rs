@@ -2,11 +2,13 @@ use std::sync::Arc;
 
 use crate::config::Config;
 use crate::database::Database;
-use crate::modules::auth::AuthService;
+use crate::modules::audit::{AuditRepository, AuditService};
+use crate::modules::auth::{AuthRepository, AuthService};
 use crate::modules::post::{PostRepository, PostService};
 use crate::modules::user::{UserRepository, UserService};
 
 pub struct Services {
+    pub audit: Arc<AuditService>,
     pub auth: Arc<AuthService>,
     pub post: Arc<PostService>,
     pub user: Arc<UserService>,
@@ -15,12 +17,21 @@ pub struct Services {
 impl Services {
     pub fn new(config: &Config, database: Arc<Database>) -> Self {
         let user_repository = Arc::new(UserRepository::new(Arc::clone(&database)));
-        let user_service = Arc::new(UserService::new(Arc::clone(&user_repository)));
+        let user_service = Arc::new(UserService::new(config, Arc::clone(&user_repository)));
         let post_repository = Arc::new(PostRepository::new(Arc::clone(&database)));
         let post_service = Arc::new(PostService::new(post_repository));
-        let auth_service = Arc::new(AuthService::new(config, Arc::clone(&user_service)));
+        let audit_repository = Arc::new(AuditRepository::new(Arc::clone(&database)));
+        let audit_service = Arc::new(AuditService::new(audit_repository));
+        let auth_repository = Arc::new(AuthRepository::new(Arc::clone(&database)));
+        let auth_service = Arc::new(AuthService::new(
+            config,
+            auth_repository,
+            Arc::clone(&audit_service),
+            Arc::clone(&user_service),
+        ));
 
         Self {
+            audit: audit_service,
             auth: auth_service,
             post: post_service,
             user: user_service,
@@ -0,0 +1,396 @@
+//! OpenTelemetry export of the spans of this crate, such as GraphQL
+//! operations and the database queries they run. Spans are encoded as
+//! OTLP/HTTP JSON and sent in batches from a background thread, so exporting
+//! never blocks requests.
+//!
+//! Span fields become attributes, except for `otel.name`, `otel.kind` and
+//! `otel.status_code` which set the name, kind and status of the span.
+
+use serde_json::{json, Value};
+use std::fmt::Debug;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::config::TelemetryConfig;
+
+/// Spans of other crates, e.g. Rocket's or sqlx's, aren't exported
+const EXPORTED_TARGET: &str = env!("CARGO_CRATE_NAME");
+
+/// Finished spans waiting to be exported, spans finishing while the queue is
+/// full are dropped
+const QUEUE_CAPACITY: usize = 2048;
+
+const BATCH_SIZE: usize = 512;
+
+/// Longest time a finished span waits for its batch to be exported
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SpanKind {
+    Internal = 1,
+    Server = 2,
+    Client = 3,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum AttributeValue {
+    String(String),
+    Int(i64),
+    Bool(bool),
+    Double(f64),
+}
+
+impl AttributeValue {
+    /// OTLP JSON representation, 64-bit integers are encoded as strings
+    fn to_json(&self) -> Value {
+        match self {
+            AttributeValue::String(value) => json!({ "stringValue": value }),
+            AttributeValue::Int(value) => json!({ "intValue": value.to_string() }),
+            AttributeValue::Bool(value) => json!({ "boolValue": value }),
+            AttributeValue::Double(value) => json!({ "doubleValue": value }),
+        }
+    }
+}
+
+/// Span being recorded, kept within the extensions of its `tracing` span
+#[derive(Debug)]
+struct SpanData {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    name: String,
+    kind: SpanKind,
+    error: bool,
+    attributes: Vec<(&'static str, AttributeValue)>,
+    started_at: SystemTime,
+}
+
+impl SpanData {
+    /// Starts a span within the trace of `parent`, or a new trace
+    fn new(name: &str, parent: Option<([u8; 16], [u8; 8])>) -> Self {
+        Self {
+            trace_id: parent.map_or_else(rand::random, |(trace_id, _)| trace_id),
+            span_id: rand::random(),
+            parent_span_id: parent.map(|(_, span_id)| span_id),
+            name: name.to_string(),
+            kind: SpanKind::Internal,
+            error: false,
+            attributes: Vec::new(),
+            started_at: SystemTime::now(),
+        }
+    }
+
+    fn set(&mut self, key: &'static str, value: AttributeValue) {
+        match self.attributes.iter_mut().find(|(name, _)| *name == key) {
+            Some((_, current)) => *current = value,
+            None => self.attributes.push((key, value)),
+        }
+    }
+}
+
+impl Visit for SpanData {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "otel.name" => self.name = value.to_string(),
+            "otel.kind" => {
+                self.kind = match value {
+                    "server" => SpanKind::Server,
+                    "client" => SpanKind::Client,
+                    _ => SpanKind::Internal,
+                }
+            }
+            "otel.status_code" => self.error = value == "ERROR",
+            name => self.set(name, AttributeValue::String(value.to_string())),
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.set(field.name(), AttributeValue::Int(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        let value = i64::try_from(value).unwrap_or(i64::MAX);
+
+        self.set(field.name(), AttributeValue::Int(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.set(field.name(), AttributeValue::Bool(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.set(field.name(), AttributeValue::Double(value));
+    }
+
+    /// Fields recorded through `%` or `?` end up here
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.record_str(field, &format!("{value:?}"));
+    }
+}
+
+#[derive(Debug)]
+struct FinishedSpan {
+    data: SpanData,
+    ended_at: SystemTime,
+}
+
+/// Records the spans of this crate and queues them for export once closed
+pub struct OtlpLayer {
+    spans: SyncSender<FinishedSpan>,
+}
+
+impl OtlpLayer {
+    /// Starts the thread exporting spans to the configured collector, `None`
+    /// when no collector is configured
+    pub fn from_config(config: TelemetryConfig) -> Option<Self> {
+        let endpoint = config.endpoint?;
+        let (spans, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let exporter = Exporter {
+            url: format!("{}/v1/traces", endpoint.trim_end_matches('/')),
+            service_name: config.service_name,
+        };
+
+        std::thread::Builder::new()
+            .name(String::from("otlp-exporter"))
+            .spawn(move || exporter.run(receiver))
+            .expect("Failed to start the OTLP exporter");
+
+        Some(Self { spans })
+    }
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(id) {
+            Some(span) if span.metadata().target().starts_with(EXPORTED_TARGET) => span,
+            _ => return,
+        };
+        let parent = span.scope().skip(1).find_map(|parent| {
+            parent
+                .extensions()
+                .get::<SpanData>()
+                .map(|data| (data.trace_id, data.span_id))
+        });
+        let mut data = SpanData::new(span.name(), parent);
+
+        attrs.record(&mut data);
+        span.extensions_mut().insert(data);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+                values.record(data);
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let data = match ctx.span(&id) {
+            Some(span) => span.extensions_mut().remove::<SpanData>(),
+            None => None,
+        };
+
+        if let Some(data) = data {
+            // Dropping the span when the queue is full keeps requests from
+            // waiting on the collector
+            let _ = self.spans.try_send(FinishedSpan {
+                data,
+                ended_at: SystemTime::now(),
+            });
+        }
+    }
+}
+
+struct Exporter {
+    url: String,
+    service_name: String,
+}
+
+impl Exporter {
+    /// Exports spans in batches until the layer is dropped
+    fn run(self, spans: Receiver<FinishedSpan>) {
+        // The blocking client must be built out of the async runtime
+        let client = reqwest::blocking::Client::new();
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        let mut deadline = Instant::now() + EXPORT_INTERVAL;
+
+        loop {
+            let disconnected =
+                match spans.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(span) => {
+                        batch.push(span);
+
+                        if batch.len() < BATCH_SIZE {
+                            continue;
+                        }
+
+                        false
+                    }
+                    Err(RecvTimeoutError::Timeout) => false,
+                    Err(RecvTimeoutError::Disconnected) => true,
+                };
+
+            if !batch.is_empty() {
+                self.export(&client, &batch);
+                batch.clear();
+            }
+
+            if disconnected {
+                return;
+            }
+
+            deadline = Instant::now() + EXPORT_INTERVAL;
+        }
+    }
+
+    fn export(&self, client: &reqwest::blocking::Client, spans: &[FinishedSpan]) {
+        let body = encode(&self.service_name, spans).to_string();
+        let result = client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .timeout(EXPORT_TIMEOUT)
+            .body(body)
+            .send()
+            .and_then(|response| response.error_for_status());
+
+        if let Err(err) = result {
+            tracing::warn!(spans = spans.len(), error = %err, "failed to export spans");
+        }
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    let nanos = time
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+
+    nanos.to_string()
+}
+
+/// OTLP/HTTP JSON `ExportTraceServiceRequest` holding `spans`
+fn encode(service_name: &str, spans: &[FinishedSpan]) -> Value {
+    let spans = spans
+        .iter()
+        .map(|FinishedSpan { data, ended_at }| {
+            let mut span = json!({
+                "traceId": hex::encode(data.trace_id),
+                "spanId": hex::encode(data.span_id),
+                "name": data.name,
+                "kind": data.kind as u8,
+                "startTimeUnixNano": unix_nanos(data.started_at),
+                "endTimeUnixNano": unix_nanos(*ended_at),
+                "attributes": data
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| json!({ "key": key, "value": value.to_json() }))
+                    .collect::<Vec<Value>>(),
+            });
+
+            if let Some(parent_span_id) = data.parent_span_id {
+                span["parentSpanId"] = json!(hex::encode(parent_span_id));
+            }
+
+            if data.error {
+                span["status"] = json!({ "code": 2 });
+            }
+
+            span
+        })
+        .collect::<Vec<Value>>();
+
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{
+                    "key": "service.name",
+                    "value": { "stringValue": service_name },
+                }],
+            },
+            "scopeSpans": [{
+                "scope": { "name": EXPORTED_TARGET },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    use super::{encode, AttributeValue, OtlpLayer, SpanKind};
+
+    #[test]
+    fn exports_nested_spans_of_this_crate_with_their_attributes() {
+        let (spans, receiver) = mpsc::sync_channel(16);
+        let subscriber = Registry::default().with(OtlpLayer { spans });
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!(
+                "graphql_request",
+                request_id = "f3b2",
+                otel.kind = "server",
+                enduser.id = tracing::field::Empty,
+                otel.status_code = tracing::field::Empty,
+            );
+            let _entered = request.enter();
+
+            request.record("enduser.id", &"42");
+            tracing::info_span!("db_query", otel.kind = "client", rows = 3_u64).in_scope(|| {});
+            tracing::info_span!(target: "sqlx::query", "query").in_scope(|| {});
+            request.record("otel.status_code", &"ERROR");
+        });
+
+        let query = receiver.try_recv().unwrap();
+        let request = receiver.try_recv().unwrap();
+
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(query.data.name, "db_query");
+        assert_eq!(query.data.kind, SpanKind::Client);
+        assert_eq!(query.data.trace_id, request.data.trace_id);
+        assert_eq!(query.data.parent_span_id, Some(request.data.span_id));
+        assert_eq!(
+            query.data.attributes,
+            vec![("rows", AttributeValue::Int(3))]
+        );
+        assert_eq!(request.data.kind, SpanKind::Server);
+        assert_eq!(request.data.parent_span_id, None);
+        assert!(request.data.error);
+        assert_eq!(
+            request.data.attributes,
+            vec![
+                ("request_id", AttributeValue::String(String::from("f3b2"))),
+                ("enduser.id", AttributeValue::String(String::from("42"))),
+            ]
+        );
+
+        let body = encode("nexus-api", &[query, request]);
+        let spans = &body["resourceSpans"][0]["scopeSpans"][0]["spans"];
+
+        assert_eq!(
+            body["resourceSpans"][0]["resource"]["attributes"][0]["value"]["stringValue"],
+            "nexus-api"
+        );
+        assert_eq!(spans[0]["parentSpanId"], spans[1]["spanId"]);
+        assert_eq!(spans[0]["kind"], 3);
+        assert_eq!(spans[0]["attributes"][0]["value"]["intValue"], "3");
+        assert_eq!(spans[1]["status"]["code"], 2);
+        assert_eq!(spans[1]["traceId"].as_str().unwrap().len(), 32);
+        assert!(spans[1].get("parentSpanId").is_none());
+    }
+}
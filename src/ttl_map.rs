@@ -0,0 +1,202 @@
+use std::borrow::Borrow;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+use std::time::Instant;
+
+/// In-memory map whose entries expire, holding at most `capacity` of them.
+/// Expired entries are dropped in expiry order as new ones are inserted, so
+/// inserting never scans the whole map. Once full, the entry expiring the
+/// soonest is evicted to make room.
+///
+/// Callers pass the current time in, so expiry is tested without waiting.
+pub struct TtlMap<K, V> {
+    capacity: usize,
+    entries: HashMap<K, (V, Instant)>,
+    /// When each entry expires, soonest first. Entries inserted again or
+    /// removed since leave a stale expiry behind, skipped once reached.
+    expiries: BinaryHeap<Reverse<(Instant, K)>>,
+}
+
+impl<K: Clone + Eq + Hash + Ord, V> TtlMap<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            expiries: BinaryHeap::new(),
+        }
+    }
+
+    /// Value of `key`, unless it expired by `now`
+    pub fn get<Q>(&self, key: &Q, now: Instant) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries
+            .get(key)
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(value, _)| value)
+    }
+
+    /// Value of `key` to update in place, unless it expired by `now`. Its
+    /// expiry is kept, insert the value again to change it.
+    pub fn get_mut<Q>(&mut self, key: &Q, now: Instant) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries
+            .get_mut(key)
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(value, _)| value)
+    }
+
+    /// Removes the entry of `key`, returning its value unless it expired by
+    /// `now`
+    pub fn remove<Q>(&mut self, key: &Q, now: Instant) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries
+            .remove(key)
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(value, _)| value)
+    }
+
+    /// Inserts `value` under `key` until `expires_at`, replacing the value
+    /// and expiry of `key` if it's already present
+    pub fn insert(&mut self, key: K, value: V, expires_at: Instant, now: Instant) {
+        self.prune(now);
+
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            self.evict();
+        }
+
+        self.entries.insert(key.clone(), (value, expires_at));
+        self.expiries.push(Reverse((expires_at, key)));
+
+        // Stale expiries accumulate as entries are inserted again, they are
+        // dropped once they outnumber the entries
+        if self.expiries.len() > 2 * self.capacity.max(self.entries.len()) {
+            self.expiries = self
+                .entries
+                .iter()
+                .map(|(key, (_, expires_at))| Reverse((*expires_at, key.clone())))
+                .collect();
+        }
+    }
+
+    /// Drops the entries expired by `now`
+    fn prune(&mut self, now: Instant) {
+        while let Some(Reverse((expires_at, key))) = self.expiries.peek() {
+            if *expires_at > now {
+                break;
+            }
+
+            let (expires_at, key) = (*expires_at, key.clone());
+
+            self.expiries.pop();
+            self.remove_expiring_at(&key, expires_at);
+        }
+    }
+
+    /// Drops the entry expiring the soonest
+    fn evict(&mut self) {
+        while let Some(Reverse((expires_at, key))) = self.expiries.pop() {
+            if self.remove_expiring_at(&key, expires_at) {
+                return;
+            }
+        }
+    }
+
+    /// Removes the entry of `key` if it still expires at `expires_at`, rather
+    /// than the expiry being a stale one
+    fn remove_expiring_at(&mut self, key: &K, expires_at: Instant) -> bool {
+        let current = self
+            .entries
+            .get(key)
+            .is_some_and(|(_, current)| *current == expires_at);
+
+        if current {
+            self.entries.remove(key);
+        }
+
+        current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::TtlMap;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn expired_entries_are_missing() {
+        let mut map = TtlMap::new(10);
+        let now = Instant::now();
+
+        map.insert("ana", 1, now + SECOND, now);
+
+        assert_eq!(map.get(&"ana", now), Some(&1));
+        assert_eq!(map.get(&"ana", now + SECOND), None);
+        assert_eq!(map.get_mut(&"ana", now + SECOND), None);
+        assert_eq!(map.remove(&"ana", now + SECOND), None);
+    }
+
+    #[test]
+    fn expired_entries_are_dropped_on_insert() {
+        let mut map = TtlMap::new(10);
+        let now = Instant::now();
+
+        map.insert("ana", 1, now + SECOND, now);
+        map.insert("bob", 2, now + 3 * SECOND, now);
+        map.insert("carol", 3, now + 3 * SECOND, now + 2 * SECOND);
+
+        assert_eq!(map.entries.len(), 2);
+        assert!(!map.entries.contains_key("ana"));
+    }
+
+    #[test]
+    fn full_maps_evict_the_entry_expiring_the_soonest() {
+        let mut map = TtlMap::new(2);
+        let now = Instant::now();
+
+        map.insert("ana", 1, now + 2 * SECOND, now);
+        map.insert("bob", 2, now + SECOND, now);
+        map.insert("carol", 3, now + 3 * SECOND, now);
+
+        assert_eq!(map.get(&"ana", now), Some(&1));
+        assert_eq!(map.get(&"bob", now), None);
+        assert_eq!(map.get(&"carol", now), Some(&3));
+    }
+
+    #[test]
+    fn entries_inserted_again_keep_their_new_expiry() {
+        let mut map = TtlMap::new(10);
+        let now = Instant::now();
+
+        map.insert("ana", 1, now + SECOND, now);
+        map.insert("ana", 2, now + 3 * SECOND, now);
+        map.insert("bob", 3, now + 3 * SECOND, now + 2 * SECOND);
+
+        assert_eq!(map.get(&"ana", now + 2 * SECOND), Some(&2));
+    }
+
+    #[test]
+    fn stale_expiries_are_bounded() {
+        let mut map = TtlMap::new(2);
+        let now = Instant::now();
+
+        for offset in 1..100 {
+            map.insert("ana", offset, now + offset * SECOND, now);
+        }
+
+        assert!(map.expiries.len() <= 4);
+        assert_eq!(map.get(&"ana", now), Some(&99));
+    }
+}